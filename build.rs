@@ -0,0 +1,27 @@
+//! Build metadata served by `GET /api/v1/version` (see `src/version.rs`).
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn main() {
+    // Builds outside of a checkout (say, from a source tarball) can pass the commit explicitly.
+    let commit = std::env::var("OBSERVATORY_GIT_COMMIT")
+        .ok()
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .unwrap_or_default();
+    println!("cargo:rustc-env=OBSERVATORY_GIT_COMMIT={}", commit);
+
+    let built_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    println!("cargo:rustc-env=OBSERVATORY_BUILT_AT={}", built_at);
+}
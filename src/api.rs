@@ -0,0 +1,380 @@
+//! Administrative HTTP API. It is only available if `server.admin_tokens` are set, and every request has to be authenticated
+//! (see [`auth`]).
+use std::str::FromStr;
+
+use viz::{IntoResponse, Request, RequestExt, Response, ResponseExt, StatusCode};
+
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::comments;
+use crate::helpers::conflicts::Conflict;
+use crate::helpers::diff::DiffSummary;
+use crate::structs::GraphQLPullRequestFile;
+use crate::{audit, controller, version};
+
+pub mod auth;
+
+fn controller_handle(req: &Request) -> Result<controller::ControllerHandle, StatusCode> {
+    req.state::<controller::ControllerHandle>()
+        .ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/// `GET /api/v1/state/export`: a JSON snapshot of the app state (see [`crate::state::Snapshot`]).
+pub async fn export_state(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let snapshot = controller_handle.export_state().await.map_err(|e| {
+        log::error!("Failed to export state: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_error()
+    })?;
+    Ok(Response::json(snapshot)?)
+}
+
+/// `GET /api/v1/hydration`: how far reading repositories on start-up has got (see [`crate::hydration::Progress`]).
+pub async fn hydration(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    Ok(Response::json(controller_handle.hydration())?)
+}
+
+/// `GET /api/v1/ratelimit`: the core and search rate limit budget of every installation token.
+pub async fn rate_limits(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let limits = controller_handle.rate_limits().await.map_err(|e| {
+        log::error!("Failed to read rate limits: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_error()
+    })?;
+    Ok(Response::json(limits)?)
+}
+
+/// `GET /api/v1/version`: the version and build of the app, and the GitHub App it works for (see [`version::Version`]).
+pub async fn version(req: Request) -> viz::Result<Response> {
+    let version = req
+        .state::<version::Version>()
+        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
+    Ok(Response::json(version)?)
+}
+
+/// `GET /api/v1/installations`: known installations, their repositories and tokens (see
+/// [`crate::structs::InstallationSummary`]).
+pub async fn installations(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let installations = controller_handle.installations().await.map_err(|e| {
+        log::error!("Failed to list installations: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR.into_error()
+    })?;
+    Ok(Response::json(installations)?)
+}
+
+/// `POST /api/v1/installations/{id}/token`: replace the installation token right away, for example after the app's
+/// permissions have changed. Responds with 502 if GitHub refuses to issue one.
+pub async fn refresh_installation_token(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let installation_id = req.param::<i64>("id")?;
+    controller_handle
+        .refresh_installation_token(installation_id)
+        .await
+        .map_err(|e| {
+            log::error!(
+                "Failed to refresh token for installation {}: {:?}",
+                installation_id,
+                e
+            );
+            StatusCode::BAD_GATEWAY.into_error()
+        })?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// `DELETE /api/v1/installations/{id}`: drop the installation and everything known about its repositories, without
+/// uninstalling the app. It is read again on the next start-up.
+pub async fn forget_installation(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let installation_id = req.param::<i64>("id")?;
+    let forgotten = controller_handle
+        .forget_installation(installation_id)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to forget installation {}: {:?}", installation_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_error()
+        })?;
+    match forgotten {
+        true => Ok(StatusCode::NO_CONTENT.into_response()),
+        false => Err(StatusCode::NOT_FOUND.into_error()),
+    }
+}
+
+/// `POST /api/v1/repos/{owner}/{name}/resync`: read open pull requests of the repository anew and check all of them for
+/// conflicts, like [`trigger`] without filters. The check happens in the background.
+pub async fn resync_repository(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let (owner, name) = req.params::<(String, String)>()?;
+    controller_handle
+        .rescan(&format!("{}/{}", owner, name), None, None)
+        .await;
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ConflictGraphQuery {
+    /// `json` (the default) or `dot`.
+    pub format: Option<String>,
+}
+
+/// `GET /api/v1/repos/{owner}/{name}/conflict-graph?format=json|dot`: open pulls of the repository and conflicts between
+/// them (see [`crate::graph`]). The DOT format can be rendered with Graphviz, as in `dot -Tsvg`.
+pub async fn conflict_graph(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let (owner, name) = req.params::<(String, String)>()?;
+    let format = req
+        .query::<ConflictGraphQuery>()
+        .ok()
+        .and_then(|q| q.format)
+        .unwrap_or_else(|| "json".to_string());
+    let graph = controller_handle
+        .conflict_graph(&format!("{}/{}", owner, name))
+        .await
+        .map_err(|e| {
+            log::error!("Failed to build the conflict graph: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_error()
+        })?
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_error())?;
+    match format.as_str() {
+        "json" => Ok(Response::json(graph)?),
+        "dot" => Ok(Response::text(graph.to_dot())),
+        _ => Err(StatusCode::BAD_REQUEST.into_error()),
+    }
+}
+
+/// `GET /api/v1/repos/{owner}/{name}/articles/{path}`: open pulls which change an article, such as `wiki/Article` (or
+/// `wiki/Article/en.md`, which means the same), in its original or translations. Lets other tools tell that an article has
+/// pending edits.
+pub async fn article_pulls(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let (owner, name, path) = req.params::<(String, String, String)>()?;
+    let path = percent_encoding::percent_decode_str(&path).decode_utf8_lossy();
+    let pulls = controller_handle
+        .article_pulls(&format!("{}/{}", owner, name), &path)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to look up pulls changing {}: {:?}", path, e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_error()
+        })?
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_error())?;
+    Ok(Response::json(pulls)?)
+}
+
+/// `GET /api/v1/repos/{owner}/{name}/history`: the latest merged change to every original article of the repository, by
+/// file path (see [`crate::history`]).
+pub async fn article_history(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let (owner, name) = req.params::<(String, String)>()?;
+    let history = controller_handle
+        .article_history(&format!("{}/{}", owner, name))
+        .await
+        .map_err(|e| {
+            log::error!("Failed to read the article history: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_error()
+        })?
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_error())?;
+    Ok(Response::json(history)?)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    /// The repository whose settings apply, such as maintainers to mention in incomplete translations.
+    pub repo: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CommentPreview {
+    pub markdown: String,
+    pub html: String,
+}
+
+/// `POST /api/v1/preview-comment?repo=owner/name`: render the comment about a made-up conflict, passed as JSON (see
+/// [`Conflict`]), without posting it anywhere. Shows how changes to templates or settings look without a real pull request.
+pub async fn preview_comment(mut req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let full_repo_name = req
+        .query::<PreviewQuery>()
+        .ok()
+        .and_then(|q| q.repo)
+        .unwrap_or_default();
+    let conflict = req.json::<Conflict>().await.map_err(|e| {
+        log::warn!(
+            "Comment preview request with a malformed conflict, rejecting: {:?}",
+            e
+        );
+        StatusCode::BAD_REQUEST.into_error()
+    })?;
+    let markdown = controller_handle
+        .preview_comment(&full_repo_name, conflict)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to render a comment preview: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_error()
+        })?;
+    Ok(Response::json(CommentPreview {
+        html: comments::to_html(&markdown),
+        markdown,
+    })?)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimulationRequest {
+    /// Paths of changed files, all considered modified. Ignored if `diff` is set.
+    #[serde(default)]
+    pub files: Vec<String>,
+
+    /// Changes in the unified diff format, as printed by `git diff`. Unlike plain paths, it tells added, deleted and
+    /// renamed files apart, and its hunks are compared with those of other pulls.
+    pub diff: Option<String>,
+
+    /// Only compare with pulls targeting this branch.
+    pub base: Option<String>,
+}
+
+/// `POST /api/v1/repos/{owner}/{name}/simulate`: conflicts which a pull with the changes from the body (see
+/// [`SimulationRequest`]) would have with open pulls of the repository, if it was opened now. Nothing is stored or posted.
+/// The hypothetical pull has number 0. Useful for planning large edits.
+pub async fn simulate(mut req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let (owner, name) = req.params::<(String, String)>()?;
+    let simulation = req.json::<SimulationRequest>().await.map_err(|e| {
+        log::warn!("Malformed simulation request, rejecting: {:?}", e);
+        StatusCode::BAD_REQUEST.into_error()
+    })?;
+    let diff = match simulation.diff {
+        Some(diff) => DiffSummary::from(&unidiff::PatchSet::from_str(&diff).map_err(|e| {
+            log::warn!(
+                "Simulation request with a malformed diff, rejecting: {:?}",
+                e
+            );
+            StatusCode::BAD_REQUEST.into_error()
+        })?),
+        None => DiffSummary::from_files(
+            &simulation
+                .files
+                .into_iter()
+                .map(|path| GraphQLPullRequestFile {
+                    path,
+                    change_type: "MODIFIED".to_string(),
+                })
+                .collect::<Vec<_>>(),
+        ),
+    };
+    let conflicts = controller_handle
+        .simulate(&format!("{}/{}", owner, name), diff, simulation.base)
+        .await
+        .map_err(|e| {
+            log::error!("Failed to simulate a pull: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_error()
+        })?
+        .ok_or_else(|| StatusCode::NOT_FOUND.into_error())?;
+    Ok(Response::json(conflicts)?)
+}
+
+/// The parts of a `workflow_dispatch` event payload which matter for [`trigger`], so that a CI job can forward its own event
+/// as is. Other fields are ignored.
+#[derive(Debug, Deserialize)]
+pub struct TriggerPayload {
+    /// The branch the workflow runs on (`refs/heads/...` or just the name). Only pulls targeting it are checked.
+    #[serde(rename = "ref")]
+    pub git_ref: Option<String>,
+
+    #[serde(default)]
+    pub inputs: TriggerInputs,
+
+    pub repository: TriggerRepository,
+}
+
+/// Workflow inputs are always strings.
+#[derive(Debug, Default, Deserialize)]
+pub struct TriggerInputs {
+    /// Only check this pull request.
+    pub pull_number: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TriggerRepository {
+    pub full_name: String,
+}
+
+/// `POST /api/v1/trigger`: check open pull requests of a repository again, for changes which GitHub sends no events about
+/// (force-pushes of the base branch, wiki syncs). Takes a `workflow_dispatch` event payload (see [`TriggerPayload`]).
+/// The check happens in the background.
+pub async fn trigger(mut req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let payload = req.json::<TriggerPayload>().await.map_err(|e| {
+        log::warn!(
+            "Rescan request with a malformed payload, rejecting: {:?}",
+            e
+        );
+        StatusCode::BAD_REQUEST.into_error()
+    })?;
+    let pull_number = match payload.inputs.pull_number.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(n) => Some(n.parse::<i32>().map_err(|_| {
+            log::warn!(
+                "Rescan request with an invalid pull number {:?}, rejecting",
+                n
+            );
+            StatusCode::BAD_REQUEST.into_error()
+        })?),
+    };
+    let base_branch = payload
+        .git_ref
+        .map(|r| r.strip_prefix("refs/heads/").unwrap_or(&r).to_owned());
+    controller_handle
+        .rescan(&payload.repository.full_name, base_branch, pull_number)
+        .await;
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MessagesQuery {
+    pub limit: Option<usize>,
+}
+
+/// How many traced messages are returned if the request doesn't say.
+const DEFAULT_MESSAGES_LIMIT: usize = 100;
+
+/// `GET /api/v1/messages?limit=N`: latest messages handled by the controller, newest first, with their sequence numbers and
+/// durations (see [`crate::trace`]).
+pub async fn messages(req: Request) -> viz::Result<Response> {
+    let controller_handle = controller_handle(&req).map_err(|s| s.into_error())?;
+    let limit = req
+        .query::<MessagesQuery>()
+        .ok()
+        .and_then(|q| q.limit)
+        .unwrap_or(DEFAULT_MESSAGES_LIMIT);
+    Ok(Response::json(controller_handle.recent_messages(limit))?)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditQuery {
+    pub repo: Option<String>,
+    pub limit: Option<usize>,
+}
+
+/// How many audit log entries are returned if the request doesn't say.
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+
+/// `GET /api/v1/audit?repo=owner/name&limit=N`: latest audit log entries, newest first (see [`crate::audit`]).
+pub async fn audit(req: Request) -> viz::Result<Response> {
+    let query = req.query::<AuditQuery>().unwrap_or(AuditQuery {
+        repo: None,
+        limit: None,
+    });
+    let log = req
+        .state::<audit::AuditLog>()
+        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
+    let entries = log
+        .read(
+            query.repo.as_deref(),
+            query.limit.unwrap_or(DEFAULT_AUDIT_LIMIT),
+        )
+        .map_err(|e| {
+            log::error!("Failed to read the audit log: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_error()
+        })?;
+    Ok(Response::json(entries)?)
+}
@@ -0,0 +1,165 @@
+//! Authentication of administrative requests. Every route except the public ones requires one of `server.admin_tokens`:
+//! either a static token in the `Authorization: Bearer ...` header, or an HMAC signature of the request.
+//!
+//! A signed request carries the current Unix time in [`TIMESTAMP_HEADER`], and `sha256={hex HMAC-SHA256}` in
+//! [`SIGNATURE_HEADER`], computed over `{timestamp}.{METHOD}.{path and query}.{body}` with the shared secret.
+use std::sync::Arc;
+
+use viz::types::{Limits, PayloadError};
+use viz::{
+    async_trait, Body, Handler, IntoResponse, Request, RequestExt, Response, StatusCode, Transform,
+};
+
+use crate::config::AdminToken;
+use crate::helpers::digest;
+
+pub const TIMESTAMP_HEADER: &str = "X-Observatory-Timestamp";
+pub const SIGNATURE_HEADER: &str = "X-Observatory-Signature-256";
+
+/// Signed requests older (or newer) than this are rejected, so that a captured request can't be replayed later.
+const MAX_CLOCK_SKEW_SECONDS: i64 = 5 * 60;
+
+/// The middleware which rejects unauthenticated requests to anything but the public paths.
+#[derive(Debug, Clone)]
+pub struct AdminAuth {
+    tokens: Arc<Vec<AdminToken>>,
+    public_paths: Arc<Vec<String>>,
+}
+
+impl AdminAuth {
+    pub fn new(tokens: Vec<AdminToken>, public_paths: Vec<String>) -> Self {
+        Self {
+            tokens: Arc::new(tokens),
+            public_paths: Arc::new(public_paths),
+        }
+    }
+
+    fn is_public(&self, path: &str) -> bool {
+        self.public_paths.iter().any(|p| p == path)
+    }
+
+    fn has_bearer_token(&self, req: &Request) -> bool {
+        let Some(presented) = req
+            .header::<_, String>("Authorization")
+            .and_then(|h| h.strip_prefix("Bearer ").map(|t| t.to_owned()))
+        else {
+            return false;
+        };
+        self.tokens.iter().any(|t| match t {
+            AdminToken::Token { token } => {
+                ring::constant_time::verify_slices_are_equal(token.as_bytes(), presented.as_bytes())
+                    .is_ok()
+            }
+            AdminToken::HmacSecret { .. } => false,
+        })
+    }
+
+    /// Check the signature of the request, if there is one. The body is put back for the handler. It is read in full
+    /// before the signature can be checked, so it's capped by the `bytes` limit (`server.data_limit`), and oversized
+    /// bodies are rejected with 413, same as in the handlers.
+    async fn has_valid_signature(&self, req: &mut Request) -> viz::Result<bool> {
+        let (Some(timestamp), Some(signature)) = (
+            req.header::<_, String>(TIMESTAMP_HEADER),
+            req.header::<_, String>(SIGNATURE_HEADER),
+        ) else {
+            return Ok(false);
+        };
+        let Some(signature) = signature
+            .strip_prefix("sha256=")
+            .and_then(digest::hash_from_string)
+        else {
+            return Ok(false);
+        };
+        match timestamp.parse::<i64>() {
+            Ok(t) if (chrono::Utc::now().timestamp() - t).abs() <= MAX_CLOCK_SKEW_SECONDS => {}
+            _ => return Ok(false),
+        }
+
+        let limit = req
+            .extensions()
+            .get::<Limits>()
+            .and_then(|l| l.get("bytes"))
+            .unwrap_or(Limits::NORMAL);
+        let too_large = || {
+            log::warn!(
+                "Body of a signed request is larger than {} bytes, rejecting",
+                limit
+            );
+            StatusCode::PAYLOAD_TOO_LARGE.into_error()
+        };
+        if req.content_length().is_some_and(|size| size > limit) {
+            return Err(too_large());
+        }
+        let body = match req.bytes_with("bytes", limit).await {
+            Ok(body) => body,
+            Err(PayloadError::TooLarge) => return Err(too_large()),
+            Err(e) => {
+                log::warn!("Failed to read the body of a signed request: {:?}", e);
+                return Ok(false);
+            }
+        };
+        let path = req
+            .uri()
+            .path_and_query()
+            .map(|p| p.as_str())
+            .unwrap_or("/");
+        let mut message = format!("{}.{}.{}.", timestamp, req.method(), path).into_bytes();
+        message.extend_from_slice(&body);
+        *req.body_mut() = Body::from(body);
+
+        Ok(self.tokens.iter().any(|t| match t {
+            AdminToken::HmacSecret { hmac_secret } => ring::hmac::verify(
+                &ring::hmac::Key::new(ring::hmac::HMAC_SHA256, hmac_secret.as_bytes()),
+                &message,
+                &signature,
+            )
+            .is_ok(),
+            AdminToken::Token { .. } => false,
+        }))
+    }
+}
+
+/// Hex HMAC-SHA256 of the message.
+pub fn sign(secret: &str, message: &[u8]) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    digest::hash_to_string(ring::hmac::sign(&key, message).as_ref())
+}
+
+impl<H: Clone> Transform<H> for AdminAuth {
+    type Output = AdminAuthMiddleware<H>;
+
+    fn transform(&self, h: H) -> Self::Output {
+        AdminAuthMiddleware {
+            h,
+            auth: self.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AdminAuthMiddleware<H> {
+    h: H,
+    auth: AdminAuth,
+}
+
+#[async_trait]
+impl<H> Handler<Request> for AdminAuthMiddleware<H>
+where
+    H: Handler<Request, Output = viz::Result<Response>> + Clone,
+{
+    type Output = viz::Result<Response>;
+
+    async fn call(&self, mut req: Request) -> Self::Output {
+        if !self.auth.is_public(req.uri().path())
+            && !self.auth.has_bearer_token(&req)
+            && !self.auth.has_valid_signature(&mut req).await?
+        {
+            log::warn!(
+                "Request to {} without valid credentials, rejecting",
+                req.uri().path()
+            );
+            return Err(StatusCode::UNAUTHORIZED.into_error());
+        }
+        self.h.call(req).await
+    }
+}
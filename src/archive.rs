@@ -0,0 +1,177 @@
+//! Copies of received webhook payloads, kept for debugging. An archived payload can be fed back into the app with
+//! `observatory replay <file>` to reproduce a problem locally.
+//!
+//! Every payload is stored in its own gzipped file named `{timestamp}.{delivery ID}.{event type}.json.gz`, so that
+//! the event can be restored from the file alone.
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+use eyre::Result;
+
+use crate::config;
+use crate::handler::Event;
+
+const EXTENSION: &str = ".json.gz";
+
+/// Used in file names of events which have arrived without the `X-GitHub-Delivery` header.
+const UNKNOWN_DELIVERY: &str = "unknown";
+
+#[derive(Debug, Clone)]
+pub struct PayloadArchive {
+    dir: PathBuf,
+    max_files: usize,
+    max_age: chrono::Duration,
+}
+
+impl PayloadArchive {
+    /// Set up the archive if `debug.payload_dir` is set. The directory is created if it doesn't exist.
+    pub fn new(settings: &config::Debugging) -> Result<Option<Self>> {
+        let Some(dir) = &settings.payload_dir else {
+            return Ok(None);
+        };
+        std::fs::create_dir_all(dir)?;
+        Ok(Some(Self {
+            dir: PathBuf::from(dir),
+            max_files: settings.payload_max_files,
+            max_age: chrono::Duration::days(settings.payload_max_age_days.into()),
+        }))
+    }
+
+    /// Save the event, and remove payloads which have outlived the retention limits. Returns the path to the new file.
+    pub fn store(&self, event: &Event) -> Result<PathBuf> {
+        let file_name = format!(
+            "{}.{}.{}{}",
+            chrono::Utc::now().format("%Y%m%dT%H%M%S%6fZ"),
+            event.delivery_id.as_deref().unwrap_or(UNKNOWN_DELIVERY),
+            event.event_type,
+            EXTENSION
+        );
+        let path = self.dir.join(file_name);
+        let mut encoder = flate2::write::GzEncoder::new(
+            std::fs::File::create(&path)?,
+            flate2::Compression::default(),
+        );
+        encoder.write_all(event.body.as_bytes())?;
+        encoder.finish()?;
+
+        if let Err(e) = self.prune() {
+            log::error!(
+                "Failed to clean up the payload archive {:?}: {:?}",
+                self.dir,
+                e
+            );
+        }
+        Ok(path)
+    }
+
+    /// Remove payloads older than the maximum age, and then the oldest ones over the file limit.
+    fn prune(&self) -> Result<()> {
+        let mut files = Vec::new();
+        for entry in std::fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().to_string();
+            if name.ends_with(EXTENSION) {
+                files.push((entry.metadata()?.modified()?, entry.path()));
+            }
+        }
+        // Names start with timestamps, so this puts the oldest payloads first.
+        files.sort_by(|a, b| a.1.cmp(&b.1));
+
+        let now = std::time::SystemTime::now();
+        let max_age = self.max_age.to_std().unwrap_or_default();
+        let expired = files
+            .iter()
+            .take_while(|(modified, _)| now.duration_since(*modified).unwrap_or_default() > max_age)
+            .count();
+        let excess = files.len().saturating_sub(self.max_files);
+        for (_, path) in files.iter().take(expired.max(excess)) {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// Restore an archived event, taking its type and delivery ID from the file name.
+pub fn load(path: &Path) -> Result<Event> {
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let parts: Vec<_> = file_name
+        .strip_suffix(EXTENSION)
+        .map(|n| n.split('.').collect())
+        .unwrap_or_default();
+    let [_, delivery_id, event_type] = parts[..] else {
+        eyre::bail!(
+            "{:?} is not an archived payload (expected a name like {{timestamp}}.{{delivery ID}}.{{event type}}{})",
+            path,
+            EXTENSION
+        );
+    };
+
+    let mut body = String::new();
+    flate2::read::GzDecoder::new(std::fs::File::open(path)?).read_to_string(&mut body)?;
+    Ok(Event {
+        event_type: event_type.to_string(),
+        delivery_id: (delivery_id != UNKNOWN_DELIVERY).then(|| delivery_id.to_string()),
+        body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_archive(max_files: usize) -> PayloadArchive {
+        let dir = std::env::temp_dir().join(format!(
+            "observatory-payloads-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap()
+        ));
+        PayloadArchive::new(&config::Debugging {
+            payload_dir: Some(dir.to_str().unwrap().to_string()),
+            payload_max_files: max_files,
+            payload_max_age_days: 7,
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    fn make_event(delivery_id: Option<&str>) -> Event {
+        Event {
+            event_type: "pull_request".to_string(),
+            delivery_id: delivery_id.map(|d| d.to_string()),
+            body: r#"{"action": "opened"}"#.to_string(),
+        }
+    }
+
+    #[test]
+    fn stored_payload_is_loaded_back() {
+        let archive = make_archive(10);
+        for delivery_id in [Some("72d3162e-cc78-11e3-81ab-4c9367dc0958"), None] {
+            let event = make_event(delivery_id);
+            let path = archive.store(&event).unwrap();
+            let loaded = load(&path).unwrap();
+            assert_eq!(loaded.event_type, event.event_type);
+            assert_eq!(loaded.delivery_id, event.delivery_id);
+            assert_eq!(loaded.body, event.body);
+        }
+        assert!(load(&archive.dir.join("payload.json")).is_err());
+        std::fs::remove_dir_all(&archive.dir).unwrap();
+    }
+
+    #[test]
+    fn oldest_payloads_are_pruned() {
+        let archive = make_archive(2);
+        let paths: Vec<_> = (0..3)
+            .map(|i| {
+                let path = archive.store(&make_event(Some(&format!("delivery-{i}"))));
+                std::thread::sleep(std::time::Duration::from_millis(10));
+                path.unwrap()
+            })
+            .collect();
+        assert!(!paths[0].exists());
+        assert!(paths[1].exists());
+        assert!(paths[2].exists());
+        std::fs::remove_dir_all(&archive.dir).unwrap();
+    }
+}
@@ -0,0 +1,168 @@
+//! An append-only log of everything the bot does on GitHub and with its conflict cache, one JSON entry per line.
+use std::io::{BufRead, Write};
+use std::sync::{Arc, Mutex};
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::conflicts::Conflict;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub timestamp: chrono::DateTime<chrono::Utc>,
+    pub repository: String,
+
+    /// The `X-GitHub-Delivery` ID of the webhook event which caused the action, if there was one.
+    #[serde(default)]
+    pub delivery_id: Option<String>,
+
+    #[serde(flatten)]
+    pub action: Action,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "action", rename_all = "kebab-case")]
+pub enum Action {
+    CommentPosted {
+        pull_number: i32,
+    },
+    CommentUpdated {
+        pull_number: i32,
+        comment_id: i64,
+    },
+    CommentDeleted {
+        pull_number: i32,
+        comment_id: i64,
+    },
+    CommentMinimized {
+        pull_number: i32,
+        comment_id: i64,
+    },
+    LabelsAdded {
+        pull_number: i32,
+        labels: Vec<String>,
+    },
+    LabelRemoved {
+        pull_number: i32,
+        label: String,
+    },
+
+    /// A conflict was found, or its contents changed.
+    ConflictStored {
+        conflict: Conflict,
+    },
+    ConflictRemoved {
+        conflict: Conflict,
+    },
+}
+
+/// The log file handle. Without a path, nothing is recorded.
+#[derive(Debug, Clone, Default)]
+pub struct AuditLog {
+    path: Option<String>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl AuditLog {
+    pub fn new(path: Option<String>) -> Self {
+        Self {
+            path,
+            lock: Arc::default(),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.path.is_some()
+    }
+
+    /// Append an entry. The log is an aid for humans, so failing to write it is not fatal.
+    pub fn record(&self, entry: &Entry) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        let _guard = self.lock.lock().unwrap();
+        let result = std::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut f| writeln!(f, "{}", serde_json::to_string(entry).unwrap()));
+        if let Err(e) = result {
+            log::error!("Failed to write to the audit log {:?}: {:?}", path, e);
+        }
+    }
+
+    /// Read up to `limit` latest entries, optionally only for one repository. Entries are returned newest first.
+    pub fn read(&self, repository: Option<&str>, limit: usize) -> Result<Vec<Entry>> {
+        let Some(path) = &self.path else {
+            return Ok(Vec::new());
+        };
+        let _guard = self.lock.lock().unwrap();
+        let file = match std::fs::File::open(path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        for line in std::io::BufReader::new(file).lines() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry: Entry = serde_json::from_str(&line)?;
+            if repository.is_none_or(|r| entry.repository == r) {
+                entries.push(entry);
+            }
+        }
+        entries.reverse();
+        entries.truncate(limit);
+        Ok(entries)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_entry(repository: &str, pull_number: i32) -> Entry {
+        Entry {
+            timestamp: chrono::Utc::now(),
+            repository: repository.to_string(),
+            delivery_id: Some(format!("delivery-{}", pull_number)),
+            action: Action::CommentPosted { pull_number },
+        }
+    }
+
+    #[test]
+    fn read_filters_and_limits() {
+        let path = std::env::temp_dir().join(format!(
+            "observatory-audit-{}.jsonl",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap()
+        ));
+        let log = AuditLog::new(Some(path.to_str().unwrap().to_string()));
+        assert!(log.read(None, 10).unwrap().is_empty());
+
+        let entries = [
+            make_entry("test/repo", 1),
+            make_entry("test/other-repo", 2),
+            make_entry("test/repo", 3),
+        ];
+        for e in &entries {
+            log.record(e);
+        }
+
+        assert_eq!(
+            log.read(Some("test/repo"), 10).unwrap(),
+            vec![entries[2].clone(), entries[0].clone()]
+        );
+        assert_eq!(log.read(None, 1).unwrap(), vec![entries[2].clone()]);
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn disabled_log_is_empty() {
+        let log = AuditLog::default();
+        log.record(&make_entry("test/repo", 1));
+        assert!(log.read(None, 10).unwrap().is_empty());
+    }
+}
@@ -2,11 +2,17 @@
 ///
 /// To avoid silently using unexpected defaults, all values must be defined only in the YAML file.
 /// For detailed information on what every setting does, refer to `.config.yaml`.
-use std::net::Ipv4Addr;
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr};
 
 use eyre::Result;
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 
+use crate::github::RetryPolicies;
+pub use crate::helpers::conflicts::Severity;
+use crate::repo_config::RepoConfig;
+
 pub const DEFAULT_FILE_NAME: &str = "config.yaml";
 pub const STDERR_LOG_FILE: &str = "-";
 
@@ -16,21 +22,223 @@ pub struct Config {
     pub logging: Logging,
     pub github: GitHub,
     pub controller: Controller,
+    pub debug: Debugging,
 }
 
 impl Config {
     pub fn from_path(path: &str) -> Result<Config> {
         let contents = std::fs::read_to_string(path)?;
-        let settings = serde_yaml::from_str::<Config>(contents.as_str())?;
+        let mut settings = serde_yaml::from_str::<Config>(contents.as_str())?;
+        settings.normalize();
         Ok(settings)
     }
+
+    /// Bring values written in older formats to the current one. Events endpoints used to be listed without a leading
+    /// slash, and configs which still do so keep working.
+    fn normalize(&mut self) {
+        let paths: Vec<&mut String> = match &mut self.server.events_endpoint {
+            EventsEndpoints::Single(path) => vec![path],
+            EventsEndpoints::Multiple(endpoints) => {
+                endpoints.iter_mut().map(|e| &mut e.path).collect()
+            }
+        };
+        for path in paths {
+            if !path.starts_with('/') {
+                path.insert(0, '/');
+            }
+        }
+    }
+
+    /// Check the settings for mistakes which would otherwise surface as panics later on.
+    ///
+    /// All problems are collected at once, so that they can be fixed in one go. An empty list means the config is usable.
+    /// Checks which require network access (such as reaching the GitHub App) are left to the caller.
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.github.app_id.parse::<i64>().is_err() {
+            problems.push(format!(
+                "github.app_id: expected a number, got {:?}",
+                self.github.app_id
+            ));
+        }
+
+        match std::fs::read(&self.github.app_key_path) {
+            Err(e) => problems.push(format!(
+                "github.app_key_path: failed to read {:?}: {}",
+                self.github.app_key_path, e
+            )),
+            Ok(key) => {
+                if let Err(e) = jsonwebtoken::EncodingKey::from_rsa_pem(&key) {
+                    problems.push(format!(
+                        "github.app_key_path: {:?} is not a valid RSA PEM key: {}",
+                        self.github.app_key_path, e
+                    ));
+                }
+            }
+        }
+
+        let endpoints = self.events_endpoints();
+        if endpoints.is_empty() {
+            problems.push("server.events_endpoint: expected at least one endpoint".to_string());
+        }
+        for (i, e) in endpoints.iter().enumerate() {
+            if endpoints[..i].iter().any(|other| other.path == e.path) {
+                problems.push(format!(
+                    "server.events_endpoint: path {:?} is listed more than once",
+                    e.path
+                ));
+            }
+        }
+
+        if let Some(tls) = &self.server.tls {
+            for (name, path) in [("cert_path", &tls.cert_path), ("key_path", &tls.key_path)] {
+                if let Err(e) = std::fs::read(path) {
+                    problems.push(format!(
+                        "server.tls.{}: failed to read {:?}: {}",
+                        name, path, e
+                    ));
+                }
+            }
+        }
+
+        for (i, t) in self.server.admin_tokens.iter().enumerate() {
+            let (AdminToken::Token { token: value }
+            | AdminToken::HmacSecret { hmac_secret: value }) = t;
+            if value.is_empty() {
+                problems.push(format!(
+                    "server.admin_tokens[{}]: expected a non-empty value",
+                    i
+                ));
+            }
+        }
+
+        for (i, p) in self.server.trusted_proxies.iter().enumerate() {
+            if let Err(e) = parse_ip_range(p) {
+                problems.push(format!(
+                    "server.trusted_proxies[{}]: expected an address or a range, got {:?}: {}",
+                    i, p, e
+                ));
+            }
+        }
+
+        for (i, n) in self.controller.notifiers.iter().enumerate() {
+            let Sink::Email(email) = &n.sink else {
+                continue;
+            };
+            if email.to.is_empty() {
+                problems.push(format!(
+                    "controller.notifiers[{}].to: expected at least one address",
+                    i
+                ));
+            }
+            for address in std::iter::once(&email.from).chain(&email.to) {
+                if let Err(e) = address.parse::<lettre::message::Mailbox>() {
+                    problems.push(format!(
+                        "controller.notifiers[{}]: {:?} is not a valid email address: {}",
+                        i, address, e
+                    ));
+                }
+            }
+        }
+
+        if self.logging.file != STDERR_LOG_FILE {
+            if let Err(e) = std::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.logging.file)
+            {
+                problems.push(format!(
+                    "logging.file: {:?} is not writable: {}",
+                    self.logging.file, e
+                ));
+            }
+        }
+
+        problems
+    }
+
+    /// Webhook endpoints with their secrets. A single path uses `github.webhook_secret`.
+    pub fn events_endpoints(&self) -> Vec<EventsEndpoint> {
+        match &self.server.events_endpoint {
+            EventsEndpoints::Single(path) => vec![EventsEndpoint {
+                path: path.clone(),
+                secret: self.github.webhook_secret.clone(),
+            }],
+            EventsEndpoints::Multiple(endpoints) => endpoints.clone(),
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
 pub struct Server {
     pub bind_ip: Ipv4Addr,
     pub port: u16,
-    pub events_endpoint: String,
+    pub events_endpoint: EventsEndpoints,
+
+    /// Credentials for the administrative API under `/api/v1` (see [`crate::api::auth`]). The API is disabled if there are none.
+    pub admin_tokens: Vec<AdminToken>,
+
+    /// Serve HTTPS instead of plain HTTP. Disabled if not set.
+    pub tls: Option<Tls>,
+
+    /// Only accept webhook events from GitHub's hook IP ranges (see [`crate::helpers::allowlist`]).
+    pub github_ips_only: bool,
+
+    /// Addresses or ranges of reverse proxies in front of the app, whose `X-Forwarded-For` headers are believed when
+    /// checking where webhook events come from.
+    pub trusted_proxies: Vec<String>,
+
+    /// The largest request body accepted, in bytes. Larger ones are rejected with 413 before they are read in full.
+    pub data_limit: u64,
+}
+
+impl Server {
+    /// Ranges from `trusted_proxies`, without the malformed ones (see [`Config::validate`]).
+    pub fn trusted_proxy_ranges(&self) -> Vec<IpNet> {
+        self.trusted_proxies
+            .iter()
+            .filter_map(|p| parse_ip_range(p).ok())
+            .collect()
+    }
+}
+
+/// An address range, such as `10.0.0.0/8`, or a single address.
+fn parse_ip_range(s: &str) -> Result<IpNet, ipnet::AddrParseError> {
+    s.parse::<IpNet>()
+        .or_else(|e| s.parse::<IpAddr>().map(IpNet::from).map_err(|_| e))
+}
+
+/// A way to authenticate administrative requests.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum AdminToken {
+    /// A static token, passed as `Authorization: Bearer {token}`.
+    Token { token: String },
+
+    /// A secret which requests are signed with, so that it never travels over the wire.
+    HmacSecret { hmac_secret: String },
+}
+
+/// PEM files for serving HTTPS.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Tls {
+    pub cert_path: String,
+    pub key_path: String,
+}
+
+/// Either a single webhook path, or several paths with their own secrets (for example, when serving several GitHub Apps).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(untagged)]
+pub enum EventsEndpoints {
+    Single(String),
+    Multiple(Vec<EventsEndpoint>),
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct EventsEndpoint {
+    pub path: String,
+    pub secret: String,
 }
 
 #[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
@@ -48,9 +256,295 @@ pub struct GitHub {
     pub webhook_secret: String,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
 pub struct Controller {
     pub post_comments: bool,
+    pub graphql: bool,
+    pub notify_originals: bool,
+    pub check_outdated_translations: bool,
+    pub reconcile_on_startup: bool,
+
+    /// When pull requests of a repository are read (see [`HydrationMode`]).
+    pub hydration: HydrationMode,
+
+    /// Path to the audit log (see [`crate::audit`]). Nothing is recorded if not set.
+    pub audit_log: Option<String>,
+
+    /// Path to the file with comment writes waiting for another attempt (see [`crate::retries`]). Kept in memory if not set.
+    pub retry_queue: Option<String>,
+
+    /// Collapse updates of a pull request which arrive within this many seconds into one conflict refresh. Disabled if 0.
+    pub debounce_seconds: u64,
+
+    /// Keep at most this many open pull requests of a repository, the most recently updated ones, and forget the rest.
+    /// Unlimited if 0.
+    pub max_pulls_per_repo: usize,
+
+    /// Secret for signing headers of the app's comments. If set, comments with missing or invalid signatures are ignored.
+    pub signing_key: Option<String>,
+    pub notifications: Notifications,
+
+    /// Destinations for conflict notifications besides comments (see [`crate::notifiers`]).
+    pub notifiers: Vec<Notifier>,
+    pub access: Access,
+
+    /// Settings for all repositories of an account, keyed by its login. They take precedence over the settings above, and
+    /// are themselves overridden by repositories' own files (see [`crate::repo_config`]).
+    pub accounts: HashMap<String, RepoConfig>,
+
+    /// Full names of repositories which keep translations of each other's articles, such as `wiki-en` and `wiki-ru`. Pulls
+    /// are compared across repositories of the same group (see [`crate::helpers::conflicts::compare_pulls_across`]).
+    pub repo_groups: Vec<Vec<String>>,
+
+    /// Periodic comparison of the app's state with GitHub (see [`crate::self_check`]).
+    pub self_check: SelfCheck,
+
+    /// How often stored conflicts are pruned and checked for consistency, in hours (see
+    /// [`crate::helpers::conflicts::Storage::compact`]). Disabled if 0.
+    pub compaction_interval_hours: u64,
+
+    /// What happens when GitHub responds with HTTP 501 (see [`FatalHttpErrors`]).
+    pub fatal_http_errors: FatalHttpErrors,
+
+    /// How persistently requests to GitHub are retried, by kind (see [`RetryPolicies`]).
+    pub retries: RetryPolicies,
+
+    /// When to slow down background work because the container is short of resources (see [`crate::throttle`]).
+    pub throttling: Throttling,
+
+    /// Give up on a message which takes longer than this many seconds while others are waiting, and rebuild the state (see
+    /// [`crate::watchdog`]). Disabled if 0.
+    pub watchdog_seconds: u64,
+}
+
+impl Controller {
+    /// Stop writing comments anywhere, including accounts which enable them in `accounts`.
+    pub fn disable_comments(&mut self) {
+        self.post_comments = false;
+        for account in self.accounts.values_mut() {
+            account.post_comments = Some(false);
+        }
+    }
+
+    /// Settings shared by all repositories of the account which owns the repository.
+    pub fn account_settings(&self, full_repo_name: &str) -> RepoConfig {
+        let owner = full_repo_name.split('/').next().unwrap_or_default();
+        self.accounts.get(owner).cloned().unwrap_or_default()
+    }
+
+    /// Other repositories from the groups of the repository.
+    pub fn sibling_repositories(&self, full_repo_name: &str) -> Vec<String> {
+        let mut siblings: Vec<String> = self
+            .repo_groups
+            .iter()
+            .filter(|group| group.iter().any(|r| r == full_repo_name))
+            .flatten()
+            .filter(|r| *r != full_repo_name)
+            .cloned()
+            .collect();
+        siblings.sort();
+        siblings.dedup();
+        siblings
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Notifications {
+    pub style: NotificationStyle,
+    pub resolved: ResolvedStyle,
+
+    /// The least severe overlap which is still reported. Other conflict types are always reported.
+    pub min_severity: Severity,
+
+    pub approved: ApprovedStyle,
+    pub self_overlap: SelfOverlapStyle,
+
+    /// In digests, also mention git conflicts of the pull request with its base branch, as reported by GitHub.
+    pub git_conflicts: bool,
+
+    /// With per-conflict comments, post at most this many new comments in a pull request per hour, and fold the rest
+    /// into a digest. Unlimited if 0.
+    pub max_comments_per_hour: usize,
+
+    /// In comments about incomplete translations, mention the latest merged change to each original (see
+    /// [`crate::history`]).
+    pub article_history: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct SelfCheck {
+    /// How often the check runs, in minutes. Disabled if 0.
+    pub interval_minutes: u64,
+
+    /// How many open pull requests of every repository are checked each time. All of them are covered over several runs.
+    pub pulls_per_repo: usize,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Throttling {
+    /// Memory pressure of the app's cgroup, in percent of time stalled over the last 10 seconds, above which background
+    /// work is slowed down. Disabled if 0.
+    pub memory_pressure: f64,
+
+    /// Same as `memory_pressure`, for CPU.
+    pub cpu_pressure: f64,
+}
+
+/// A destination for conflict notifications, limited to some repositories.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Notifier {
+    /// Full names of repositories whose conflicts are sent. Empty means all.
+    pub repositories: Vec<String>,
+
+    #[serde(flatten)]
+    pub sink: Sink,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+#[serde(tag = "kind", rename_all = "kebab-case")]
+pub enum Sink {
+    /// A Discord channel webhook.
+    Discord { webhook_url: String },
+
+    /// A Slack incoming webhook.
+    Slack { webhook_url: String },
+
+    /// Emails about incomplete translations (other conflicts are not sent).
+    Email(Email),
+}
+
+/// SMTP settings and addresses for email notifications. Addresses may include names, e.g. `Observatory <bot@example.com>`.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone)]
+pub struct Email {
+    /// The server is connected to with STARTTLS.
+    pub smtp_server: String,
+    pub smtp_port: u16,
+    pub username: String,
+    pub password: String,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+/// When pull requests of a repository are read.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum HydrationMode {
+    /// As soon as the repository is added, on start-up or later.
+    #[default]
+    Eager,
+
+    /// Once the first request about the repository arrives, such as a webhook event or a rescan.
+    Lazy,
+}
+
+/// What happens when GitHub says that it doesn't support a request at all (HTTP 501 Not Implemented), which means that
+/// the app is out of touch with the API.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum FatalHttpErrors {
+    /// The request fails like any other, and the caller reports it.
+    #[default]
+    Fail,
+
+    /// The controller panics, and rebuilds its state from scratch.
+    Panic,
+}
+
+/// What happens to conflict comments in pull requests approved by a maintainer.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ApprovedStyle {
+    /// Nothing special, approved pulls are notified like any other.
+    #[default]
+    Notify,
+
+    /// Don't post new comments, but keep updating existing ones.
+    Suppress,
+
+    /// Post comments as usual, with a note that the pull request is already approved.
+    Tag,
+}
+
+/// What happens to overlaps between pull requests opened by the same person.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum SelfOverlapStyle {
+    /// Nothing special, the author is notified like anyone else.
+    #[default]
+    Notify,
+
+    /// Don't report such overlaps at all.
+    Suppress,
+
+    /// Post comments as usual, with a note that both pull requests are the author's own.
+    Soften,
+}
+
+/// What happens to comments about conflicts which no longer exist.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResolvedStyle {
+    /// Remove the comment.
+    #[default]
+    Delete,
+
+    /// Keep the comment, but strike its contents through and add a banner.
+    Banner,
+
+    /// Hide the comment as resolved via the GraphQL API.
+    Minimize,
+}
+
+/// How conflicts are presented in pull request comments.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotificationStyle {
+    /// One comment for every conflicting pull request and conflict type.
+    #[default]
+    PerConflict,
+
+    /// A single comment with a table of all conflicts, updated in place.
+    Digest,
+}
+
+/// Restrictions on which installations and repositories the app is allowed to track.
+///
+/// Empty allowlists permit everything; denylists take precedence over allowlists.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Access {
+    pub allowed_accounts: Vec<String>,
+    pub denied_accounts: Vec<String>,
+    pub allowed_repositories: Vec<String>,
+    pub denied_repositories: Vec<String>,
+    pub uninstall_denied: bool,
+}
+
+impl Access {
+    pub fn allows_account(&self, login: &str) -> bool {
+        !self.denied_accounts.iter().any(|a| a == login)
+            && (self.allowed_accounts.is_empty()
+                || self.allowed_accounts.iter().any(|a| a == login))
+    }
+
+    pub fn allows_repository(&self, full_repo_name: &str) -> bool {
+        let owner = full_repo_name.split('/').next().unwrap_or_default();
+        !self.denied_accounts.iter().any(|a| a == owner)
+            && !self.denied_repositories.iter().any(|r| r == full_repo_name)
+            && (self.allowed_repositories.is_empty()
+                || self
+                    .allowed_repositories
+                    .iter()
+                    .any(|r| r == full_repo_name))
+    }
+}
+
+/// Settings for investigating problems, which are not needed in normal operation.
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+pub struct Debugging {
+    /// Directory for copies of received webhook payloads (see [`crate::archive`]). Nothing is kept if not set.
+    pub payload_dir: Option<String>,
+    pub payload_max_files: usize,
+    pub payload_max_age_days: u32,
 }
 
 // Unfortunate copypaste: https://serde.rs/remote-derive.html
@@ -74,6 +568,7 @@ enum LevelFilterDef {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::github::RetryPolicy;
 
     #[test]
     fn template_correctness() {
@@ -82,7 +577,12 @@ mod tests {
             server: Server {
                 bind_ip: Ipv4Addr::new(127, 0, 0, 1),
                 port: 3000,
-                events_endpoint: "github-events".to_string(),
+                events_endpoint: EventsEndpoints::Single("/github-events".to_string()),
+                admin_tokens: Vec::new(),
+                tls: None,
+                github_ips_only: false,
+                trusted_proxies: Vec::new(),
+                data_limit: 10 * 1024 * 1024,
             },
             logging: Logging {
                 level: log::LevelFilter::Debug,
@@ -95,8 +595,330 @@ mod tests {
             },
             controller: Controller {
                 post_comments: true,
+                graphql: false,
+                notify_originals: false,
+                check_outdated_translations: false,
+                reconcile_on_startup: false,
+                hydration: HydrationMode::Eager,
+                audit_log: None,
+                retry_queue: None,
+                debounce_seconds: 0,
+                max_pulls_per_repo: 0,
+                signing_key: None,
+                notifications: Notifications {
+                    style: NotificationStyle::PerConflict,
+                    resolved: ResolvedStyle::Delete,
+                    min_severity: Severity::FileLevel,
+                    approved: ApprovedStyle::Notify,
+                    self_overlap: SelfOverlapStyle::Notify,
+                    git_conflicts: false,
+                    max_comments_per_hour: 0,
+                    article_history: false,
+                },
+                notifiers: Vec::new(),
+                access: Access {
+                    allowed_accounts: Vec::new(),
+                    denied_accounts: Vec::new(),
+                    allowed_repositories: Vec::new(),
+                    denied_repositories: Vec::new(),
+                    uninstall_denied: false,
+                },
+                accounts: HashMap::new(),
+                repo_groups: Vec::new(),
+                self_check: SelfCheck {
+                    interval_minutes: 0,
+                    pulls_per_repo: 3,
+                },
+                compaction_interval_hours: 24,
+                fatal_http_errors: FatalHttpErrors::Fail,
+                retries: RetryPolicies {
+                    diffs: RetryPolicy {
+                        max_retries: 5,
+                        base_delay_ms: 1000,
+                        max_delay_ms: 10000,
+                    },
+                    reads: RetryPolicy {
+                        max_retries: 10,
+                        base_delay_ms: 1000,
+                        max_delay_ms: 30000,
+                    },
+                    comment_writes: RetryPolicy {
+                        max_retries: 3,
+                        base_delay_ms: 1000,
+                        max_delay_ms: 5000,
+                    },
+                },
+                throttling: Throttling {
+                    memory_pressure: 0.0,
+                    cpu_pressure: 0.0,
+                },
+                watchdog_seconds: 600,
+            },
+            debug: Debugging {
+                payload_dir: None,
+                payload_max_files: 1000,
+                payload_max_age_days: 7,
             },
         };
         assert_eq!(settings, template);
     }
+
+    #[test]
+    fn access_lists() {
+        let access = Access {
+            allowed_accounts: vec!["ppy".to_string()],
+            denied_accounts: vec!["spammer".to_string()],
+            allowed_repositories: Vec::new(),
+            denied_repositories: vec!["ppy/osu".to_string()],
+            uninstall_denied: false,
+        };
+        assert!(access.allows_account("ppy"));
+        assert!(!access.allows_account("TicClick"));
+        assert!(!access.allows_account("spammer"));
+
+        assert!(access.allows_repository("ppy/osu-wiki"));
+        assert!(!access.allows_repository("ppy/osu"));
+        assert!(!access.allows_repository("spammer/osu-wiki"));
+
+        let open = Access::default();
+        assert!(open.allows_account("anyone"));
+        assert!(open.allows_repository("anyone/anything"));
+    }
+
+    #[test]
+    fn account_settings() {
+        let mut settings = Config::from_path(".config.yaml").unwrap();
+        settings.controller.accounts = serde_yaml::from_str(
+            r#"
+ppy:
+  post_comments: true
+  labels: ["conflict"]
+TicClick:
+  post_comments: false
+"#,
+        )
+        .unwrap();
+        let ppy = settings.controller.account_settings("ppy/osu-wiki");
+        assert_eq!(ppy.post_comments, Some(true));
+        assert_eq!(ppy.labels, vec!["conflict".to_string()]);
+        assert_eq!(
+            settings
+                .controller
+                .account_settings("TicClick/osu-wiki")
+                .post_comments,
+            Some(false)
+        );
+        assert_eq!(
+            settings.controller.account_settings("someone/osu-wiki"),
+            RepoConfig::default()
+        );
+    }
+
+    #[test]
+    fn sibling_repositories() {
+        let controller = Controller {
+            repo_groups: vec![
+                vec!["ppy/wiki-en".to_string(), "ppy/wiki-ru".to_string()],
+                vec!["ppy/wiki-en".to_string(), "ppy/wiki-pt-br".to_string()],
+            ],
+            ..Default::default()
+        };
+        assert_eq!(
+            controller.sibling_repositories("ppy/wiki-en"),
+            vec!["ppy/wiki-pt-br".to_string(), "ppy/wiki-ru".to_string()]
+        );
+        assert_eq!(
+            controller.sibling_repositories("ppy/wiki-ru"),
+            vec!["ppy/wiki-en".to_string()]
+        );
+        assert!(controller.sibling_repositories("ppy/osu-wiki").is_empty());
+    }
+
+    #[test]
+    fn validate_collects_all_problems() {
+        let mut settings = Config::from_path(".config.yaml").unwrap();
+        settings.github.app_id = "observatory".to_string();
+        settings.github.app_key_path = "./does-not-exist.pem".to_string();
+        settings.server.events_endpoint = EventsEndpoints::Multiple(Vec::new());
+        settings.logging.file = "./does-not-exist/observatory.log".to_string();
+
+        let problems = settings.validate();
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].starts_with("github.app_id"));
+        assert!(problems[1].starts_with("github.app_key_path"));
+        assert!(problems[2].starts_with("server.events_endpoint"));
+        assert!(problems[3].starts_with("logging.file"));
+    }
+
+    #[test]
+    fn trusted_proxies() {
+        let mut settings = Config::from_path(".config.yaml").unwrap();
+        settings.server.trusted_proxies = vec![
+            "10.0.0.0/8".to_string(),
+            "::1".to_string(),
+            "localhost".to_string(),
+        ];
+        assert_eq!(
+            settings.server.trusted_proxy_ranges(),
+            vec![
+                "10.0.0.0/8".parse::<IpNet>().unwrap(),
+                "::1/128".parse::<IpNet>().unwrap()
+            ]
+        );
+
+        let problems: Vec<_> = settings
+            .validate()
+            .into_iter()
+            .filter(|p| p.starts_with("server.trusted_proxies"))
+            .collect();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("server.trusted_proxies[2]"));
+    }
+
+    #[test]
+    fn admin_tokens() {
+        let mut settings = Config::from_path(".config.yaml").unwrap();
+        assert!(settings.server.admin_tokens.is_empty());
+
+        settings.server.admin_tokens = serde_yaml::from_str(
+            r#"
+- token: letmein
+- hmac_secret: sesame
+- token: ""
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            settings.server.admin_tokens,
+            vec![
+                AdminToken::Token {
+                    token: "letmein".to_string()
+                },
+                AdminToken::HmacSecret {
+                    hmac_secret: "sesame".to_string()
+                },
+                AdminToken::Token {
+                    token: String::new()
+                },
+            ]
+        );
+
+        let problems: Vec<_> = settings
+            .validate()
+            .into_iter()
+            .filter(|p| p.starts_with("server.admin_tokens"))
+            .collect();
+        assert_eq!(
+            problems,
+            vec!["server.admin_tokens[2]: expected a non-empty value"]
+        );
+    }
+
+    #[test]
+    fn multiple_events_endpoints() {
+        let mut settings = Config::from_path(".config.yaml").unwrap();
+        assert_eq!(
+            settings.events_endpoints(),
+            vec![EventsEndpoint {
+                path: "/github-events".to_string(),
+                secret: "iseedeadpeople".to_string(),
+            }]
+        );
+
+        // Paths without a leading slash come from older configs.
+        settings.server.events_endpoint = EventsEndpoints::Single("github-events".to_string());
+        settings.normalize();
+        assert_eq!(settings.events_endpoints()[0].path, "/github-events");
+
+        settings.server.events_endpoint = serde_yaml::from_str(
+            r#"
+- path: /github-events
+  secret: first
+- path: /other-app-events
+  secret: second
+- path: other-app-events
+  secret: third
+- path: /github-events
+  secret: fourth
+"#,
+        )
+        .unwrap();
+        settings.normalize();
+        assert_eq!(settings.events_endpoints().len(), 4);
+        assert_eq!(settings.events_endpoints()[1].secret, "second");
+        assert_eq!(settings.events_endpoints()[2].path, "/other-app-events");
+
+        let problems: Vec<_> = settings
+            .validate()
+            .into_iter()
+            .filter(|p| p.starts_with("server.events_endpoint"))
+            .collect();
+        assert_eq!(problems.len(), 2);
+        assert!(problems
+            .iter()
+            .all(|p| p.contains("is listed more than once")));
+    }
+
+    #[test]
+    fn validate_checks_tls_files() {
+        let mut settings = Config::from_path(".config.yaml").unwrap();
+        settings.server.tls = Some(Tls {
+            cert_path: ".config.yaml".to_string(),
+            key_path: "./does-not-exist.pem".to_string(),
+        });
+
+        let problems: Vec<_> = settings
+            .validate()
+            .into_iter()
+            .filter(|p| p.starts_with("server.tls"))
+            .collect();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("server.tls.key_path"));
+    }
+
+    #[test]
+    fn validate_checks_email_addresses() {
+        let mut settings = Config::from_path(".config.yaml").unwrap();
+        settings.controller.notifiers = serde_yaml::from_str(
+            r#"
+- kind: discord
+  webhook_url: "https://discord.com/api/webhooks/1"
+  repositories: []
+- kind: email
+  smtp_server: "smtp.example.com"
+  smtp_port: 587
+  username: "observatory"
+  password: "hunter2"
+  from: "Observatory <observatory@example.com>"
+  to: ["translators@example.com", "not an address"]
+  repositories: ["ppy/osu-wiki"]
+"#,
+        )
+        .unwrap();
+        assert_eq!(
+            settings.controller.notifiers[1].repositories,
+            vec!["ppy/osu-wiki".to_string()]
+        );
+
+        let problems: Vec<_> = settings
+            .validate()
+            .into_iter()
+            .filter(|p| p.starts_with("controller.notifiers"))
+            .collect();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("controller.notifiers[1]: \"not an address\""));
+    }
+
+    #[test]
+    fn validate_rejects_bad_private_key() {
+        let key_path = std::env::temp_dir().join("observatory-bad-key.pem");
+        std::fs::write(&key_path, "not a key").unwrap();
+
+        let mut settings = Config::from_path(".config.yaml").unwrap();
+        settings.github.app_key_path = key_path.to_str().unwrap().to_string();
+
+        let problems = settings.validate();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("is not a valid RSA PEM key"));
+    }
 }
@@ -1,17 +1,75 @@
 /// `controller` contains core logic of the app. Refer to [`Controller`] for more details.
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 
 use eyre::Result;
+use futures::{FutureExt, StreamExt};
 use tokio::sync::mpsc;
 
+use crate::audit;
 use crate::config;
 use crate::controller::ControllerRequest;
-use crate::github::{Client, GitHub};
-use crate::helpers::comments::CommentHeader;
+use crate::github::{self, Client, GitHub, GitHubInterface, HttpError, SecondaryRateLimit};
+use crate::graph;
+use crate::helpers::cgroup::CGroup;
+use crate::helpers::commands::{self, Command};
+use crate::helpers::comments::{self, CommentHeader};
 use crate::helpers::conflicts::{self, ConflictType};
-use crate::helpers::ToMarkdown;
+use crate::helpers::diff::DiffSummary;
+use crate::helpers::digest::RequestValidator;
+use crate::helpers::{self, ToMarkdown};
+use crate::history::ArticleHistory;
+use crate::hydration::Hydration;
 use crate::memory;
+use crate::notifiers;
+use crate::repo_config::{self, RepoConfig};
+use crate::retries;
+use crate::self_check;
+use crate::state;
 use crate::structs::*;
+use crate::throttle::Throttle;
+use crate::trace::{self, Trace};
+use crate::watchdog::Watchdog;
+
+/// How many pulls may have their comments read or changed at the same time. GitHub discourages concurrent writes,
+/// so this is kept low.
+const COMMENT_WRITE_CONCURRENCY: usize = 4;
+
+/// How many diffs may be read at the same time while a repository is added (see [`Controller::prefetch_diffs`]).
+const DIFF_PREFETCH_CONCURRENCY: usize = 8;
+
+/// How long to wait before reconciling comments of another pull while background work is throttled (see [`Throttle`]).
+const THROTTLED_RECONCILE_PAUSE: std::time::Duration = std::time::Duration::from_secs(1);
+
+/// The period over which new comments in a pull are counted against `notifications.max_comments_per_hour`.
+const COMMENT_BUDGET_WINDOW: chrono::Duration = chrono::Duration::hours(1);
+
+/// When new comments were posted in a pull, oldest first.
+type PostingTimes = Vec<chrono::DateTime<chrono::Utc>>;
+
+/// How often pulls expected to be merged by GitHub are checked on (see [`Controller::expect_merge`]).
+const MERGE_CHECK_INTERVAL: std::time::Duration = std::time::Duration::from_secs(5 * 60);
+
+/// How many times a pull expected to be merged is checked on before giving up, which covers slow merge queues.
+const MERGE_CHECK_ATTEMPTS: usize = 12;
+
+/// The latest update of a pull request, waiting for its debounce window to pass (see [`Controller::debounce`]).
+#[derive(Debug)]
+struct DebouncedUpdate {
+    sequence: u64,
+    pull_request: Box<PullRequest>,
+    delivery_id: Option<String>,
+}
+
+/// A change to a single comment about a conflict, planned by [`Controller::send_updates`].
+#[derive(Debug)]
+enum CommentAction {
+    Post(conflicts::Conflict, String),
+    Update(conflicts::Conflict, IssueComment, String),
+    Retire(conflicts::Conflict, IssueComment),
+}
 
 /// Controller is a representation of a GitHub App, which contains a per-repository cache of
 /// pull requests and corresponding `.diff` files. It is used from the facade, [`super::ControllerHandle`].
@@ -23,15 +81,15 @@ use crate::structs::*;
 /// (for details, see [`ConflictType`]). After that, it leaves comments on the pull request which depends on the changes; typically, that is
 /// a translation, whose owner needs to be made aware of changes they may be missing.
 #[derive(Debug)]
-pub(super) struct Controller {
+pub(super) struct Controller<G: GitHubInterface = Client> {
     /// The event queue with requests coming from the controller handle.
     receiver: mpsc::Receiver<ControllerRequest>,
 
     /// Information about a GitHub app (used to detect own comments).
     app: Option<App>,
 
-    /// GitHub API client -- see [`github::Client`] for details.
-    github: Client,
+    /// GitHub backend -- typically [`Client`], see [`GitHubInterface`] for details.
+    github: G,
 
     /// The cache with pull requests and their diffs.
     memory: memory::Memory,
@@ -41,63 +99,631 @@ pub(super) struct Controller {
 
     /// Controller-specific settings taken from `config.yaml`.
     config: config::Controller,
+
+    /// The record of changes made by the app (see [`audit::AuditLog`]).
+    audit: audit::AuditLog,
+
+    /// The webhook delivery which caused the message being processed, if any.
+    delivery_id: Option<String>,
+
+    /// Conflict comments which failed to be written (see [`retries::RetryQueue`]).
+    retries: retries::RetryQueue,
+
+    /// The latest merged change to every original (see [`crate::history`]).
+    history: ArticleHistory,
+
+    /// How many times the controller has recovered from a panic or a stuck message (see [`Self::recover`]).
+    restarts: Arc<AtomicUsize>,
+
+    /// How many divergences from GitHub have been found (see [`Self::self_check`]).
+    divergences: Arc<AtomicUsize>,
+
+    /// Progress of reading repositories on start-up (see [`Self::init`]).
+    hydration: Hydration,
+
+    /// Recently handled messages (see [`Self::run_forever`]).
+    trace: Trace,
+
+    /// Gives up on messages which take too long (see [`crate::watchdog`]).
+    watchdog: Watchdog,
+
+    /// Whether background work should be slowed down, as the container is short of resources (see `controller.throttling`).
+    throttle: Throttle,
+
+    /// Repositories with more open pulls than `max_pulls_per_repo`, of which only the most recently updated are kept.
+    over_limit: Arc<Mutex<BTreeSet<String>>>,
+
+    /// The number of the next self-check, which picks the pulls to check.
+    self_check_round: usize,
+
+    /// Whether GitHub has refused to write a comment, which may mean that the app information is out of date.
+    app_stale: AtomicBool,
+
+    /// The way back into the event queue, for requests postponed due to GitHub rate limits (see [`Self::postpone`]).
+    requeue: Option<mpsc::WeakSender<ControllerRequest>>,
+
+    /// Signs and checks headers of the app's comments, if `signing_key` is set in the config.
+    signer: Option<RequestValidator>,
+
+    /// Destinations for conflict notifications besides comments (see [`notifiers::Notifiers`]).
+    notifiers: notifiers::Notifiers,
+
+    /// Pull request updates waiting to be applied: full repository name + pull number -> the latest update.
+    debounced: HashMap<(String, i32), DebouncedUpdate>,
+
+    /// The sequence number of the latest debounced update.
+    debounce_sequence: u64,
+
+    /// Pulls which GitHub is expected to merge on its own: full repository name + pull number.
+    expected_merges: Mutex<BTreeSet<(String, i32)>>,
+
+    /// Repositories whose pull requests haven't been read yet (see `controller.hydration`).
+    unhydrated: Mutex<BTreeSet<String>>,
+
+    /// When new comments were posted in pulls during the last hour: full repository name + pull number -> times (see
+    /// `notifications.max_comments_per_hour`).
+    posted_comments: Mutex<HashMap<(String, i32), PostingTimes>>,
+
+    /// Settings committed to repositories, by full repository name (see [`repo_config`]).
+    repo_configs: Mutex<HashMap<String, RepoConfig>>,
 }
 
 impl Controller {
+    /// Create an unitialized controller.
+    pub(super) fn new(
+        receiver: mpsc::Receiver<ControllerRequest>,
+        github: GitHub,
+        app_id: String,
+        private_key: String,
+        config: config::Controller,
+    ) -> Self {
+        let client = Client::new(github, app_id, private_key)
+            .with_fatal_http_errors(config.fatal_http_errors)
+            .with_retry_policies(config.retries);
+        Self::with_backend(receiver, client, config)
+    }
+
+    /// Refresh installation tokens in the background, ahead of their expiration (see [`Client::refresh_tokens`]).
+    pub(super) fn start_token_refresh(&self) {
+        self.github.spawn_token_refresh();
+    }
+}
+
+impl<G: GitHubInterface> Controller<G> {
+    /// Create an unitialized controller on top of an arbitrary GitHub backend.
+    pub(super) fn with_backend(
+        receiver: mpsc::Receiver<ControllerRequest>,
+        github: G,
+        config: config::Controller,
+    ) -> Self {
+        let trace = Trace::default();
+        Self {
+            receiver,
+            app: None,
+            github,
+            memory: memory::Memory::new(),
+            conflicts: conflicts::Storage::default(),
+            audit: audit::AuditLog::new(config.audit_log.clone()),
+            delivery_id: None,
+            retries: retries::RetryQueue::new(config.retry_queue.clone()),
+            history: ArticleHistory::default(),
+            restarts: Arc::default(),
+            divergences: Arc::default(),
+            hydration: Hydration::default(),
+            watchdog: Watchdog::new(trace.clone()),
+            trace,
+            throttle: Throttle::new(config.throttling.clone(), CGroup::current()),
+            over_limit: Arc::default(),
+            self_check_round: 0,
+            app_stale: AtomicBool::new(false),
+            requeue: None,
+            signer: config.signing_key.clone().map(RequestValidator::new),
+            notifiers: notifiers::Notifiers::new(&config.notifiers),
+            debounced: HashMap::new(),
+            debounce_sequence: 0,
+            expected_merges: Mutex::default(),
+            unhydrated: Mutex::default(),
+            posted_comments: Mutex::default(),
+            repo_configs: Mutex::default(),
+            config,
+        }
+    }
+
+    /// Let the controller put postponed requests back into its own queue. Without this, they are dropped.
+    pub(super) fn set_requeue(&mut self, sender: mpsc::WeakSender<ControllerRequest>) {
+        self.requeue = Some(sender);
+    }
+
+    /// Send the request to the controller again after a delay. This is used for events which have hit a secondary
+    /// rate limit (see [`SecondaryRateLimit`]) and would otherwise be lost.
+    fn postpone(&self, request: ControllerRequest, delay: std::time::Duration) {
+        let request = match &self.delivery_id {
+            Some(delivery_id) => ControllerRequest::Delivered {
+                delivery_id: delivery_id.clone(),
+                request: Box::new(request),
+            },
+            None => request,
+        };
+        log::warn!("Postponing a request by {:?} due to a rate limit", delay);
+        if let Err(request) = self.send_later(request, delay) {
+            log::error!(
+                "Can't postpone a request, dropping it instead: {:?}",
+                request
+            );
+        }
+    }
+
+    /// Put a request back into the queue after a delay. Without a way back into the queue, the request is returned.
+    fn send_later(
+        &self,
+        request: ControllerRequest,
+        delay: std::time::Duration,
+    ) -> Result<(), ControllerRequest> {
+        let Some(sender) = self.requeue.as_ref().and_then(|s| s.upgrade()) else {
+            return Err(request);
+        };
+        tokio::spawn(async move {
+            tokio::time::sleep(delay).await;
+            let _ = sender.send(request).await;
+        });
+        Ok(())
+    }
+
+    /// Hold a pull request update until no other updates of the same pull arrive for `debounce_seconds`, so that a burst
+    /// of pushes leads to a single conflict refresh. Returns the update back if it can't be delayed.
+    fn debounce(
+        &mut self,
+        full_repo_name: String,
+        pull_request: Box<PullRequest>,
+    ) -> Option<(String, Box<PullRequest>)> {
+        self.debounce_sequence += 1;
+        let sequence = self.debounce_sequence;
+        let pull_number = pull_request.number;
+        let wakeup = ControllerRequest::DebouncedUpdate {
+            full_repo_name: full_repo_name.clone(),
+            pull_number,
+            sequence,
+        };
+        let delay = std::time::Duration::from_secs(self.config.debounce_seconds);
+        if self.send_later(wakeup, delay).is_err() {
+            return Some((full_repo_name, pull_request));
+        }
+
+        let update = DebouncedUpdate {
+            sequence,
+            pull_request,
+            delivery_id: self.delivery_id.clone(),
+        };
+        if self
+            .debounced
+            .insert((full_repo_name, pull_number), update)
+            .is_some()
+        {
+            log::debug!(
+                "Pull #{}: collapsing the update with the previous one",
+                pull_number
+            );
+        }
+        None
+    }
+
+    /// Apply an update of a pull request, postponing it if GitHub asks to slow down.
+    async fn apply_update(
+        &self,
+        full_repo_name: String,
+        pull_request: Box<PullRequest>,
+        trigger_updates: bool,
+    ) {
+        let pull_number = pull_request.number;
+        if let Err(e) = self
+            .update_pull(&full_repo_name, (*pull_request).clone(), trigger_updates)
+            .await
+        {
+            match SecondaryRateLimit::find(&e) {
+                Some(limit) => self.postpone(
+                    ControllerRequest::PullRequestUpdated {
+                        full_repo_name,
+                        pull_request,
+                        trigger_updates,
+                    },
+                    limit.retry_after,
+                ),
+                None => log::error!(
+                    "Pull #{}: failed to update information and trigger comments: {:?}",
+                    pull_number,
+                    e
+                ),
+            }
+        }
+    }
+
+    /// The counter of recoveries from panics, which can be read while the controller is running.
+    pub(super) fn restarts(&self) -> Arc<AtomicUsize> {
+        self.restarts.clone()
+    }
+
+    /// The counter of divergences found by the self-check, which can be read while the controller is running.
+    pub(super) fn divergences(&self) -> Arc<AtomicUsize> {
+        self.divergences.clone()
+    }
+
+    /// Start-up progress, which can be read while the controller is busy with it.
+    pub(super) fn hydration(&self) -> Hydration {
+        self.hydration.clone()
+    }
+
+    /// The message trace, which can be read while the controller is busy.
+    pub(super) fn trace(&self) -> Trace {
+        self.trace.clone()
+    }
+
+    /// The watchdog, which checks on the controller from the outside.
+    pub(super) fn watchdog(&self) -> Watchdog {
+        self.watchdog.clone()
+    }
+
+    /// The throttle of background work, which can be read while the controller is busy.
+    pub(super) fn throttle(&self) -> Throttle {
+        self.throttle.clone()
+    }
+
+    /// Repositories which have hit the pull limit, which can be read while the controller is running.
+    pub(super) fn over_limit(&self) -> Arc<Mutex<BTreeSet<String>>> {
+        self.over_limit.clone()
+    }
+
     /// Start processing events one at a time. This function blocks until the receiver is destroyed, which happens
     /// on handle destruction automatically.
+    ///
+    /// A panic while handling a message doesn't stop the loop: the controller rebuilds its state and moves on. The same
+    /// happens if the watchdog gives up on a message (see [`crate::watchdog`]).
+    ///
+    /// Every message is numbered and timed in the trace (see [`crate::trace`]).
     pub(super) async fn run_forever(&mut self) {
         while let Some(msg) = self.receiver.recv().await {
-            self.handle_message(msg).await;
+            let delivery_id = match &msg {
+                ControllerRequest::Delivered { delivery_id, .. } => Some(delivery_id.as_str()),
+                _ => None,
+            };
+            let sequence = self
+                .trace
+                .start(msg.kind(), msg.full_repo_name(), delivery_id);
+            let watchdog = self.watchdog.clone();
+            let outcome = tokio::select! {
+                result = AssertUnwindSafe(self.handle_message(msg)).catch_unwind() => match result {
+                    Ok(()) => trace::Outcome::Done,
+                    Err(_) => trace::Outcome::Panicked,
+                },
+                _ = watchdog.given_up(sequence) => trace::Outcome::Abandoned,
+            };
+            self.trace.finish(sequence, outcome);
+            match outcome {
+                trace::Outcome::Panicked => {
+                    self.recover("has panicked while handling a message").await
+                }
+                trace::Outcome::Abandoned => self.recover("has been stuck on a message").await,
+                _ => {}
+            }
+        }
+    }
+
+    /// Start over after a panic or a message given up on, since the caches may have been left half-updated. The state
+    /// is read from GitHub again, as on start-up; if that fails too, the controller carries on with whatever it manages
+    /// to read.
+    async fn recover(&mut self, reason: &str) {
+        let restarts = self.restarts.fetch_add(1, Ordering::Relaxed) + 1;
+        log::error!(
+            "The controller {}, rebuilding its state (restart #{})",
+            reason,
+            restarts
+        );
+        // Everything below is read from GitHub again. A panic may have left the shared caches locked, so they are
+        // emptied regardless.
+        self.memory = memory::Memory::new();
+        self.conflicts = conflicts::Storage::default();
+        self.debounced.clear();
+        self.github.reset();
+        helpers::reset_cache(&self.unhydrated);
+        helpers::reset_cache(&self.over_limit);
+        helpers::reset_cache(&self.repo_configs);
+        // Merge checks still in the queue find nothing to check, and closed pulls are finalized by their events.
+        helpers::reset_cache(&self.expected_merges);
+        // Comments which have been posted stay posted, and the merge history can't be read from GitHub (see
+        // [`crate::history`]), so these are kept as they are.
+        self.posted_comments.clear_poison();
+        self.history.clear_poison();
+        match AssertUnwindSafe(self.init()).catch_unwind().await {
+            Ok(Ok(())) => log::info!("The controller state has been rebuilt"),
+            Ok(Err(e)) => log::error!("Failed to rebuild the controller state: {:?}", e),
+            Err(_) => log::error!("Failed to rebuild the controller state: init() has panicked"),
         }
     }
 
     /// Dispatch the message from a handle to an appropriate method, and possibly return the call result.
     async fn handle_message(&mut self, message: ControllerRequest) {
+        let message = match message {
+            ControllerRequest::Delivered {
+                delivery_id,
+                request,
+            } => {
+                self.delivery_id = Some(delivery_id);
+                *request
+            }
+            message => {
+                self.delivery_id = None;
+                message
+            }
+        };
+        if self.app_stale.swap(false, Ordering::Relaxed) {
+            self.refresh_app().await;
+        }
+
+        if let Some(full_repo_name) = message.full_repo_name() {
+            if self.is_suspended(full_repo_name) {
+                log::info!(
+                    "{}: the installation is suspended, ignoring the request",
+                    full_repo_name
+                );
+                return;
+            }
+            self.hydrate_lazily(full_repo_name).await;
+        }
+
         match message {
+            ControllerRequest::Delivered { .. } => {
+                log::error!("Nested delivery wrappers are not supported, ignoring the message");
+            }
+
             ControllerRequest::Init { reply_to } => {
                 reply_to.send(self.init().await).unwrap();
             }
 
+            ControllerRequest::ExportState { reply_to } => {
+                let _ = reply_to.send(self.export_state());
+            }
+            ControllerRequest::ImportState { snapshot, reply_to } => {
+                let result = self.import_state(*snapshot).await;
+                self.hydration.finish(result.is_ok());
+                reply_to.send(result).unwrap();
+            }
+            ControllerRequest::RateLimits { reply_to } => {
+                let _ = reply_to.send(self.rate_limits().await);
+            }
+            ControllerRequest::Installations { reply_to } => {
+                let _ = reply_to.send(self.installations());
+            }
+            ControllerRequest::RefreshInstallationToken {
+                installation_id,
+                reply_to,
+            } => {
+                let _ = reply_to.send(
+                    self.github
+                        .refresh_installation_token(installation_id)
+                        .await,
+                );
+            }
+            ControllerRequest::ForgetInstallation {
+                installation_id,
+                reply_to,
+            } => {
+                let _ = reply_to.send(self.forget_installation(installation_id));
+            }
+            ControllerRequest::PreviewComment {
+                full_repo_name,
+                conflict,
+                reply_to,
+            } => {
+                let _ = reply_to.send(self.render_conflict(&full_repo_name, &conflict));
+            }
+            ControllerRequest::Simulate {
+                full_repo_name,
+                diff,
+                base_branch,
+                reply_to,
+            } => {
+                let _ =
+                    reply_to.send(self.simulate(&full_repo_name, *diff, base_branch.as_deref()));
+            }
+            ControllerRequest::ArticlePulls {
+                full_repo_name,
+                path,
+                reply_to,
+            } => {
+                let _ = reply_to.send(self.article_pulls(&full_repo_name, &path));
+            }
+            ControllerRequest::ArticleHistory {
+                full_repo_name,
+                reply_to,
+            } => {
+                let history = self
+                    .memory
+                    .has_repository(&full_repo_name)
+                    .then(|| self.history.repository(&full_repo_name));
+                let _ = reply_to.send(history);
+            }
+            ControllerRequest::ConflictGraph {
+                full_repo_name,
+                reply_to,
+            } => {
+                let _ = reply_to.send(self.conflict_graph(&full_repo_name));
+            }
+            ControllerRequest::RetryPendingSyncs => {
+                self.retry_pending_syncs(chrono::Utc::now()).await
+            }
+            ControllerRequest::SelfCheck => {
+                self.self_check().await;
+            }
+            ControllerRequest::Compact => {
+                self.compact();
+            }
+            ControllerRequest::RefreshApp => {
+                self.refresh_app().await;
+            }
+
             ControllerRequest::PullRequestCreated {
                 full_repo_name,
                 pull_request,
                 trigger_updates,
             } => {
                 let pull_number = pull_request.number;
-                self.upsert_pull(&full_repo_name, *pull_request, trigger_updates)
+                if let Err(e) = self
+                    .upsert_pull(&full_repo_name, (*pull_request).clone(), trigger_updates)
                     .await
-                    .unwrap_or_else(|e| {
-                        log::error!(
+                {
+                    match SecondaryRateLimit::find(&e) {
+                        Some(limit) => self.postpone(
+                            ControllerRequest::PullRequestCreated {
+                                full_repo_name,
+                                pull_request,
+                                trigger_updates,
+                            },
+                            limit.retry_after,
+                        ),
+                        None => log::error!(
                             "Pull #{}: failed to add information and trigger comments: {:?}",
                             pull_number,
                             e
-                        );
-                    })
+                        ),
+                    }
+                }
             }
 
             ControllerRequest::PullRequestUpdated {
                 full_repo_name,
                 pull_request,
                 trigger_updates,
+            } => {
+                if trigger_updates && self.config.debounce_seconds > 0 {
+                    if let Some((full_repo_name, pull_request)) =
+                        self.debounce(full_repo_name, pull_request)
+                    {
+                        self.apply_update(full_repo_name, pull_request, true).await;
+                    }
+                } else {
+                    self.apply_update(full_repo_name, pull_request, trigger_updates)
+                        .await;
+                }
+            }
+            ControllerRequest::DebouncedUpdate {
+                full_repo_name,
+                pull_number,
+                sequence,
+            } => {
+                let key = (full_repo_name, pull_number);
+                if self
+                    .debounced
+                    .get(&key)
+                    .is_some_and(|u| u.sequence == sequence)
+                {
+                    let update = self.debounced.remove(&key).unwrap();
+                    self.delivery_id = update.delivery_id;
+                    self.apply_update(key.0, update.pull_request, true).await;
+                }
+            }
+            ControllerRequest::PullRequestReopened {
+                full_repo_name,
+                pull_request,
             } => {
                 let pull_number = pull_request.number;
-                self.update_pull(&full_repo_name, *pull_request, trigger_updates)
+                if let Err(e) = self
+                    .reopen_pull(&full_repo_name, (*pull_request).clone())
                     .await
-                    .unwrap_or_else(|e| {
-                        log::error!(
-                            "Pull #{}: failed to update information and trigger comments: {:?}",
+                {
+                    match SecondaryRateLimit::find(&e) {
+                        Some(limit) => self.postpone(
+                            ControllerRequest::PullRequestReopened {
+                                full_repo_name,
+                                pull_request,
+                            },
+                            limit.retry_after,
+                        ),
+                        None => log::error!(
+                            "Pull #{}: failed to reopen and trigger comments: {:?}",
                             pull_number,
                             e
-                        );
-                    })
+                        ),
+                    }
+                }
             }
             ControllerRequest::PullRequestClosed {
                 full_repo_name,
                 pull_request,
             } => {
+                // An update which is still waiting would bring the pull back otherwise.
+                if self
+                    .debounced
+                    .remove(&(full_repo_name.clone(), pull_request.number))
+                    .is_some()
+                {
+                    log::debug!(
+                        "Pull #{}: dropping the pending update, since the pull is closed",
+                        pull_request.number
+                    );
+                }
                 self.finalize_pull(&full_repo_name, *pull_request).await;
             }
+            ControllerRequest::PullRequestReviewed {
+                full_repo_name,
+                pull_number,
+                review,
+            } => self.record_review(&full_repo_name, pull_number, &review),
+
+            ControllerRequest::CommandReceived {
+                full_repo_name,
+                pull_number,
+                comment,
+                command,
+            } => self
+                .run_command(&full_repo_name, pull_number, &comment, command.clone())
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!(
+                        "Pull #{}: failed to run command {:?}: {:?}",
+                        pull_number,
+                        command,
+                        e
+                    );
+                }),
+
+            ControllerRequest::MergeExpected {
+                full_repo_name,
+                pull_request,
+            } => self.expect_merge(&full_repo_name, *pull_request).await,
+            ControllerRequest::MergeCancelled {
+                full_repo_name,
+                pull_number,
+            } => {
+                self.expected_merges
+                    .lock()
+                    .unwrap()
+                    .remove(&(full_repo_name, pull_number));
+            }
+            ControllerRequest::MergeCheck {
+                full_repo_name,
+                pull_number,
+                attempt,
+            } => {
+                self.check_merges(&full_repo_name, pull_number, attempt)
+                    .await
+            }
+
+            ControllerRequest::RescanRequested {
+                full_repo_name,
+                base_branch,
+                pull_number,
+            } => self
+                .rescan(&full_repo_name, base_branch.as_deref(), pull_number)
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!("{}: rescan failed: {:?}", full_repo_name, e);
+                }),
+
+            ControllerRequest::RepoConfigChanged { full_repo_name } => self
+                .reload_repo_config(&full_repo_name)
+                .await
+                .unwrap_or_else(|e| {
+                    log::error!("{}: failed to apply new settings: {:?}", full_repo_name, e);
+                }),
 
             ControllerRequest::InstallationCreated { installation } => {
                 let iid = installation.id;
@@ -110,6 +736,25 @@ impl Controller {
             ControllerRequest::InstallationDeleted { installation } => {
                 self.delete_installation(*installation)
             }
+            ControllerRequest::InstallationSuspended { installation } => {
+                self.suspend_installation(*installation)
+            }
+            ControllerRequest::InstallationUnsuspended { installation } => {
+                let iid = installation.id;
+                self.unsuspend_installation(*installation)
+                    .await
+                    .unwrap_or_else(|e| {
+                        log::error!("Installation #{}: resumption failed: {:?}", iid, e);
+                    });
+            }
+
+            ControllerRequest::InstallationTargetRenamed {
+                installation_id,
+                account,
+            } => {
+                self.rename_installation_target(installation_id, account)
+                    .await;
+            }
 
             ControllerRequest::InstallationRepositoriesAdded {
                 installation_id,
@@ -124,423 +769,3312 @@ impl Controller {
             } => {
                 self.remove_repositories(installation_id, &repositories);
             }
-        }
-    }
 
-    /// Create an unitialized controller.
-    pub(super) fn new(
-        receiver: mpsc::Receiver<ControllerRequest>,
-        github: GitHub,
-        app_id: String,
-        private_key: String,
-        config: config::Controller,
-    ) -> Self {
-        Self {
-            receiver,
-            app: None,
-            github: Client::new(github, app_id, private_key),
-            memory: memory::Memory::new(),
-            conflicts: conflicts::Storage::default(),
-            config,
+            ControllerRequest::RepositoryRenamed {
+                installation_id,
+                repository,
+            } => {
+                self.rename_repository(installation_id, repository).await;
+            }
         }
     }
 
     /// Build the in-memory pull request cache on start-up. This will consume a lot of GitHub API quota,
     /// but fighting a stale database cache is left as an exercise for another day.
     async fn init(&mut self) -> Result<()> {
+        self.hydration.begin();
+        let result = self.hydrate().await;
+        self.hydration.finish(result.is_ok());
+        result
+    }
+
+    /// Read the app, its installations and their pull requests, reporting the progress along the way.
+    async fn hydrate(&mut self) -> Result<()> {
         self.app = Some(self.github.read_app().await?);
         log::info!("GitHub application: {:?}", self.app.as_ref().unwrap());
 
         let installations = self.github.read_installations().await?;
         log::info!("Active installations: {:?}", installations);
+        self.hydration.set_installations(installations.len());
         for i in installations {
             let id = i.id;
             match self.add_installation(i).await {
                 Err(e) => log::error!("Failed to add installation {}: {}", id, e),
                 Ok(_) => log::info!("Processed repositories from installation {}", id),
             }
+            self.hydration.finish_installation();
         }
-        Ok(())
-    }
 
-    /// Add an installation and fetch pull requests (one installation may have several repos).
-    async fn add_installation(&self, installation: Installation) -> Result<()> {
-        let iid = installation.id;
-        self.github
-            .read_and_cache_installation_repos(installation)
-            .await?;
-        self.add_repositories(iid, self.github.cached_repositories(iid))
-            .await;
+        if !self.config.reconcile_on_startup {
+            return Ok(());
+        }
+        for full_repo_name in self.memory.repositories() {
+            self.reconcile_repository(full_repo_name.as_str()).await;
+        }
         Ok(())
     }
 
-    /// Add several repositories the app just got an access to.
-    async fn add_repositories(&self, installation_id: i64, repositories: Vec<Repository>) {
-        for r in repositories {
-            log::debug!(
-                "Adding repository {:?} for installation #{}",
-                r,
-                installation_id
-            );
-            self.add_repository(&r).await.unwrap_or_else(|e| {
+    /// Reconcile comments of every known pull in the repository (see [`Self::reconcile_comments`]). While background work
+    /// is throttled, pulls are reconciled with pauses in between.
+    async fn reconcile_repository(&self, full_repo_name: &str) {
+        for pull_number in self.memory.pull_numbers(full_repo_name) {
+            if self.throttle.check() {
+                tokio::time::sleep(THROTTLED_RECONCILE_PAUSE).await;
+            }
+            if let Err(e) = self.reconcile_comments(full_repo_name, pull_number).await {
                 log::error!(
-                    "Repository {:?} for installation #{}: addition failed: {:?}",
-                    r,
-                    installation_id,
+                    "Failed to reconcile comments in {}#{}: {:?}",
+                    full_repo_name,
+                    pull_number,
                     e
                 );
-            });
+            }
         }
     }
 
-    /// Add a repository and fetch its pull requests.
-    async fn add_repository(&self, r: &Repository) -> Result<()> {
-        for p in self.github.read_pulls(&r.full_name).await? {
-            self.upsert_pull(&r.full_name, p, false).await?;
+    /// Match comments left before a restart against conflicts found from scratch on start-up.
+    ///
+    /// Incomplete translations of already merged pulls can't be found again, so they are restored from their comments.
+    /// Comments about conflicts between open pulls which no longer exist are retired. Digests keep their own list
+    /// of conflicts and are left alone.
+    async fn reconcile_comments(&self, full_repo_name: &str, pull_number: i32) -> Result<()> {
+        let known: BTreeSet<_> = self
+            .conflicts
+            .by_trigger(full_repo_name, pull_number)
+            .iter()
+            .map(|c| c.key())
+            .collect();
+
+        for comment in self
+            .github
+            .read_comments(full_repo_name, pull_number)
+            .await?
+        {
+            if !self.is_own_comment(&comment) || comment.body.contains(comments::RESOLVED_TEMPLATE)
+            {
+                continue;
+            }
+            let Some(conflict) =
+                conflicts::Conflict::from_comment(pull_number, &comment.body, self.signer.as_ref())
+            else {
+                continue;
+            };
+            if known.contains(&conflict.key()) {
+                continue;
+            }
+
+            let original_repo = conflict.original_repo.as_deref().unwrap_or(full_repo_name);
+            let original_is_open = self.memory.contains(original_repo, conflict.original);
+            match conflict.kind {
+                ConflictType::IncompleteTranslation if !original_is_open => {
+                    log::info!(
+                        "Restoring conflict from comment #{} in {}#{}",
+                        comment.id,
+                        full_repo_name,
+                        pull_number
+                    );
+                    if let Some(c) = self.conflicts.upsert(full_repo_name, &conflict) {
+                        self.audit(
+                            full_repo_name,
+                            audit::Action::ConflictStored { conflict: c },
+                        );
+                    }
+                }
+                // Overlaps with closed pulls are not tracked, but their comments are kept as is.
+                ConflictType::Overlap if !original_is_open => {}
+                _ => {
+                    if self.posts_comments(full_repo_name) {
+                        self.retire_comment(full_repo_name, pull_number, &comment)
+                            .await?;
+                    } else {
+                        log::debug!(
+                            "Would retire orphaned comment #{} in {}#{}",
+                            comment.id,
+                            full_repo_name,
+                            pull_number
+                        );
+                    }
+                }
+            }
         }
         Ok(())
     }
 
-    /// Remove an installation from cache and forget about its pull requests.
-    fn delete_installation(&self, installation: Installation) {
+    /// Known installations with their repositories, and how many pulls of each are kept in memory.
+    fn installations(&self) -> Vec<InstallationSummary> {
+        let mut installations: Vec<_> = self
+            .github
+            .cached_installations()
+            .into_iter()
+            .map(|i| {
+                let mut repositories: Vec<_> = self
+                    .github
+                    .cached_repositories(i.id)
+                    .into_iter()
+                    .map(|r| RepositorySummary {
+                        pulls: self.memory.pull_numbers(&r.full_name).len(),
+                        hydrated: !self.unhydrated.lock().unwrap().contains(&r.full_name),
+                        full_name: r.full_name,
+                    })
+                    .collect();
+                repositories.sort_by(|a, b| a.full_name.cmp(&b.full_name));
+                InstallationSummary {
+                    installation_id: i.id,
+                    account: i.account.login,
+                    suspended: i.suspended_at.is_some(),
+                    quarantined: self.github.is_quarantined(i.id),
+                    token_expires_at: self.github.token_expires_at(i.id),
+                    cannot_comment: self
+                        .github
+                        .installation_permissions(i.id)
+                        .is_some_and(|p| !github::can_write_comments(&p)),
+                    repositories,
+                }
+            })
+            .collect();
+        installations.sort_by_key(|i| i.installation_id);
+        installations
+    }
+
+    /// Drop an installation and its repositories from memory, as if it was deleted, but without uninstalling the app.
+    fn forget_installation(&self, installation_id: i64) -> bool {
+        let Some(installation) = self
+            .github
+            .cached_installations()
+            .into_iter()
+            .find(|i| i.id == installation_id)
+        else {
+            return false;
+        };
+        log::info!(
+            "Installation #{} ({}): forgetting it on request",
+            installation_id,
+            installation.account.login
+        );
+        self.delete_installation(installation);
+        true
+    }
+
+    fn conflict_graph(&self, full_repo_name: &str) -> Option<graph::ConflictGraph> {
+        let pulls: Vec<_> = self.memory.pulls(full_repo_name)?.into_values().collect();
+        let conflicts = self
+            .conflicts
+            .all()
+            .remove(full_repo_name)
+            .unwrap_or_default();
+        Some(graph::ConflictGraph::new(
+            full_repo_name,
+            &pulls,
+            &conflicts,
+        ))
+    }
+
+    /// Compare a hypothetical pull, opened just now with changes from `diff`, with open pulls of the repository the same way
+    /// [`Self::refresh_conflicts`] does, but without storing or reporting anything. The pull has number 0, and is the
+    /// trigger of every conflict. Pulls from other repositories of a group are not considered.
+    fn simulate(
+        &self,
+        full_repo_name: &str,
+        diff: DiffSummary,
+        base_branch: Option<&str>,
+    ) -> Option<Vec<conflicts::Conflict>> {
+        let mut pulls: Vec<PullRequest> = self
+            .memory
+            .pulls(full_repo_name)?
+            .into_values()
+            .filter(|p| p.diff.is_some() && base_branch.is_none_or(|b| p.base.branch == b))
+            .collect();
+        pulls.sort_by_key(|pr| pr.created_at);
+
+        let settings = self.repo_config(full_repo_name);
+        let filters = settings.path_filters();
+        let layout = settings.layout.unwrap_or_default();
+        let now = chrono::Utc::now();
+        let mut simulated = PullRequest {
+            id: 0,
+            number: 0,
+            state: "open".to_string(),
+            title: String::new(),
+            user: Actor::default(),
+            html_url: String::new(),
+            created_at: now,
+            updated_at: now,
+            diff: Some(diff),
+            base: PullRequestBranch::default(),
+            head: PullRequestBranch::default(),
+            merged_at: None,
+            merged: false,
+            mergeable_state: None,
+            labels: Vec::new(),
+            author_association: String::new(),
+        };
+        let mut out = Vec::new();
+        for other_pull in pulls {
+            // Pulls with different bases never conflict, and the hypothetical one targets whatever the other does.
+            simulated.base = other_pull.base.clone();
+            out.extend(conflicts::compare_pulls_with(
+                &simulated,
+                &other_pull,
+                &filters,
+                layout,
+            ));
+        }
+        Some(out)
+    }
+
+    /// Open pulls which change an article, looked up via the article index of [`memory::Memory`]. The article is given by its
+    /// path (`wiki/Article`) or a path of any of its files (`wiki/Article/en.md`), and is matched regardless of case.
+    fn article_pulls(&self, full_repo_name: &str, path: &str) -> Option<Vec<ArticlePull>> {
+        let layout = self.repo_config(full_repo_name).layout.unwrap_or_default();
+        let path = path.trim_matches('/');
+        let article = layout
+            .article(path)
+            .map(|a| a.path)
+            .unwrap_or_else(|| path.to_string());
+        // Files are indexed by their directories, which is the article itself only if it has a folder of its own.
+        let directory = match layout {
+            conflicts::ArticleLayout::Folder => article.clone(),
+            conflicts::ArticleLayout::Suffix => std::path::Path::new(&article)
+                .parent()
+                .map(|p| p.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+        };
+        let is_article_file = |file_path: &str| {
+            layout
+                .article(file_path)
+                .is_some_and(|a| a.path.eq_ignore_ascii_case(&article))
+        };
+
+        let pulls = self.memory.pulls_in_directory(full_repo_name, &directory)?;
+        Some(
+            pulls
+                .into_iter()
+                .filter_map(|p| {
+                    let files: Vec<String> = p
+                        .diff
+                        .iter()
+                        .flat_map(|d| &d.files)
+                        .filter(|f| {
+                            is_article_file(&f.path) || f.target_path().is_some_and(is_article_file)
+                        })
+                        .map(|f| f.path.clone())
+                        .collect();
+                    if files.is_empty() {
+                        return None;
+                    }
+                    Some(ArticlePull {
+                        number: p.number,
+                        title: p.title,
+                        html_url: p.html_url,
+                        author: p.user.login,
+                        base_branch: p.base.branch,
+                        updated_at: p.updated_at,
+                        files,
+                    })
+                })
+                .collect(),
+        )
+    }
+
+    /// Dump installations, pulls and conflicts into a snapshot, which can be used in place of [`Self::init`] later.
+    fn export_state(&self) -> state::Snapshot {
+        let mut installations: Vec<_> = self
+            .github
+            .cached_installations()
+            .into_iter()
+            .map(|i| state::InstallationSnapshot {
+                repositories: self.github.cached_repositories(i.id),
+                installation: i,
+            })
+            .collect();
+        installations.sort_by_key(|i| i.installation.id);
+
+        let pulls = self
+            .memory
+            .repositories()
+            .into_iter()
+            .map(|repo| {
+                let mut pulls = Vec::new();
+                self.memory
+                    .for_each_pull(repo.as_str(), |p| pulls.push(state::PullSnapshot::from(p)));
+                (repo.to_string(), pulls)
+            })
+            .collect();
+
+        let conflicts = self
+            .conflicts
+            .all()
+            .into_iter()
+            .map(|(repo, mut conflicts)| {
+                conflicts.sort();
+                (repo, conflicts)
+            })
+            .collect();
+
+        state::Snapshot {
+            version: state::SNAPSHOT_VERSION,
+            created_at: chrono::Utc::now(),
+            installations,
+            pulls,
+            conflicts,
+            article_history: self.history.all(),
+        }
+    }
+
+    /// Build the cache from a snapshot instead of reading everything from GitHub. Only the app information (needed to recognize
+    /// own comments) and installation tokens are requested.
+    async fn import_state(&mut self, snapshot: state::Snapshot) -> Result<()> {
+        self.app = Some(self.github.read_app().await?);
+        log::info!(
+            "GitHub application: {:?}, importing state from {}",
+            self.app.as_ref().unwrap(),
+            snapshot.created_at
+        );
+
+        for i in snapshot.installations {
+            self.github
+                .restore_installation(i.installation, i.repositories);
+        }
+        for (full_repo_name, pulls) in snapshot.pulls {
+            for p in pulls {
+                self.memory.insert_pull(&full_repo_name, p.into_pull());
+            }
+        }
+        for (full_repo_name, conflicts) in snapshot.conflicts {
+            for c in conflicts {
+                self.conflicts.upsert(&full_repo_name, &c);
+            }
+        }
+        for (full_repo_name, entries) in snapshot.article_history {
+            self.history.restore(&full_repo_name, entries);
+        }
+
+        // Unlike `init()`, nothing has requested installation tokens yet.
+        self.github.refresh_tokens().await;
+
+        // Settings files are not a part of the snapshot, and may have changed since it was taken anyway.
+        for i in self.github.cached_installations() {
+            if i.suspended_at.is_none() {
+                for r in self.github.cached_repositories(i.id) {
+                    self.load_repo_config(&r.full_name).await;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the rate limit budget of every installation. Installations whose budget can't be read are skipped,
+    /// and so are suspended and quarantined ones (see [`Client::is_quarantined`]).
+    async fn rate_limits(&self) -> Vec<InstallationRateLimits> {
+        let mut installations = self.github.cached_installations();
+        installations.sort_by_key(|i| i.id);
+
+        let mut out = Vec::new();
+        for i in installations {
+            if i.suspended_at.is_some() {
+                continue;
+            }
+            if self.github.is_quarantined(i.id) {
+                log::debug!(
+                    "Installation {} is quarantined, skipping its rate limits",
+                    i.id
+                );
+                continue;
+            }
+            match self.github.read_rate_limits(i.id).await {
+                Ok(limits) => out.push(InstallationRateLimits {
+                    installation_id: i.id,
+                    account: i.account.login,
+                    core: limits.resources.core,
+                    search: limits.resources.search,
+                }),
+                Err(e) => log::error!(
+                    "Failed to read rate limits of installation {}: {:?}",
+                    i.id,
+                    e
+                ),
+            }
+        }
+        out
+    }
+
+    /// Add an installation and fetch pull requests (one installation may have several repos).
+    ///
+    /// Installations from accounts which are not allowed by the config are ignored, and optionally uninstalled.
+    async fn add_installation(&self, installation: Installation) -> Result<()> {
+        let iid = installation.id;
+        if !self
+            .config
+            .access
+            .allows_account(&installation.account.login)
+        {
+            log::warn!(
+                "Installation #{}: account {} is not allowed, ignoring it",
+                iid,
+                installation.account.login
+            );
+            if self.config.access.uninstall_denied {
+                log::warn!("Installation #{}: uninstalling the app", iid);
+                self.github.delete_app_installation(iid).await?;
+            }
+            return Ok(());
+        }
+
+        if installation.suspended_at.is_some() {
+            log::warn!(
+                "Installation #{}: suspended, its repositories will be read once it's unsuspended",
+                iid
+            );
+            self.github.restore_installation(installation, Vec::new());
+            return Ok(());
+        }
+
+        self.github
+            .read_and_cache_installation_repos(installation)
+            .await?;
+        self.check_permissions(iid);
+        self.add_repositories(iid, self.github.cached_repositories(iid))
+            .await;
+        Ok(())
+    }
+
+    /// Complain about an installation whose permissions don't allow posting comments, so that it's noticed right away
+    /// rather than through failing comment writes. The installation is still handled as usual.
+    fn check_permissions(&self, installation_id: i64) {
+        let Some(permissions) = self.github.installation_permissions(installation_id) else {
+            return;
+        };
+        if github::can_write_comments(&permissions) {
+            return;
+        }
+        if self.config.post_comments {
+            log::error!(
+                "Installation #{}: comments can't be posted without a write access to pull requests or issues \
+                (permissions: {:?}). Grant it in the app settings, and accept the new permissions for the installation",
+                installation_id,
+                permissions
+            );
+        } else {
+            log::warn!(
+                "Installation #{}: no write access to pull requests or issues (permissions: {:?})",
+                installation_id,
+                permissions
+            );
+        }
+    }
+
+    /// Stop handling requests about the installation's repositories. Its pulls and conflicts are kept as they are.
+    fn suspend_installation(&self, mut installation: Installation) {
+        log::warn!("Installation #{}: suspended", installation.id);
+        installation
+            .suspended_at
+            .get_or_insert_with(chrono::Utc::now);
         let repos = self.github.cached_repositories(installation.id);
-        self.github.remove_installation(&installation);
-        self.remove_repositories(installation.id, &repos);
+        self.github.restore_installation(installation, repos);
     }
 
-    /// Remove muliple repositories which the app has just lost its access to.
-    fn remove_repositories(&self, installation_id: i64, repositories: &[Repository]) {
-        for r in repositories {
+    /// Resume handling requests about the installation's repositories. Events which have happened during the suspension
+    /// are lost, so the repositories are read from scratch, as on start-up.
+    async fn unsuspend_installation(&self, mut installation: Installation) -> Result<()> {
+        log::info!("Installation #{}: unsuspended", installation.id);
+        installation.suspended_at = None;
+        let iid = installation.id;
+        self.delete_installation(installation.clone());
+        self.add_installation(installation).await?;
+        if self.config.reconcile_on_startup {
+            for r in self.github.cached_repositories(iid) {
+                self.reconcile_repository(&r.full_name).await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Follow the rename of an account which the app is installed on. Full names of its repositories change along with
+    /// the account, so they are renamed as well, keeping their pulls and conflicts.
+    async fn rename_installation_target(&self, installation_id: i64, account: Actor) {
+        let Some(mut installation) = self
+            .github
+            .cached_installations()
+            .into_iter()
+            .find(|i| i.id == installation_id)
+        else {
+            log::warn!(
+                "Installation #{}: account {} is renamed, but the installation isn't known, ignoring it",
+                installation_id,
+                account.login
+            );
+            return;
+        };
+        let previous = installation.account.login.clone();
+        if previous == account.login {
+            return;
+        }
+
+        log::info!(
+            "Installation #{}: account {} is now known as {}",
+            installation_id,
+            previous,
+            account.login
+        );
+        let repos = self.github.cached_repositories(installation_id);
+        installation.account = account.clone();
+        self.github
+            .restore_installation(installation.clone(), repos.clone());
+
+        if !self.config.access.allows_account(&account.login) {
+            log::warn!(
+                "Installation #{}: account {} is not allowed under its new name, ignoring it. \
+                Add it to the config to keep the installation",
+                installation_id,
+                account.login
+            );
+            self.delete_installation(installation);
+            return;
+        }
+
+        let prefix = format!("{}/", previous);
+        for r in repos {
+            let Some(name) = r.full_name.strip_prefix(&prefix) else {
+                continue;
+            };
+            let renamed = Repository {
+                full_name: format!("{}/{}", account.login, name),
+                owner: r.owner.as_ref().map(|_| account.clone()),
+                ..r.clone()
+            };
+            self.rename_repository(installation_id, renamed).await;
+        }
+    }
+
+    /// Read the settings file of a repository and remember it. A missing or broken file means default settings.
+    async fn load_repo_config(&self, full_repo_name: &str) -> RepoConfig {
+        let settings = match self
+            .github
+            .read_file(full_repo_name, repo_config::PATH)
+            .await
+        {
+            Ok(None) => RepoConfig::default(),
+            Ok(Some(contents)) => match RepoConfig::parse(&contents) {
+                Ok(settings) => {
+                    log::info!(
+                        "{}: using settings from {}: {:?}",
+                        full_repo_name,
+                        repo_config::PATH,
+                        settings
+                    );
+                    settings
+                }
+                Err(e) => {
+                    log::warn!(
+                        "{}: failed to parse {}, using default settings: {:?}",
+                        full_repo_name,
+                        repo_config::PATH,
+                        e
+                    );
+                    RepoConfig::default()
+                }
+            },
+            Err(e) => {
+                log::warn!(
+                    "{}: failed to read {}, using default settings: {:?}",
+                    full_repo_name,
+                    repo_config::PATH,
+                    e
+                );
+                RepoConfig::default()
+            }
+        };
+        self.repo_configs
+            .lock()
+            .unwrap()
+            .insert(full_repo_name.to_string(), settings.clone());
+        settings
+    }
+
+    /// Read the settings file of a repository again after it has changed, and check open pulls under the new settings.
+    async fn reload_repo_config(&self, full_repo_name: &str) -> Result<()> {
+        if !self.is_known_repository(full_repo_name) {
             log::debug!(
-                "Removing repository {:?} for installation #{}",
+                "{}: the repository is not known, ignoring changes to its settings",
+                full_repo_name
+            );
+            return Ok(());
+        }
+        let previous = self.own_repo_config(full_repo_name);
+        if self.load_repo_config(full_repo_name).await == previous {
+            return Ok(());
+        }
+        self.rescan(full_repo_name, None, None).await
+    }
+
+    /// Settings from the repository's own file, without the account's.
+    fn own_repo_config(&self, full_repo_name: &str) -> RepoConfig {
+        self.repo_configs
+            .lock()
+            .unwrap()
+            .get(full_repo_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Effective settings of the repository: the account's, overridden by the repository's own.
+    fn repo_config(&self, full_repo_name: &str) -> RepoConfig {
+        self.config
+            .account_settings(full_repo_name)
+            .merged_with(&self.own_repo_config(full_repo_name))
+    }
+
+    /// Whether comments (and other changes, such as labels) are written to the repository, or only logged.
+    ///
+    /// Account settings may enable comments disabled globally, but a repository can only disable them for itself.
+    fn posts_comments(&self, full_repo_name: &str) -> bool {
+        self.config
+            .account_settings(full_repo_name)
+            .post_comments
+            .unwrap_or(self.config.post_comments)
+            && self
+                .own_repo_config(full_repo_name)
+                .post_comments
+                .unwrap_or(true)
+    }
+
+    fn is_known_repository(&self, full_repo_name: &str) -> bool {
+        self.github.cached_installations().into_iter().any(|i| {
+            self.github
+                .cached_repositories(i.id)
+                .iter()
+                .any(|r| r.full_name == full_repo_name)
+        })
+    }
+
+    /// Render a comment, signing its header if the config says so.
+    fn render(&self, item: &impl ToMarkdown) -> String {
+        let body = item.to_markdown();
+        match &self.signer {
+            Some(key) => comments::sign_comment(&body, key),
+            None => body,
+        }
+    }
+
+    /// Read the header of a comment. Comments with forged headers are treated as if they had none.
+    fn header(&self, body: &str) -> Option<CommentHeader> {
+        CommentHeader::from_comment(body, self.signer.as_ref())
+    }
+
+    /// Whether the repository belongs to a suspended installation.
+    fn is_suspended(&self, full_repo_name: &str) -> bool {
+        self.github
+            .cached_installations()
+            .into_iter()
+            .filter(|i| i.suspended_at.is_some())
+            .any(|i| {
+                self.github
+                    .cached_repositories(i.id)
+                    .iter()
+                    .any(|r| r.full_name == full_repo_name)
+            })
+    }
+
+    /// Add several repositories the app just got an access to. Repositories which are not allowed by the config are skipped,
+    /// and so are archived ones.
+    async fn add_repositories(&self, installation_id: i64, repositories: Vec<Repository>) {
+        let (allowed, denied): (Vec<_>, Vec<_>) = repositories
+            .into_iter()
+            .partition(|r| !r.archived && self.config.access.allows_repository(&r.full_name));
+        if !denied.is_empty() {
+            log::warn!(
+                "Installation #{}: ignoring repositories which are archived or not allowed: {:?}",
+                installation_id,
+                denied.iter().map(|r| &r.full_name).collect::<Vec<_>>()
+            );
+            self.github.remove_repositories(installation_id, &denied);
+        }
+
+        self.github
+            .cache_repositories(installation_id, allowed.clone());
+        self.hydration.add_repositories(allowed.len());
+        for r in allowed {
+            self.hydration.start_repository(&r.full_name);
+            log::debug!(
+                "Adding repository {:?} for installation #{}",
                 r,
                 installation_id
             );
-            self.memory.drop_repository(&r.full_name);
-            self.conflicts.remove_repository(&r.full_name);
+            self.add_repository(&r).await.unwrap_or_else(|e| {
+                log::error!(
+                    "Repository {:?} for installation #{}: addition failed: {:?}",
+                    r,
+                    installation_id,
+                    e
+                );
+            });
+            self.hydration.finish_repository();
         }
-        self.github
-            .remove_repositories(installation_id, repositories);
     }
 
-    /// Purge a pull request from memory, excluding it from conflict detection.
-    /// If a request contains original articles and has just been merged, send notifications to pull requests with translations
-    /// (https://github.com/TicClick/observatory/issues/12 has the rationale).
-    ///
-    /// This should be done only when a pull request is closed or merged.
-    async fn finalize_pull(&self, full_repo_name: &str, mut closed_pull: PullRequest) {
-        log::info!(
-            "Finalizing pull #{} (merged: {})",
-            closed_pull.number,
-            closed_pull.is_merged()
-        );
-        if closed_pull.is_merged() {
-            if let Some(pulls_map) = self.memory.pulls(full_repo_name) {
-                if let Some(p) = pulls_map.get(&closed_pull.number) {
-                    closed_pull = p.clone();
-                } else {
-                    closed_pull.diff = self
-                        .github
-                        .read_pull_diff(full_repo_name, closed_pull.number)
-                        .await
-                        .ok();
+    /// Add a repository and fetch its pull requests. With `controller.hydration` set to `lazy`, they are only fetched
+    /// once the first request about the repository arrives (see [`Self::hydrate_lazily`]).
+    async fn add_repository(&self, r: &Repository) -> Result<()> {
+        if self.config.hydration == config::HydrationMode::Lazy {
+            log::debug!(
+                "{}: pull requests will be read on the first request about the repository",
+                r.full_name
+            );
+            self.unhydrated.lock().unwrap().insert(r.full_name.clone());
+            return Ok(());
+        }
+        self.read_repository(&r.full_name).await
+    }
+
+    /// Read settings and pull requests of a repository.
+    ///
+    /// With `graphql` enabled, pull requests and their files are read in bulk, with a fallback to the REST API (one request per pull,
+    /// several at a time, see [`Self::prefetch_diffs`]).
+    async fn read_repository(&self, full_repo_name: &str) -> Result<()> {
+        self.load_repo_config(full_repo_name).await;
+        if self.config.graphql {
+            match self.github.read_pulls_graphql(full_repo_name).await {
+                Ok(pulls) => {
+                    let pulls = pulls.into_iter().map(|(p, _)| p).collect();
+                    let pulls = self.limit_pulls(full_repo_name, pulls);
+                    if self.is_unread(full_repo_name) {
+                        return self.bootstrap_conflicts(full_repo_name, pulls).await;
+                    }
+                    for p in pulls {
+                        self.process_pull(full_repo_name, p, false).await?;
+                        self.hydration.pull_hydrated();
+                    }
+                    return Ok(());
+                }
+                Err(e) => log::warn!(
+                    "Failed to read pulls from {} via GraphQL, falling back to REST: {:?}",
+                    full_repo_name,
+                    e
+                ),
+            }
+        }
+
+        let pulls = self.github.read_pulls(full_repo_name).await?;
+        let pulls = self.limit_pulls(full_repo_name, pulls);
+        let mut diffs = self.prefetch_diffs(full_repo_name, &pulls).await;
+        if self.is_unread(full_repo_name)
+            && self.config.access.allows_repository(full_repo_name)
+            && pulls.iter().all(|p| diffs.contains_key(&p.number))
+        {
+            let pulls = pulls
+                .into_iter()
+                .map(|mut p| {
+                    p.set_diff(diffs.remove(&p.number).unwrap());
+                    p
+                })
+                .collect();
+            return self.bootstrap_conflicts(full_repo_name, pulls).await;
+        }
+        for p in pulls {
+            let diff = diffs.remove(&p.number);
+            self.upsert_pull_with_diff(full_repo_name, p, false, diff)
+                .await?;
+            self.hydration.pull_hydrated();
+        }
+        Ok(())
+    }
+
+    /// Whether none of the pulls of a repository are known yet, so that they can be compared all at once (see
+    /// [`Self::bootstrap_conflicts`]).
+    fn is_unread(&self, full_repo_name: &str) -> bool {
+        self.memory
+            .pulls(full_repo_name)
+            .is_none_or(|pulls| pulls.is_empty())
+    }
+
+    /// Store pulls of a repository which is read for the first time, together with conflicts between them, comparing
+    /// every pair of pulls which share articles once. Pulls are treated as if they were added one by one in the given
+    /// order, so the later pull of every pair is the trigger. Nothing is posted, like with any other pull read on start-up.
+    /// Pulls need to come with their diffs.
+    async fn bootstrap_conflicts(
+        &self,
+        full_repo_name: &str,
+        pulls: Vec<PullRequest>,
+    ) -> Result<()> {
+        for p in &pulls {
+            self.memory.insert_pull(full_repo_name, p.clone());
+        }
+
+        let settings = self.repo_config(full_repo_name);
+        let filters = settings.path_filters();
+        let layout = settings.layout.unwrap_or_default();
+        let order: HashMap<i32, usize> = pulls
+            .iter()
+            .enumerate()
+            .map(|(i, p)| (p.number, i))
+            .collect();
+        let mut found = Vec::new();
+        for (i, p) in pulls.iter().enumerate() {
+            if !self.needs_comparison(full_repo_name, p) {
+                continue;
+            }
+            for other in self
+                .memory
+                .pulls_sharing_articles(full_repo_name, p)
+                .into_values()
+                .filter(|other| order.get(&other.number).is_some_and(|j| *j < i))
+            {
+                found.extend(conflicts::compare_pulls_with(p, &other, &filters, layout));
+            }
+        }
+        let stored = self.conflicts.upsert_many(full_repo_name, &found);
+        log::info!(
+            "{}: {} pull(s) read, {} conflict(s) found between them",
+            full_repo_name,
+            pulls.len(),
+            stored.len()
+        );
+        for conflict in stored {
+            self.audit(full_repo_name, audit::Action::ConflictStored { conflict });
+        }
+
+        for p in &pulls {
+            self.refresh_group_conflicts(full_repo_name, p, ConflictType::Overlap, false)
+                .await?;
+            self.hydration.pull_hydrated();
+        }
+        Ok(())
+    }
+
+    /// Read diffs of pulls which are about to be added, several at a time, so that they can be compared one after another
+    /// without waiting for GitHub in between. Pulls with a stored diff of the same head are skipped. Failures are left for
+    /// [`Self::upsert_pull`] to deal with, and once GitHub reports a secondary rate limit, no more diffs are requested.
+    /// While background work is throttled, diffs are read one at a time.
+    async fn prefetch_diffs(
+        &self,
+        full_repo_name: &str,
+        pulls: &[PullRequest],
+    ) -> HashMap<i32, unidiff::PatchSet> {
+        if !self.config.access.allows_repository(full_repo_name) {
+            return HashMap::new();
+        }
+        let concurrency = if self.throttle.check() {
+            1
+        } else {
+            DIFF_PREFETCH_CONCURRENCY
+        };
+        let rate_limited = AtomicBool::new(false);
+        let rate_limited = &rate_limited;
+        let to_read: Vec<i32> = pulls
+            .iter()
+            .filter(|p| self.stored_diff(full_repo_name, p).is_none())
+            .map(|p| p.number)
+            .collect();
+        futures::stream::iter(to_read)
+            .map(|pull_number| async move {
+                if rate_limited.load(Ordering::Relaxed) {
+                    return None;
+                }
+                match self
+                    .github
+                    .read_pull_diff(full_repo_name, pull_number)
+                    .await
+                {
+                    Ok(diff) => {
+                        self.hydration.diff_fetched();
+                        Some((pull_number, diff))
+                    }
+                    Err(e) => {
+                        if SecondaryRateLimit::find(&e).is_some() {
+                            rate_limited.store(true, Ordering::Relaxed);
+                        }
+                        log::debug!(
+                            "Pull #{}: failed to prefetch the diff: {:?}",
+                            pull_number,
+                            e
+                        );
+                        None
+                    }
+                }
+            })
+            .buffer_unordered(concurrency)
+            .filter_map(|d| async move { d })
+            .collect()
+            .await
+    }
+
+    /// Read pull requests of a lazily added repository before handling the first request about it. Other repositories of
+    /// its groups are read as well, since pulls are compared across them. A repository which fails to be read is tried
+    /// again with the next request.
+    async fn hydrate_lazily(&self, full_repo_name: &str) {
+        let mut names = vec![full_repo_name.to_string()];
+        names.extend(self.config.sibling_repositories(full_repo_name));
+        for name in names {
+            if !self.unhydrated.lock().unwrap().remove(&name) {
+                continue;
+            }
+            log::info!("{}: reading pull requests on the first request", name);
+            if let Err(e) = self.read_repository(&name).await {
+                log::error!("{}: failed to read pull requests: {:?}", name, e);
+                self.unhydrated.lock().unwrap().insert(name);
+                continue;
+            }
+            if self.config.reconcile_on_startup {
+                self.reconcile_repository(&name).await;
+            }
+        }
+    }
+
+    /// Keep the `max_pulls_per_repo` most recently updated pulls out of all open ones, if there are more.
+    fn limit_pulls(&self, full_repo_name: &str, mut pulls: Vec<PullRequest>) -> Vec<PullRequest> {
+        let limit = self.config.max_pulls_per_repo;
+        if limit == 0 || pulls.len() <= limit {
+            return pulls;
+        }
+        self.note_over_limit(full_repo_name, pulls.len());
+        pulls.sort_by_key(|p| std::cmp::Reverse((p.updated_at, p.number)));
+        pulls.truncate(limit);
+        pulls
+    }
+
+    /// Forget the least recently updated pulls of a repository which don't fit into `max_pulls_per_repo`, along with their
+    /// conflicts. Comments about them stay as they are. Returns whether `pull_number` is still kept.
+    fn forget_pulls_over_limit(&self, full_repo_name: &str, pull_number: i32) -> bool {
+        let limit = self.config.max_pulls_per_repo;
+        if limit == 0 {
+            return true;
+        }
+        let excess = self.memory.pulls_beyond(full_repo_name, limit);
+        if excess.is_empty() {
+            return true;
+        }
+        self.note_over_limit(full_repo_name, limit + excess.len());
+        for p in &excess {
+            log::debug!(
+                "Pull #{}: forgetting it to stay within the limit of {} pulls in {}",
+                p.number,
+                limit,
+                full_repo_name
+            );
+            self.memory.remove_pull(full_repo_name, p);
+            self.conflicts
+                .remove_conflicts_by_pull(full_repo_name, p.number);
+        }
+        !excess.iter().any(|p| p.number == pull_number)
+    }
+
+    fn note_over_limit(&self, full_repo_name: &str, count: usize) {
+        if self
+            .over_limit
+            .lock()
+            .unwrap()
+            .insert(full_repo_name.to_string())
+        {
+            log::warn!(
+                "{}: {} open pulls exceed the limit of {}, only the most recently updated ones are tracked",
+                full_repo_name,
+                count,
+                self.config.max_pulls_per_repo
+            );
+        }
+    }
+
+    /// Remove an installation from cache and forget about its pull requests.
+    fn delete_installation(&self, installation: Installation) {
+        let repos = self.github.cached_repositories(installation.id);
+        self.github.remove_installation(&installation);
+        self.remove_repositories(installation.id, &repos);
+    }
+
+    /// Remove muliple repositories which the app has just lost its access to.
+    fn remove_repositories(&self, installation_id: i64, repositories: &[Repository]) {
+        for r in repositories {
+            log::debug!(
+                "Removing repository {:?} for installation #{}",
+                r,
+                installation_id
+            );
+            self.memory.drop_repository(&r.full_name);
+            self.conflicts.remove_repository(&r.full_name);
+            self.retries.remove_repository(&r.full_name);
+            self.history.remove_repository(&r.full_name);
+            self.repo_configs.lock().unwrap().remove(&r.full_name);
+            self.unhydrated.lock().unwrap().remove(&r.full_name);
+        }
+        self.github
+            .remove_repositories(installation_id, repositories);
+    }
+
+    /// Move everything known about a repository to its new name, after it has been renamed or transferred.
+    ///
+    /// A repository which wasn't known before is added, and one whose new name is not allowed by the config is removed.
+    async fn rename_repository(&self, installation_id: i64, repository: Repository) {
+        let Some(previous) = self
+            .github
+            .rename_repository(installation_id, repository.clone())
+        else {
+            log::info!(
+                "Repository {} is renamed, but wasn't known before, adding it",
+                repository.full_name
+            );
+            self.add_repositories(installation_id, vec![repository])
+                .await;
+            return;
+        };
+        if previous.full_name == repository.full_name {
+            return;
+        }
+
+        log::info!(
+            "Repository {} is now known as {}",
+            previous.full_name,
+            repository.full_name
+        );
+        self.memory
+            .rename_repository(&previous.full_name, &repository.full_name);
+        self.conflicts
+            .rename_repository(&previous.full_name, &repository.full_name);
+        self.retries
+            .rename_repository(&previous.full_name, &repository.full_name);
+        self.history
+            .rename_repository(&previous.full_name, &repository.full_name);
+        let mut repo_configs = self.repo_configs.lock().unwrap();
+        if let Some(settings) = repo_configs.remove(&previous.full_name) {
+            repo_configs.insert(repository.full_name.clone(), settings);
+        }
+        drop(repo_configs);
+        let mut unhydrated = self.unhydrated.lock().unwrap();
+        if unhydrated.remove(&previous.full_name) {
+            unhydrated.insert(repository.full_name.clone());
+        }
+        drop(unhydrated);
+
+        if !self.config.access.allows_repository(&repository.full_name) {
+            log::warn!(
+                "Installation #{}: repository {} is not allowed under its new name, removing it",
+                installation_id,
+                repository.full_name
+            );
+            self.remove_repositories(installation_id, &[repository]);
+        }
+    }
+
+    /// Refresh a pull which GitHub is about to merge on its own (with a merge queue or auto-merge), and check on it
+    /// periodically until it's closed. Pulls merged by a merge queue don't always come with the usual events, and would
+    /// otherwise be left around along with their conflicts.
+    async fn expect_merge(&self, full_repo_name: &str, pull: PullRequest) {
+        let pull_number = pull.number;
+        if let Err(e) = self.upsert_pull(full_repo_name, pull, false).await {
+            log::error!(
+                "Pull #{}: failed to refresh a pull which is about to be merged: {:?}",
+                pull_number,
+                e
+            );
+        }
+        if self
+            .expected_merges
+            .lock()
+            .unwrap()
+            .insert((full_repo_name.to_string(), pull_number))
+        {
+            self.schedule_merge_check(full_repo_name, pull_number, 1);
+        }
+    }
+
+    fn schedule_merge_check(&self, full_repo_name: &str, pull_number: i32, attempt: usize) {
+        let request = ControllerRequest::MergeCheck {
+            full_repo_name: full_repo_name.to_string(),
+            pull_number: Some(pull_number),
+            attempt,
+        };
+        if self.send_later(request, MERGE_CHECK_INTERVAL).is_err() {
+            log::warn!(
+                "Pull #{}: can't schedule a check for the merge, relying on events instead",
+                pull_number
+            );
+        }
+    }
+
+    /// Finalize pulls expected to be merged which have been closed in the meantime. A check of a single pull is repeated
+    /// until it's closed, or [`MERGE_CHECK_ATTEMPTS`] run out.
+    async fn check_merges(&self, full_repo_name: &str, pull_number: Option<i32>, attempt: usize) {
+        let pull_numbers: Vec<i32> = self
+            .expected_merges
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(r, n)| r == full_repo_name && pull_number.is_none_or(|p| p == *n))
+            .map(|(_, n)| *n)
+            .collect();
+        for n in pull_numbers {
+            let pull = match self.github.read_pull(full_repo_name, n).await {
+                Ok(pull) => pull,
+                Err(e) => {
+                    log::warn!("Pull #{}: failed to check on the merge: {:?}", n, e);
+                    continue;
+                }
+            };
+            if pull.state != "open" {
+                self.expected_merges
+                    .lock()
+                    .unwrap()
+                    .remove(&(full_repo_name.to_string(), n));
+                if self.memory.contains(full_repo_name, n) {
+                    log::info!("Pull #{}: closed without an event, finalizing it", n);
+                    self.finalize_pull(full_repo_name, pull).await;
+                }
+                continue;
+            }
+            if pull_number.is_none() {
+                continue;
+            }
+            if attempt < MERGE_CHECK_ATTEMPTS {
+                self.schedule_merge_check(full_repo_name, n, attempt + 1);
+            } else {
+                log::debug!("Pull #{}: still open, no longer checking on the merge", n);
+                self.expected_merges
+                    .lock()
+                    .unwrap()
+                    .remove(&(full_repo_name.to_string(), n));
+            }
+        }
+    }
+
+    /// Purge a pull request from memory, excluding it from conflict detection.
+    /// If a request contains original articles and has just been merged, send notifications to pull requests with translations
+    /// (https://github.com/TicClick/observatory/issues/12 has the rationale).
+    ///
+    /// This should be done only when a pull request is closed or merged.
+    async fn finalize_pull(&self, full_repo_name: &str, mut closed_pull: PullRequest) {
+        log::info!(
+            "Finalizing pull #{} (merged: {})",
+            closed_pull.number,
+            closed_pull.is_merged()
+        );
+        self.expected_merges
+            .lock()
+            .unwrap()
+            .remove(&(full_repo_name.to_string(), closed_pull.number));
+        if closed_pull.is_merged() && self.memory.has_repository(full_repo_name) {
+            if let Some(p) = self.memory.pull(full_repo_name, closed_pull.number) {
+                // The stored copy has the diff, and the event has the merge time.
+                closed_pull = PullRequest {
+                    merged_at: closed_pull.merged_at,
+                    ..p
+                };
+            } else {
+                if let Ok(diff) = self
+                    .github
+                    .read_pull_diff(full_repo_name, closed_pull.number)
+                    .await
+                {
+                    closed_pull.set_diff(diff);
+                }
+            }
+            self.history.record(
+                full_repo_name,
+                &closed_pull,
+                self.repo_config(full_repo_name).layout.unwrap_or_default(),
+            );
+
+            let candidates = self.conflict_candidates(full_repo_name, &closed_pull);
+            let (pending_updates, conflicts_to_remove) = self
+                .refresh_conflicts(
+                    full_repo_name,
+                    candidates,
+                    &closed_pull,
+                    ConflictType::IncompleteTranslation,
+                    true,
+                )
+                .await;
+            if !pending_updates.is_empty() {
+                let _ = self
+                    .send_updates(pending_updates, conflicts_to_remove, full_repo_name)
+                    .await;
+            }
+            if let Err(e) = self
+                .refresh_group_conflicts(
+                    full_repo_name,
+                    &closed_pull,
+                    ConflictType::IncompleteTranslation,
+                    true,
+                )
+                .await
+            {
+                log::error!(
+                    "Pull #{}: failed to notify pulls in other repositories of the group: {:?}",
+                    closed_pull.number,
+                    e
+                );
+            }
+        }
+
+        let affected_originals = self.originals_related_to(full_repo_name, closed_pull.number);
+        self.memory.remove_pull(full_repo_name, &closed_pull);
+        if self.audit.is_enabled() {
+            let mut removed = self
+                .conflicts
+                .by_trigger(full_repo_name, closed_pull.number);
+            removed.extend(
+                self.conflicts
+                    .by_original(full_repo_name, closed_pull.number),
+            );
+            for conflict in removed {
+                self.audit(full_repo_name, audit::Action::ConflictRemoved { conflict });
+            }
+        }
+        self.conflicts
+            .remove_conflicts_by_pull(full_repo_name, closed_pull.number);
+        for sibling in self.config.sibling_repositories(full_repo_name) {
+            for conflict in self.conflicts.remove_conflicts_by_original_in(
+                &sibling,
+                full_repo_name,
+                closed_pull.number,
+            ) {
+                self.audit(&sibling, audit::Action::ConflictRemoved { conflict });
+            }
+        }
+
+        if let Err(e) = self
+            .update_translations_in_flight(full_repo_name, affected_originals)
+            .await
+        {
+            log::error!(
+                "Pull #{}: failed to update summaries in original pulls: {:?}",
+                closed_pull.number,
+                e
+            );
+        }
+    }
+
+    /// Start over with a pull request which has been reopened, possibly long after being closed.
+    ///
+    /// Whatever is known about the pull may be outdated (for example, if its closure was missed), and its counterparts
+    /// may have been merged or closed since. The cached copy of the pull is dropped in favour of a fresh diff, and conflicts
+    /// with pulls which are no longer tracked are removed (with their comments in the reopened pull) before the pull is
+    /// compared against others from scratch.
+    async fn reopen_pull(&self, full_repo_name: &str, pull: PullRequest) -> Result<()> {
+        log::info!(
+            "Pull #{}: reopened, discarding its cached state",
+            pull.number
+        );
+        self.memory.remove_pull(full_repo_name, &pull);
+        let orphaned = self
+            .conflicts
+            .remove_orphaned(full_repo_name, pull.number, |n| {
+                self.memory.pull(full_repo_name, n).is_some()
+            });
+        let mut to_remove: HashMap<i32, Vec<conflicts::Conflict>> = HashMap::new();
+        for conflict in orphaned {
+            log::info!(
+                "Pull #{}: dropping a stale conflict {:?}",
+                pull.number,
+                conflict
+            );
+            self.retries.complete(full_repo_name, &conflict);
+            self.audit(
+                full_repo_name,
+                audit::Action::ConflictRemoved {
+                    conflict: conflict.clone(),
+                },
+            );
+            if conflict.trigger == pull.number {
+                to_remove
+                    .entry(conflict.trigger)
+                    .or_default()
+                    .push(conflict);
+            }
+        }
+        if !to_remove.is_empty() {
+            self.send_updates(HashMap::new(), to_remove, full_repo_name)
+                .await?;
+        }
+        self.upsert_pull(full_repo_name, pull, true).await
+    }
+
+    /// Keep track of maintainer approvals, which affect notifications according to `notifications.approved`.
+    ///
+    /// Only reviews which arrive while the app is running are known: approvals made before start-up are not read.
+    fn record_review(&self, full_repo_name: &str, pull_number: i32, review: &PullRequestReview) {
+        if !commands::MAINTAINER_ASSOCIATIONS.contains(&review.author_association.as_str()) {
+            return;
+        }
+        let approved = match review.state.to_lowercase().as_str() {
+            "approved" => true,
+            "changes_requested" | "dismissed" => false,
+            // Plain comments don't change the verdict.
+            _ => return,
+        };
+        log::debug!(
+            "Pull #{}: {} {} it",
+            pull_number,
+            review.user.login,
+            if approved {
+                "approved"
+            } else {
+                "no longer approves"
+            }
+        );
+        self.memory
+            .set_approval(full_repo_name, pull_number, review.user.id, approved);
+    }
+
+    /// Adjust a conflict comment for the pull: welcome first-time contributors if the repository asks for it, and handle
+    /// approved pulls according to `notifications.approved`.
+    /// Returns `None` if the comment should not be posted at all (only applies to new comments).
+    fn comment_body_for(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        body: String,
+        is_new: bool,
+    ) -> Option<String> {
+        let body = self.welcome_first_time_contributor(full_repo_name, pull_number, body);
+        if !self.memory.is_approved(full_repo_name, pull_number) {
+            return Some(body);
+        }
+        match self.config.notifications.approved {
+            config::ApprovedStyle::Notify => Some(body),
+            config::ApprovedStyle::Suppress => (!is_new).then_some(body),
+            config::ApprovedStyle::Tag => {
+                Some(format!("{}\n\n{}", body, comments::APPROVED_TEMPLATE))
+            }
+        }
+    }
+
+    /// Open a comment with a friendlier note if the pull is its author's first one (see
+    /// `welcome_first_time_contributors` in [`RepoConfig`]).
+    fn welcome_first_time_contributor(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        body: String,
+    ) -> String {
+        let settings = self.repo_config(full_repo_name);
+        if !settings.welcome_first_time_contributors.unwrap_or(false) {
+            return body;
+        }
+        match self.memory.pull(full_repo_name, pull_number) {
+            Some(p) if p.is_from_first_time_contributor() => {
+                comments::with_preamble(&body, &comments::welcome(&settings.guidelines))
+            }
+            _ => body,
+        }
+    }
+
+    /// Return numbers of original pulls which have translations depending on them, and are related to `pull_number`
+    /// (either it is an original, or a translation of one).
+    fn originals_related_to(&self, full_repo_name: &str, pull_number: i32) -> BTreeSet<i32> {
+        self.conflicts
+            .by_trigger(full_repo_name, pull_number)
+            .into_iter()
+            .chain(self.conflicts.by_original(full_repo_name, pull_number))
+            .filter(|c| c.kind == ConflictType::IncompleteTranslation && c.original_repo.is_none())
+            .map(|c| c.original)
+            .collect()
+    }
+
+    /// Maintain a single summary comment in each of the original pulls, listing open translations which depend on them.
+    /// The comment is removed once there are no such translations left.
+    ///
+    /// This only happens if `notify_originals` is set in the config.
+    async fn update_translations_in_flight(
+        &self,
+        full_repo_name: &str,
+        originals: BTreeSet<i32>,
+    ) -> Result<()> {
+        if !self.config.notify_originals {
+            return Ok(());
+        }
+
+        for original in originals {
+            if !self.memory.contains(full_repo_name, original) {
+                continue;
+            }
+
+            let translations: Vec<_> = self
+                .conflicts
+                .by_original(full_repo_name, original)
+                .into_iter()
+                .filter(|c| c.kind == ConflictType::IncompleteTranslation && !c.dismissed)
+                .map(|c| {
+                    (
+                        self.github.github().pull_url(full_repo_name, c.trigger),
+                        c.file_set,
+                    )
+                })
+                .collect();
+
+            let existing_comment = self
+                .github
+                .read_comments(full_repo_name, original)
+                .await?
+                .into_iter()
+                .filter(|c| self.is_own_comment(c))
+                .find(|c| {
+                    self.header(&c.body).is_some_and(|h| {
+                        h.pull_number == original
+                            && h.conflict_type == ConflictType::TranslationsInFlight
+                    })
+                });
+
+            let pull_url = self.github.github().pull_url(full_repo_name, original);
+            let result = match (existing_comment, translations.is_empty()) {
+                (None, true) => Ok(()),
+                (Some(comment), true) => {
+                    if self.posts_comments(full_repo_name) {
+                        self.delete_comment(full_repo_name, original, comment.id)
+                            .await
+                    } else {
+                        log::debug!(
+                            "Would delete translations summary #{} in {}",
+                            comment.id,
+                            pull_url
+                        );
+                        Ok(())
+                    }
+                }
+                (existing_comment, false) => {
+                    let body = self.render(&conflicts::TranslationsInFlight {
+                        original,
+                        translations,
+                    });
+                    if existing_comment.as_ref().is_some_and(|c| c.body == body) {
+                        Ok(())
+                    } else if !self.posts_comments(full_repo_name) {
+                        log::debug!("Would post or update translations summary in {}", pull_url);
+                        Ok(())
+                    } else if let Some(comment) = existing_comment {
+                        self.update_comment(full_repo_name, original, comment.id, body)
+                            .await
+                    } else {
+                        self.post_comment(full_repo_name, original, body).await
+                    }
+                }
+            };
+            if let Err(e) = result {
+                log::error!(
+                    "Failed to update translations summary in {}: {:?}",
+                    pull_url,
+                    e
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Compare the new pull with existing ones for conflicts:
+    /// - Known conflicts (same kind + same file set) are skipped, otherwise memory is updated.
+    /// - Conflicts that don't occur anymore are removed from cache, with subsequent comment removal.
+    /// - If `notify` is set, new, changed and resolved conflicts of `kind_to_match` are sent to [`notifiers`].
+    async fn refresh_conflicts(
+        &self,
+        full_repo_name: &str,
+        pulls_map: HashMap<i32, PullRequest>,
+        new_pull: &PullRequest,
+        kind_to_match: ConflictType,
+        notify: bool,
+    ) -> (
+        HashMap<i32, Vec<conflicts::Conflict>>,
+        HashMap<i32, Vec<conflicts::Conflict>>,
+    ) {
+        log::info!(
+            "Running conflict refresh procedure for pull #{}, looking for conflict type {:?}",
+            new_pull.number,
+            kind_to_match
+        );
+        let mut pulls: Vec<PullRequest> = pulls_map
+            .into_values()
+            .filter(|other| other.number != new_pull.number)
+            .collect();
+        pulls.sort_by_key(|pr| pr.created_at);
+
+        let mut pending_updates: HashMap<i32, Vec<conflicts::Conflict>> = HashMap::new();
+        let mut conflicts_to_remove: HashMap<i32, Vec<conflicts::Conflict>> = HashMap::new();
+        let mut notifications = Vec::new();
+        let settings = self.repo_config(full_repo_name);
+        let filters = settings.path_filters();
+        for other_pull in pulls {
+            let conflicts = conflicts::compare_pulls_with(
+                new_pull,
+                &other_pull,
+                &filters,
+                settings.layout.unwrap_or_default(),
+            );
+            if !conflicts.is_empty() {
+                log::info!(
+                    "Pull #{}: found conflicts with #{}: {:?}",
+                    new_pull.number,
+                    other_pull.number,
+                    conflicts
+                )
+            }
+
+            // Note: after a conflict disappears, any interfering updates to the original pull will flip the roles:
+            // the pull which triggered the new conflict will be considered an original. This is a scenario rare enough
+            // (think indecisive people bringing changes in and out), but one that we should consider and have written down.
+            // Also, it's simpler than maintaining a cache of "inactive" conflicts, at least for now.
+            // Related test: test_new_comment_is_posted_after_removal_in_different_pull
+
+            let removed_conflicts = self.conflicts.remove_missing(
+                full_repo_name,
+                None,
+                other_pull.number,
+                new_pull.number,
+                &conflicts,
+            );
+
+            for removed in removed_conflicts {
+                self.audit(
+                    full_repo_name,
+                    audit::Action::ConflictRemoved {
+                        conflict: removed.clone(),
+                    },
+                );
+                if removed.kind == kind_to_match
+                    && !removed.dismissed
+                    && self.warrants_comment(&removed)
+                {
+                    notifications.push(self.notification(full_repo_name, &removed, true));
+                }
+                conflicts_to_remove
+                    .entry(removed.trigger)
+                    .or_default()
+                    .push(removed);
+            }
+
+            // This always triggers notifications for `IncompleteTranslation` conflicts -- this is intended,
+            // since this function is called when they're merged. `Overlap` conflicts may not require an update if their
+            // contents are identical.
+            for conflict in conflicts {
+                // Remove conflicts related to translations getting merged if their overlap with other PRs doesn't consists of translations.
+                // See: https://github.com/TicClick/observatory/issues/25.
+                if conflict.kind == kind_to_match
+                    && kind_to_match == ConflictType::IncompleteTranslation
+                    && conflict.file_set.iter().all(|f| f.ends_with("en.md"))
+                    && conflict.trigger == new_pull.number
+                {
+                    conflicts_to_remove
+                        .entry(conflict.trigger)
+                        .or_default()
+                        .push(conflict.clone());
+
+                    continue;
+                }
+
+                let upserted = self.conflicts.upsert(full_repo_name, &conflict.clone());
+                if let Some(stored) = &upserted {
+                    self.audit(
+                        full_repo_name,
+                        audit::Action::ConflictStored {
+                            conflict: stored.clone(),
+                        },
+                    );
+                }
+                if self.conflicts.is_dismissed(full_repo_name, &conflict) {
+                    log::debug!(
+                        "Pull #{}: conflict {:?} was dismissed, not reporting it",
+                        new_pull.number,
+                        conflict.key()
+                    );
+                    continue;
+                }
+                if !self.warrants_comment(&conflict) {
+                    log::debug!(
+                        "Pull #{}: conflict {:?} is not worth reporting (severity {:?}, same author: {})",
+                        new_pull.number,
+                        conflict.key(),
+                        conflict.severity,
+                        conflict.same_author
+                    );
+                    // The conflict may have been more severe before, in which case its comment needs to go.
+                    if upserted.is_some() {
+                        conflicts_to_remove
+                            .entry(conflict.trigger)
+                            .or_default()
+                            .push(conflict);
+                    }
+                    continue;
+                }
+
+                match upserted {
+                    Some(updated_conflict) => {
+                        if updated_conflict.kind == kind_to_match {
+                            notifications.push(self.notification(
+                                full_repo_name,
+                                &updated_conflict,
+                                false,
+                            ));
+                            pending_updates
+                                .entry(updated_conflict.trigger)
+                                .or_default()
+                                .push(updated_conflict);
+                        }
+                    }
+                    None => {
+                        if conflict.kind == kind_to_match {
+                            pending_updates
+                                .entry(conflict.trigger)
+                                .or_default()
+                                .push(conflict);
+                        }
+                    }
+                }
+            }
+        }
+
+        log::info!(
+            "Result of conflict refresh for pull #{}, type {:?}: SAVING new conflicts {:?}, REMOVING conflicts {:?}",
+            new_pull.number, kind_to_match, pending_updates, conflicts_to_remove
+        );
+        if notify {
+            self.notifiers.send(&notifications).await;
+        }
+        (pending_updates, conflicts_to_remove)
+    }
+
+    /// Compare a pull with pulls from other repositories of its groups (see `controller.repo_groups`), the same way
+    /// [`Self::refresh_conflicts`] does within a repository. Conflicts are stored in the repository of their trigger, and
+    /// comments there are updated if `trigger_updates` is set.
+    async fn refresh_group_conflicts(
+        &self,
+        full_repo_name: &str,
+        new_pull: &PullRequest,
+        kind_to_match: ConflictType,
+        trigger_updates: bool,
+    ) -> Result<()> {
+        let siblings = self.config.sibling_repositories(full_repo_name);
+        if siblings.is_empty() {
+            return Ok(());
+        }
+        let settings = self.repo_config(full_repo_name);
+        let filters = settings.path_filters();
+        let layout = settings.layout.unwrap_or_default();
+
+        // Repository -> trigger pull -> conflicts.
+        let mut pending_updates: HashMap<String, HashMap<i32, Vec<conflicts::Conflict>>> =
+            HashMap::new();
+        let mut conflicts_to_remove: HashMap<String, HashMap<i32, Vec<conflicts::Conflict>>> =
+            HashMap::new();
+        let mut notifications = Vec::new();
+        for sibling in siblings {
+            let mut pulls: Vec<PullRequest> = self
+                .memory
+                .pulls(&sibling)
+                .unwrap_or_default()
+                .into_values()
+                .collect();
+            pulls.sort_by_key(|pr| pr.created_at);
+            for other_pull in pulls {
+                // The newer pull is always compared against the older one, so that an overlap keeps its direction
+                // regardless of which side is updated.
+                let conflicts = if (other_pull.created_at, other_pull.number)
+                    > (new_pull.created_at, new_pull.number)
+                {
+                    conflicts::compare_pulls_across(
+                        &sibling,
+                        &other_pull,
+                        full_repo_name,
+                        new_pull,
+                        &filters,
+                        layout,
+                    )
+                } else {
+                    conflicts::compare_pulls_across(
+                        full_repo_name,
+                        new_pull,
+                        &sibling,
+                        &other_pull,
+                        &filters,
+                        layout,
+                    )
+                };
+                if !conflicts.is_empty() {
+                    log::info!(
+                        "Pull #{}: found conflicts with {}#{}: {:?}",
+                        new_pull.number,
+                        sibling,
+                        other_pull.number,
+                        conflicts
+                    )
+                }
+
+                let removed_here = self.conflicts.remove_missing(
+                    full_repo_name,
+                    Some(&sibling),
+                    other_pull.number,
+                    new_pull.number,
+                    &conflicts,
+                );
+                let removed_there = self.conflicts.remove_missing(
+                    &sibling,
+                    Some(full_repo_name),
+                    new_pull.number,
+                    other_pull.number,
+                    &conflicts,
+                );
+                let removed = removed_here
+                    .into_iter()
+                    .map(|c| (full_repo_name.to_string(), c))
+                    .chain(removed_there.into_iter().map(|c| (sibling.clone(), c)));
+                for (repo, removed) in removed {
+                    self.audit(
+                        &repo,
+                        audit::Action::ConflictRemoved {
+                            conflict: removed.clone(),
+                        },
+                    );
+                    if removed.kind == kind_to_match
+                        && !removed.dismissed
+                        && self.warrants_comment(&removed)
+                    {
+                        notifications.push(self.notification(&repo, &removed, true));
+                    }
+                    conflicts_to_remove
+                        .entry(repo)
+                        .or_default()
+                        .entry(removed.trigger)
+                        .or_default()
+                        .push(removed);
+                }
+
+                for conflict in conflicts {
+                    // Conflicts are stored next to their trigger, which is in the repository other than the original's.
+                    let repo = if conflict.original_repo.as_deref() == Some(full_repo_name) {
+                        sibling.clone()
+                    } else {
+                        full_repo_name.to_string()
+                    };
+                    let upserted = self.conflicts.upsert(&repo, &conflict);
+                    if let Some(stored) = &upserted {
+                        self.audit(
+                            &repo,
+                            audit::Action::ConflictStored {
+                                conflict: stored.clone(),
+                            },
+                        );
+                    }
+                    if conflict.kind != kind_to_match
+                        || self.conflicts.is_dismissed(&repo, &conflict)
+                    {
+                        continue;
+                    }
+                    if !self.warrants_comment(&conflict) {
+                        if upserted.is_some() {
+                            conflicts_to_remove
+                                .entry(repo)
+                                .or_default()
+                                .entry(conflict.trigger)
+                                .or_default()
+                                .push(conflict);
+                        }
+                        continue;
+                    }
+                    if let Some(updated) = &upserted {
+                        notifications.push(self.notification(&repo, updated, false));
+                    }
+                    pending_updates
+                        .entry(repo)
+                        .or_default()
+                        .entry(conflict.trigger)
+                        .or_default()
+                        .push(upserted.unwrap_or(conflict));
+                }
+            }
+        }
+
+        if !trigger_updates {
+            return Ok(());
+        }
+        self.notifiers.send(&notifications).await;
+        let repos: BTreeSet<String> = pending_updates
+            .keys()
+            .chain(conflicts_to_remove.keys())
+            .cloned()
+            .collect();
+        for repo in repos {
+            self.send_updates(
+                pending_updates.remove(&repo).unwrap_or_default(),
+                conflicts_to_remove.remove(&repo).unwrap_or_default(),
+                &repo,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    fn notification(
+        &self,
+        full_repo_name: &str,
+        c: &conflicts::Conflict,
+        resolved: bool,
+    ) -> notifiers::Notification {
+        notifiers::Notification {
+            full_repo_name: full_repo_name.to_string(),
+            conflict: c.clone(),
+            trigger_url: self.github.github().pull_url(full_repo_name, c.trigger),
+            resolved,
+        }
+    }
+
+    /// Check whether a conflict should be reported according to `notifications.min_severity` and
+    /// `notifications.self_overlap`.
+    fn warrants_comment(&self, c: &conflicts::Conflict) -> bool {
+        c.kind != ConflictType::Overlap
+            || (c.severity >= self.config.notifications.min_severity
+                && !(c.same_author
+                    && self.config.notifications.self_overlap
+                        == config::SelfOverlapStyle::Suppress))
+    }
+
+    /// Render a conflict comment, noting overlaps between pulls of the same author according to `notifications.self_overlap`,
+    /// and mentioning maintainers of the translation's language in incomplete translations (see `mentions` in [`RepoConfig`]).
+    fn render_conflict(&self, full_repo_name: &str, c: &conflicts::Conflict) -> String {
+        let mut body = self.render(c);
+        if c.same_author
+            && self.config.notifications.self_overlap == config::SelfOverlapStyle::Soften
+        {
+            body = format!("{}\n\n{}", body, comments::SELF_OVERLAP_TEMPLATE);
+        }
+        if c.kind == ConflictType::IncompleteTranslation {
+            let history = self.originals_history(full_repo_name, c);
+            if !history.is_empty() {
+                body = format!(
+                    "{}\n\n{} {}",
+                    body,
+                    comments::ARTICLE_HISTORY_TEMPLATE,
+                    history.join(", ")
+                );
+            }
+            let mentions = self.translation_maintainers(full_repo_name, c);
+            if !mentions.is_empty() {
+                body = format!(
+                    "{}\n\n{} {}",
+                    body,
+                    comments::MENTIONS_TEMPLATE,
+                    mentions.join(" ")
+                );
+            }
+        }
+        body
+    }
+
+    /// The latest merged changes to the originals of an incomplete translation, if `notifications.article_history` is set.
+    /// Originals without known merges are skipped.
+    fn originals_history(&self, full_repo_name: &str, c: &conflicts::Conflict) -> Vec<String> {
+        if !self.config.notifications.article_history {
+            return Vec::new();
+        }
+        let original_repo = c.original_repo.as_deref().unwrap_or(full_repo_name);
+        c.file_set
+            .iter()
+            .filter_map(|f| {
+                let merge = self.history.last_merge(original_repo, f)?;
+                let pull = match &c.original_repo {
+                    Some(repo) => format!("{}#{}", repo, merge.pull_number),
+                    None => format!("#{}", merge.pull_number),
+                };
+                Some(format!(
+                    "`{}` by {} (merged on {})",
+                    f,
+                    pull,
+                    merge.merged_at.format("%Y-%m-%d")
+                ))
+            })
+            .collect()
+    }
+
+    /// Maintainers to mention about an incomplete translation, by the languages of the trigger pull's translations of the
+    /// articles in the conflict.
+    fn translation_maintainers(
+        &self,
+        full_repo_name: &str,
+        c: &conflicts::Conflict,
+    ) -> Vec<String> {
+        let settings = self.repo_config(full_repo_name);
+        if settings.mentions.is_empty() {
+            return Vec::new();
+        }
+        let layout = settings.layout.unwrap_or_default();
+        let articles: Vec<_> = c
+            .file_set
+            .iter()
+            .filter_map(|f| layout.article(f))
+            .collect();
+        let languages: BTreeSet<String> = self
+            .memory
+            .pull(full_repo_name, c.trigger)
+            .and_then(|p| p.diff)
+            .map(|d| d.files)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|f| layout.article(&f.path))
+            .filter(|t| {
+                t.is_translation()
+                    && articles
+                        .iter()
+                        .any(|a| a.path.eq_ignore_ascii_case(&t.path))
+            })
+            .map(|t| t.language)
+            .collect();
+
+        let mut out = Vec::new();
+        for language in &languages {
+            for m in settings.mentions.get(language).into_iter().flatten() {
+                if !out.contains(m) {
+                    out.push(m.clone());
+                }
+            }
+        }
+        out
+    }
+
+    async fn update_pull(
+        &self,
+        full_repo_name: &str,
+        new_pull: PullRequest,
+        trigger_updates: bool,
+    ) -> Result<()> {
+        if self.memory.contains(full_repo_name, new_pull.number) {
+            self.upsert_pull(full_repo_name, new_pull, trigger_updates)
+                .await
+        } else {
+            log::info!(
+                "Pull #{} can't be updated because it wasn't added in the first place",
+                new_pull.number
+            );
+            Ok(())
+        }
+    }
+
+    /// Handle pull request changes. This includes fetching a `.diff` file from another GitHub domain,
+    /// which may have its own rate limits.
+    ///
+    /// If `trigger_updates` is set, check if the update conflicts with existing pull requests,
+    /// and make its author aware (or other PRs' owners, in rare cases). For details, see [`helpers::conflicts::Storage`].
+    ///
+    /// Translators are not notified on changes in original articles -- see finalize_pull() for that.
+    async fn upsert_pull(
+        &self,
+        full_repo_name: &str,
+        new_pull: PullRequest,
+        trigger_updates: bool,
+    ) -> Result<()> {
+        self.upsert_pull_with_diff(full_repo_name, new_pull, trigger_updates, None)
+            .await
+    }
+
+    /// Same as [`Self::upsert_pull`], with the diff of the pull already read, if it's `prefetched`.
+    async fn upsert_pull_with_diff(
+        &self,
+        full_repo_name: &str,
+        mut new_pull: PullRequest,
+        trigger_updates: bool,
+        prefetched: Option<unidiff::PatchSet>,
+    ) -> Result<()> {
+        if !self.config.access.allows_repository(full_repo_name) {
+            log::debug!(
+                "Pull #{}: repository {} is not allowed, skipping",
+                new_pull.number,
+                full_repo_name
+            );
+            return Ok(());
+        }
+
+        if let Some(diff) = self.stored_diff(full_repo_name, &new_pull) {
+            log::debug!(
+                "Pull #{}: head {} is unchanged, reusing its stored diff",
+                new_pull.number,
+                new_pull.head.sha
+            );
+            new_pull.diff = Some(diff);
+            if !self.needs_comparison(full_repo_name, &new_pull) {
+                self.memory.insert_pull(full_repo_name, new_pull);
+                return Ok(());
+            }
+            return self
+                .process_pull(full_repo_name, new_pull, trigger_updates)
+                .await;
+        }
+
+        let fetched = match prefetched {
+            Some(diff) => Ok(diff),
+            None => self
+                .github
+                .read_pull_diff(full_repo_name, new_pull.number)
+                .await
+                .inspect(|_| self.hydration.diff_fetched()),
+        };
+        let diff = match fetched {
+            Ok(diff) => diff,
+            Err(e)
+                if HttpError::find(&e)
+                    .is_some_and(|e| e.status == reqwest::StatusCode::NOT_FOUND) =>
+            {
+                // The pull may have been closed by the time the event is handled, and its diff is gone with it.
+                // The event which closes it is handled separately, so there is nothing to do here.
+                match self.github.read_pull(full_repo_name, new_pull.number).await {
+                    Ok(current) if current.state != "open" => {
+                        log::info!(
+                            "Pull #{}: diff is not available and the pull is {}, skipping",
+                            new_pull.number,
+                            current.state
+                        );
+                        return Ok(());
+                    }
+                    _ => return Err(e),
+                }
+            }
+            Err(e) => return Err(e),
+        };
+        new_pull.set_diff(diff);
+
+        if !self.needs_comparison(full_repo_name, &new_pull) {
+            log::debug!(
+                "Pull #{}: no articles are changed, storing it without comparing",
+                new_pull.number
+            );
+            self.memory.insert_pull(full_repo_name, new_pull);
+            return Ok(());
+        }
+
+        let is_new = !self.memory.contains(full_repo_name, new_pull.number);
+        self.process_pull(full_repo_name, new_pull.clone(), trigger_updates)
+            .await?;
+        if is_new && trigger_updates && self.config.check_outdated_translations {
+            self.check_outdated_translation(full_repo_name, &new_pull)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Whether a pull is compared with others. Pulls which change no articles checked in the repository (such as ones
+    /// which only touch images or workflows) can't conflict with anything, and are only stored, unless they already have
+    /// conflicts which need to be cleared. Pulls in repository groups are always compared, since their conflicts are also
+    /// kept in other repositories.
+    fn needs_comparison(&self, full_repo_name: &str, pull: &PullRequest) -> bool {
+        let repo_config = self.repo_config(full_repo_name);
+        let layout = repo_config.layout.unwrap_or_default();
+        let filter = repo_config.file_filter();
+        let changes_articles = pull.diff.as_ref().is_none_or(|diff| {
+            diff.files.iter().any(|f| {
+                [Some(f.path.as_str()), f.target_path()]
+                    .into_iter()
+                    .flatten()
+                    .any(|p| layout.article(p).is_some() && filter.accepts(p))
+            })
+        });
+        changes_articles
+            || !self.config.sibling_repositories(full_repo_name).is_empty()
+            || !self
+                .conflicts
+                .by_trigger(full_repo_name, pull.number)
+                .is_empty()
+            || !self
+                .conflicts
+                .by_original(full_repo_name, pull.number)
+                .is_empty()
+    }
+
+    /// The diff of a pull as it was stored, if the pull still has the same head and base branch (diffs are only fetched
+    /// again after either of them changes). Diffs without hunks, such as the ones read via GraphQL, are not reused.
+    fn stored_diff(&self, full_repo_name: &str, pull: &PullRequest) -> Option<DiffSummary> {
+        if pull.head.sha.is_empty() {
+            return None;
+        }
+        let stored = self.memory.pull(full_repo_name, pull.number)?;
+        if stored.head.sha != pull.head.sha || stored.base.branch != pull.base.branch {
+            return None;
+        }
+        stored.diff.filter(|d| d.has_line_ranges)
+    }
+
+    /// Warn the author of a new translation if the original articles were changed after the pull request was branched off
+    /// (for example, the translation is based on an old clone of the repository).
+    ///
+    /// This only happens if `check_outdated_translations` is set in the config.
+    async fn check_outdated_translation(
+        &self,
+        full_repo_name: &str,
+        pull: &PullRequest,
+    ) -> Result<()> {
+        let layout = self.repo_config(full_repo_name).layout.unwrap_or_default();
+        let originals: BTreeSet<String> = match &pull.diff {
+            Some(diff) => diff
+                .files
+                .iter()
+                .filter_map(|f| layout.article(&f.path))
+                .filter(|a| a.is_translation())
+                .map(|a| a.original_file_path())
+                .collect(),
+            None => BTreeSet::new(),
+        };
+        if originals.is_empty() || pull.head.sha.is_empty() {
+            return Ok(());
+        }
+
+        let comparison = self
+            .github
+            .compare(full_repo_name, &pull.head.sha, &pull.base.branch)
+            .await?;
+        let files: Vec<_> = comparison
+            .files
+            .into_iter()
+            .map(|f| f.filename)
+            .filter(|f| originals.contains(f))
+            .collect();
+        if files.is_empty() {
+            return Ok(());
+        }
+
+        let already_posted = self
+            .github
+            .read_comments(full_repo_name, pull.number)
+            .await?
+            .into_iter()
+            .filter(|c| self.is_own_comment(c))
+            .any(|c| {
+                self.header(&c.body).is_some_and(|h| {
+                    h.pull_number == pull.number
+                        && h.conflict_type == ConflictType::OutdatedTranslation
+                })
+            });
+        if already_posted {
+            return Ok(());
+        }
+
+        let body = self.render(&conflicts::OutdatedTranslation {
+            pull_number: pull.number,
+            compare_url: comparison.html_url,
+            files,
+        });
+        if self.posts_comments(full_repo_name) {
+            self.post_comment(full_repo_name, pull.number, body).await
+        } else {
+            log::debug!(
+                "Would post a notice about outdated originals in {}",
+                self.github.github().pull_url(full_repo_name, pull.number)
+            );
+            Ok(())
+        }
+    }
+
+    /// Save a pull request with an already known diff, and refresh its conflicts (see [`Self::upsert_pull`]).
+    async fn process_pull(
+        &self,
+        full_repo_name: &str,
+        new_pull: PullRequest,
+        trigger_updates: bool,
+    ) -> Result<()> {
+        self.memory.insert_pull(full_repo_name, new_pull.clone());
+        if !self.forget_pulls_over_limit(full_repo_name, new_pull.number) {
+            return Ok(());
+        }
+
+        let candidates = self.conflict_candidates(full_repo_name, &new_pull);
+        let (pending_updates, conflicts_to_remove) = self
+            .refresh_conflicts(
+                full_repo_name,
+                candidates,
+                &new_pull,
+                ConflictType::Overlap,
+                trigger_updates,
+            )
+            .await;
+        self.refresh_group_conflicts(
+            full_repo_name,
+            &new_pull,
+            ConflictType::Overlap,
+            trigger_updates,
+        )
+        .await?;
+        if trigger_updates {
+            let mut labelled: BTreeSet<i32> = pending_updates
+                .keys()
+                .chain(conflicts_to_remove.keys())
+                .cloned()
+                .collect();
+            labelled.insert(new_pull.number);
+            let mut affected_originals = self.originals_related_to(full_repo_name, new_pull.number);
+            affected_originals.extend(
+                conflicts_to_remove
+                    .values()
+                    .flatten()
+                    .filter(|c| {
+                        c.kind == ConflictType::IncompleteTranslation && c.original_repo.is_none()
+                    })
+                    .map(|c| c.original),
+            );
+            self.send_updates(pending_updates, conflicts_to_remove, full_repo_name)
+                .await?;
+            self.update_translations_in_flight(full_repo_name, affected_originals)
+                .await?;
+            self.sync_labels(full_repo_name, labelled).await;
+        }
+        Ok(())
+    }
+
+    /// Add labels from the repository settings to pulls which have conflicts worth a comment, and remove them from pulls
+    /// which no longer do. Failures are only logged: labels are a convenience, and comments carry the actual information.
+    async fn sync_labels(&self, full_repo_name: &str, pull_numbers: BTreeSet<i32>) {
+        let labels = self.repo_config(full_repo_name).labels;
+        if labels.is_empty() {
+            return;
+        }
+        for pull_number in pull_numbers {
+            let Some(pull) = self.memory.pull(full_repo_name, pull_number) else {
+                continue;
+            };
+            let has_conflicts = self
+                .conflicts
+                .by_trigger(full_repo_name, pull_number)
+                .iter()
+                .any(|c| !c.dismissed && self.warrants_comment(c));
+            let (to_add, to_remove): (Vec<_>, Vec<_>) = labels
+                .iter()
+                .filter(|l| pull.labels.iter().any(|pl| pl.name == **l) != has_conflicts)
+                .cloned()
+                .partition(|_| has_conflicts);
+            if to_add.is_empty() && to_remove.is_empty() {
+                continue;
+            }
+            if !self.posts_comments(full_repo_name) {
+                log::debug!(
+                    "Would add labels {:?} and remove labels {:?} in {}",
+                    to_add,
+                    to_remove,
+                    self.github.github().pull_url(full_repo_name, pull_number)
+                );
+                continue;
+            }
+
+            let mut current = pull.labels;
+            if !to_add.is_empty() {
+                match self
+                    .github
+                    .add_labels(full_repo_name, pull_number, &to_add)
+                    .await
+                {
+                    Ok(()) => {
+                        current.extend(to_add.iter().map(|name| Label { name: name.clone() }));
+                        self.audit(
+                            full_repo_name,
+                            audit::Action::LabelsAdded {
+                                pull_number,
+                                labels: to_add,
+                            },
+                        );
+                    }
+                    Err(e) => log::error!(
+                        "Pull #{}: failed to add labels {:?}: {:?}",
+                        pull_number,
+                        to_add,
+                        e
+                    ),
+                }
+            }
+            for label in to_remove {
+                match self
+                    .github
+                    .remove_label(full_repo_name, pull_number, &label)
+                    .await
+                {
+                    Ok(()) => {
+                        current.retain(|l| l.name != label);
+                        self.audit(
+                            full_repo_name,
+                            audit::Action::LabelRemoved { pull_number, label },
+                        );
+                    }
+                    Err(e) => log::error!(
+                        "Pull #{}: failed to remove label {:?}: {:?}",
+                        pull_number,
+                        label,
+                        e
+                    ),
+                }
+            }
+            self.memory.set_labels(full_repo_name, pull_number, current);
+        }
+    }
+
+    /// Pulls which may conflict with the given one: those changing the same articles, and those it had conflicts with before
+    /// (so that obsolete conflicts are cleaned up).
+    fn conflict_candidates(
+        &self,
+        full_repo_name: &str,
+        pull: &PullRequest,
+    ) -> HashMap<i32, PullRequest> {
+        let mut candidates = self.memory.pulls_sharing_articles(full_repo_name, pull);
+        let known_conflicts = self
+            .conflicts
+            .by_original(full_repo_name, pull.number)
+            .into_iter()
+            .chain(self.conflicts.by_trigger(full_repo_name, pull.number))
+            .filter(|c| c.original_repo.is_none());
+        for c in known_conflicts {
+            for number in [c.original, c.trigger] {
+                if let std::collections::hash_map::Entry::Vacant(e) = candidates.entry(number) {
+                    if let Some(p) = self.memory.pull(full_repo_name, number) {
+                        e.insert(p);
+                    }
+                }
+            }
+        }
+        candidates
+    }
+
+    /// Notify pull request authors about conflicts by sending a comment for every
+    /// `(conflict source, conflict type)` combination.
+    ///
+    /// Every comment contains a machine-readable YAML header, hidden between separate HTML comment tags.
+    /// The header is a reliable alternative to parsing everything from comments (provided no one tampers with it).
+    ///
+    /// Comments already left by the bot are reused for updates, both to avoid spam and make notification process easier.
+    /// Comments about obsolete conflicts are removed; the lists of conflicts to update and to remove have no intersection.
+    /// Changes are grouped per pull: pulls are handled concurrently, and comments which wouldn't change are left alone.
+    ///
+    /// In the digest mode, all conflicts of a pull request are listed in a single comment instead (see [`Self::send_digest_updates`]).
+    async fn send_updates(
+        &self,
+        mut pending: HashMap<i32, Vec<conflicts::Conflict>>,
+        mut to_remove: HashMap<i32, Vec<conflicts::Conflict>>,
+        full_repo_name: &str,
+    ) -> Result<()> {
+        if self.config.notifications.style == config::NotificationStyle::Digest {
+            return self
+                .send_digest_updates(pending, to_remove, full_repo_name)
+                .await;
+        }
+
+        // Read all comments in affected pulls and find these which point to other pulls ("originals").
+        let affected_pulls: BTreeSet<i32> =
+            pending.keys().chain(to_remove.keys()).cloned().collect();
+        let existing_comments: Vec<(i32, Result<Vec<IssueComment>>)> =
+            futures::stream::iter(affected_pulls)
+                .map(|pull_number| async move {
+                    (
+                        pull_number,
+                        self.github.read_comments(full_repo_name, pull_number).await,
+                    )
+                })
+                .buffer_unordered(COMMENT_WRITE_CONCURRENCY)
+                .collect()
+                .await;
+
+        let mut actions: HashMap<i32, Vec<CommentAction>> = HashMap::new();
+        // Conflicts over the comment budget of their pull, which go to its digest instead.
+        let mut folded_pending: HashMap<i32, Vec<conflicts::Conflict>> = HashMap::new();
+        let mut folded_remove: HashMap<i32, Vec<conflicts::Conflict>> = HashMap::new();
+        for (pull_number, comments) in existing_comments {
+            // (Repository, if it's not this one) + pull number + conflict type -> comment.
+            let mut pull_references: HashMap<(Option<String>, i32, ConflictType), IssueComment> =
+                HashMap::new();
+            let mut in_digest = BTreeSet::new();
+            let own_comments: Vec<IssueComment> = comments?
+                .into_iter()
+                .filter(|c| self.is_own_comment(c))
+                .collect();
+            let minimized = if pending.contains_key(&pull_number) {
+                self.minimized_comments(full_repo_name, pull_number, &own_comments)
+                    .await
+            } else {
+                HashSet::new()
+            };
+            for c in own_comments
+                .into_iter()
+                .filter(|c| !minimized.contains(&c.node_id))
+            {
+                if let Some(header) = self.header(&c.body) {
+                    if header.conflict_type == ConflictType::Digest
+                        && header.pull_number == pull_number
+                    {
+                        in_digest.extend(header.conflicts.iter().map(|e| e.key()));
+                    }
+                    pull_references.insert(
+                        (header.repository, header.pull_number, header.conflict_type),
+                        c,
+                    );
+                }
+            }
+
+            let pull_actions = actions.entry(pull_number).or_default();
+            for r in to_remove.remove(&pull_number).unwrap_or_default() {
+                if in_digest.contains(&r.key()) {
+                    folded_remove
+                        .entry(pull_number)
+                        .or_default()
+                        .push(r.clone());
+                }
+                if let Some(existing_comment) =
+                    pull_references.get(&(r.original_repo.clone(), r.original, r.kind.clone()))
+                {
+                    pull_actions.push(CommentAction::Retire(r, existing_comment.clone()));
+                }
+            }
+
+            for u in pending.remove(&pull_number).unwrap_or_default() {
+                let existing_comment =
+                    pull_references.get(&(u.original_repo.clone(), u.original, u.kind.clone()));
+                // Once in the digest, a conflict stays there until it is resolved.
+                if existing_comment.is_none() && in_digest.contains(&u.key()) {
+                    folded_pending.entry(pull_number).or_default().push(u);
+                    continue;
+                }
+                if u.dismissed {
+                    continue;
+                }
+                let body = match self.comment_body_for(
+                    full_repo_name,
+                    pull_number,
+                    self.render_conflict(full_repo_name, &u),
+                    existing_comment.is_none(),
+                ) {
+                    Some(body) => body,
+                    None => {
+                        log::debug!(
+                            "Pull #{} is approved, not posting a NEW comment about #{} of kind {:?}",
+                            pull_number,
+                            u.original,
+                            u.kind
+                        );
+                        continue;
+                    }
+                };
+                match existing_comment {
+                    Some(c) if c.body == body => {
+                        log::debug!(
+                            "Comment #{} about pull #{} of kind {:?} is up to date",
+                            c.id,
+                            u.original,
+                            u.kind
+                        );
+                    }
+                    Some(c) => {
+                        if self.header(&c.body).is_some_and(|h| h.is_outdated()) {
+                            log::info!(
+                                "Comment #{} has an outdated header, rewriting it (version {})",
+                                c.id,
+                                comments::HEADER_VERSION
+                            );
+                        }
+                        pull_actions.push(CommentAction::Update(u, c.clone(), body))
+                    }
+                    None => pull_actions.push(CommentAction::Post(u, body)),
+                }
+            }
+
+            let new_comments = pull_actions
+                .iter()
+                .filter(|a| matches!(a, CommentAction::Post(..)))
+                .count();
+            let mut allowed = self.comment_budget(full_repo_name, pull_number, new_comments);
+            pull_actions.retain(|a| match a {
+                CommentAction::Post(u, _) if allowed == 0 => {
+                    folded_pending
+                        .entry(pull_number)
+                        .or_default()
+                        .push(u.clone());
+                    false
+                }
+                CommentAction::Post(..) => {
+                    allowed -= 1;
+                    true
+                }
+                _ => true,
+            });
+        }
+
+        // Comments in different pulls are independent, but changes within one pull are applied in order.
+        futures::stream::iter(actions)
+            .for_each_concurrent(
+                COMMENT_WRITE_CONCURRENCY,
+                |(pull_number, pull_actions)| async move {
+                    for action in pull_actions {
+                        self.apply_comment_action(full_repo_name, pull_number, action)
+                            .await;
+                    }
+                },
+            )
+            .await;
+
+        if !folded_pending.is_empty() || !folded_remove.is_empty() {
+            for (pull_number, cc) in &folded_pending {
+                log::info!(
+                    "Pull #{}: {} conflict(s) are over the comment budget, listing them in a digest",
+                    pull_number,
+                    cc.len()
+                );
+            }
+            self.send_digest_updates(folded_pending, folded_remove, full_repo_name)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// With `notifications.resolved: minimize`, comments about resolved conflicts are hidden but keep their headers. Pick
+    /// them out, so that a conflict which comes back gets a new comment instead of an edit to one nobody sees. If the
+    /// lookup fails, hidden comments are reused.
+    async fn minimized_comments(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        comments: &[IssueComment],
+    ) -> HashSet<String> {
+        if self.config.notifications.resolved != config::ResolvedStyle::Minimize {
+            return HashSet::new();
+        }
+        let node_ids: Vec<String> = comments
+            .iter()
+            .filter(|c| !c.node_id.is_empty() && self.header(&c.body).is_some())
+            .map(|c| c.node_id.clone())
+            .collect();
+        if node_ids.is_empty() {
+            return HashSet::new();
+        }
+        match self
+            .github
+            .minimized_comments(full_repo_name, &node_ids)
+            .await
+        {
+            Ok(minimized) => minimized.into_iter().collect(),
+            Err(e) => {
+                log::warn!(
+                    "Failed to check which comments are minimized in {}: {:?}",
+                    self.github.github().pull_url(full_repo_name, pull_number),
+                    e
+                );
+                HashSet::new()
+            }
+        }
+    }
+
+    /// How many of `wanted` new comments in a pull fit into its hourly budget (see `notifications.max_comments_per_hour`).
+    /// The budget is only spent once a comment is posted (see [`Self::spend_comment_budget`]).
+    fn comment_budget(&self, full_repo_name: &str, pull_number: i32, wanted: usize) -> usize {
+        let limit = self.config.notifications.max_comments_per_hour;
+        if limit == 0 || wanted == 0 {
+            return wanted;
+        }
+        let now = chrono::Utc::now();
+        let mut posted = self.posted_comments.lock().unwrap();
+        posted.retain(|_, times| {
+            times.retain(|t| now - *t < COMMENT_BUDGET_WINDOW);
+            !times.is_empty()
+        });
+        let used = posted
+            .get(&(full_repo_name.to_string(), pull_number))
+            .map_or(0, |times| times.len());
+        wanted.min(limit.saturating_sub(used))
+    }
+
+    fn spend_comment_budget(&self, full_repo_name: &str, pull_number: i32) {
+        if self.config.notifications.max_comments_per_hour == 0 {
+            return;
+        }
+        self.posted_comments
+            .lock()
+            .unwrap()
+            .entry((full_repo_name.to_string(), pull_number))
+            .or_default()
+            .push(chrono::Utc::now());
+    }
+
+    /// Carry out a single planned comment change (see [`Self::send_updates`]). Failures are logged and scheduled
+    /// for another attempt (see [`Self::retry_pending_syncs`]), and don't affect other changes.
+    async fn apply_comment_action(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        action: CommentAction,
+    ) {
+        let pull_url = self.github.github().pull_url(full_repo_name, pull_number);
+        match action {
+            CommentAction::Retire(r, existing_comment) => {
+                if !self.posts_comments(full_repo_name) {
+                    return;
+                }
+                if let Err(e) = self
+                    .retire_comment(full_repo_name, pull_number, &existing_comment)
+                    .await
+                {
+                    log::error!(
+                        "Failed to retire comment #{} about pull #{} of kind {:?} in {}: {:?}",
+                        existing_comment.id,
+                        r.original,
+                        r.kind,
+                        pull_url,
+                        e
+                    );
+                    self.retries.schedule(full_repo_name, &r, true);
+                } else {
+                    log::debug!(
+                        "Retired comment #{} about pull #{} of kind {:?} in {}",
+                        existing_comment.id,
+                        r.original,
+                        r.kind,
+                        pull_url,
+                    );
+                    self.retries.complete(full_repo_name, &r);
+                }
+            }
+            CommentAction::Update(u, existing_comment, body) => {
+                if !self.posts_comments(full_repo_name) {
+                    log::debug!(
+                        "Would update comment #{} about pull #{} of kind {:?} in {}",
+                        existing_comment.id,
+                        u.original,
+                        u.kind,
+                        pull_url,
+                    );
+                } else if let Err(e) = self
+                    .update_comment(full_repo_name, pull_number, existing_comment.id, body)
+                    .await
+                {
+                    log::error!(
+                        "Failed to update comment #{} about pull #{} of kind {:?} in {}: {:?}",
+                        existing_comment.id,
+                        u.original,
+                        u.kind,
+                        pull_url,
+                        e
+                    );
+                    self.mark_out_of_sync(full_repo_name, &u);
+                } else {
+                    self.mark_in_sync(full_repo_name, &u);
+                }
+            }
+            CommentAction::Post(u, body) => {
+                if !self.posts_comments(full_repo_name) {
+                    log::debug!(
+                        "Would post a NEW comment about #{} of kind {:?} in {}",
+                        u.original,
+                        u.kind,
+                        pull_url,
+                    );
+                } else if let Err(e) = self.post_comment(full_repo_name, pull_number, body).await {
+                    log::error!(
+                        "Failed to post a NEW comment about pull #{} of kind {:?} in {}: {:?}",
+                        u.original,
+                        u.kind,
+                        pull_url,
+                        e
+                    );
+                    self.mark_out_of_sync(full_repo_name, &u);
+                } else {
+                    self.spend_comment_budget(full_repo_name, pull_number);
+                    self.mark_in_sync(full_repo_name, &u);
+                }
+            }
+        }
+    }
+
+    fn mark_out_of_sync(&self, full_repo_name: &str, c: &conflicts::Conflict) {
+        self.conflicts.set_needs_sync(full_repo_name, c, true);
+        self.retries.schedule(full_repo_name, c, false);
+    }
+
+    fn mark_in_sync(&self, full_repo_name: &str, c: &conflicts::Conflict) {
+        self.conflicts.set_needs_sync(full_repo_name, c, false);
+        self.retries.complete(full_repo_name, c);
+    }
+
+    /// Compare a few open pulls of every repository with GitHub (see [`crate::self_check`]), and log what differs.
+    /// Every run checks the next few pulls, so that all of them are covered over time. Runs are skipped while background
+    /// work is throttled.
+    async fn self_check(&mut self) {
+        if self.throttle.check() {
+            log::info!("Self-check: skipped, as background work is throttled");
+            return;
+        }
+        let round = self.self_check_round;
+        self.self_check_round += 1;
+        for repo in self.memory.repositories() {
+            let full_repo_name = repo.to_string();
+            if self.is_suspended(&full_repo_name) {
+                continue;
+            }
+            let mut pulls: Vec<PullRequest> = self
+                .memory
+                .pulls(&full_repo_name)
+                .unwrap_or_default()
+                .into_values()
+                .collect();
+            pulls.sort_by_key(|p| p.number);
+            for pull in self_check::sample(&pulls, round, self.config.self_check.pulls_per_repo) {
+                let divergences = match self.check_pull(&full_repo_name, pull).await {
+                    Ok(divergences) => divergences,
+                    Err(e) => {
+                        log::warn!(
+                            "Self-check: failed to check {}#{}: {:?}",
+                            full_repo_name,
+                            pull.number,
+                            e
+                        );
+                        continue;
+                    }
+                };
+                for d in divergences {
+                    log::warn!("Self-check: {}#{}: {}", full_repo_name, pull.number, d);
+                    self.divergences.fetch_add(1, Ordering::Relaxed);
+                }
+            }
+        }
+    }
+
+    /// Prune stored conflicts and check them for consistency (see [`conflicts::Storage::compact`]), as a safety net against
+    /// the state slowly drifting after weeks of uptime. Whatever is found is fixed and logged.
+    fn compact(&self) {
+        let unhydrated = self.unhydrated.lock().unwrap().clone();
+        for full_repo_name in self.conflicts.repositories() {
+            // Pulls of repositories which haven't been read yet are not known, but their conflicts are kept.
+            if unhydrated.contains(&full_repo_name) {
+                continue;
+            }
+            let report = self
+                .conflicts
+                .compact(&full_repo_name, |original_repo, pull_number| {
+                    let repo = original_repo.unwrap_or(&full_repo_name);
+                    unhydrated.contains(repo) || self.memory.contains(repo, pull_number)
+                });
+            if report.is_clean() {
+                continue;
+            }
+            for c in &report.orphaned {
+                log::warn!(
+                    "Compaction: {}: dropped {:?} between #{} and #{}, one of which is no longer known",
+                    full_repo_name,
+                    c.kind,
+                    c.trigger,
+                    c.original
+                );
+            }
+            for c in &report.self_conflicts {
+                log::warn!(
+                    "Compaction: {}: dropped {:?} of #{} with itself",
+                    full_repo_name,
+                    c.kind,
+                    c.trigger
+                );
+            }
+            if report.file_sets_fixed > 0 || report.refiled > 0 {
+                log::warn!(
+                    "Compaction: {}: deduplicated {} file set(s), refiled {} conflict(s)",
+                    full_repo_name,
+                    report.file_sets_fixed,
+                    report.refiled
+                );
+            }
+        }
+        log::info!("Compaction: done");
+    }
+
+    /// Compare a pull with its current diff and comments on GitHub.
+    ///
+    /// Comments are only checked with per-conflict notifications, in repositories where the app posts them. Conflicts whose
+    /// comments are waiting for another attempt (see [`crate::retries`]) or are held back on purpose don't count.
+    async fn check_pull(
+        &self,
+        full_repo_name: &str,
+        pull: &PullRequest,
+    ) -> Result<Vec<self_check::Divergence>> {
+        let mut out = Vec::new();
+        let current = DiffSummary::from(
+            &self
+                .github
+                .read_pull_diff(full_repo_name, pull.number)
+                .await?,
+        );
+        if let Some(d) = pull
+            .diff
+            .as_ref()
+            .and_then(|stored| self_check::compare_files(stored, &current))
+        {
+            out.push(d);
+        }
+
+        if self.config.notifications.style != config::NotificationStyle::PerConflict
+            || !self.posts_comments(full_repo_name)
+        {
+            return Ok(out);
+        }
+        let held_back = self.memory.is_approved(full_repo_name, pull.number)
+            && self.config.notifications.approved == config::ApprovedStyle::Suppress;
+        let stored = self.conflicts.by_trigger(full_repo_name, pull.number);
+        let headers: Vec<_> = self
+            .github
+            .read_comments(full_repo_name, pull.number)
+            .await?
+            .into_iter()
+            .filter(|c| self.is_own_comment(c) && !c.body.contains(comments::RESOLVED_TEMPLATE))
+            .filter_map(|c| self.header(&c.body).map(|h| (c.id, h)))
+            .collect();
+        // Conflicts over the comment budget are listed in the pull's digest instead.
+        let in_digest: BTreeSet<_> = headers
+            .iter()
+            .filter(|(_, h)| {
+                h.conflict_type == ConflictType::Digest && h.pull_number == pull.number
+            })
+            .flat_map(|(_, h)| h.conflicts.iter().map(|e| e.key()))
+            .collect();
+        out.extend(self_check::compare_comments(&stored, &headers, |c| {
+            !held_back
+                && !c.dismissed
+                && !c.needs_sync
+                && !in_digest.contains(&c.key())
+                && self.warrants_comment(c)
+        }));
+        Ok(out)
+    }
+
+    /// Bring comments about conflicts which failed to be written before in line with the stored conflicts.
+    /// Comments are planned from scratch, since the conflicts may have changed in the meantime.
+    async fn retry_pending_syncs(&self, now: chrono::DateTime<chrono::Utc>) {
+        for attempt in self.retries.due(now) {
+            let full_repo_name = attempt.full_repo_name.as_str();
+            // Kept in the queue until the installation is unsuspended.
+            if self.is_suspended(full_repo_name) {
+                continue;
+            }
+            // The repository has been archived, deleted, or made inaccessible while the app was down.
+            if !self.is_known_repository(full_repo_name) {
+                log::info!(
+                    "Dropping comment sync for pull #{} in {}, since the repository is gone",
+                    attempt.conflict.trigger,
+                    full_repo_name
+                );
+                self.retries.complete_attempt(&attempt);
+                continue;
+            }
+            let stored = self
+                .conflicts
+                .by_trigger(full_repo_name, attempt.conflict.trigger)
+                .into_iter()
+                .find(|c| c.key() == attempt.conflict.key());
+            let trigger = attempt.conflict.trigger;
+            let (pending, to_remove) = match (attempt.resolved, stored) {
+                (false, Some(c)) => (HashMap::from([(trigger, vec![c])]), HashMap::new()),
+                (true, None) => (
+                    HashMap::new(),
+                    HashMap::from([(trigger, vec![attempt.conflict.clone()])]),
+                ),
+                // The conflict has been removed or found again since, which has taken care of its comment.
+                _ => {
+                    self.retries.complete_attempt(&attempt);
+                    continue;
+                }
+            };
+
+            log::info!(
+                "Retrying comment sync for pull #{} of kind {:?} in {} (attempt #{})",
+                attempt.conflict.original,
+                attempt.conflict.kind,
+                full_repo_name,
+                attempt.attempts + 1
+            );
+            match self.send_updates(pending, to_remove, full_repo_name).await {
+                // Failed writes are rescheduled, in which case the attempt stays in the queue.
+                Ok(()) => {
+                    if self.retries.complete_attempt(&attempt) && !attempt.resolved {
+                        self.conflicts
+                            .set_needs_sync(full_repo_name, &attempt.conflict, false);
+                    }
+                }
+                Err(e) => {
+                    log::error!(
+                        "Failed to retry comment sync in {}: {:?}",
+                        full_repo_name,
+                        e
+                    );
+                    self.retries
+                        .schedule(full_repo_name, &attempt.conflict, attempt.resolved);
+                }
+            }
+        }
+    }
+
+    /// Maintain exactly one comment per pull request with the table of its conflicts.
+    ///
+    /// The list of conflicts is stored in the comment header, which is updated with incoming changes:
+    /// this way, conflicts which are no longer tracked (such as ones with already merged pulls) are preserved.
+    async fn send_digest_updates(
+        &self,
+        mut pending: HashMap<i32, Vec<conflicts::Conflict>>,
+        mut to_remove: HashMap<i32, Vec<conflicts::Conflict>>,
+        full_repo_name: &str,
+    ) -> Result<()> {
+        let affected_pulls: BTreeSet<i32> =
+            pending.keys().chain(to_remove.keys()).cloned().collect();
+        for pull_number in affected_pulls {
+            let existing_comment = self
+                .github
+                .read_comments(full_repo_name, pull_number)
+                .await?
+                .into_iter()
+                .filter(|c| self.is_own_comment(c))
+                .find_map(|c| {
+                    self.header(&c.body)
+                        .filter(|h| {
+                            h.pull_number == pull_number && h.conflict_type == ConflictType::Digest
+                        })
+                        .map(|h| (c, h.conflicts))
+                });
+
+            let (existing_comment, mut entries) = match existing_comment {
+                Some((c, entries)) => (Some(c), entries),
+                None => (None, Vec::new()),
+            };
+            for r in to_remove.remove(&pull_number).unwrap_or_default() {
+                entries.retain(|e| e.key() != r.key());
+            }
+            for u in pending.remove(&pull_number).unwrap_or_default() {
+                entries.retain(|e| e.key() != u.key());
+                if !u.dismissed {
+                    entries.push(u);
                 }
+            }
+            entries.sort();
 
-                let (pending_updates, conflicts_to_remove) = self
-                    .refresh_conflicts(
+            let pull_url = self.github.github().pull_url(full_repo_name, pull_number);
+            let result = match (existing_comment, entries.is_empty()) {
+                (None, true) => Ok(()),
+                (Some(comment), true) => {
+                    if self.posts_comments(full_repo_name) {
+                        self.delete_comment(full_repo_name, pull_number, comment.id)
+                            .await
+                    } else {
+                        log::debug!("Would delete digest #{} in {}", comment.id, pull_url);
+                        Ok(())
+                    }
+                }
+                (existing_comment, false) => {
+                    let mut digest = self.render(&conflicts::Digest {
+                        pull_number,
+                        conflicts: entries,
+                    });
+                    if self.has_git_conflicts(full_repo_name, pull_number).await {
+                        digest = format!("{}\n\n{}", digest, comments::GIT_CONFLICTS_TEMPLATE);
+                    }
+                    let body = self.comment_body_for(
                         full_repo_name,
-                        pulls_map,
-                        &closed_pull,
-                        ConflictType::IncompleteTranslation,
-                    )
-                    .await;
-                if !pending_updates.is_empty() {
-                    let _ = self
-                        .send_updates(pending_updates, conflicts_to_remove, full_repo_name)
-                        .await;
+                        pull_number,
+                        digest,
+                        existing_comment.is_none(),
+                    );
+                    let Some(body) = body else {
+                        log::debug!("Pull #{} is approved, not posting a digest", pull_number);
+                        continue;
+                    };
+                    if existing_comment.as_ref().is_some_and(|c| c.body == body) {
+                        Ok(())
+                    } else if !self.posts_comments(full_repo_name) {
+                        log::debug!("Would post or update digest in {}", pull_url);
+                        Ok(())
+                    } else if let Some(comment) = existing_comment {
+                        self.update_comment(full_repo_name, pull_number, comment.id, body)
+                            .await
+                    } else {
+                        self.post_comment(full_repo_name, pull_number, body).await
+                    }
                 }
+            };
+            if let Err(e) = result {
+                log::error!("Failed to update digest in {}: {:?}", pull_url, e);
             }
         }
+        Ok(())
+    }
 
-        self.memory.remove_pull(full_repo_name, &closed_pull);
-        self.conflicts
-            .remove_conflicts_by_pull(full_repo_name, closed_pull.number);
+    /// Whether GitHub reports git conflicts for the pull, if `notifications.git_conflicts` is set. The merge status is read
+    /// anew every time, since it changes with pushes to either branch and no event is sent about it.
+    async fn has_git_conflicts(&self, full_repo_name: &str, pull_number: i32) -> bool {
+        if !self.config.notifications.git_conflicts {
+            return false;
+        }
+        match self.github.read_pull(full_repo_name, pull_number).await {
+            Ok(pull) => pull.has_git_conflicts(),
+            Err(e) => {
+                log::warn!(
+                    "Pull #{}: failed to read its merge status: {:?}",
+                    pull_number,
+                    e
+                );
+                false
+            }
+        }
     }
 
-    /// Compare the new pull with existing ones for conflicts:
-    /// - Known conflicts (same kind + same file set) are skipped, otherwise memory is updated.
-    /// - Conflicts that don't occur anymore are removed from cache, with subsequent comment removal.
-    async fn refresh_conflicts(
+    /// Get rid of a comment about a conflict which no longer exists, according to the `notifications.resolved` setting.
+    async fn retire_comment(
         &self,
         full_repo_name: &str,
-        pulls_map: HashMap<i32, PullRequest>,
-        new_pull: &PullRequest,
-        kind_to_match: ConflictType,
-    ) -> (
-        HashMap<i32, Vec<conflicts::Conflict>>,
-        HashMap<i32, Vec<conflicts::Conflict>>,
-    ) {
-        log::info!(
-            "Running conflict refresh procedure for pull #{}, looking for conflict type {:?}",
-            new_pull.number,
-            kind_to_match
-        );
-        let mut pulls: Vec<PullRequest> = pulls_map
-            .into_values()
-            .filter(|other| other.number != new_pull.number)
-            .collect();
-        pulls.sort_by_key(|pr| pr.created_at);
+        pull_number: i32,
+        comment: &IssueComment,
+    ) -> Result<()> {
+        match self.config.notifications.resolved {
+            config::ResolvedStyle::Delete => {
+                self.delete_comment(full_repo_name, pull_number, comment.id)
+                    .await
+            }
+            config::ResolvedStyle::Banner => {
+                let body = comments::mark_resolved(&comment.body);
+                if body == comment.body {
+                    return Ok(());
+                }
+                self.update_comment(full_repo_name, pull_number, comment.id, body)
+                    .await
+            }
+            config::ResolvedStyle::Minimize => {
+                self.minimize_comment(full_repo_name, pull_number, comment)
+                    .await
+            }
+        }
+    }
 
-        let mut pending_updates: HashMap<i32, Vec<conflicts::Conflict>> = HashMap::new();
-        let mut conflicts_to_remove: HashMap<i32, Vec<conflicts::Conflict>> = HashMap::new();
-        for other_pull in pulls {
-            let conflicts = conflicts::compare_pulls(new_pull, &other_pull);
-            if !conflicts.is_empty() {
+    /// Execute a command left in a pull request comment. Only maintainers and the pull request author may control the bot;
+    /// commands from anyone else are ignored.
+    async fn run_command(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        comment: &IssueComment,
+        command: Command,
+    ) -> Result<()> {
+        if self.is_own_comment(comment) {
+            return Ok(());
+        }
+
+        let pull = match self.memory.pull(full_repo_name, pull_number) {
+            Some(p) => p,
+            None => {
                 log::info!(
-                    "Pull #{}: found conflicts with #{}: {:?}",
-                    new_pull.number,
-                    other_pull.number,
-                    conflicts
-                )
+                    "Pull #{} is not tracked, ignoring command {:?}",
+                    pull_number,
+                    command
+                );
+                return Ok(());
             }
+        };
 
-            // Note: after a conflict disappears, any interfering updates to the original pull will flip the roles:
-            // the pull which triggered the new conflict will be considered an original. This is a scenario rare enough
-            // (think indecisive people bringing changes in and out), but one that we should consider and have written down.
-            // Also, it's simpler than maintaining a cache of "inactive" conflicts, at least for now.
-            // Related test: test_new_comment_is_posted_after_removal_in_different_pull
-
-            let removed_conflicts = self.conflicts.remove_missing(
-                full_repo_name,
-                other_pull.number,
-                new_pull.number,
-                &conflicts,
+        let is_maintainer =
+            commands::MAINTAINER_ASSOCIATIONS.contains(&comment.author_association.as_str());
+        if !is_maintainer && comment.user.id != pull.user.id {
+            log::info!(
+                "Pull #{}: {} is not allowed to run command {:?}",
+                pull_number,
+                comment.user.login,
+                command
             );
+            return Ok(());
+        }
 
-            for removed in removed_conflicts {
-                conflicts_to_remove
-                    .entry(removed.trigger)
-                    .or_default()
-                    .push(removed);
-            }
-
-            // This always triggers notifications for `IncompleteTranslation` conflicts -- this is intended,
-            // since this function is called when they're merged. `Overlap` conflicts may not require an update if their
-            // contents are identical.
-            for conflict in conflicts {
-                // Remove conflicts related to translations getting merged if their overlap with other PRs doesn't consists of translations.
-                // See: https://github.com/TicClick/observatory/issues/25.
-                if conflict.kind == kind_to_match
-                    && kind_to_match == ConflictType::IncompleteTranslation
-                    && conflict.file_set.iter().all(|f| f.ends_with("en.md"))
-                    && conflict.trigger == new_pull.number
+        match command {
+            Command::Recheck => self.upsert_pull(full_repo_name, pull, true).await,
+            Command::IgnoreConflict(other_pull) => {
+                let mut to_remove: HashMap<i32, Vec<conflicts::Conflict>> = HashMap::new();
+                for c in self
+                    .conflicts
+                    .dismiss(full_repo_name, other_pull, pull_number)
                 {
-                    conflicts_to_remove
-                        .entry(conflict.trigger)
-                        .or_default()
-                        .push(conflict.clone());
-
-                    continue;
+                    to_remove.entry(c.trigger).or_default().push(c);
                 }
+                self.send_updates(HashMap::new(), to_remove, full_repo_name)
+                    .await
+            }
+            Command::Status => {
+                let mut known_conflicts = self.conflicts.by_trigger(full_repo_name, pull_number);
+                known_conflicts.extend(self.conflicts.by_original(full_repo_name, pull_number));
 
-                match self.conflicts.upsert(full_repo_name, &conflict.clone()) {
-                    Some(updated_conflict) => {
-                        if updated_conflict.kind == kind_to_match {
-                            pending_updates
-                                .entry(updated_conflict.trigger)
-                                .or_default()
-                                .push(updated_conflict);
-                        }
-                    }
-                    None => {
-                        if conflict.kind == kind_to_match {
-                            pending_updates
-                                .entry(conflict.trigger)
-                                .or_default()
-                                .push(conflict);
-                        }
+                let body = if known_conflicts.is_empty() {
+                    comments::STATUS_NO_CONFLICTS_TEMPLATE.to_string()
+                } else {
+                    let mut lines = vec![comments::STATUS_TEMPLATE.to_string()];
+                    for c in known_conflicts {
+                        let other = match &c.original_repo {
+                            Some(repo) => format!("{}#{}", repo, c.original),
+                            None if c.trigger == pull_number => format!("#{}", c.original),
+                            None => format!("#{}", c.trigger),
+                        };
+                        lines.push(format!(
+                            "- {:?}: {} ({})",
+                            c.kind,
+                            other,
+                            c.file_set.join(", ")
+                        ));
                     }
+                    lines.join("\n")
+                };
+
+                if self.posts_comments(full_repo_name) {
+                    self.post_comment(full_repo_name, pull_number, body).await
+                } else {
+                    log::debug!(
+                        "Would post a status comment in {}: {:?}",
+                        self.github.github().pull_url(full_repo_name, pull_number),
+                        body
+                    );
+                    Ok(())
                 }
             }
         }
-
-        log::info!(
-            "Result of conflict refresh for pull #{}, type {:?}: SAVING new conflicts {:?}, REMOVING conflicts {:?}",
-            new_pull.number, kind_to_match, pending_updates, conflicts_to_remove
-        );
-        (pending_updates, conflicts_to_remove)
     }
 
-    async fn update_pull(
+    /// Read open pull requests of a repository anew and check them for conflicts, as if they were just updated. This covers
+    /// changes which GitHub sends no events about, such as syncs of the base branch which bypass pull requests.
+    ///
+    /// If set, `base_branch` and `pull_number` limit the pulls which are checked.
+    async fn rescan(
         &self,
         full_repo_name: &str,
-        new_pull: PullRequest,
-        trigger_updates: bool,
+        base_branch: Option<&str>,
+        pull_number: Option<i32>,
     ) -> Result<()> {
-        if self.memory.contains(full_repo_name, &new_pull) {
-            self.upsert_pull(full_repo_name, new_pull, trigger_updates)
-                .await
-        } else {
+        if !self.is_known_repository(full_repo_name) {
             log::info!(
-                "Pull #{} can't be updated because it wasn't added in the first place",
-                new_pull.number
+                "{}: the repository is not known, ignoring the rescan request",
+                full_repo_name
             );
-            Ok(())
+            return Ok(());
         }
+
+        let pulls: Vec<_> = self
+            .github
+            .read_pulls(full_repo_name)
+            .await?
+            .into_iter()
+            .filter(|p| pull_number.is_none_or(|n| p.number == n))
+            .filter(|p| base_branch.is_none_or(|b| p.base.branch == b))
+            .collect();
+        let pulls = self.limit_pulls(full_repo_name, pulls);
+        log::info!(
+            "{}: rescanning {} pull(s) on request",
+            full_repo_name,
+            pulls.len()
+        );
+        for p in pulls {
+            self.upsert_pull(full_repo_name, p, true).await?;
+        }
+        Ok(())
     }
 
-    /// Handle pull request changes. This includes fetching a `.diff` file from another GitHub domain,
-    /// which may have its own rate limits.
-    ///
-    /// If `trigger_updates` is set, check if the update conflicts with existing pull requests,
-    /// and make its author aware (or other PRs' owners, in rare cases). For details, see [`helpers::conflicts::Storage`].
-    ///
-    /// Translators are not notified on changes in original articles -- see finalize_pull() for that.
-    async fn upsert_pull(
+    /// Add an entry to the audit log, attributing it to the webhook delivery being processed.
+    fn audit(&self, full_repo_name: &str, action: audit::Action) {
+        self.audit.record(&audit::Entry {
+            timestamp: chrono::Utc::now(),
+            repository: full_repo_name.to_string(),
+            delivery_id: self.delivery_id.clone(),
+            action,
+        });
+    }
+
+    /// Have the app information read again before the next request if GitHub refuses to write a comment: one which is
+    /// taken for the app's own may belong to someone else after the app has been renamed.
+    fn note_write_error(&self, e: &eyre::Report) {
+        if HttpError::find(e).is_some_and(|e| {
+            e.status == reqwest::StatusCode::UNAUTHORIZED
+                || e.status == reqwest::StatusCode::FORBIDDEN
+        }) {
+            self.app_stale.store(true, Ordering::Relaxed);
+        }
+    }
+
+    async fn post_comment(
         &self,
         full_repo_name: &str,
-        mut new_pull: PullRequest,
-        trigger_updates: bool,
+        pull_number: i32,
+        body: String,
     ) -> Result<()> {
-        let diff = self
-            .github
-            .read_pull_diff(full_repo_name, new_pull.number)
-            .await?;
-        new_pull.diff = Some(diff);
-        self.memory.insert_pull(full_repo_name, new_pull.clone());
+        self.github
+            .post_comment(full_repo_name, pull_number, body)
+            .await
+            .inspect_err(|e| self.note_write_error(e))?;
+        self.audit(full_repo_name, audit::Action::CommentPosted { pull_number });
+        Ok(())
+    }
 
-        if let Some(pulls_map) = self.memory.pulls(full_repo_name) {
-            let (pending_updates, conflicts_to_remove) = self
-                .refresh_conflicts(full_repo_name, pulls_map, &new_pull, ConflictType::Overlap)
-                .await;
-            if trigger_updates {
-                self.send_updates(pending_updates, conflicts_to_remove, full_repo_name)
-                    .await?;
-            }
-        }
+    async fn update_comment(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        comment_id: i64,
+        body: String,
+    ) -> Result<()> {
+        self.github
+            .update_comment(full_repo_name, pull_number, comment_id, body)
+            .await
+            .inspect_err(|e| self.note_write_error(e))?;
+        self.audit(
+            full_repo_name,
+            audit::Action::CommentUpdated {
+                pull_number,
+                comment_id,
+            },
+        );
         Ok(())
     }
 
-    /// Notify pull request authors about conflicts by sending a comment for every
-    /// `(conflict source, conflict type)` combination.
-    ///
-    /// Every comment contains a machine-readable YAML header, hidden between separate HTML comment tags.
-    /// The header is a reliable alternative to parsing everything from comments (provided no one tampers with it).
-    ///
-    /// Comments already left by the bot are reused for updates, both to avoid spam and make notification process easier.
-    /// Comments about obsolete conflicts are removed; the lists of conflicts to update and to remove have no intersection.
-    async fn send_updates(
+    async fn delete_comment(
         &self,
-        pending: HashMap<i32, Vec<conflicts::Conflict>>,
-        to_remove: HashMap<i32, Vec<conflicts::Conflict>>,
         full_repo_name: &str,
+        pull_number: i32,
+        comment_id: i64,
     ) -> Result<()> {
-        // Read all comments in affected pulls and find these which point to other pulls ("originals").
-        let mut pull_references: HashMap<(i32, ConflictType), IssueComment> = HashMap::new();
-        for pull_number in pending.keys().chain(to_remove.keys()) {
-            let existing_comments = self
-                .github
-                .read_comments(full_repo_name, *pull_number)
-                .await?
-                .into_iter()
-                .filter(|c| self.has_control_over(&c.user));
-            for c in existing_comments {
-                if let Some(header) = CommentHeader::from_comment(&c.body) {
-                    pull_references.insert((header.pull_number, header.conflict_type), c);
-                }
-            }
-        }
+        self.github
+            .delete_comment(full_repo_name, pull_number, comment_id)
+            .await
+            .inspect_err(|e| self.note_write_error(e))?;
+        self.audit(
+            full_repo_name,
+            audit::Action::CommentDeleted {
+                pull_number,
+                comment_id,
+            },
+        );
+        Ok(())
+    }
 
-        for (pull_to_clean, obsolete_conflicts) in to_remove.into_iter() {
-            for r in obsolete_conflicts {
-                let key = (r.original, r.kind.clone());
-                if let Some(existing_comment) = pull_references.get(&key) {
-                    if self.config.post_comments {
-                        if let Err(e) = self
-                            .github
-                            .delete_comment(full_repo_name, existing_comment.id)
-                            .await
-                        {
-                            log::error!(
-                                "Failed to delete comment #{} about pull #{} of kind {:?} in {}: {:?}",
-                                existing_comment.id,
-                                r.original,
-                                r.kind,
-                                self.github.github.pull_url(full_repo_name, pull_to_clean),
-                                e
-                            );
-                        } else {
-                            log::debug!(
-                                "Would delete comment #{} about pull #{} of kind {:?} in {}",
-                                existing_comment.id,
-                                r.original,
-                                r.kind,
-                                self.github.github.pull_url(full_repo_name, pull_to_clean),
-                            );
-                        }
-                    }
-                }
-            }
-        }
+    async fn minimize_comment(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        comment: &IssueComment,
+    ) -> Result<()> {
+        self.github
+            .minimize_comment(full_repo_name, &comment.node_id)
+            .await
+            .inspect_err(|e| self.note_write_error(e))?;
+        self.audit(
+            full_repo_name,
+            audit::Action::CommentMinimized {
+                pull_number,
+                comment_id: comment.id,
+            },
+        );
+        Ok(())
+    }
 
-        for (pull_to_notify, updates) in pending.into_iter() {
-            for u in updates {
-                let key = (u.original, u.kind.clone());
-                if let Some(existing_comment) = pull_references.get(&key) {
-                    if self.config.post_comments {
-                        if let Err(e) = self
-                            .github
-                            .update_comment(full_repo_name, existing_comment.id, u.to_markdown())
-                            .await
-                        {
-                            log::error!(
-                                "Failed to update comment #{} about pull #{} of kind {:?} in {}: {:?}",
-                                existing_comment.id,
-                                u.original,
-                                u.kind,
-                                self.github.github.pull_url(full_repo_name, pull_to_notify),
-                                e
-                            );
-                        }
-                    } else {
-                        log::debug!(
-                            "Would update comment #{} about pull #{} of kind {:?} in {}",
-                            existing_comment.id,
-                            u.original,
-                            u.kind,
-                            self.github.github.pull_url(full_repo_name, pull_to_notify),
-                        );
-                    }
-                } else if self.config.post_comments {
-                    if let Err(e) = self
-                        .github
-                        .post_comment(full_repo_name, pull_to_notify, u.to_markdown())
-                        .await
-                    {
-                        log::error!(
-                            "Failed to post a NEW comment about pull #{} of kind {:?} in {}: {:?}",
-                            u.original,
-                            u.kind,
-                            self.github.github.pull_url(full_repo_name, pull_to_notify),
-                            e
-                        );
-                    }
-                } else {
-                    log::debug!(
-                        "Would post a NEW comment about #{} of kind {:?} in {}",
-                        u.original,
-                        u.kind,
-                        self.github.github.pull_url(full_repo_name, pull_to_notify),
+    /// Read the app information again: its slug, which its comments are recognized by, may have changed since start-up.
+    async fn refresh_app(&mut self) {
+        match self.github.read_app().await {
+            Ok(app) => {
+                if let Some(old) = self.app.as_ref().filter(|old| old.slug != app.slug) {
+                    log::warn!(
+                        "GitHub application was renamed from {} to {}",
+                        old.slug,
+                        app.slug
                     );
                 }
+                self.app = Some(app);
             }
+            Err(e) => log::error!("Failed to refresh GitHub application information: {:?}", e),
         }
-        Ok(())
     }
 
-    /// A helper for checking if the comment is made by the bot itself.
-    ///
-    /// Curiously, there is no way of telling this from the comment's JSON.
+    /// Whether the comment was made by the app. GitHub reports which app a comment was made through, and only comments
+    /// without this information (such as ones read via GraphQL) are judged by the login of their author.
+    fn is_own_comment(&self, comment: &IssueComment) -> bool {
+        match (&self.app, &comment.performed_via_github_app) {
+            (Some(app), Some(via)) => via.id == app.id,
+            _ => self.has_control_over(&comment.user),
+        }
+    }
+
+    /// A helper for checking if the user is the bot itself, judging by its login (see [`Self::refresh_app`]).
     fn has_control_over(&self, user: &Actor) -> bool {
         if let Some(app) = &self.app {
             user.login == format!("{}[bot]", &app.slug)
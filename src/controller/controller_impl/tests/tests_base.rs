@@ -2,8 +2,10 @@ use std::sync::Arc;
 use tokio::sync::{oneshot, Mutex};
 
 use super::*;
+use crate::helpers::comments::CommentHeader;
+use crate::helpers::conflicts::ConflictType;
 use crate::structs::*;
-use crate::test::GitHubServer;
+use crate::test::{GitHubServer, MockGitHub};
 
 #[tokio::test]
 async fn test_has_control_over() {
@@ -33,6 +35,56 @@ async fn test_has_control_over() {
     }));
 }
 
+#[tokio::test]
+async fn test_own_comments_recognized_via_app() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let mut comment = server.make_comment("test/repo", 1, "text", "old-slug[bot]");
+    assert!(!c.is_own_comment(&comment));
+
+    comment.performed_via_github_app = Some(CommentApp {
+        id: crate::test::TEST_APP_ID,
+    });
+    assert!(c.is_own_comment(&comment));
+
+    comment.performed_via_github_app = Some(CommentApp { id: 12345 });
+    assert!(!c.is_own_comment(&comment));
+
+    // The app is known for sure, so the login doesn't matter.
+    comment.user.login = "test-app[bot]".to_string();
+    assert!(!c.is_own_comment(&comment));
+    comment.performed_via_github_app = None;
+    assert!(c.is_own_comment(&comment));
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_refresh_app_follows_renames() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let mut c = new_controller(&server, true).await;
+    let mut app = server.make_app();
+    app.slug = "renamed-app".to_string();
+    server = server.with_github_app(&app);
+    c.refresh_app().await;
+
+    assert!(c.has_control_over(&Actor {
+        id: 1,
+        login: "renamed-app[bot]".to_string()
+    }));
+    assert!(!c.has_control_over(&Actor {
+        id: 1,
+        login: "test-app[bot]".to_string()
+    }));
+}
+
 #[tokio::test]
 async fn test_has_control_over_uninitialized() {
     let server = GitHubServer::new()
@@ -73,6 +125,232 @@ async fn test_run_forever_stops_after_transmitter_is_destroyed() {
     assert!(rx.await.is_ok());
 }
 
+#[tokio::test]
+async fn test_run_forever_recovers_from_panic() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let (request_tx, mut c) = make_controller_with_config(
+        &server,
+        true,
+        crate::config::Controller {
+            post_comments: true,
+            fatal_http_errors: crate::config::FatalHttpErrors::Panic,
+            ..Default::default()
+        },
+    )
+    .await;
+    let restarts = c.restarts();
+    let handle = async move {
+        c.run_forever().await;
+    };
+
+    // The diff is not mocked, and the mock server responds with HTTP 501, which makes the client panic.
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let _ = request_tx
+        .send(ControllerRequest::PullRequestCreated {
+            full_repo_name: "test/repo".to_string(),
+            pull_request: Box::new(pull),
+            trigger_updates: false,
+        })
+        .await;
+    let (tx, rx) = oneshot::channel();
+    let _ = request_tx
+        .send(ControllerRequest::ExportState { reply_to: tx })
+        .await;
+
+    drop(request_tx);
+    tokio::join!(handle);
+    let snapshot = rx.await.unwrap();
+    assert_eq!(restarts.load(std::sync::atomic::Ordering::Relaxed), 1);
+    assert_eq!(snapshot.installations.len(), 1);
+    assert!(snapshot.pulls.values().all(|pulls| pulls.is_empty()));
+}
+
+#[tokio::test]
+async fn test_recover_resets_poisoned_caches() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let mut c = new_controller(&server, true).await;
+    c.expected_merges
+        .lock()
+        .unwrap()
+        .insert(("test/repo".to_string(), 1));
+    c.posted_comments
+        .lock()
+        .unwrap()
+        .insert(("test/repo".to_string(), 1), vec![chrono::Utc::now()]);
+
+    fn poison<T>(m: &std::sync::Mutex<T>) {
+        let _ = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let _g = m.lock().unwrap();
+            panic!("poisoning the lock");
+        }));
+        assert!(m.is_poisoned());
+    }
+    poison(&c.expected_merges);
+    poison(&c.unhydrated);
+    poison(&c.over_limit);
+    poison(&c.repo_configs);
+    poison(&c.posted_comments);
+    poison(&c.github.installations);
+
+    c.recover("has panicked").await;
+    assert!(c.expected_merges.lock().unwrap().is_empty());
+    assert!(c.unhydrated.lock().unwrap().is_empty());
+    assert!(c.over_limit.lock().unwrap().is_empty());
+    assert!(c.repo_configs.lock().is_ok());
+    assert_eq!(c.posted_comments.lock().unwrap().len(), 1);
+    assert_eq!(c.github.cached_installations().len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_run_forever_gives_up_on_stuck_messages() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    // The diff can't be read, and is retried for a long while.
+    for path in [
+        format!("/test/repo/pull/{}.diff", pull.number),
+        format!("/repos/test/repo/pulls/{}", pull.number),
+    ] {
+        server
+            .server
+            .mock("GET", path.as_str())
+            .with_status(500)
+            .create();
+    }
+
+    let (request_tx, mut c) = make_controller_with_config(
+        &server,
+        true,
+        crate::config::Controller {
+            retries: crate::github::RetryPolicies {
+                diffs: crate::github::RetryPolicy {
+                    max_retries: 1000,
+                    base_delay_ms: 20,
+                    max_delay_ms: 20,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .await;
+    let restarts = c.restarts();
+    let trace = c.trace();
+    let watchdog = c.watchdog();
+    let handle = tokio::spawn(async move {
+        c.run_forever().await;
+    });
+    tokio::spawn(async move {
+        while !watchdog.check(chrono::Duration::zero(), 1) {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    });
+
+    let _ = request_tx
+        .send(ControllerRequest::PullRequestCreated {
+            full_repo_name: "test/repo".to_string(),
+            pull_request: Box::new(pull),
+            trigger_updates: false,
+        })
+        .await;
+    let (tx, rx) = oneshot::channel();
+    let _ = request_tx
+        .send(ControllerRequest::ExportState { reply_to: tx })
+        .await;
+    let snapshot = tokio::time::timeout(std::time::Duration::from_secs(10), rx)
+        .await
+        .unwrap()
+        .unwrap();
+
+    drop(request_tx);
+    handle.await.unwrap();
+    assert_eq!(restarts.load(std::sync::atomic::Ordering::Relaxed), 1);
+    assert!(snapshot.pulls.values().all(|pulls| pulls.is_empty()));
+    let messages = trace.latest(2);
+    assert_eq!(messages[1].kind, "PullRequestCreated");
+    assert_eq!(messages[1].outcome, crate::trace::Outcome::Abandoned);
+    assert_eq!(messages[0].outcome, crate::trace::Outcome::Done);
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_run_forever_gives_up_during_retry_pauses() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    // The first failure is followed by a pause much longer than the test.
+    for path in [
+        format!("/test/repo/pull/{}.diff", pull.number),
+        format!("/repos/test/repo/pulls/{}", pull.number),
+    ] {
+        server
+            .server
+            .mock("GET", path.as_str())
+            .with_status(500)
+            .create();
+    }
+
+    let (request_tx, mut c) = make_controller_with_config(
+        &server,
+        true,
+        crate::config::Controller {
+            retries: crate::github::RetryPolicies {
+                diffs: crate::github::RetryPolicy {
+                    max_retries: 2,
+                    base_delay_ms: 60_000,
+                    max_delay_ms: 60_000,
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+    )
+    .await;
+    let restarts = c.restarts();
+    let trace = c.trace();
+    let watchdog = c.watchdog();
+    let handle = tokio::spawn(async move {
+        c.run_forever().await;
+    });
+    tokio::spawn(async move {
+        // Long enough for the request to fail, so that the message is given up on in the pause after it.
+        while !watchdog.check(chrono::Duration::seconds(1), 1) {
+            tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+        }
+    });
+
+    let _ = request_tx
+        .send(ControllerRequest::PullRequestCreated {
+            full_repo_name: "test/repo".to_string(),
+            pull_request: Box::new(pull),
+            trigger_updates: false,
+        })
+        .await;
+    let (tx, rx) = oneshot::channel();
+    let _ = request_tx
+        .send(ControllerRequest::ExportState { reply_to: tx })
+        .await;
+    tokio::time::timeout(std::time::Duration::from_secs(10), rx)
+        .await
+        .unwrap()
+        .unwrap();
+
+    drop(request_tx);
+    handle.await.unwrap();
+    assert_eq!(restarts.load(std::sync::atomic::Ordering::Relaxed), 1);
+    assert_eq!(trace.latest(2)[1].outcome, crate::trace::Outcome::Abandoned);
+}
+
 #[tokio::test]
 async fn test_handle_message_init() {
     let server = GitHubServer::new()
@@ -174,6 +452,100 @@ async fn test_handle_message_pull_request_created_and_updated() {
     );
 }
 
+#[tokio::test]
+async fn test_quick_updates_are_debounced() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let pr = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pull("test/repo", &pr)
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        debounce_seconds: 1,
+        ..Default::default()
+    };
+    let (request_tx, mut c) = make_controller_with_config(&server, true, config).await;
+    c.set_requeue(request_tx.downgrade());
+
+    let first = server.change_pull_diff("test/repo", pr.number, &["wiki/First/en.md"]);
+    let second = server.change_pull_diff("test/repo", pr.number, &["wiki/Second/en.md"]);
+    let diff = server
+        .server
+        .mock("GET", "/test/repo/pull/1.diff")
+        .with_status(200)
+        .with_body(second.diff.as_ref().unwrap().to_string())
+        .expect(1)
+        .create();
+
+    for pull in [first, second.clone()] {
+        c.handle_message(ControllerRequest::PullRequestUpdated {
+            full_repo_name: "test/repo".into(),
+            pull_request: Box::new(pull),
+            trigger_updates: true,
+        })
+        .await;
+    }
+    assert_eq!(
+        c.memory.pull("test/repo", pr.number).unwrap().updated_at,
+        pr.updated_at
+    );
+
+    // Both updates come back after the delay, but only the latest one is applied.
+    for _ in 0..2 {
+        let request = c.receiver.recv().await.unwrap();
+        assert!(matches!(request, ControllerRequest::DebouncedUpdate { .. }));
+        c.handle_message(request).await;
+    }
+    diff.assert();
+    assert_eq!(
+        c.memory.pull("test/repo", pr.number).unwrap().updated_at,
+        second.updated_at
+    );
+    assert!(c.debounced.is_empty());
+}
+
+#[tokio::test]
+async fn test_closing_pull_cancels_its_debounced_update() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let pr = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pull("test/repo", &pr)
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        debounce_seconds: 1,
+        ..Default::default()
+    };
+    let (request_tx, mut c) = make_controller_with_config(&server, true, config).await;
+    c.set_requeue(request_tx.downgrade());
+
+    let updated = server.change_pull_diff("test/repo", pr.number, &["wiki/Other/en.md"]);
+    let diff = server
+        .server
+        .mock("GET", "/test/repo/pull/1.diff")
+        .expect(0)
+        .create();
+    c.handle_message(ControllerRequest::PullRequestUpdated {
+        full_repo_name: "test/repo".into(),
+        pull_request: Box::new(updated.clone()),
+        trigger_updates: true,
+    })
+    .await;
+    c.handle_message(ControllerRequest::PullRequestClosed {
+        full_repo_name: "test/repo".into(),
+        pull_request: Box::new(updated),
+    })
+    .await;
+    assert!(c.debounced.is_empty());
+
+    // The update comes back after the delay, and is ignored.
+    let request = c.receiver.recv().await.unwrap();
+    assert!(matches!(request, ControllerRequest::DebouncedUpdate { .. }));
+    c.handle_message(request).await;
+    diff.assert();
+    assert!(c.memory.pull("test/repo", pr.number).is_none());
+}
+
 #[tokio::test]
 async fn test_handle_message_pull_request_closed() {
     let mut server = GitHubServer::new()
@@ -379,3 +751,33 @@ async fn test_handle_message_installation_repositories_removed() {
         vec![retained_repo]
     );
 }
+
+#[tokio::test]
+async fn test_controller_with_in_memory_backend() {
+    let mut server = GitHubServer::new().await;
+    let installation = server.make_installation();
+    server.make_repo(installation.id, "test/repo");
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let overlap = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+
+    let (_tx, rx) = tokio::sync::mpsc::channel(10);
+    let mut c = Controller::with_backend(
+        rx,
+        MockGitHub::from(&server),
+        crate::config::Controller {
+            post_comments: true,
+            ..Default::default()
+        },
+    );
+    c.init().await.unwrap();
+    assert_eq!(c.memory.pulls("test/repo").unwrap().len(), 2);
+
+    c.upsert_pull("test/repo", overlap.clone(), true)
+        .await
+        .unwrap();
+    let comments = c.github.comments("test/repo", overlap.number);
+    assert_eq!(comments.len(), 1);
+    let header = CommentHeader::from_comment(&comments[0].body, None).unwrap();
+    assert_eq!(header.pull_number, original.number);
+    assert_eq!(header.conflict_type, ConflictType::Overlap);
+}
@@ -0,0 +1,218 @@
+use super::*;
+
+use crate::helpers::commands::Command;
+use crate::helpers::comments;
+use crate::helpers::{conflicts::Conflict, ToMarkdown};
+use crate::structs::{Actor, IssueComment};
+
+fn make_command_comment(user: Actor, author_association: &str) -> IssueComment {
+    IssueComment {
+        id: 100,
+        body: "@observatory command".into(),
+        user,
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        author_association: author_association.into(),
+        node_id: String::new(),
+        performed_via_github_app: None,
+    }
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_ignore_conflict_removes_comment() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server.with_pulls("test/repo", &[p1.clone(), p2.clone()]);
+    for p in [&p1, &p2] {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+    assert_eq!(c.conflicts.by_trigger("test/repo", p2.number).len(), 1);
+
+    let existing_comment = server.make_comment(
+        "test/repo",
+        p2.number,
+        &Conflict::overlap(
+            p2.number,
+            p1.number,
+            p1.html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()],
+        )
+        .to_markdown(),
+        "test-app[bot]",
+    );
+    server = server.with_comments("test/repo", p2.number, &[existing_comment]);
+    let delete_mock = server.mock_delete_comment("test/repo", 1).expect(1);
+
+    c.run_command(
+        "test/repo",
+        p2.number,
+        &make_command_comment(p2.user.clone(), "NONE"),
+        Command::IgnoreConflict(p1.number),
+    )
+    .await
+    .unwrap();
+
+    let stored = c.conflicts.by_trigger("test/repo", p2.number);
+    assert_eq!(stored.len(), 1);
+    assert!(stored[0].dismissed);
+    delete_mock.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_dismissed_conflict_is_not_reposted_until_file_set_changes() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p2.number, &Vec::new());
+    for p in [&p1, &p2] {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+    c.conflicts.dismiss("test/repo", p1.number, p2.number);
+
+    let silent_mock = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(0);
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    silent_mock.assert();
+    silent_mock.remove();
+
+    let p2 = server.change_pull_diff(
+        "test/repo",
+        p2.number,
+        &["wiki/Article/en.md", "wiki/Other_article/en.md"],
+    );
+    let p1 = server.change_pull_diff(
+        "test/repo",
+        p1.number,
+        &["wiki/Article/en.md", "wiki/Other_article/en.md"],
+    );
+    server = server.with_pulls("test/repo", &[p1.clone(), p2.clone()]);
+    c.upsert_pull("test/repo", p1.clone(), false).await.unwrap();
+
+    let loud_mock = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(1);
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    loud_mock.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_command_from_outsider_is_ignored() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server.with_pulls("test/repo", &[p1.clone(), p2.clone()]);
+    for p in [&p1, &p2] {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+
+    let outsider = Actor {
+        id: 999,
+        login: "someone".into(),
+    };
+    c.run_command(
+        "test/repo",
+        p2.number,
+        &make_command_comment(outsider, "CONTRIBUTOR"),
+        Command::IgnoreConflict(p1.number),
+    )
+    .await
+    .unwrap();
+
+    assert_eq!(c.conflicts.by_trigger("test/repo", p2.number).len(), 1);
+}
+
+#[tokio::test]
+async fn test_status_lists_conflicts() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server.with_pulls("test/repo", &[p1.clone(), p2.clone()]);
+    for p in [&p1, &p2] {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+
+    let expected_body = format!(
+        "{}\n- Overlap: #{} (wiki/Article/en.md)",
+        comments::STATUS_TEMPLATE,
+        p2.number
+    );
+    let status_mock = server
+        .mock_pull_comments("test/repo", p1.number, Some(expected_body))
+        .expect(1);
+
+    let maintainer = Actor {
+        id: 999,
+        login: "maintainer".into(),
+    };
+    c.run_command(
+        "test/repo",
+        p1.number,
+        &make_command_comment(maintainer, "MEMBER"),
+        Command::Status,
+    )
+    .await
+    .unwrap();
+    status_mock.assert();
+}
+
+#[tokio::test]
+async fn test_rescan_checks_selected_pulls_again() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Other_article/en.md"]);
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_default_app_installations()
+        .with_comments("test/repo", p2.number, &Vec::new());
+    let c = new_controller(&server, true).await;
+
+    // The change comes without an event.
+    let p2 = server.change_pull_diff("test/repo", p2.number, &["wiki/Article/en.md"]);
+    server = server.with_pull("test/repo", &p2);
+
+    let silent_mock = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(0);
+    c.rescan("test/repo", Some("staging"), None).await.unwrap();
+    c.rescan("test/repo", None, Some(p1.number)).await.unwrap();
+    c.rescan("test/other-repo", None, None).await.unwrap();
+    silent_mock.assert();
+    silent_mock.remove();
+    assert!(c.conflicts.by_trigger("test/repo", p2.number).is_empty());
+
+    let comment_mock = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(1);
+    c.rescan("test/repo", Some("master"), Some(p2.number))
+        .await
+        .unwrap();
+    comment_mock.assert();
+    assert_eq!(c.conflicts.by_trigger("test/repo", p2.number).len(), 1);
+}
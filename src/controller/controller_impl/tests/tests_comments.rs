@@ -1,6 +1,13 @@
-use crate::helpers::{conflicts::Conflict, ToMarkdown};
+use crate::helpers::{
+    conflicts::{self, Conflict},
+    ToMarkdown,
+};
 
 use super::*;
+use crate::controller::ControllerRequest;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::structs::{IssueComment, Label, PullRequestReview};
 
 #[allow(unused_assignments)]
 #[tokio::test]
@@ -94,6 +101,7 @@ async fn test_one_pull_and_conflict_one_comment() {
         pulls[0].html_url.clone(),
         vec!["wiki/Article/en.md".to_string()],
     )
+    .with_merge_first(pulls[1].number)
     .to_markdown();
     let c1 = server
         .mock_pull_comments("test/repo", pulls[0].number, None)
@@ -112,7 +120,7 @@ async fn test_one_pull_and_conflict_one_comment() {
         "test/repo",
         pulls[1].number,
         conflict_comment.as_str(),
-        "test-app[bot]".into(),
+        "test-app[bot]",
     );
 
     let p1 = server.change_pull_diff(
@@ -123,7 +131,7 @@ async fn test_one_pull_and_conflict_one_comment() {
     server = server.with_pull("test/repo", &p1).with_comments(
         "test/repo",
         pulls[1].number,
-        &vec![dummy_comment.clone()],
+        std::slice::from_ref(&dummy_comment),
     );
 
     let updated_comment_body_both_articles = Conflict::overlap(
@@ -158,6 +166,7 @@ async fn test_one_pull_and_conflict_one_comment() {
         pulls[0].html_url.clone(),
         vec!["wiki/Other_article/en.md".to_string()],
     )
+    .with_merge_first(pulls[1].number)
     .to_markdown();
     let mock_comment = server.mock_comment(
         "test/repo",
@@ -199,6 +208,7 @@ async fn test_one_pull_and_conflict_one_comment_updated() {
         pulls[0].html_url.clone(),
         vec!["wiki/Article/en.md".to_string()],
     )
+    .with_merge_first(pulls[1].number)
     .to_markdown();
 
     let c1 = server
@@ -218,7 +228,7 @@ async fn test_one_pull_and_conflict_one_comment_updated() {
         "test/repo",
         pulls[1].number,
         conflict_comment.as_str(),
-        "test-app[bot]".into(),
+        "test-app[bot]",
     );
 
     let p1 = server.change_pull_diff(
@@ -229,7 +239,7 @@ async fn test_one_pull_and_conflict_one_comment_updated() {
     server = server.with_pull("test/repo", &p1).with_comments(
         "test/repo",
         pulls[1].number,
-        &vec![dummy_comment.clone()],
+        std::slice::from_ref(&dummy_comment),
     );
 
     let updated_comment_body_both_articles = Conflict::overlap(
@@ -255,7 +265,7 @@ async fn test_one_pull_and_conflict_one_comment_updated() {
     server = server.with_pull("test/repo", &p2).with_comments(
         "test/repo",
         pulls[1].number,
-        &vec![dummy_comment.clone()],
+        std::slice::from_ref(&dummy_comment),
     );
 
     let updated_comment_body_other_article = Conflict::overlap(
@@ -331,6 +341,7 @@ async fn test_post_comment_on_pull_request_merge() {
         pulls[0].html_url.clone(),
         vec!["wiki/Article_2/ru.md".to_string()],
     )
+    .with_merge_first(pulls[2].number)
     .to_markdown();
     let c3_overlap_1 = server
         .mock_pull_comments("test/repo", pulls[2].number, Some(overlap_comment_1))
@@ -342,6 +353,7 @@ async fn test_post_comment_on_pull_request_merge() {
         pulls[1].html_url.clone(),
         vec!["wiki/Other_article/en.md".to_string()],
     )
+    .with_merge_first(pulls[2].number)
     .to_markdown();
     let c3_overlap_2 = server
         .mock_pull_comments("test/repo", pulls[2].number, Some(overlap_comment_2))
@@ -420,12 +432,12 @@ async fn test_obsolete_comment_is_removed() {
         "test/repo",
         pulls[1].number,
         overlap_comment.as_str(),
-        "test-app[bot]".into(),
+        "test-app[bot]",
     );
     server = server.with_pull("test/repo", &p1).with_comments(
         "test/repo",
         pulls[1].number,
-        &vec![oc.clone()],
+        std::slice::from_ref(&oc),
     );
 
     let delete_comment = server.mock_delete_comment("test/repo", oc.id);
@@ -469,7 +481,7 @@ async fn test_only_target_comment_is_removed() {
         "test/repo",
         pulls[1].number,
         pull2_overlap1.as_str(),
-        "test-app[bot]".into(),
+        "test-app[bot]",
     );
     let pull2_overlap1_mock = server
         .mock_pull_comments("test/repo", pulls[1].number, Some(pull2_overlap1.clone()))
@@ -486,7 +498,7 @@ async fn test_only_target_comment_is_removed() {
         "test/repo",
         pulls[2].number,
         pull3_overlap1.as_str(),
-        "test-app[bot]".into(),
+        "test-app[bot]",
     );
     let pull3_overlap1_mock = server
         .mock_pull_comments("test/repo", pulls[2].number, Some(pull3_overlap1.clone()))
@@ -503,7 +515,7 @@ async fn test_only_target_comment_is_removed() {
         "test/repo",
         pulls[2].number,
         pull3_overlap2.as_str(),
-        "test-app[bot]".into(),
+        "test-app[bot]",
     );
     let pull3_overlap2_mock = server
         .mock_pull_comments("test/repo", pulls[2].number, Some(pull3_overlap2.clone()))
@@ -513,12 +525,12 @@ async fn test_only_target_comment_is_removed() {
         .with_comments(
             "test/repo",
             pulls[1].number,
-            &vec![pull2_overlap1_comment.clone()],
+            std::slice::from_ref(&pull2_overlap1_comment),
         )
         .with_comments(
             "test/repo",
             pulls[2].number,
-            &vec![
+            &[
                 pull3_overlap1_comment.clone(),
                 pull3_overlap2_comment.clone(),
             ],
@@ -589,7 +601,7 @@ async fn test_new_comment_is_posted_after_removal_in_different_pull() {
         "test/repo",
         pulls[1].number,
         overlap_text.as_str(),
-        "test-app[bot]".into(),
+        "test-app[bot]",
     );
     let overlap_mock = server
         .mock_pull_comments("test/repo", pulls[1].number, Some(overlap_text.clone()))
@@ -609,7 +621,7 @@ async fn test_new_comment_is_posted_after_removal_in_different_pull() {
     server = server.with_pull("test/repo", &p1).with_comments(
         "test/repo",
         pulls[1].number,
-        &vec![overlap_comment.clone()],
+        std::slice::from_ref(&overlap_comment),
     );
 
     let delete_overlap_comment = server
@@ -626,6 +638,7 @@ async fn test_new_comment_is_posted_after_removal_in_different_pull() {
         pulls[1].html_url.clone(),
         vec!["wiki/Article/ru.md".to_string()],
     )
+    .with_merge_first(pulls[0].number)
     .to_markdown();
     let new_overlap_mock = server
         .mock_pull_comments("test/repo", pulls[0].number, Some(new_overlap_text.clone()))
@@ -679,7 +692,7 @@ async fn test_original_merges_trigger_notifications() {
         "test/repo",
         pulls[0].number,
         incomplete_translation_text.as_str(),
-        "test-app[bot]".into(),
+        "test-app[bot]",
     );
     let incomplete_translation_mock = server
         .mock_pull_comments(
@@ -692,7 +705,7 @@ async fn test_original_merges_trigger_notifications() {
     server = server.with_pull("test/repo", &pulls[0]).with_comments(
         "test/repo",
         pulls[0].number,
-        &vec![incomplete_translation_comment.clone()],
+        std::slice::from_ref(&incomplete_translation_comment),
     );
 
     pulls.last_mut().unwrap().merged = true;
@@ -701,3 +714,1559 @@ async fn test_original_merges_trigger_notifications() {
 
     incomplete_translation_mock.assert();
 }
+
+#[tokio::test]
+async fn test_original_receives_translations_summary() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notify_originals: true,
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let translation = server.make_pull("test/repo", &["wiki/Article/ru.md"]);
+
+    server = server
+        .with_pulls("test/repo", &[original.clone(), translation.clone()])
+        .with_comments("test/repo", original.number, &Vec::new())
+        .with_comments("test/repo", translation.number, &Vec::new());
+
+    let summary = conflicts::TranslationsInFlight {
+        original: original.number,
+        translations: vec![(
+            translation.html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()],
+        )],
+    }
+    .to_markdown();
+    let summary_mock = server
+        .mock_pull_comments("test/repo", original.number, Some(summary))
+        .expect(1);
+
+    c.upsert_pull("test/repo", original.clone(), true)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", translation.clone(), true)
+        .await
+        .unwrap();
+    summary_mock.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_digest_lists_all_conflicts_in_one_comment() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            style: crate::config::NotificationStyle::Digest,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let pulls = [
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Other_article/en.md"]),
+        server.make_pull(
+            "test/repo",
+            &["wiki/Article/en.md", "wiki/Other_article/en.md"],
+        ),
+    ];
+    server = server.with_pulls("test/repo", &pulls).with_comments(
+        "test/repo",
+        pulls[2].number,
+        &Vec::new(),
+    );
+
+    let digest = conflicts::Digest {
+        pull_number: pulls[2].number,
+        conflicts: vec![
+            Conflict::overlap(
+                pulls[2].number,
+                pulls[0].number,
+                pulls[0].html_url.clone(),
+                vec!["wiki/Article/en.md".to_string()],
+            )
+            .with_merge_first(pulls[2].number),
+            Conflict::overlap(
+                pulls[2].number,
+                pulls[1].number,
+                pulls[1].html_url.clone(),
+                vec!["wiki/Other_article/en.md".to_string()],
+            )
+            .with_merge_first(pulls[2].number),
+        ],
+    };
+    let digest_mock = server
+        .mock_pull_comments("test/repo", pulls[2].number, Some(digest.to_markdown()))
+        .expect(1);
+
+    c.upsert_pull("test/repo", pulls[0].clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", pulls[1].clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", pulls[2].clone(), true)
+        .await
+        .unwrap();
+    digest_mock.assert();
+
+    // Once the comment exists, it is updated in place with the remaining conflict.
+    let existing = server.make_comment(
+        "test/repo",
+        pulls[2].number,
+        &digest.to_markdown(),
+        "test-app[bot]",
+    );
+    server = server.with_comments(
+        "test/repo",
+        pulls[2].number,
+        std::slice::from_ref(&existing),
+    );
+    let p0 = server.change_pull_diff("test/repo", pulls[0].number, &["wiki/Third/en.md"]);
+    server = server.with_pull("test/repo", &p0);
+
+    let updated = conflicts::Digest {
+        pull_number: pulls[2].number,
+        conflicts: vec![digest.conflicts[1].clone()],
+    };
+    let update_mock = server
+        .mock_comment("test/repo", existing.id, updated.to_markdown())
+        .expect(1);
+    c.upsert_pull("test/repo", p0, true).await.unwrap();
+    update_mock.assert();
+}
+
+#[tokio::test]
+async fn test_comments_over_budget_are_folded_into_digest() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            max_comments_per_hour: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let pulls = [
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Other_article/en.md"]),
+        server.make_pull(
+            "test/repo",
+            &["wiki/Article/en.md", "wiki/Other_article/en.md"],
+        ),
+    ];
+    server = server.with_pulls("test/repo", &pulls).with_comments(
+        "test/repo",
+        pulls[2].number,
+        &Vec::new(),
+    );
+
+    let posted = serde_json::to_string(&server.make_comment(
+        "test/repo",
+        pulls[2].number,
+        "",
+        "test-app[bot]",
+    ))
+    .unwrap();
+    let digest_mock = server
+        .server
+        .mock(
+            "POST",
+            format!("/repos/test/repo/issues/{}/comments", pulls[2].number).as_str(),
+        )
+        .match_body(mockito::Matcher::Regex(
+            "Other pull requests have changes".to_string(),
+        ))
+        .with_status(201)
+        .with_body(&posted)
+        .expect(1)
+        .create();
+
+    let comment_mock = server
+        .server
+        .mock(
+            "POST",
+            format!("/repos/test/repo/issues/{}/comments", pulls[2].number).as_str(),
+        )
+        .match_body(mockito::Matcher::Regex(
+            "Someone else has edited same files".to_string(),
+        ))
+        .with_status(201)
+        .with_body(&posted)
+        .expect(1)
+        .create();
+    c.upsert_pull("test/repo", pulls[0].clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", pulls[1].clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", pulls[2].clone(), true)
+        .await
+        .unwrap();
+    comment_mock.assert();
+    digest_mock.assert();
+}
+
+#[tokio::test]
+async fn test_failed_comments_dont_spend_budget() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            max_comments_per_hour: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p2.number, &[]);
+
+    let failing_post = server
+        .server
+        .mock("POST", "/repos/test/repo/issues/2/comments")
+        .with_status(422)
+        .create();
+    c.upsert_pull("test/repo", p1.clone(), false).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    failing_post.remove();
+
+    // The retry is still within the hour, and isn't folded into a digest.
+    let stored = c.conflicts.by_trigger("test/repo", p2.number);
+    let post = server
+        .mock_pull_comments("test/repo", p2.number, Some(stored[0].to_markdown()))
+        .expect(1);
+    c.retry_pending_syncs(chrono::Utc::now() + chrono::Duration::minutes(5))
+        .await;
+    post.assert();
+}
+
+#[tokio::test]
+async fn test_digest_mentions_git_conflicts() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            style: crate::config::NotificationStyle::Digest,
+            git_conflicts: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let pulls = [
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+    ];
+    let mut details = pulls[1].clone();
+    details.mergeable_state = Some("dirty".to_string());
+    server = server
+        .with_pulls("test/repo", &pulls)
+        .with_pull_details("test/repo", &details)
+        .with_comments("test/repo", pulls[1].number, &Vec::new());
+
+    let digest = conflicts::Digest {
+        pull_number: pulls[1].number,
+        conflicts: vec![Conflict::overlap(
+            pulls[1].number,
+            pulls[0].number,
+            pulls[0].html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()],
+        )],
+    };
+    let digest_mock = server
+        .mock_pull_comments(
+            "test/repo",
+            pulls[1].number,
+            Some(format!(
+                "{}\n\n{}",
+                digest.to_markdown(),
+                crate::helpers::comments::GIT_CONFLICTS_TEMPLATE
+            )),
+        )
+        .expect(1);
+
+    c.upsert_pull("test/repo", pulls[0].clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", pulls[1].clone(), true)
+        .await
+        .unwrap();
+    digest_mock.assert();
+}
+
+async fn obsolete_comment_setup(
+    resolved: crate::config::ResolvedStyle,
+) -> (GitHubServer, Controller, IssueComment) {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            resolved,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+
+    let pulls = [
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+    ];
+    server = server.with_pulls("test/repo", &pulls);
+    for p in pulls.iter() {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+
+    let overlap_comment = Conflict::overlap(
+        pulls[1].number,
+        pulls[0].number,
+        pulls[0].html_url.clone(),
+        vec!["wiki/Article/en.md".to_string()],
+    )
+    .to_markdown();
+    let mut oc = server.make_comment(
+        "test/repo",
+        pulls[1].number,
+        overlap_comment.as_str(),
+        "test-app[bot]",
+    );
+    oc.node_id = "IC_test".to_string();
+
+    let p1 = server.change_pull_diff("test/repo", pulls[1].number, &["wiki/Article_2/en.md"]);
+    server = server.with_pull("test/repo", &p1).with_comments(
+        "test/repo",
+        pulls[1].number,
+        std::slice::from_ref(&oc),
+    );
+    (server, c, oc)
+}
+
+#[tokio::test]
+async fn test_obsolete_comment_is_marked_resolved() {
+    let (mut server, c, oc) = obsolete_comment_setup(crate::config::ResolvedStyle::Banner).await;
+
+    let delete_mock = server.mock_delete_comment("test/repo", oc.id).expect(0);
+    let banner_mock = server
+        .mock_comment(
+            "test/repo",
+            oc.id,
+            crate::helpers::comments::mark_resolved(&oc.body),
+        )
+        .expect(1);
+
+    let p1 = server.pulls["test/repo"][&2].clone();
+    c.upsert_pull("test/repo", p1, true).await.unwrap();
+    delete_mock.assert();
+    banner_mock.assert();
+}
+
+#[tokio::test]
+async fn test_obsolete_comment_is_minimized() {
+    let (mut server, c, oc) = obsolete_comment_setup(crate::config::ResolvedStyle::Minimize).await;
+
+    let delete_mock = server.mock_delete_comment("test/repo", oc.id).expect(0);
+    let minimize_mock = server
+        .server
+        .mock("POST", "/graphql")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "variables": { "id": "IC_test" }
+        })))
+        .with_status(200)
+        .with_body(r#"{"data": {"minimizeComment": {"minimizedComment": {"isMinimized": true}}}}"#)
+        .expect(1)
+        .create();
+
+    let p1 = server.pulls["test/repo"][&2].clone();
+    c.upsert_pull("test/repo", p1, true).await.unwrap();
+    delete_mock.assert();
+    minimize_mock.assert();
+}
+
+#[tokio::test]
+async fn test_conflict_coming_back_after_minimized_comment_is_posted_again() {
+    let (mut server, c, oc) = obsolete_comment_setup(crate::config::ResolvedStyle::Minimize).await;
+    let minimize_mock = server
+        .server
+        .mock("POST", "/graphql")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "variables": { "id": "IC_test" }
+        })))
+        .with_status(200)
+        .with_body(r#"{"data": {"minimizeComment": {"minimizedComment": {"isMinimized": true}}}}"#)
+        .expect(1)
+        .create();
+    let p1 = server.pulls["test/repo"][&2].clone();
+    c.upsert_pull("test/repo", p1, true).await.unwrap();
+    minimize_mock.assert();
+
+    // The minimized comment still has its header, but is not visible anymore.
+    let lookup_mock = server
+        .server
+        .mock("POST", "/graphql")
+        .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+            "variables": { "ids": ["IC_test"] }
+        })))
+        .with_status(200)
+        .with_body(r#"{"data": {"nodes": [{"id": "IC_test", "isMinimized": true}]}}"#)
+        .expect(1)
+        .create();
+    let update_mock = server
+        .server
+        .mock(
+            "PATCH",
+            format!("/repos/test/repo/issues/comments/{}", oc.id).as_str(),
+        )
+        .expect(0)
+        .create();
+    let post_mock = server.mock_pull_comments("test/repo", 2, None).expect(1);
+
+    let p1 = server.change_pull_diff("test/repo", 2, &["wiki/Article/en.md"]);
+    let _server = server.with_pull("test/repo", &p1);
+    c.upsert_pull("test/repo", p1, true).await.unwrap();
+    lookup_mock.assert();
+    update_mock.assert();
+    post_mock.assert();
+}
+
+#[tokio::test]
+async fn test_file_level_overlap_below_min_severity_not_reported() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let mut config = crate::config::Controller {
+        post_comments: true,
+        ..Default::default()
+    };
+    config.notifications.min_severity = conflicts::Severity::LineLevel;
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pull("test/repo", &p1)
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_pull("test/repo", &p2)
+        .with_comments("test/repo", p2.number, &Vec::new());
+
+    let c2 = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(0);
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    c2.assert();
+
+    // The conflict is still known, only not reported.
+    assert_eq!(
+        c.conflicts.by_trigger("test/repo", p2.number)[0].severity,
+        conflicts::Severity::FileLevel
+    );
+
+    // A pull changing the same lines as the first one is worth a comment.
+    let mut p3 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    p3.diff = p1.diff.clone();
+    server = server
+        .with_pull("test/repo", &p3)
+        .with_comments("test/repo", p3.number, &Vec::new());
+    let comment = Conflict::overlap(
+        p3.number,
+        p1.number,
+        p1.html_url.clone(),
+        vec!["wiki/Article/en.md".to_string()],
+    )
+    .with_line_ranges(crate::structs::LineRanges::from([(
+        "wiki/Article/en.md".to_string(),
+        vec![(10, 16)],
+    )]))
+    .to_markdown();
+    let c3 = server
+        .mock_pull_comments("test/repo", p3.number, Some(comment))
+        .expect(1);
+    c.upsert_pull("test/repo", p3.clone(), true).await.unwrap();
+    c3.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_outdated_translation_notice() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        check_outdated_translations: true,
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let outdated = server.make_pull("test/repo", &["wiki/Article/ru.md"]);
+    let fresh = server.make_pull("test/repo", &["wiki/Other_article/ru.md"]);
+
+    server = server
+        .with_pulls("test/repo", &[outdated.clone(), fresh.clone()])
+        .with_comments("test/repo", outdated.number, &Vec::new())
+        .with_comments("test/repo", fresh.number, &Vec::new());
+
+    let compare_mock = server
+        .mock_compare(
+            "test/repo",
+            &outdated.head.sha,
+            "master",
+            &["wiki/Article/en.md", "wiki/Article/ru.md", "README.md"],
+        )
+        .expect(1);
+    server
+        .mock_compare("test/repo", &fresh.head.sha, "master", &["README.md"])
+        .expect(1);
+
+    let notice = conflicts::OutdatedTranslation {
+        pull_number: outdated.number,
+        compare_url: format!(
+            "{}/test/repo/compare/{}...master",
+            server.url.base_url, outdated.head.sha
+        ),
+        files: vec!["wiki/Article/en.md".to_string()],
+    }
+    .to_markdown();
+    let notice_mock = server
+        .mock_pull_comments("test/repo", outdated.number, Some(notice))
+        .expect(1);
+    let no_notice_mock = server
+        .mock_pull_comments("test/repo", fresh.number, None)
+        .expect(0);
+
+    c.upsert_pull("test/repo", outdated.clone(), true)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", fresh.clone(), true)
+        .await
+        .unwrap();
+
+    // Updates to known pulls are not checked again.
+    c.upsert_pull("test/repo", outdated.clone(), true)
+        .await
+        .unwrap();
+
+    compare_mock.assert();
+    notice_mock.assert();
+    no_notice_mock.assert();
+}
+
+fn make_review(reviewer_id: i64, state: &str, author_association: &str) -> PullRequestReview {
+    PullRequestReview {
+        id: reviewer_id * 100,
+        user: crate::structs::Actor {
+            id: reviewer_id,
+            login: format!("reviewer-{}", reviewer_id),
+        },
+        state: state.to_string(),
+        author_association: author_association.to_string(),
+    }
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_approved_pull_gets_no_new_comments() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            approved: crate::config::ApprovedStyle::Suppress,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_comments("test/repo", p2.number, &Vec::new());
+
+    // Reviews from non-maintainers and plain comments don't count.
+    c.record_review("test/repo", p2.number, &make_review(1, "approved", "NONE"));
+    c.record_review(
+        "test/repo",
+        p2.number,
+        &make_review(2, "commented", "MEMBER"),
+    );
+    assert!(!c.memory.is_approved("test/repo", p2.number));
+
+    c.record_review(
+        "test/repo",
+        p2.number,
+        &make_review(3, "APPROVED", "MEMBER"),
+    );
+    assert!(c.memory.is_approved("test/repo", p2.number));
+
+    let comments_mock = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(0);
+
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    comments_mock.assert();
+
+    c.record_review(
+        "test/repo",
+        p2.number,
+        &make_review(3, "dismissed", "MEMBER"),
+    );
+    assert!(!c.memory.is_approved("test/repo", p2.number));
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_approved_pull_comments_are_tagged() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            approved: crate::config::ApprovedStyle::Tag,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_comments("test/repo", p2.number, &Vec::new());
+
+    c.record_review("test/repo", p2.number, &make_review(3, "approved", "OWNER"));
+
+    let body = format!(
+        "{}\n\n{}",
+        Conflict::overlap(
+            p2.number,
+            p1.number,
+            p1.html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()],
+        )
+        .to_markdown(),
+        crate::helpers::comments::APPROVED_TEMPLATE
+    );
+    let comments_mock = server
+        .mock_pull_comments("test/repo", p2.number, Some(body))
+        .expect(1);
+
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    comments_mock.assert();
+}
+
+#[tokio::test]
+async fn test_self_overlap_is_suppressed() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            self_overlap: crate::config::SelfOverlapStyle::Suppress,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let mut p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    p2.user = p1.user.clone();
+
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_comments("test/repo", p2.number, &Vec::new());
+
+    let comments_mock = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(0);
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    comments_mock.assert();
+
+    // The conflict is still known, only not reported.
+    assert!(c.conflicts.by_trigger("test/repo", p2.number)[0].same_author);
+}
+
+#[tokio::test]
+async fn test_self_overlap_comments_are_softened() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            self_overlap: crate::config::SelfOverlapStyle::Soften,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let mut p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    p2.user = p1.user.clone();
+
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_comments("test/repo", p2.number, &Vec::new());
+
+    let body = format!(
+        "{}\n\n{}",
+        Conflict::overlap(
+            p2.number,
+            p1.number,
+            p1.html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()],
+        )
+        .to_markdown(),
+        crate::helpers::comments::SELF_OVERLAP_TEMPLATE
+    );
+    let comments_mock = server
+        .mock_pull_comments("test/repo", p2.number, Some(body))
+        .expect(1);
+
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    comments_mock.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_self_check_reports_missing_comments() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let mut config = crate::config::Controller {
+        post_comments: true,
+        ..Default::default()
+    };
+    config.self_check.pulls_per_repo = 5;
+    let (_, mut c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_comments("test/repo", p2.number, &Vec::new());
+
+    // Conflicts are found, but no comments are posted.
+    c.upsert_pull("test/repo", p1.clone(), false).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), false).await.unwrap();
+
+    c.self_check().await;
+    assert_eq!(c.divergences.load(std::sync::atomic::Ordering::Relaxed), 1);
+}
+
+#[tokio::test]
+async fn test_send_updates_skips_unchanged_comments() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let up_to_date = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let outdated = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+
+    let make_conflict = |trigger: i32, files: &[&str]| {
+        Conflict::overlap(
+            trigger,
+            original.number,
+            original.html_url.clone(),
+            files.iter().map(|f| f.to_string()).collect(),
+        )
+    };
+    let current = make_conflict(up_to_date.number, &["wiki/Article/en.md"]);
+    let stale = make_conflict(outdated.number, &["wiki/Other_article/en.md"]);
+    let fresh = make_conflict(
+        outdated.number,
+        &["wiki/Article/en.md", "wiki/Other_article/en.md"],
+    );
+
+    let current_comment = server.make_comment(
+        "test/repo",
+        up_to_date.number,
+        &current.to_markdown(),
+        "test-app[bot]",
+    );
+    let stale_comment = server.make_comment(
+        "test/repo",
+        outdated.number,
+        &stale.to_markdown(),
+        "test-app[bot]",
+    );
+    server = server
+        .with_comments(
+            "test/repo",
+            up_to_date.number,
+            std::slice::from_ref(&current_comment),
+        )
+        .with_comments(
+            "test/repo",
+            outdated.number,
+            std::slice::from_ref(&stale_comment),
+        );
+
+    let no_update_mock = server
+        .mock_comment("test/repo", current_comment.id, current.to_markdown())
+        .expect(0);
+    let update_mock = server
+        .mock_comment("test/repo", stale_comment.id, fresh.to_markdown())
+        .expect(1);
+    let no_post_mock = server
+        .mock_pull_comments("test/repo", up_to_date.number, None)
+        .expect(0);
+
+    let pending = HashMap::from([
+        (up_to_date.number, vec![current]),
+        (outdated.number, vec![fresh]),
+    ]);
+    c.send_updates(pending, HashMap::new(), "test/repo")
+        .await
+        .unwrap();
+
+    no_update_mock.assert();
+    update_mock.assert();
+    no_post_mock.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_legacy_comment_headers_are_migrated() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let conflict = Conflict::overlap(
+        pull.number,
+        original.number,
+        original.html_url.clone(),
+        vec!["wiki/Article/en.md".to_string()],
+    );
+
+    // Written before headers had versions, and before overlaps were called that.
+    let legacy_body = conflict
+        .to_markdown()
+        .replace("version: 1\n", "")
+        .replace("conflict_type: Overlap", "conflict_type: ExistingChange");
+    let legacy_comment =
+        server.make_comment("test/repo", pull.number, &legacy_body, "test-app[bot]");
+    server = server.with_comments(
+        "test/repo",
+        pull.number,
+        std::slice::from_ref(&legacy_comment),
+    );
+    let update_mock = server
+        .mock_comment("test/repo", legacy_comment.id, conflict.to_markdown())
+        .expect(1);
+    let no_post_mock = server
+        .mock_pull_comments("test/repo", pull.number, None)
+        .expect(0);
+
+    c.send_updates(
+        HashMap::from([(pull.number, vec![conflict])]),
+        HashMap::new(),
+        "test/repo",
+    )
+    .await
+    .unwrap();
+    update_mock.assert();
+    no_post_mock.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_comments_with_forged_headers_are_ignored() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        signing_key: Some("iseedeadpeople".to_string()),
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let key = crate::helpers::digest::RequestValidator::new("iseedeadpeople".to_string());
+
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let conflict = Conflict::overlap(
+        pull.number,
+        original.number,
+        original.html_url.clone(),
+        vec!["wiki/Article/en.md".to_string()],
+    );
+
+    // Someone has pointed a comment about another pull to this conflict, which the app shouldn't take for its own.
+    let other = Conflict::overlap(
+        pull.number,
+        3,
+        "https://github.com/test/repo/pull/3".to_string(),
+        vec!["wiki/Article/en.md".to_string()],
+    );
+    let forged = crate::helpers::comments::sign_comment(&other.to_markdown(), &key)
+        .replace("pull_number: 3", "pull_number: 1");
+    let forged = server.make_comment("test/repo", pull.number, &forged, "test-app[bot]");
+    server = server.with_comments("test/repo", pull.number, std::slice::from_ref(&forged));
+    let no_update_mock = server
+        .server
+        .mock(
+            "PATCH",
+            format!("/repos/test/repo/issues/comments/{}", forged.id).as_str(),
+        )
+        .expect(0)
+        .create();
+    let post_mock = server
+        .mock_pull_comments(
+            "test/repo",
+            pull.number,
+            Some(crate::helpers::comments::sign_comment(
+                &conflict.to_markdown(),
+                &key,
+            )),
+        )
+        .expect(1);
+
+    c.send_updates(
+        HashMap::from([(pull.number, vec![conflict])]),
+        HashMap::new(),
+        "test/repo",
+    )
+    .await
+    .unwrap();
+    no_update_mock.assert();
+    post_mock.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_actions_are_audited() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let audit_path = std::env::temp_dir().join(format!(
+        "observatory-controller-audit-{}.jsonl",
+        chrono::Utc::now().timestamp_nanos_opt().unwrap()
+    ));
+    let config = crate::config::Controller {
+        post_comments: true,
+        audit_log: Some(audit_path.to_str().unwrap().to_string()),
+        ..Default::default()
+    };
+    let (_, mut c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_comments("test/repo", p2.number, &Vec::new());
+    server.mock_pull_comments("test/repo", p2.number, None);
+
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.handle_message(ControllerRequest::Delivered {
+        delivery_id: "delivery-2".to_string(),
+        request: Box::new(ControllerRequest::PullRequestCreated {
+            full_repo_name: "test/repo".to_string(),
+            pull_request: Box::new(p2.clone()),
+            trigger_updates: true,
+        }),
+    })
+    .await;
+
+    let conflict = Conflict::overlap(
+        p2.number,
+        p1.number,
+        p1.html_url.clone(),
+        vec!["wiki/Article/en.md".to_string()],
+    );
+    let entries: Vec<_> =
+        crate::audit::AuditLog::new(Some(audit_path.to_str().unwrap().to_string()))
+            .read(Some("test/repo"), 10)
+            .unwrap()
+            .into_iter()
+            .map(|e| (e.delivery_id, e.action))
+            .collect();
+    assert_eq!(
+        entries,
+        vec![
+            (
+                Some("delivery-2".to_string()),
+                crate::audit::Action::CommentPosted {
+                    pull_number: p2.number
+                }
+            ),
+            (
+                Some("delivery-2".to_string()),
+                crate::audit::Action::ConflictStored { conflict }
+            ),
+        ]
+    );
+    std::fs::remove_file(audit_path).unwrap();
+}
+
+#[tokio::test]
+async fn test_orphaned_comments_are_reconciled_on_startup() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/repo");
+
+    let translation = server.make_pull("test/repo", &["wiki/Article/ru.md"]);
+    let unrelated = server.make_pull("test/repo", &["wiki/Other_article/en.md"]);
+    let merged_original = Conflict::incomplete_translation(
+        translation.number,
+        10,
+        server.url.pull_url("test/repo", 10),
+        vec!["wiki/Article/en.md".to_string()],
+    );
+    let stale_overlap = Conflict::overlap(
+        translation.number,
+        unrelated.number,
+        unrelated.html_url.clone(),
+        vec!["wiki/Article/ru.md".to_string()],
+    );
+    let comments = vec![
+        server.make_comment(
+            "test/repo",
+            translation.number,
+            &merged_original.to_markdown(),
+            "test-app[bot]",
+        ),
+        server.make_comment(
+            "test/repo",
+            translation.number,
+            &stale_overlap.to_markdown(),
+            "test-app[bot]",
+        ),
+    ];
+    server = server
+        .with_app_installations(&[(installation, vec![repo])])
+        .with_pulls("test/repo", &[translation.clone(), unrelated.clone()])
+        .with_comments("test/repo", translation.number, &comments)
+        .with_comments("test/repo", unrelated.number, &[]);
+
+    let delete_restored = server.mock_delete_comment("test/repo", 1).expect(0);
+    let delete_stale = server.mock_delete_comment("test/repo", 2).expect(1);
+
+    let (_, c) = make_controller_with_config(
+        &server,
+        true,
+        crate::config::Controller {
+            post_comments: true,
+            reconcile_on_startup: true,
+            ..Default::default()
+        },
+    )
+    .await;
+    assert_eq!(
+        c.conflicts.by_trigger("test/repo", translation.number),
+        vec![merged_original]
+    );
+    delete_restored.assert();
+    delete_stale.assert();
+}
+
+#[tokio::test]
+async fn test_failed_comments_are_retried() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &[])
+        .with_comments("test/repo", p2.number, &[]);
+
+    let failing_post = server
+        .server
+        .mock("POST", "/repos/test/repo/issues/2/comments")
+        .with_status(422)
+        .create();
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+
+    let stored = c.conflicts.by_trigger("test/repo", p2.number);
+    assert_eq!(stored.len(), 1);
+    assert!(stored[0].needs_sync);
+    assert_eq!(c.retries.all().len(), 1);
+
+    // Nothing is retried too early.
+    failing_post.remove();
+    let post = server
+        .mock_pull_comments("test/repo", p2.number, Some(stored[0].to_markdown()))
+        .expect(1);
+    c.retry_pending_syncs(chrono::Utc::now()).await;
+    assert_eq!(c.retries.all().len(), 1);
+
+    c.retry_pending_syncs(chrono::Utc::now() + chrono::Duration::minutes(5))
+        .await;
+    post.assert();
+    assert!(c.retries.all().is_empty());
+    assert!(!c.conflicts.by_trigger("test/repo", p2.number)[0].needs_sync);
+}
+
+#[tokio::test]
+async fn test_reopened_pull_drops_stale_conflicts() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let overlap_comment = server.make_comment(
+        "test/repo",
+        p2.number,
+        &Conflict::overlap(
+            p2.number,
+            p1.number,
+            p1.html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()],
+        )
+        .to_markdown(),
+        "test-app[bot]",
+    );
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &[])
+        .with_comments(
+            "test/repo",
+            p2.number,
+            std::slice::from_ref(&overlap_comment),
+        );
+    let post = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(0);
+
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    assert_eq!(c.conflicts.by_trigger("test/repo", p2.number).len(), 1);
+
+    // The first pull is gone, but the conflict is still there (for example, the closure has been missed).
+    c.memory.remove_pull("test/repo", &p1);
+    let delete = server.mock_delete_comment("test/repo", overlap_comment.id);
+
+    c.reopen_pull("test/repo", p2.clone()).await.unwrap();
+    delete.assert();
+    post.assert();
+    assert!(c.conflicts.by_trigger("test/repo", p2.number).is_empty());
+    assert!(c.memory.pull("test/repo", p2.number).is_some());
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_conflict_changes_are_sent_to_notifiers() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let pulls = [
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+    ];
+    server = server
+        .with_pulls("test/repo", &pulls)
+        .with_default_app_installations();
+
+    let found = server
+        .server
+        .mock("POST", "/discord")
+        .match_body(mockito::Matcher::Regex("has edited same files".to_string()))
+        .expect(2)
+        .create();
+    let resolved = server
+        .server
+        .mock("POST", "/discord")
+        .match_body(mockito::Matcher::Regex("Resolved".to_string()))
+        .expect(2)
+        .create();
+
+    // Conflicts found on start-up are not announced.
+    let config = crate::config::Controller {
+        notifiers: vec![crate::config::Notifier {
+            repositories: Vec::new(),
+            sink: crate::config::Sink::Discord {
+                webhook_url: format!("{}/discord", server.server.url()),
+            },
+        }],
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+
+    let p3 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pull("test/repo", &p3)
+        .with_comments("test/repo", p3.number, &Vec::new());
+    c.upsert_pull("test/repo", p3.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p3.clone(), true).await.unwrap();
+
+    let p3 = server.change_pull_diff("test/repo", p3.number, &["wiki/Other_article/en.md"]);
+    server = server.with_pull("test/repo", &p3);
+    c.upsert_pull("test/repo", p3, true).await.unwrap();
+
+    found.assert();
+    resolved.assert();
+}
+
+#[tokio::test]
+async fn test_conflicting_pulls_are_labelled() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_repo_config("test/repo", "labels: [conflict]")
+        .with_default_app_installations();
+
+    let c = new_controller(&server, true).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pull("test/repo", &p1)
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_pull("test/repo", &p2)
+        .with_comments("test/repo", p2.number, &Vec::new());
+    server.mock_pull_comments("test/repo", p2.number, None);
+
+    let added = server
+        .server
+        .mock("POST", "/repos/test/repo/issues/2/labels")
+        .match_body(mockito::Matcher::Json(
+            serde_json::json!({ "labels": ["conflict"] }),
+        ))
+        .with_status(200)
+        .with_body("[]")
+        .expect(1)
+        .create();
+    let not_added = server
+        .server
+        .mock("POST", "/repos/test/repo/issues/1/labels")
+        .expect(0)
+        .create();
+
+    c.upsert_pull("test/repo", p1.clone(), true).await.unwrap();
+    c.upsert_pull("test/repo", p2.clone(), true).await.unwrap();
+    added.assert();
+    not_added.assert();
+
+    let removed = server
+        .server
+        .mock("DELETE", "/repos/test/repo/issues/2/labels/conflict")
+        .with_status(200)
+        .with_body("[]")
+        .expect(1)
+        .create();
+    let mut p2 = server.change_pull_diff("test/repo", p2.number, &["wiki/Other/en.md"]);
+    p2.labels = vec![Label {
+        name: "conflict".to_string(),
+    }];
+    server = server.with_pull("test/repo", &p2);
+    server
+        .mock_comment("test/repo", 1, String::new())
+        .expect_at_least(0);
+    c.upsert_pull("test/repo", p2, true).await.unwrap();
+    removed.assert();
+}
+
+#[tokio::test]
+async fn test_account_settings_override_global_ones() {
+    for (global, account, expected_comments) in [(false, true, 1), (true, false, 0)] {
+        let mut server = GitHubServer::new()
+            .await
+            .with_default_github_app()
+            .with_default_app_installations();
+        let config = crate::config::Controller {
+            post_comments: global,
+            accounts: HashMap::from([(
+                "test".to_string(),
+                crate::repo_config::RepoConfig {
+                    post_comments: Some(account),
+                    ..Default::default()
+                },
+            )]),
+            ..Default::default()
+        };
+        let (_, c) = make_controller_with_config(&server, true, config).await;
+        let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+        let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+        server = server
+            .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+            .with_comments("test/repo", p1.number, &Vec::new())
+            .with_comments("test/repo", p2.number, &Vec::new());
+        let comment = server
+            .mock_pull_comments("test/repo", p2.number, None)
+            .expect(expected_comments);
+
+        c.upsert_pull("test/repo", p1, true).await.unwrap();
+        c.upsert_pull("test/repo", p2, true).await.unwrap();
+        comment.assert();
+    }
+}
+
+#[tokio::test]
+async fn test_disabled_comments_override_account_settings() {
+    // As in a replay without `--post-comments`.
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let mut config = crate::config::Controller {
+        post_comments: true,
+        accounts: HashMap::from([(
+            "test".to_string(),
+            crate::repo_config::RepoConfig {
+                post_comments: Some(true),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+    config.disable_comments();
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_comments("test/repo", p2.number, &Vec::new());
+    let comment = server
+        .mock_pull_comments("test/repo", p2.number, None)
+        .expect(0);
+
+    c.upsert_pull("test/repo", p1, true).await.unwrap();
+    c.upsert_pull("test/repo", p2, true).await.unwrap();
+    comment.assert();
+}
+
+#[tokio::test]
+async fn test_first_time_contributors_are_welcomed() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let config = crate::config::Controller {
+        post_comments: true,
+        accounts: HashMap::from([(
+            "test".to_string(),
+            crate::repo_config::RepoConfig {
+                welcome_first_time_contributors: Some(true),
+                guidelines: vec!["https://osu.ppy.sh/wiki/osu!_wiki/Translation".to_string()],
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let mut p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    p2.author_association = "FIRST_TIME_CONTRIBUTOR".to_string();
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_comments("test/repo", p1.number, &Vec::new())
+        .with_comments("test/repo", p2.number, &Vec::new());
+    let posted =
+        serde_json::to_string(&server.make_comment("test/repo", p2.number, "", "test-app[bot]"))
+            .unwrap();
+    let comment = server
+        .server
+        .mock(
+            "POST",
+            format!("/repos/test/repo/issues/{}/comments", p2.number).as_str(),
+        )
+        .match_body(mockito::Matcher::Regex(
+            r"Thanks for your first contribution!.+osu!_wiki/Translation".to_string(),
+        ))
+        .with_status(201)
+        .with_body(&posted)
+        .expect(1)
+        .create();
+
+    c.upsert_pull("test/repo", p1, true).await.unwrap();
+    c.upsert_pull("test/repo", p2, true).await.unwrap();
+    comment.assert();
+}
+
+#[tokio::test]
+async fn test_translation_maintainers_are_mentioned() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let config = crate::config::Controller {
+        post_comments: true,
+        accounts: HashMap::from([(
+            "test".to_string(),
+            crate::repo_config::RepoConfig {
+                mentions: BTreeMap::from([
+                    ("ru".to_string(), vec!["@ruteam".to_string()]),
+                    ("fr".to_string(), vec!["@frteam".to_string()]),
+                ]),
+                ..Default::default()
+            },
+        )]),
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let translation = server.make_pull("test/repo", &["wiki/Article/ru.md"]);
+    server = server
+        .with_pulls("test/repo", &[original.clone(), translation.clone()])
+        .with_comments("test/repo", original.number, &Vec::new())
+        .with_comments("test/repo", translation.number, &Vec::new());
+    c.upsert_pull("test/repo", original.clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", translation.clone(), false)
+        .await
+        .unwrap();
+
+    let text = Conflict::incomplete_translation(
+        translation.number,
+        original.number,
+        original.html_url.clone(),
+        vec!["wiki/Article/en.md".to_string()],
+    )
+    .to_markdown();
+    let comment = server
+        .mock_pull_comments(
+            "test/repo",
+            translation.number,
+            Some(format!("{}\n\n**cc:** @ruteam", text)),
+        )
+        .expect(1);
+
+    let mut merged = original;
+    merged.merged = true;
+    c.finalize_pull("test/repo", merged).await;
+    comment.assert();
+}
+
+#[tokio::test]
+async fn test_original_history_is_mentioned() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let config = crate::config::Controller {
+        post_comments: true,
+        notifications: crate::config::Notifications {
+            article_history: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let translation = server.make_pull("test/repo", &["wiki/Article/ru.md"]);
+    server = server
+        .with_pulls("test/repo", &[original.clone(), translation.clone()])
+        .with_comments("test/repo", original.number, &Vec::new())
+        .with_comments("test/repo", translation.number, &Vec::new());
+    c.upsert_pull("test/repo", original.clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", translation.clone(), false)
+        .await
+        .unwrap();
+
+    let text = Conflict::incomplete_translation(
+        translation.number,
+        original.number,
+        original.html_url.clone(),
+        vec!["wiki/Article/en.md".to_string()],
+    )
+    .to_markdown();
+    let comment = server
+        .mock_pull_comments(
+            "test/repo",
+            translation.number,
+            Some(format!(
+                "{}\n\n**Originals last changed:** `wiki/Article/en.md` by #{} (merged on 2024-01-02)",
+                text, original.number
+            )),
+        )
+        .expect(1);
+
+    let mut merged = original;
+    merged.merged = true;
+    merged.merged_at = Some("2024-01-02T03:04:05Z".parse().unwrap());
+    c.finalize_pull("test/repo", merged.clone()).await;
+    comment.assert();
+    assert_eq!(
+        c.history.repository("test/repo"),
+        BTreeMap::from([(
+            "wiki/Article/en.md".to_string(),
+            crate::history::LastMerge {
+                pull_number: merged.number,
+                html_url: merged.html_url,
+                merged_at: merged.merged_at.unwrap(),
+            }
+        )])
+    );
+}
@@ -22,6 +22,106 @@ async fn test_add_pull() {
     assert!(m.unwrap().get(&pull_number).unwrap().diff.is_some());
 }
 
+#[tokio::test]
+async fn test_diff_is_fetched_again_only_for_new_head() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let diff_path = format!("/test/repo/pull/{}.diff", pull.number);
+    let first = server
+        .server
+        .mock("GET", diff_path.as_str())
+        .with_body(pull.diff.as_ref().unwrap().to_string())
+        .expect(1)
+        .create();
+
+    let c = new_controller(&server, true).await;
+    c.upsert_pull("test/repo", pull.clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", pull.clone(), false)
+        .await
+        .unwrap();
+    first.assert();
+
+    let updated = server.change_pull_diff("test/repo", pull.number, &["wiki/Other/en.md"]);
+    let second = server
+        .server
+        .mock("GET", diff_path.as_str())
+        .with_body(updated.diff.as_ref().unwrap().to_string())
+        .expect(1)
+        .create();
+    c.upsert_pull("test/repo", updated.clone(), false)
+        .await
+        .unwrap();
+    second.assert();
+    let stored = c.memory.pull("test/repo", pull.number).unwrap();
+    assert_eq!(stored.diff.unwrap().files[0].path, "wiki/Other/en.md");
+}
+
+#[tokio::test]
+async fn test_add_just_closed_pull() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let mut pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    pull.state = "closed".to_string();
+    server = server.with_pull_without_diff("test/repo", &pull);
+
+    let c = new_controller(&server, true).await;
+    c.upsert_pull("test/repo", pull.clone(), false)
+        .await
+        .unwrap();
+    assert!(!c.memory.contains("test/repo", pull.number));
+}
+
+#[tokio::test]
+async fn test_add_open_pull_without_diff() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server.with_pull_without_diff("test/repo", &pull);
+
+    let c = new_controller(&server, true).await;
+    assert!(c
+        .upsert_pull("test/repo", pull.clone(), false)
+        .await
+        .is_err());
+    assert!(!c.memory.contains("test/repo", pull.number));
+}
+
+#[tokio::test]
+async fn test_stored_diff_only_lists_files() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md", "wiki/Article/ru.md"]);
+    server = server.with_pull("test/repo", &pull);
+
+    let c = new_controller(&server, true).await;
+    c.upsert_pull("test/repo", pull.clone(), false)
+        .await
+        .unwrap();
+
+    let stored = c.memory.pulls("test/repo").unwrap()[&pull.number]
+        .diff
+        .clone()
+        .unwrap();
+    let paths: Vec<_> = stored.files.iter().map(|f| f.path.clone()).collect();
+    assert_eq!(paths, vec!["wiki/Article/en.md", "wiki/Article/ru.md"]);
+    assert!(!stored.to_string().contains("Do whatever you want."));
+}
+
 #[tokio::test]
 async fn test_simple_overlap_originals() {
     let mut server = GitHubServer::new()
@@ -164,6 +264,56 @@ async fn test_simple_late_incomplete_translation() {
     );
 }
 
+#[tokio::test]
+async fn test_incomplete_translation_across_repo_group() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let installation = server.make_installation();
+    let repos = vec![
+        server.make_repo(installation.id, "test/repo"),
+        server.make_repo(installation.id, "test/repo-ru"),
+    ];
+    server = server.with_app_installations(&[(installation, repos)]);
+
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let translation = server.make_pull("test/repo-ru", &["wiki/Article/ru.md"]);
+    let unrelated = server.make_pull("test/repo-ru", &["wiki/Other/ru.md"]);
+    server = server
+        .with_pull("test/repo", &original)
+        .with_pulls("test/repo-ru", &[translation.clone(), unrelated.clone()]);
+
+    let config = crate::config::Controller {
+        repo_groups: vec![vec!["test/repo".to_string(), "test/repo-ru".to_string()]],
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    c.upsert_pull("test/repo", original.clone(), false)
+        .await
+        .unwrap();
+    for p in [&translation, &unrelated] {
+        c.upsert_pull("test/repo-ru", p.clone(), false)
+            .await
+            .unwrap();
+    }
+
+    // Both pulls are #1, but in different repositories.
+    assert!(c.conflicts.by_trigger("test/repo", 1).is_empty());
+    assert_eq!(
+        c.conflicts.by_trigger("test/repo-ru", 1),
+        vec![Conflict::incomplete_translation(
+            1,
+            1,
+            original.html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()]
+        )
+        .with_original_repo(Some("test/repo".to_string()))]
+    );
+    assert!(c.conflicts.by_trigger("test/repo-ru", 2).is_empty());
+
+    // Closing the original drops the conflict in the other repository.
+    c.finalize_pull("test/repo", original).await;
+    assert!(c.conflicts.by_trigger("test/repo-ru", 1).is_empty());
+}
+
 #[tokio::test]
 async fn test_multiple_overlapping_changes() {
     let mut server = GitHubServer::new()
@@ -706,7 +856,8 @@ async fn test_three_conflicts_at_once() {
                 2,
                 server.url.pull_url("test/repo", 2),
                 vec!["wiki/Other_article/ru.md".to_string(),]
-            ),
+            )
+            .with_merge_first(3),
             Conflict::incomplete_translation(
                 3,
                 1,
@@ -897,7 +1048,8 @@ async fn test_only_obsolete_conflict_is_removed_incomplete_translation() {
             1,
             server.url.pull_url("test/repo", 1),
             vec!["wiki/Article/ru.md".to_string()]
-        )]
+        )
+        .with_merge_first(2)]
     );
 }
 
@@ -972,3 +1124,310 @@ async fn test_no_notification_on_translation_merge_with_existing_conflicts() {
     c.finalize_pull("test/repo", pulls.last().unwrap().clone())
         .await;
 }
+
+#[tokio::test]
+async fn test_pulls_indexed_by_article() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let pulls = vec![
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/ru.md", "wiki/Other/ru.md"]),
+        server.make_pull("test/repo", &["wiki/Other/en.md"]),
+        server.make_pull("test/repo", &["wiki/Unrelated/en.md"]),
+    ];
+    server = server.with_pulls("test/repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    for p in pulls.iter() {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+
+    let related = |c: &Controller, p: &crate::structs::PullRequest| {
+        let mut numbers: Vec<_> = c
+            .memory
+            .pulls_sharing_articles("test/repo", p)
+            .into_keys()
+            .collect();
+        numbers.sort();
+        numbers
+    };
+    assert_eq!(related(&c, &pulls[0]), vec![1, 2]);
+    assert_eq!(related(&c, &pulls[1]), vec![1, 2, 3]);
+    assert_eq!(related(&c, &pulls[3]), vec![4]);
+
+    c.memory.remove_pull("test/repo", &pulls[1]);
+    assert_eq!(related(&c, &pulls[0]), vec![1]);
+    assert_eq!(related(&c, &pulls[2]), vec![3]);
+}
+
+#[tokio::test]
+async fn test_pulls_with_different_base_branches_do_not_conflict() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let mut pulls = vec![
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+    ];
+    pulls[1].base.branch = "release".to_string();
+    server = server.with_pulls("test/repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    for p in pulls.iter() {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+    assert!(c.conflicts.by_trigger("test/repo", 2).is_empty());
+}
+
+#[tokio::test]
+async fn test_retargeted_pull_conflicts_removed() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let pulls = vec![
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+    ];
+    server = server.with_pulls("test/repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    for p in pulls.iter() {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+    assert_eq!(c.conflicts.by_trigger("test/repo", 2).len(), 1);
+
+    let mut retargeted = pulls[1].clone();
+    retargeted.base.branch = "release".to_string();
+    retargeted.updated_at += chrono::Duration::seconds(1);
+    c.update_pull("test/repo", retargeted, false).await.unwrap();
+    assert!(c.conflicts.by_trigger("test/repo", 2).is_empty());
+}
+
+#[tokio::test]
+async fn test_pulls_indexed_by_base_branch() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let mut pulls = vec![
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/ru.md"]),
+        server.make_pull("test/repo", &["wiki/Article/ko.md"]),
+    ];
+    pulls[2].base.branch = "release".to_string();
+    server = server.with_pulls("test/repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    for p in pulls.iter() {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+
+    let mut master: Vec<_> = c
+        .memory
+        .pulls_sharing_articles("test/repo", &pulls[0])
+        .into_keys()
+        .collect();
+    master.sort();
+    assert_eq!(master, vec![1, 2]);
+
+    let release: Vec<_> = c
+        .memory
+        .pulls_sharing_articles("test/repo", &pulls[2])
+        .into_keys()
+        .collect();
+    assert_eq!(release, vec![3]);
+}
+
+#[tokio::test]
+async fn test_repo_config_limits_checked_files() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_repo_config("test/repo", "languages: [ru]\nignore: [\"wiki/Legal/**\"]")
+        .with_default_app_installations();
+
+    let pulls = vec![
+        server.make_pull(
+            "test/repo",
+            &["wiki/Legal/Terms/en.md", "wiki/Article/fr.md"],
+        ),
+        server.make_pull(
+            "test/repo",
+            &["wiki/Legal/Terms/en.md", "wiki/Article/fr.md"],
+        ),
+        server.make_pull("test/repo", &["wiki/Article/ru.md"]),
+        server.make_pull("test/repo", &["wiki/Article/ru.md"]),
+    ];
+    server = server.with_pulls("test/repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    for p in &pulls {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+
+    assert!(c.conflicts.by_trigger("test/repo", 2).is_empty());
+    assert_eq!(
+        c.conflicts.by_trigger("test/repo", 4),
+        vec![Conflict::overlap(
+            4,
+            3,
+            pulls[2].html_url.clone(),
+            vec!["wiki/Article/ru.md".to_string()]
+        )]
+    );
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_changed_repo_config_is_applied() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_repo_config("test/repo", "languages: [fr]");
+    let pulls = [
+        server.make_pull("test/repo", &["wiki/Article/ru.md"]),
+        server.make_pull("test/repo", &["wiki/Article/ru.md"]),
+    ];
+    server = server
+        .with_pulls("test/repo", &pulls)
+        .with_default_app_installations();
+    for p in &pulls {
+        server = server.with_comments("test/repo", p.number, &Vec::new());
+        server.mock_pull_comments("test/repo", p.number, None);
+    }
+
+    let mut c = new_controller(&server, true).await;
+    assert!(c.conflicts.all().is_empty());
+
+    // Unrelated repositories are left alone.
+    c.handle_message(ControllerRequest::RepoConfigChanged {
+        full_repo_name: "test/other-repo".to_string(),
+    })
+    .await;
+
+    server = server.with_repo_config("test/repo", "languages: [fr, ru]");
+    c.handle_message(ControllerRequest::RepoConfigChanged {
+        full_repo_name: "test/repo".to_string(),
+    })
+    .await;
+    assert_eq!(c.conflicts.all()["test/repo"].len(), 1);
+    assert_eq!(
+        c.repo_config("test/repo").languages,
+        vec!["fr".to_string(), "ru".to_string()]
+    );
+}
+
+#[tokio::test]
+async fn test_pull_without_articles_is_not_compared() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let other = server.make_pull("test/repo", &["README.md", "wiki/Article/notes.md"]);
+    server = server.with_pulls("test/repo", &[original.clone(), other.clone()]);
+    let comments = server
+        .mock_pull_comments("test/repo", other.number, None)
+        .expect(0);
+
+    let c = new_controller(&server, true).await;
+    c.upsert_pull("test/repo", original.clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", other.clone(), true)
+        .await
+        .unwrap();
+    comments.assert();
+    assert!(c.memory.contains("test/repo", other.number));
+    assert!(c.conflicts.by_trigger("test/repo", other.number).is_empty());
+}
+
+#[tokio::test]
+#[allow(unused_assignments)]
+async fn test_pull_which_drops_articles_loses_its_conflicts() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let other = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server.with_pulls("test/repo", &[original.clone(), other.clone()]);
+
+    let c = new_controller(&server, true).await;
+    c.upsert_pull("test/repo", original.clone(), false)
+        .await
+        .unwrap();
+    c.upsert_pull("test/repo", other.clone(), false)
+        .await
+        .unwrap();
+    assert_eq!(c.conflicts.by_trigger("test/repo", other.number).len(), 1);
+
+    let updated = server.change_pull_diff("test/repo", other.number, &["README.md"]);
+    server = server.with_pull("test/repo", &updated);
+    c.upsert_pull("test/repo", updated, false).await.unwrap();
+    assert!(c.conflicts.by_trigger("test/repo", other.number).is_empty());
+}
+
+#[tokio::test]
+#[allow(unused_assignments)]
+async fn test_pull_merged_by_queue_is_finalized_without_event() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let queued = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let cancelled = server.make_pull("test/repo", &["wiki/Other/en.md"]);
+    server = server
+        .with_pulls(
+            "test/repo",
+            &[original.clone(), queued.clone(), cancelled.clone()],
+        )
+        .with_default_app_installations();
+
+    let mut c = new_controller(&server, true).await;
+    for p in [&queued, &cancelled] {
+        c.handle_message(ControllerRequest::MergeExpected {
+            full_repo_name: "test/repo".into(),
+            pull_request: Box::new(p.clone()),
+        })
+        .await;
+    }
+    c.handle_message(ControllerRequest::MergeCancelled {
+        full_repo_name: "test/repo".into(),
+        pull_number: cancelled.number,
+    })
+    .await;
+    // Pulls are read in no particular order, so either of them may be the original.
+    let involving = |c: &Controller, n: i32| {
+        c.conflicts.by_trigger("test/repo", n).len() + c.conflicts.by_original("test/repo", n).len()
+    };
+    assert_eq!(involving(&c, queued.number), 1);
+
+    let mut merged = queued.clone();
+    merged.state = "closed".to_string();
+    merged.merged = true;
+    merged.merged_at = Some(chrono::Utc::now());
+    server = server.with_pull_details("test/repo", &merged);
+    c.handle_message(ControllerRequest::MergeCheck {
+        full_repo_name: "test/repo".into(),
+        pull_number: None,
+        attempt: 0,
+    })
+    .await;
+    assert!(!c.memory.contains("test/repo", queued.number));
+    assert!(c.memory.contains("test/repo", cancelled.number));
+    assert!(c
+        .conflicts
+        .by_trigger("test/repo", queued.number)
+        .is_empty());
+    assert!(c.expected_merges.lock().unwrap().is_empty());
+}
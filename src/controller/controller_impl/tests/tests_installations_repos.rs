@@ -1,4 +1,5 @@
 use super::*;
+use crate::memory::RepoKey;
 
 #[allow(unused_assignments)]
 #[tokio::test]
@@ -70,16 +71,10 @@ async fn test_add_installation_pull_requests_fetched() {
         .with_pulls("test/my-repo", &pulls);
 
     c.add_installation(installation.clone()).await.unwrap();
-    let mut pulls_from_memory: Vec<_> = c
-        .memory
-        .pulls("test/my-repo")
-        .unwrap()
-        .keys()
-        .cloned()
-        .collect();
-    pulls_from_memory.sort();
-
-    assert_eq!(pulls_from_memory, vec![pulls[0].number, pulls[1].number]);
+    assert_eq!(
+        c.memory.pull_numbers("test/my-repo"),
+        vec![pulls[0].number, pulls[1].number]
+    );
 }
 
 #[tokio::test]
@@ -220,7 +215,7 @@ async fn test_delete_installation() {
     c.init().await.unwrap();
     c.delete_installation(installation);
 
-    assert!(c.memory.pulls.lock().unwrap().is_empty());
+    assert!(c.memory.repositories().is_empty());
 }
 
 #[allow(unused_assignments)]
@@ -251,14 +246,13 @@ async fn test_remove_repositories() {
     c.init().await.unwrap();
     c.remove_repositories(installation.id, &[repos[0].clone()]);
 
-    let second_repo_only = c.memory.pulls.lock().unwrap();
-    assert_eq!(second_repo_only.len(), 1);
-    let cached_pr = second_repo_only
-        .get("test/other-repo-repo")
-        .unwrap()
-        .values()
-        .next()
-        .take()
+    assert_eq!(
+        c.memory.repositories(),
+        vec![RepoKey::from("test/other-repo-repo")]
+    );
+    let cached_pr = c
+        .memory
+        .pull("test/other-repo-repo", pulls[2].number)
         .unwrap();
     assert_eq!(cached_pr.id, pulls[2].id);
 }
@@ -285,8 +279,603 @@ async fn test_remove_pull() {
     c.init().await.unwrap();
     c.finalize_pull("test/my-repo", pulls[1].clone()).await;
 
-    let repos = c.memory.pulls.lock().unwrap();
-    let first_repo = repos.get("test/my-repo").unwrap();
-    let cached_pr = first_repo.values().next().take().unwrap();
-    assert_eq!(cached_pr.id, 1);
+    assert_eq!(c.memory.pull_numbers("test/my-repo"), vec![pulls[0].number]);
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_add_installation_denied_account() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_app_installations(&Vec::new());
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/repo");
+    server = server.with_app_installations(&[(installation.clone(), vec![repo])]);
+    let uninstall = server
+        .server
+        .mock("DELETE", "/app/installations/1")
+        .with_status(204)
+        .expect(1)
+        .create();
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        access: crate::config::Access {
+            denied_accounts: vec!["TicClick".to_string()],
+            uninstall_denied: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, false, config).await;
+
+    c.add_installation(installation.clone()).await.unwrap();
+    assert!(c.github.cached_repositories(installation.id).is_empty());
+    assert!(c.memory.pulls("test/repo").is_none());
+    uninstall.assert();
+}
+
+#[tokio::test]
+async fn test_add_repositories_denied_repository() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+
+    let installation = server.make_installation();
+    let repos = [
+        server.make_repo(installation.id, "test/my-repo"),
+        server.make_repo(installation.id, "test/my-other-repo"),
+    ];
+    let pulls = [
+        server.make_pull("test/my-repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/my-other-repo", &["wiki/Article/en.md"]),
+    ];
+    server = server
+        .with_app_installations(&[(installation.clone(), repos.to_vec())])
+        .with_pull("test/my-repo", &pulls[0])
+        .with_pull("test/my-other-repo", &pulls[1]);
+
+    let config = crate::config::Controller {
+        post_comments: true,
+        access: crate::config::Access {
+            denied_repositories: vec!["test/my-other-repo".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+
+    assert!(c.memory.pulls("test/my-repo").is_some());
+    assert!(c.memory.pulls("test/my-other-repo").is_none());
+    assert_eq!(
+        c.github.cached_repositories(installation.id),
+        vec![repos[0].clone()]
+    );
+}
+
+#[tokio::test]
+async fn test_add_repository_via_graphql() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/my-repo");
+    let pulls = [
+        server.make_pull("test/my-repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/my-repo", &["wiki/Article/ru.md"]),
+    ];
+
+    // No diffs are mocked: they should come from the GraphQL response.
+    server = server
+        .with_app_installations(&[(installation.clone(), vec![repo.clone()])])
+        .with_graphql_pulls("test/my-repo");
+
+    let (_, c) = make_controller_with_config(
+        &server,
+        true,
+        crate::config::Controller {
+            graphql: true,
+            ..Default::default()
+        },
+    )
+    .await;
+    c.add_repositories(installation.id, vec![repo]).await;
+
+    let stored = c.memory.pulls("test/my-repo").unwrap();
+    assert_eq!(stored.len(), 2);
+    for p in &pulls {
+        let files: Vec<_> = stored[&p.number]
+            .diff
+            .as_ref()
+            .unwrap()
+            .files
+            .iter()
+            .map(|f| f.path.clone())
+            .collect();
+        let expected: Vec<_> = p
+            .diff
+            .as_ref()
+            .unwrap()
+            .files
+            .iter()
+            .map(|f| f.path.clone())
+            .collect();
+        assert_eq!(files, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_add_repository_graphql_falls_back_to_rest() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/my-repo");
+    let pulls = [server.make_pull("test/my-repo", &["wiki/Article/en.md"])];
+    server = server
+        .with_app_installations(&[(installation.clone(), vec![repo.clone()])])
+        .with_pulls("test/my-repo", &pulls);
+    server
+        .server
+        .mock("POST", "/graphql")
+        .with_status(200)
+        .with_body(r#"{"data": null, "errors": [{"message": "Something went wrong"}]}"#)
+        .create();
+
+    let (_, c) = make_controller_with_config(
+        &server,
+        true,
+        crate::config::Controller {
+            graphql: true,
+            ..Default::default()
+        },
+    )
+    .await;
+    c.add_repositories(installation.id, vec![repo]).await;
+
+    let stored = c.memory.pulls("test/my-repo").unwrap();
+    assert_eq!(stored.keys().cloned().collect::<Vec<_>>(), vec![1]);
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_suspended_installation_is_left_alone() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_app_installations(&Vec::new());
+    let mut c = new_controller(&server, true).await;
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/repo");
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let pulls = server
+        .server
+        .mock(
+            "GET",
+            "/repos/test/repo/pulls?state=open&direction=asc&sort=created&per_page=100&page=1",
+        )
+        .with_status(200)
+        .with_body(serde_json::to_string(&[&p1]).unwrap())
+        .expect(2)
+        .create();
+    server = server
+        .with_app_installations(&[(installation.clone(), vec![repo.clone()])])
+        .with_pull("test/repo", &p1);
+    c.add_installation(installation.clone()).await.unwrap();
+
+    c.handle_message(ControllerRequest::InstallationSuspended {
+        installation: Box::new(installation.clone()),
+    })
+    .await;
+    assert!(c.github.cached_installations()[0].suspended_at.is_some());
+    assert_eq!(c.github.cached_repositories(1), vec![repo]);
+
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    c.handle_message(ControllerRequest::PullRequestCreated {
+        full_repo_name: "test/repo".to_string(),
+        pull_request: Box::new(p2.clone()),
+        trigger_updates: true,
+    })
+    .await;
+    let known = c.memory.pulls("test/repo").unwrap();
+    assert!(known.contains_key(&p1.number));
+    assert!(!known.contains_key(&p2.number));
+    assert!(c.rate_limits().await.is_empty());
+
+    // Unsuspending reads the pulls again, since events could have been missed in the meantime.
+    c.handle_message(ControllerRequest::InstallationUnsuspended {
+        installation: Box::new(installation),
+    })
+    .await;
+    assert!(c.github.cached_installations()[0].suspended_at.is_none());
+    assert!(c
+        .memory
+        .pulls("test/repo")
+        .unwrap()
+        .contains_key(&p1.number));
+    pulls.assert();
+}
+
+#[tokio::test]
+async fn test_suspended_installation_is_not_read_on_startup() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let mut installation = server.make_installation();
+    installation.suspended_at = Some(chrono::Utc::now());
+    server.make_repo(installation.id, "test/repo");
+    let token = server
+        .server
+        .mock("POST", "/app/installations/1/access_tokens")
+        .expect(0)
+        .create();
+    server = server.with_app_installations(&[(installation, Vec::new())]);
+
+    let c = new_controller(&server, true).await;
+    assert_eq!(c.github.cached_installations().len(), 1);
+    assert!(c.github.cached_repositories(1).is_empty());
+    token.assert();
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_renamed_repository_keeps_its_state() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_app_installations(&Vec::new());
+    let mut c = new_controller(&server, true).await;
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/repo");
+    let pulls = [
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+    ];
+    server = server
+        .with_pulls("test/repo", &pulls)
+        .with_app_installations(&[(installation.clone(), vec![repo.clone()])]);
+    c.add_installation(installation).await.unwrap();
+    assert_eq!(c.conflicts.all()["test/repo"].len(), 1);
+
+    let renamed = crate::structs::Repository {
+        name: "renamed".to_string(),
+        full_name: "test/renamed".to_string(),
+        ..repo
+    };
+    c.handle_message(ControllerRequest::RepositoryRenamed {
+        installation_id: 1,
+        repository: renamed.clone(),
+    })
+    .await;
+
+    assert!(c.memory.pulls("test/repo").is_none());
+    assert_eq!(c.memory.pulls("test/renamed").unwrap().len(), 2);
+    let conflicts = c.conflicts.all();
+    assert!(!conflicts.contains_key("test/repo"));
+    assert_eq!(conflicts["test/renamed"].len(), 1);
+    assert_eq!(c.github.cached_repositories(1), vec![renamed]);
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_renamed_account_keeps_its_repositories() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_app_installations(&Vec::new());
+    let mut c = new_controller(&server, true).await;
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "TicClick/repo");
+    let pulls = [
+        server.make_pull("TicClick/repo", &["wiki/Article/en.md"]),
+        server.make_pull("TicClick/repo", &["wiki/Article/en.md"]),
+    ];
+    server = server
+        .with_pulls("TicClick/repo", &pulls)
+        .with_app_installations(&[(installation.clone(), vec![repo.clone()])]);
+    c.add_installation(installation).await.unwrap();
+
+    let account = crate::structs::Actor {
+        id: 1,
+        login: "TicClick-renamed".to_string(),
+    };
+    c.handle_message(ControllerRequest::InstallationTargetRenamed {
+        installation_id: 1,
+        account: account.clone(),
+    })
+    .await;
+
+    assert_eq!(c.github.cached_installations()[0].account, account);
+    assert!(c.memory.pulls("TicClick/repo").is_none());
+    assert_eq!(c.memory.pulls("TicClick-renamed/repo").unwrap().len(), 2);
+    assert_eq!(c.conflicts.all()["TicClick-renamed/repo"].len(), 1);
+    assert_eq!(
+        c.github.cached_repositories(1),
+        vec![crate::structs::Repository {
+            full_name: "TicClick-renamed/repo".to_string(),
+            ..repo
+        }]
+    );
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_renamed_account_is_dropped_if_not_allowed() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_app_installations(&Vec::new());
+    let config = crate::config::Controller {
+        post_comments: true,
+        access: crate::config::Access {
+            allowed_accounts: vec!["TicClick".to_string()],
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let (_, mut c) = make_controller_with_config(&server, true, config).await;
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "TicClick/repo");
+    let pulls = [server.make_pull("TicClick/repo", &["wiki/Article/en.md"])];
+    server = server
+        .with_pulls("TicClick/repo", &pulls)
+        .with_app_installations(&[(installation.clone(), vec![repo])]);
+    c.add_installation(installation).await.unwrap();
+
+    c.handle_message(ControllerRequest::InstallationTargetRenamed {
+        installation_id: 1,
+        account: crate::structs::Actor {
+            id: 1,
+            login: "someone-else".to_string(),
+        },
+    })
+    .await;
+    assert!(c.github.cached_installations().is_empty());
+    assert!(c.memory.pulls("TicClick/repo").is_none());
+}
+
+#[allow(unused_assignments)]
+#[tokio::test]
+async fn test_archived_repository_is_left_alone() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_app_installations(&Vec::new());
+    let mut c = new_controller(&server, true).await;
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/repo");
+    let mut archived = server.make_repo(installation.id, "test/archived");
+    archived.archived = true;
+    let pulls = [
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+    ];
+    server = server
+        .with_pulls("test/repo", &pulls)
+        .with_app_installations(&[(installation.clone(), vec![repo.clone(), archived])]);
+    c.add_installation(installation).await.unwrap();
+    assert_eq!(c.github.cached_repositories(1), vec![repo.clone()]);
+    assert!(c.memory.pulls("test/archived").is_none());
+
+    // Once the other repository is archived too, pending comments about it are dropped along with everything else.
+    let conflict = c.conflicts.all()["test/repo"][0].clone();
+    c.retries.schedule("test/repo", &conflict, false);
+    c.handle_message(ControllerRequest::InstallationRepositoriesRemoved {
+        installation_id: 1,
+        repositories: vec![repo],
+    })
+    .await;
+    assert!(c.github.cached_repositories(1).is_empty());
+    assert!(c.memory.pulls("test/repo").is_none());
+    assert!(!c.conflicts.all().contains_key("test/repo"));
+    assert!(c.retries.all().is_empty());
+}
+
+#[tokio::test]
+#[allow(unused_assignments)]
+async fn test_only_most_recently_updated_pulls_are_kept() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/my-repo");
+    let since = chrono::Utc::now() - chrono::Duration::days(1);
+    let mut pulls = Vec::new();
+    for hours in [2, 0, 1] {
+        let mut p = server.make_pull("test/my-repo", &["wiki/Article/en.md"]);
+        p.updated_at = since + chrono::Duration::hours(hours);
+        server
+            .pulls
+            .get_mut("test/my-repo")
+            .unwrap()
+            .insert(p.number, p.clone());
+        pulls.push(p);
+    }
+    server = server
+        .with_app_installations(&[(installation.clone(), vec![repo])])
+        .with_pulls("test/my-repo", &pulls);
+
+    let config = crate::config::Controller {
+        max_pulls_per_repo: 2,
+        ..Default::default()
+    };
+    let (_, c) = make_controller_with_config(&server, true, config).await;
+    assert_eq!(
+        c.memory.pull_numbers("test/my-repo"),
+        vec![pulls[0].number, pulls[2].number]
+    );
+    assert_eq!(
+        c.over_limit().lock().unwrap().iter().collect::<Vec<_>>(),
+        vec!["test/my-repo"]
+    );
+
+    // A new pull pushes out the least recently updated one, with its conflicts.
+    let new_pull = server.make_pull("test/my-repo", &["wiki/Article/en.md"]);
+    server = server.with_pull("test/my-repo", &new_pull);
+    c.upsert_pull("test/my-repo", new_pull.clone(), false)
+        .await
+        .unwrap();
+    assert_eq!(
+        c.memory.pull_numbers("test/my-repo"),
+        vec![pulls[0].number, new_pull.number]
+    );
+    assert!(c
+        .conflicts
+        .by_trigger("test/my-repo", pulls[2].number)
+        .is_empty());
+    assert!(c
+        .conflicts
+        .by_original("test/my-repo", pulls[2].number)
+        .is_empty());
+}
+
+#[tokio::test]
+async fn test_lazy_hydration_waits_for_first_request() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/my-repo");
+    let pulls = [
+        server.make_pull("test/my-repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/my-repo", &["wiki/Article/en.md"]),
+    ];
+    server = server
+        .with_app_installations(&[(installation.clone(), vec![repo.clone()])])
+        .with_pulls("test/my-repo", &pulls);
+
+    let config = crate::config::Controller {
+        hydration: crate::config::HydrationMode::Lazy,
+        ..Default::default()
+    };
+    let (_, mut c) = make_controller_with_config(&server, true, config).await;
+    assert_eq!(c.github.cached_repositories(installation.id), vec![repo]);
+    assert!(c.memory.pulls("test/my-repo").is_none());
+
+    c.handle_message(ControllerRequest::PullRequestUpdated {
+        full_repo_name: "test/my-repo".into(),
+        pull_request: Box::new(pulls[1].clone()),
+        trigger_updates: false,
+    })
+    .await;
+    assert_eq!(
+        c.memory.pull_numbers("test/my-repo"),
+        vec![pulls[0].number, pulls[1].number]
+    );
+    // Pulls are read in no particular order, so either of them may be the original.
+    assert_eq!(
+        c.conflicts
+            .by_trigger("test/my-repo", pulls[1].number)
+            .len()
+            + c.conflicts
+                .by_original("test/my-repo", pulls[1].number)
+                .len(),
+        1
+    );
+    assert!(c.unhydrated.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn test_diffs_are_prefetched_on_hydration() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/my-repo");
+    let pulls = [
+        server.make_pull("test/my-repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/my-repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/my-repo", &["wiki/Article/ru.md"]),
+        server.make_pull("test/my-repo", &["wiki/Other_article/en.md"]),
+    ];
+    server = server
+        .with_app_installations(&[(installation, vec![repo])])
+        .with_pulls("test/my-repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    assert_eq!(
+        c.memory.pull_numbers("test/my-repo"),
+        pulls.iter().map(|p| p.number).collect::<Vec<_>>()
+    );
+    for p in &pulls {
+        assert_eq!(
+            c.memory.pull("test/my-repo", p.number).unwrap().diff,
+            p.diff
+        );
+    }
+    // #1 and #2 overlap, and #3 is a translation of both.
+    let conflicts: usize = c.conflicts.all().values().map(|cc| cc.len()).sum();
+    assert_eq!(conflicts, 3);
+
+    let progress = c.hydration().progress();
+    assert_eq!(progress.pulls_hydrated, pulls.len());
+    assert_eq!(progress.diffs_fetched, pulls.len());
+}
+
+#[tokio::test]
+async fn test_bootstrap_finds_same_conflicts_as_pulls_added_one_by_one() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/my-repo");
+    let pulls = [
+        server.make_pull("test/my-repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/my-repo", &["wiki/Article/en.md", "wiki/Other/en.md"]),
+        server.make_pull("test/my-repo", &["wiki/Article/ru.md"]),
+        server.make_pull("test/my-repo", &["wiki/Other/fr.md", "wiki/Other/ru.md"]),
+        server.make_pull("test/my-repo", &["README.md"]),
+    ];
+    server = server
+        .with_app_installations(&[(installation, vec![repo])])
+        .with_pulls("test/my-repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    let sorted = |c: &Controller| {
+        let mut cc = c.conflicts.all().remove("test/my-repo").unwrap_or_default();
+        for conflict in cc.iter_mut() {
+            conflict.created_at = None;
+            conflict.updated_at = None;
+        }
+        cc.sort_by_key(|conflict| conflict.key());
+        cc
+    };
+    let bootstrapped = sorted(&c);
+    assert!(!bootstrapped.is_empty());
+
+    // Forget everything, and add the pulls again in the order the repository has been read in.
+    let order = c.github.read_pulls("test/my-repo").await.unwrap();
+    for p in &order {
+        c.memory.remove_pull("test/my-repo", p);
+        c.conflicts
+            .remove_conflicts_by_pull("test/my-repo", p.number);
+    }
+    for p in order {
+        c.upsert_pull("test/my-repo", p, false).await.unwrap();
+    }
+    assert_eq!(sorted(&c), bootstrapped);
+}
+
+#[tokio::test]
+async fn test_installations_without_comment_permissions_are_flagged() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_app_installations(&Vec::new());
+    let c = new_controller(&server, true).await;
+
+    let allowed = server.make_installation();
+    let denied = server.make_installation();
+    let _server = server
+        .with_installation_permissions(
+            denied.id,
+            &[("metadata", "read"), ("pull_requests", "read")],
+        )
+        .with_app_installations(&[(allowed.clone(), Vec::new()), (denied.clone(), Vec::new())]);
+    c.add_installation(allowed.clone()).await.unwrap();
+    c.add_installation(denied.clone()).await.unwrap();
+
+    let flags: Vec<_> = c
+        .installations()
+        .iter()
+        .map(|i| (i.installation_id, i.cannot_comment))
+        .collect();
+    assert_eq!(flags, vec![(allowed.id, false), (denied.id, true)]);
 }
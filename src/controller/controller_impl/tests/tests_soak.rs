@@ -0,0 +1,144 @@
+//! A soak test: a storm of `pull_request` events against a controller on top of the in-memory GitHub backend, which
+//! reports throughput, queue latency and memory use, and fails if throughput drops below a floor.
+//!
+//! Only built with the `soak` feature, and best run in release mode:
+//!
+//! ```shell
+//! cargo test --release --features soak soak -- --nocapture
+//! ```
+//!
+//! The run is sized by `SOAK_PULLS` and `SOAK_EVENTS`, and `SOAK_MIN_EVENTS_PER_SECOND` sets the floor.
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+
+use super::*;
+use crate::test::MockGitHub;
+
+/// Articles changed by the pulls: few enough for most pulls to conflict with each other.
+const ARTICLES: usize = 20;
+const LANGUAGES: [&str; 4] = ["en", "ru", "pt-br", "zh-tw"];
+
+/// Queue latency is measured by a probe request after this many events.
+const PROBE_EVERY: usize = 50;
+
+fn env_or(name: &str, default: usize) -> usize {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(default)
+}
+
+/// Resident memory of the process, if the platform tells.
+fn resident_bytes() -> Option<usize> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: usize = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * 4096)
+}
+
+fn percentile(sorted: &[Duration], p: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    sorted[(sorted.len() - 1) * p / 100]
+}
+
+#[tokio::test(flavor = "multi_thread")]
+async fn soak_pull_request_storm() {
+    let pulls_count = env_or("SOAK_PULLS", 200);
+    let events_count = env_or("SOAK_EVENTS", 2000);
+    let min_events_per_second = env_or("SOAK_MIN_EVENTS_PER_SECOND", 20);
+
+    let mut server = GitHubServer::new().await;
+    let installation = server.make_installation();
+    server.make_repo(installation.id, "test/repo");
+    let pulls: Vec<_> = (0..pulls_count)
+        .map(|i| {
+            let files = [
+                format!("wiki/Article_{}/{}.md", i % ARTICLES, LANGUAGES[i % 4]),
+                format!(
+                    "wiki/Article_{}/{}.md",
+                    (i * 7) % ARTICLES,
+                    LANGUAGES[(i / 4) % 4]
+                ),
+            ];
+            let files: Vec<_> = files.iter().map(|f| f.as_str()).collect();
+            server.make_pull("test/repo", &files)
+        })
+        .collect();
+
+    let (tx, rx) = tokio::sync::mpsc::channel(10);
+    let mut c = Controller::with_backend(
+        rx,
+        MockGitHub::from(&server),
+        crate::config::Controller {
+            post_comments: true,
+            ..Default::default()
+        },
+    );
+    let rss_before = resident_bytes();
+    c.init().await.unwrap();
+    let controller = tokio::spawn(async move {
+        c.run_forever().await;
+        c
+    });
+
+    let started = Instant::now();
+    let mut latencies = Vec::new();
+    for i in 0..events_count {
+        let mut pull = pulls[i % pulls.len()].clone();
+        // Every event is a new push, so that its diff is read and compared again.
+        pull.updated_at = chrono::Utc::now();
+        pull.head.sha = format!("{:040x}", i + 1);
+        tx.send(ControllerRequest::PullRequestUpdated {
+            full_repo_name: "test/repo".into(),
+            pull_request: Box::new(pull),
+            trigger_updates: true,
+        })
+        .await
+        .unwrap();
+
+        if (i + 1) % PROBE_EVERY == 0 {
+            let (reply_to, reply) = oneshot::channel();
+            let sent = Instant::now();
+            tx.send(ControllerRequest::RateLimits { reply_to })
+                .await
+                .unwrap();
+            reply.await.unwrap();
+            latencies.push(sent.elapsed());
+        }
+    }
+    drop(tx);
+    let c = controller.await.unwrap();
+    let elapsed = started.elapsed();
+    let rss_after = resident_bytes();
+
+    latencies.sort();
+    let events_per_second = events_count as f64 / elapsed.as_secs_f64();
+    let conflicts: usize = c.conflicts.all().values().map(|cc| cc.len()).sum();
+    println!(
+        "soak: {} events over {} pulls in {:.2?} ({:.0} events/s), {} conflicts",
+        events_count, pulls_count, elapsed, events_per_second, conflicts
+    );
+    println!(
+        "soak: queue latency p50 {:.2?}, p95 {:.2?}, max {:.2?}",
+        percentile(&latencies, 50),
+        percentile(&latencies, 95),
+        latencies.last().copied().unwrap_or_default()
+    );
+    if let (Some(before), Some(after)) = (rss_before, rss_after) {
+        println!(
+            "soak: resident memory {} MiB -> {} MiB",
+            before >> 20,
+            after >> 20
+        );
+    }
+
+    assert_eq!(c.memory.pull_numbers("test/repo").len(), pulls_count);
+    assert!(
+        events_per_second >= min_events_per_second as f64,
+        "throughput of {:.0} events/s is below {}",
+        events_per_second,
+        min_events_per_second
+    );
+}
@@ -0,0 +1,121 @@
+use super::*;
+
+use crate::state::Snapshot;
+
+#[tokio::test]
+async fn test_export_import_roundtrip() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let pulls = vec![
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md", "wiki/Other/ru.md"]),
+    ];
+    server = server.with_pulls("test/repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    for p in pulls.iter() {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+
+    let mut merged = server.make_pull("test/repo", &["wiki/Merged/en.md"]);
+    merged.merged = true;
+    c.history.record(
+        "test/repo",
+        &merged,
+        crate::helpers::conflicts::ArticleLayout::Folder,
+    );
+
+    let snapshot = c.export_state();
+    assert_eq!(snapshot.installations.len(), 1);
+    assert_eq!(
+        snapshot.installations[0].repositories[0].full_name,
+        "test/repo"
+    );
+    assert_eq!(snapshot.conflicts["test/repo"].len(), 1);
+
+    let json = serde_json::to_string(&snapshot).unwrap();
+    let restored = Snapshot::from_json(&json).unwrap();
+
+    // Nothing but the app information is requested from GitHub.
+    let mut other = new_controller(&server, false).await;
+    other.import_state(restored).await.unwrap();
+
+    let mut numbers: Vec<_> = other
+        .memory
+        .pulls("test/repo")
+        .unwrap()
+        .into_keys()
+        .collect();
+    numbers.sort();
+    assert_eq!(numbers, vec![1, 2]);
+    assert_eq!(
+        other.conflicts.by_trigger("test/repo", 2),
+        c.conflicts.by_trigger("test/repo", 2)
+    );
+    assert_eq!(
+        other.github.cached_repositories(1),
+        c.github.cached_repositories(1)
+    );
+    assert_eq!(
+        other.history.repository("test/repo"),
+        c.history.repository("test/repo")
+    );
+    assert_eq!(other.history.repository("test/repo").len(), 1);
+
+    let stored = other.memory.pull("test/repo", 2).unwrap();
+    let files: Vec<_> = stored
+        .diff
+        .unwrap()
+        .files
+        .iter()
+        .map(|f| f.path.clone())
+        .collect();
+    assert_eq!(files, vec!["wiki/Article/en.md", "wiki/Other/ru.md"]);
+}
+
+#[tokio::test]
+async fn test_snapshot_version_checked() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let c = new_controller(&server, true).await;
+
+    let mut snapshot = c.export_state();
+    snapshot.version += 1;
+    let json = serde_json::to_string(&snapshot).unwrap();
+    assert!(Snapshot::from_json(&json).is_err());
+}
+
+#[tokio::test]
+async fn test_compaction_drops_conflicts_with_forgotten_pulls() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+
+    let pulls = vec![
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+        server.make_pull("test/repo", &["wiki/Article/en.md"]),
+    ];
+    server = server.with_pulls("test/repo", &pulls);
+
+    let c = new_controller(&server, true).await;
+    for p in pulls.iter() {
+        c.upsert_pull("test/repo", p.clone(), false).await.unwrap();
+    }
+    assert_eq!(c.conflicts.all()["test/repo"].len(), 3);
+
+    // The pull disappears from memory without its conflicts, as if something had gone wrong.
+    c.memory.remove_pull("test/repo", &pulls[0]);
+    c.compact();
+    let remaining = c.conflicts.all()["test/repo"].clone();
+    assert_eq!(remaining.len(), 1);
+    assert!(remaining
+        .iter()
+        .all(|cc| cc.trigger != pulls[0].number && cc.original != pulls[0].number));
+}
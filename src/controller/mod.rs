@@ -1,18 +1,125 @@
 mod controller_impl;
 
+use std::collections::BTreeSet;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
 use eyre::Result;
 use tokio::sync::{mpsc, oneshot};
 
 use crate::config;
+use crate::graph::ConflictGraph;
+use crate::helpers::commands::Command;
+use crate::helpers::conflicts::Conflict;
+use crate::helpers::diff::DiffSummary;
+use crate::history::RepoHistory;
+use crate::hydration::{Hydration, Progress};
+use crate::state::Snapshot;
 use crate::structs::*;
+use crate::throttle::{self, Throttle};
+use crate::trace::{self, Trace};
+
+/// How often the controller is asked to retry failed comment writes.
+const RETRY_INTERVAL: std::time::Duration = std::time::Duration::from_secs(60);
+
+/// How often the app information is read again, so that a renamed app still recognizes its own comments.
+const APP_REFRESH_INTERVAL: std::time::Duration = std::time::Duration::from_secs(6 * 60 * 60);
+
+/// How many times the watchdog looks at the controller within `controller.watchdog_seconds` (see [`crate::watchdog`]).
+const WATCHDOG_CHECKS_PER_PERIOD: u64 = 4;
 
 /// Message structs for interacting with the controller backend.
 #[derive(Debug)]
 pub enum ControllerRequest {
+    /// Another request, caused by the webhook delivery with the given ID (see [`ControllerHandle::with_delivery`]).
+    Delivered {
+        delivery_id: String,
+        request: Box<ControllerRequest>,
+    },
+
     Init {
         reply_to: oneshot::Sender<Result<()>>,
     },
 
+    ExportState {
+        reply_to: oneshot::Sender<Snapshot>,
+    },
+    ImportState {
+        snapshot: Box<Snapshot>,
+        reply_to: oneshot::Sender<Result<()>>,
+    },
+
+    RateLimits {
+        reply_to: oneshot::Sender<Vec<InstallationRateLimits>>,
+    },
+
+    /// Known installations and their repositories, sorted by ID.
+    Installations {
+        reply_to: oneshot::Sender<Vec<InstallationSummary>>,
+    },
+
+    /// Get a new token for an installation, even if the cached one is still valid.
+    RefreshInstallationToken {
+        installation_id: i64,
+        reply_to: oneshot::Sender<Result<()>>,
+    },
+
+    /// Drop an installation and everything known about its repositories, without uninstalling the app. Replies with
+    /// whether the installation was known.
+    ForgetInstallation {
+        installation_id: i64,
+        reply_to: oneshot::Sender<bool>,
+    },
+
+    /// Render a comment about a made-up conflict, as it would be posted in the repository.
+    PreviewComment {
+        full_repo_name: String,
+        conflict: Box<Conflict>,
+        reply_to: oneshot::Sender<String>,
+    },
+
+    /// Conflicts which a pull with these changes would have with open pulls of the repository, without storing anything.
+    /// Only pulls targeting `base_branch` are compared, if it's set. `None` if the repository is unknown.
+    Simulate {
+        full_repo_name: String,
+        diff: Box<DiffSummary>,
+        base_branch: Option<String>,
+        reply_to: oneshot::Sender<Option<Vec<Conflict>>>,
+    },
+
+    /// Open pulls of a repository which change an article, given by its path or a path of any of its files. `None` if the
+    /// repository is unknown.
+    ArticlePulls {
+        full_repo_name: String,
+        path: String,
+        reply_to: oneshot::Sender<Option<Vec<ArticlePull>>>,
+    },
+
+    /// The latest merged change to every original of a repository (see [`crate::history`]). `None` if the repository is
+    /// unknown.
+    ArticleHistory {
+        full_repo_name: String,
+        reply_to: oneshot::Sender<Option<RepoHistory>>,
+    },
+
+    /// Open pulls of a repository and conflicts between them (see [`crate::graph`]). `None` if the repository is unknown.
+    ConflictGraph {
+        full_repo_name: String,
+        reply_to: oneshot::Sender<Option<ConflictGraph>>,
+    },
+
+    /// Retry comment writes which have failed before and are due by now (see [`crate::retries`]).
+    RetryPendingSyncs,
+
+    /// Compare a few open pulls with GitHub (see [`crate::self_check`]).
+    SelfCheck,
+
+    /// Prune and check stored conflicts (see `controller.compaction_interval_hours`).
+    Compact,
+
+    /// Read the app information again.
+    RefreshApp,
+
     PullRequestCreated {
         full_repo_name: String,
         pull_request: Box<PullRequest>,
@@ -23,10 +130,65 @@ pub enum ControllerRequest {
         pull_request: Box<PullRequest>,
         trigger_updates: bool,
     },
+    /// The end of the debounce window of a pull request update (see `controller.debounce_seconds`). Only the update
+    /// with the given sequence number is applied: if another one has arrived in the meantime, it has its own window.
+    DebouncedUpdate {
+        full_repo_name: String,
+        pull_number: i32,
+        sequence: u64,
+    },
+    /// A pull request which was closed before. Unlike [`ControllerRequest::PullRequestCreated`], nothing known about it is trusted.
+    PullRequestReopened {
+        full_repo_name: String,
+        pull_request: Box<PullRequest>,
+    },
     PullRequestClosed {
         full_repo_name: String,
         pull_request: Box<PullRequest>,
     },
+    PullRequestReviewed {
+        full_repo_name: String,
+        pull_number: i32,
+        review: Box<PullRequestReview>,
+    },
+
+    CommandReceived {
+        full_repo_name: String,
+        pull_number: i32,
+        comment: Box<IssueComment>,
+        command: Command,
+    },
+
+    /// A pull request is going to be merged without anyone's further action: it has joined a merge queue, or auto-merge
+    /// has been enabled for it.
+    MergeExpected {
+        full_repo_name: String,
+        pull_request: Box<PullRequest>,
+    },
+    /// A pull request has left the merge queue, or auto-merge has been disabled for it.
+    MergeCancelled {
+        full_repo_name: String,
+        pull_number: i32,
+    },
+    /// Check whether a pull request expected to be merged has been closed, in case the event about it is missed
+    /// (see [`ControllerRequest::MergeExpected`]). Without `pull_number`, every such pull of the repository is checked.
+    MergeCheck {
+        full_repo_name: String,
+        pull_number: Option<i32>,
+        attempt: usize,
+    },
+
+    /// Someone (typically a CI job) has asked to check open pull requests again, for changes GitHub sends no events about.
+    RescanRequested {
+        full_repo_name: String,
+        base_branch: Option<String>,
+        pull_number: Option<i32>,
+    },
+
+    /// The settings file of the repository has changed (see [`crate::repo_config`]).
+    RepoConfigChanged {
+        full_repo_name: String,
+    },
 
     InstallationCreated {
         installation: Box<Installation>,
@@ -34,6 +196,19 @@ pub enum ControllerRequest {
     InstallationDeleted {
         installation: Box<Installation>,
     },
+    /// The installation is kept along with everything known about it, but its repositories are left alone.
+    InstallationSuspended {
+        installation: Box<Installation>,
+    },
+    InstallationUnsuspended {
+        installation: Box<Installation>,
+    },
+
+    /// The account which the app is installed on has been renamed.
+    InstallationTargetRenamed {
+        installation_id: i64,
+        account: Actor,
+    },
 
     InstallationRepositoriesAdded {
         installation_id: i64,
@@ -43,6 +218,81 @@ pub enum ControllerRequest {
         installation_id: i64,
         repositories: Vec<Repository>,
     },
+
+    /// A repository has been renamed or transferred, and has a new full name now.
+    RepositoryRenamed {
+        installation_id: i64,
+        repository: Repository,
+    },
+}
+
+impl ControllerRequest {
+    /// The name of the request, for logs and the message trace (see [`crate::trace`]).
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::Delivered { request, .. } => request.kind(),
+            Self::Init { .. } => "Init",
+            Self::ExportState { .. } => "ExportState",
+            Self::ImportState { .. } => "ImportState",
+            Self::RateLimits { .. } => "RateLimits",
+            Self::Installations { .. } => "Installations",
+            Self::RefreshInstallationToken { .. } => "RefreshInstallationToken",
+            Self::ForgetInstallation { .. } => "ForgetInstallation",
+            Self::PreviewComment { .. } => "PreviewComment",
+            Self::Simulate { .. } => "Simulate",
+            Self::ArticlePulls { .. } => "ArticlePulls",
+            Self::ArticleHistory { .. } => "ArticleHistory",
+            Self::ConflictGraph { .. } => "ConflictGraph",
+            Self::RetryPendingSyncs { .. } => "RetryPendingSyncs",
+            Self::SelfCheck { .. } => "SelfCheck",
+            Self::Compact { .. } => "Compact",
+            Self::RefreshApp { .. } => "RefreshApp",
+            Self::PullRequestCreated { .. } => "PullRequestCreated",
+            Self::PullRequestUpdated { .. } => "PullRequestUpdated",
+            Self::DebouncedUpdate { .. } => "DebouncedUpdate",
+            Self::PullRequestReopened { .. } => "PullRequestReopened",
+            Self::PullRequestClosed { .. } => "PullRequestClosed",
+            Self::PullRequestReviewed { .. } => "PullRequestReviewed",
+            Self::CommandReceived { .. } => "CommandReceived",
+            Self::MergeExpected { .. } => "MergeExpected",
+            Self::MergeCancelled { .. } => "MergeCancelled",
+            Self::MergeCheck { .. } => "MergeCheck",
+            Self::RescanRequested { .. } => "RescanRequested",
+            Self::RepoConfigChanged { .. } => "RepoConfigChanged",
+            Self::InstallationCreated { .. } => "InstallationCreated",
+            Self::InstallationDeleted { .. } => "InstallationDeleted",
+            Self::InstallationSuspended { .. } => "InstallationSuspended",
+            Self::InstallationUnsuspended { .. } => "InstallationUnsuspended",
+            Self::InstallationTargetRenamed { .. } => "InstallationTargetRenamed",
+            Self::InstallationRepositoriesAdded { .. } => "InstallationRepositoriesAdded",
+            Self::InstallationRepositoriesRemoved { .. } => "InstallationRepositoriesRemoved",
+            Self::RepositoryRenamed { .. } => "RepositoryRenamed",
+        }
+    }
+
+    /// The repository which the request is about, if it's about a single one.
+    pub fn full_repo_name(&self) -> Option<&str> {
+        match self {
+            Self::Delivered { request, .. } => request.full_repo_name(),
+            Self::PullRequestCreated { full_repo_name, .. }
+            | Self::PullRequestUpdated { full_repo_name, .. }
+            | Self::DebouncedUpdate { full_repo_name, .. }
+            | Self::PullRequestReopened { full_repo_name, .. }
+            | Self::PullRequestClosed { full_repo_name, .. }
+            | Self::PullRequestReviewed { full_repo_name, .. }
+            | Self::CommandReceived { full_repo_name, .. }
+            | Self::MergeExpected { full_repo_name, .. }
+            | Self::MergeCancelled { full_repo_name, .. }
+            | Self::MergeCheck { full_repo_name, .. }
+            | Self::RescanRequested { full_repo_name, .. }
+            | Self::Simulate { full_repo_name, .. }
+            | Self::ArticlePulls { full_repo_name, .. }
+            | Self::ArticleHistory { full_repo_name, .. }
+            | Self::ConflictGraph { full_repo_name, .. }
+            | Self::RepoConfigChanged { full_repo_name } => Some(full_repo_name),
+            _ => None,
+        }
+    }
 }
 
 /// The interface for interacting with the actual controller.
@@ -51,6 +301,22 @@ pub enum ControllerRequest {
 #[derive(Debug, Clone)]
 pub struct ControllerHandle {
     sender: mpsc::Sender<ControllerRequest>,
+
+    /// Attached to every request, so that the changes it causes can be traced back to a webhook event.
+    delivery_id: Option<String>,
+
+    /// How many times the controller has recovered from a panic or a stuck message.
+    restarts: Arc<AtomicUsize>,
+
+    /// How many divergences from GitHub the self-check has found so far.
+    divergences: Arc<AtomicUsize>,
+
+    /// Repositories with more open pulls than `controller.max_pulls_per_repo`.
+    over_limit: Arc<Mutex<BTreeSet<String>>>,
+
+    hydration: Hydration,
+    trace: Trace,
+    throttle: Throttle,
 }
 
 impl ControllerHandle {
@@ -61,12 +327,187 @@ impl ControllerHandle {
         config: config::Controller,
     ) -> Self {
         let (tx, rx) = mpsc::channel(10);
+        let self_check_interval = config.self_check.interval_minutes;
+        let compaction_interval = config.compaction_interval_hours;
+        let watchdog_seconds = config.watchdog_seconds;
+        let mut controller =
+            controller_impl::Controller::new(rx, github, app_id, private_key, config);
+        let restarts = controller.restarts();
+        let divergences = controller.divergences();
+        let over_limit = controller.over_limit();
+        let hydration = controller.hydration();
+        let trace = controller.trace();
+        let throttle = controller.throttle();
+        let watchdog = controller.watchdog();
+        controller.set_requeue(tx.downgrade());
+        tokio::spawn(async move {
+            controller.start_token_refresh();
+            controller.run_forever().await
+        });
+
+        let retry_tx = tx.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(RETRY_INTERVAL);
+            loop {
+                interval.tick().await;
+                if retry_tx
+                    .send(ControllerRequest::RetryPendingSyncs)
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
+        });
+
+        let refresh_tx = tx.clone();
         tokio::spawn(async move {
-            controller_impl::Controller::new(rx, github, app_id, private_key, config)
-                .run_forever()
-                .await
+            let mut interval = tokio::time::interval_at(
+                tokio::time::Instant::now() + APP_REFRESH_INTERVAL,
+                APP_REFRESH_INTERVAL,
+            );
+            loop {
+                interval.tick().await;
+                if refresh_tx
+                    .send(ControllerRequest::RefreshApp)
+                    .await
+                    .is_err()
+                {
+                    break;
+                }
+            }
         });
-        Self { sender: tx }
+
+        if self_check_interval > 0 {
+            let self_check_tx = tx.clone();
+            tokio::spawn(async move {
+                let period = std::time::Duration::from_secs(self_check_interval * 60);
+                // The first tick is skipped: the state has just been read from GitHub anyway.
+                let mut interval =
+                    tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+                loop {
+                    interval.tick().await;
+                    if self_check_tx
+                        .send(ControllerRequest::SelfCheck)
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+        if compaction_interval > 0 {
+            let compaction_tx = tx.clone();
+            tokio::spawn(async move {
+                let period = std::time::Duration::from_secs(compaction_interval * 60 * 60);
+                let mut interval =
+                    tokio::time::interval_at(tokio::time::Instant::now() + period, period);
+                loop {
+                    interval.tick().await;
+                    if compaction_tx
+                        .send(ControllerRequest::Compact)
+                        .await
+                        .is_err()
+                    {
+                        break;
+                    }
+                }
+            });
+        }
+        if watchdog_seconds > 0 {
+            let watchdog_tx = tx.downgrade();
+            tokio::spawn(async move {
+                let stale_after = chrono::Duration::seconds(watchdog_seconds as i64);
+                let mut interval = tokio::time::interval(std::time::Duration::from_secs(
+                    watchdog_seconds.div_ceil(WATCHDOG_CHECKS_PER_PERIOD),
+                ));
+                loop {
+                    interval.tick().await;
+                    let Some(tx) = watchdog_tx.upgrade() else {
+                        break;
+                    };
+                    watchdog.check(stale_after, tx.max_capacity() - tx.capacity());
+                }
+            });
+        }
+        Self {
+            sender: tx,
+            delivery_id: None,
+            restarts,
+            divergences,
+            over_limit,
+            hydration,
+            trace,
+            throttle,
+        }
+    }
+
+    /// A copy of the handle which marks requests as caused by a webhook delivery (for the audit log).
+    pub fn with_delivery(&self, delivery_id: String) -> Self {
+        Self {
+            sender: self.sender.clone(),
+            delivery_id: Some(delivery_id),
+            restarts: self.restarts.clone(),
+            divergences: self.divergences.clone(),
+            over_limit: self.over_limit.clone(),
+            hydration: self.hydration.clone(),
+            trace: self.trace.clone(),
+            throttle: self.throttle.clone(),
+        }
+    }
+
+    /// Whether the controller task is still running and accepting requests.
+    pub fn is_alive(&self) -> bool {
+        !self.sender.is_closed()
+    }
+
+    /// How many times the controller has rebuilt its state after a panic or a stuck message.
+    pub fn restarts(&self) -> usize {
+        self.restarts.load(Ordering::Relaxed)
+    }
+
+    /// How many requests are waiting for the controller.
+    pub fn queue_depth(&self) -> usize {
+        self.sender.max_capacity() - self.sender.capacity()
+    }
+
+    /// How many divergences from GitHub the self-check has found since start-up (see [`crate::self_check`]).
+    pub fn divergences(&self) -> usize {
+        self.divergences.load(Ordering::Relaxed)
+    }
+
+    /// How far start-up has got (see [`crate::hydration`]). Unlike other requests, this is answered while the controller is
+    /// busy starting up.
+    pub fn hydration(&self) -> Progress {
+        self.hydration.progress()
+    }
+
+    /// Up to `limit` latest messages handled by the controller, newest first (see [`crate::trace`]). Like
+    /// [`Self::hydration`], this doesn't wait for the controller.
+    pub fn recent_messages(&self, limit: usize) -> Vec<trace::Entry> {
+        self.trace.latest(limit)
+    }
+
+    /// Current pressure on the container, and whether background work is throttled because of it (see [`crate::throttle`]).
+    pub fn throttle(&self) -> throttle::State {
+        self.throttle.state()
+    }
+
+    /// Repositories with more open pulls than `controller.max_pulls_per_repo`, of which only some are tracked.
+    pub fn repositories_over_limit(&self) -> Vec<String> {
+        self.over_limit.lock().unwrap().iter().cloned().collect()
+    }
+
+    async fn send(&self, msg: ControllerRequest) {
+        let msg = match &self.delivery_id {
+            Some(delivery_id) => ControllerRequest::Delivered {
+                delivery_id: delivery_id.clone(),
+                request: Box::new(msg),
+            },
+            None => msg,
+        };
+        self.sender.send(msg).await.unwrap();
     }
 }
 
@@ -80,6 +521,149 @@ impl ControllerHandle {
         rx.await?
     }
 
+    pub async fn conflict_graph(&self, full_repo_name: &str) -> Result<Option<ConflictGraph>> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::ConflictGraph {
+                full_repo_name: full_repo_name.to_string(),
+                reply_to: tx,
+            })
+            .await;
+        Ok(rx.await?)
+    }
+
+    pub async fn simulate(
+        &self,
+        full_repo_name: &str,
+        diff: DiffSummary,
+        base_branch: Option<String>,
+    ) -> Result<Option<Vec<Conflict>>> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::Simulate {
+                full_repo_name: full_repo_name.to_string(),
+                diff: Box::new(diff),
+                base_branch,
+                reply_to: tx,
+            })
+            .await;
+        Ok(rx.await?)
+    }
+
+    pub async fn article_pulls(
+        &self,
+        full_repo_name: &str,
+        path: &str,
+    ) -> Result<Option<Vec<ArticlePull>>> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::ArticlePulls {
+                full_repo_name: full_repo_name.to_string(),
+                path: path.to_string(),
+                reply_to: tx,
+            })
+            .await;
+        Ok(rx.await?)
+    }
+
+    pub async fn article_history(&self, full_repo_name: &str) -> Result<Option<RepoHistory>> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::ArticleHistory {
+                full_repo_name: full_repo_name.to_string(),
+                reply_to: tx,
+            })
+            .await;
+        Ok(rx.await?)
+    }
+
+    pub async fn preview_comment(
+        &self,
+        full_repo_name: &str,
+        conflict: Conflict,
+    ) -> Result<String> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::PreviewComment {
+                full_repo_name: full_repo_name.to_string(),
+                conflict: Box::new(conflict),
+                reply_to: tx,
+            })
+            .await;
+        Ok(rx.await?)
+    }
+
+    pub async fn export_state(&self) -> Result<Snapshot> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::ExportState { reply_to: tx })
+            .await;
+        Ok(rx.await?)
+    }
+
+    /// Seed the controller with a previously exported state. This replaces [`Self::init`].
+    pub async fn import_state(&self, snapshot: Snapshot) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::ImportState {
+                snapshot: Box::new(snapshot),
+                reply_to: tx,
+            })
+            .await;
+        rx.await?
+    }
+
+    /// Read the rate limit budget of every installation token.
+    pub async fn rate_limits(&self) -> Result<Vec<InstallationRateLimits>> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::RateLimits { reply_to: tx })
+            .await;
+        Ok(rx.await?)
+    }
+
+    pub async fn installations(&self) -> Result<Vec<InstallationSummary>> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::Installations { reply_to: tx })
+            .await;
+        Ok(rx.await?)
+    }
+
+    pub async fn refresh_installation_token(&self, installation_id: i64) -> Result<()> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::RefreshInstallationToken {
+                installation_id,
+                reply_to: tx,
+            })
+            .await;
+        rx.await?
+    }
+
+    /// Drop an installation from the cache, until the next start-up.
+    pub async fn forget_installation(&self, installation_id: i64) -> Result<bool> {
+        let (tx, rx) = oneshot::channel();
+        let _ = self
+            .sender
+            .send(ControllerRequest::ForgetInstallation {
+                installation_id,
+                reply_to: tx,
+            })
+            .await;
+        Ok(rx.await?)
+    }
+
     pub async fn add_pull(
         &self,
         full_repo_name: &str,
@@ -91,7 +675,7 @@ impl ControllerHandle {
             pull_request: Box::new(pull_request),
             trigger_updates,
         };
-        self.sender.send(msg).await.unwrap();
+        self.send(msg).await;
     }
 
     pub async fn update_pull(
@@ -105,7 +689,15 @@ impl ControllerHandle {
             pull_request: Box::new(pull_request),
             trigger_updates,
         };
-        self.sender.send(msg).await.unwrap();
+        self.send(msg).await;
+    }
+
+    pub async fn reopen_pull(&self, full_repo_name: &str, pull_request: PullRequest) {
+        let msg = ControllerRequest::PullRequestReopened {
+            full_repo_name: full_repo_name.to_owned(),
+            pull_request: Box::new(pull_request),
+        };
+        self.send(msg).await;
     }
 
     pub async fn remove_pull(&self, full_repo_name: &str, pull_request: PullRequest) {
@@ -113,21 +705,121 @@ impl ControllerHandle {
             full_repo_name: full_repo_name.to_owned(),
             pull_request: Box::new(pull_request),
         };
-        self.sender.send(msg).await.unwrap();
+        self.send(msg).await;
+    }
+
+    pub async fn review_pull(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        review: PullRequestReview,
+    ) {
+        let msg = ControllerRequest::PullRequestReviewed {
+            full_repo_name: full_repo_name.to_owned(),
+            pull_number,
+            review: Box::new(review),
+        };
+        self.send(msg).await;
+    }
+
+    pub async fn run_command(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        comment: IssueComment,
+        command: Command,
+    ) {
+        let msg = ControllerRequest::CommandReceived {
+            full_repo_name: full_repo_name.to_owned(),
+            pull_number,
+            comment: Box::new(comment),
+            command,
+        };
+        self.send(msg).await;
+    }
+
+    pub async fn expect_merge(&self, full_repo_name: &str, pull_request: PullRequest) {
+        let msg = ControllerRequest::MergeExpected {
+            full_repo_name: full_repo_name.to_owned(),
+            pull_request: Box::new(pull_request),
+        };
+        self.send(msg).await;
+    }
+
+    pub async fn cancel_merge(&self, full_repo_name: &str, pull_number: i32) {
+        let msg = ControllerRequest::MergeCancelled {
+            full_repo_name: full_repo_name.to_owned(),
+            pull_number,
+        };
+        self.send(msg).await;
+    }
+
+    /// Check pulls of the repository which are expected to be merged right away, for example after a merge queue
+    /// has merged a group of them.
+    pub async fn check_merges(&self, full_repo_name: &str) {
+        let msg = ControllerRequest::MergeCheck {
+            full_repo_name: full_repo_name.to_owned(),
+            pull_number: None,
+            attempt: 0,
+        };
+        self.send(msg).await;
+    }
+
+    pub async fn rescan(
+        &self,
+        full_repo_name: &str,
+        base_branch: Option<String>,
+        pull_number: Option<i32>,
+    ) {
+        let msg = ControllerRequest::RescanRequested {
+            full_repo_name: full_repo_name.to_owned(),
+            base_branch,
+            pull_number,
+        };
+        self.send(msg).await;
+    }
+
+    pub async fn reload_repo_config(&self, full_repo_name: &str) {
+        let msg = ControllerRequest::RepoConfigChanged {
+            full_repo_name: full_repo_name.to_owned(),
+        };
+        self.send(msg).await;
     }
 
     pub async fn add_installation(&self, installation: Installation) {
         let msg = ControllerRequest::InstallationCreated {
             installation: Box::new(installation),
         };
-        self.sender.send(msg).await.unwrap();
+        self.send(msg).await;
     }
 
     pub async fn delete_installation(&self, installation: Installation) {
         let msg = ControllerRequest::InstallationDeleted {
             installation: Box::new(installation),
         };
-        self.sender.send(msg).await.unwrap();
+        self.send(msg).await;
+    }
+
+    pub async fn suspend_installation(&self, installation: Installation) {
+        let msg = ControllerRequest::InstallationSuspended {
+            installation: Box::new(installation),
+        };
+        self.send(msg).await;
+    }
+
+    pub async fn unsuspend_installation(&self, installation: Installation) {
+        let msg = ControllerRequest::InstallationUnsuspended {
+            installation: Box::new(installation),
+        };
+        self.send(msg).await;
+    }
+
+    pub async fn rename_installation_target(&self, installation_id: i64, account: Actor) {
+        let msg = ControllerRequest::InstallationTargetRenamed {
+            installation_id,
+            account,
+        };
+        self.send(msg).await;
     }
 
     pub async fn add_repositories(&self, installation_id: i64, repositories: Vec<Repository>) {
@@ -135,7 +827,7 @@ impl ControllerHandle {
             installation_id,
             repositories,
         };
-        self.sender.send(msg).await.unwrap();
+        self.send(msg).await;
     }
 
     pub async fn remove_repositories(&self, installation_id: i64, repositories: Vec<Repository>) {
@@ -143,6 +835,14 @@ impl ControllerHandle {
             installation_id,
             repositories,
         };
-        self.sender.send(msg).await.unwrap();
+        self.send(msg).await;
+    }
+
+    pub async fn rename_repository(&self, installation_id: i64, repository: Repository) {
+        let msg = ControllerRequest::RepositoryRenamed {
+            installation_id,
+            repository,
+        };
+        self.send(msg).await;
     }
 }
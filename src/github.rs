@@ -9,28 +9,135 @@ use std::sync::{Arc, Mutex};
 use serde::Deserialize;
 use serde::Serialize;
 
+use async_trait::async_trait;
 use eyre::Result;
 use unidiff;
 
+use crate::config::FatalHttpErrors;
+use crate::helpers;
+use crate::helpers::comments::CommentHeader;
+use crate::helpers::diff::DiffSummary;
 use crate::structs;
 
 const GITHUB_API_ROOT: &str = "https://api.github.com";
 const GITHUB_ROOT: &str = "https://github.com";
 
 const RETRYABLE_ERRORS: [u16; 4] = [429, 500, 502, 503];
-const FATAL_ERROR: u16 = 501; // HTTP 501 Not Implemented
+const FATAL_ERROR: u16 = 501; // HTTP 501 Not Implemented, see [`FatalHttpErrors`]
+
+/// Retries for regular requests. Writes which are not idempotent are sent once per attempt instead, see [`Client::post_comment`].
+const MAX_RETRIES: i32 = 10;
+const WRITE_ATTEMPTS: i32 = 3;
+const DIFF_RETRIES: i32 = 5;
+
+/// Items per page of lists, the most GitHub allows.
+const PER_PAGE: &str = "100";
+
+/// Warn about a rate limit budget once less than this share of it (in percent) is left.
+const LOW_RATE_LIMIT_PERCENT: i64 = 10;
+
+/// Installation tokens with less time than this left (in minutes) are replaced in the background.
+const TOKEN_REFRESH_MARGIN_MINUTES: i64 = 10;
+const TOKEN_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
 
 const MIN_TIMEOUT: Duration = Duration::from_secs(1);
 const MAX_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_DIFF_TIMEOUT: Duration = Duration::from_secs(10);
+const MAX_WRITE_TIMEOUT: Duration = Duration::from_secs(5);
 const BACKOFF_MP: f32 = 1.2;
 
+/// Secondary rate limits with a `Retry-After` up to this long are waited out in place. Longer ones are reported as
+/// [`SecondaryRateLimit`], so that the caller can come back later without blocking everything else.
+const MAX_INLINE_RETRY_AFTER: Duration = Duration::from_secs(5);
+
+/// GitHub asks to wait for at least a minute if a secondary rate limit response has no `Retry-After` header.
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Installation permissions which allow posting comments in pull requests: either is enough, since pull request comments
+/// are issue comments.
+const COMMENT_PERMISSIONS: [&str; 2] = ["pull_requests", "issues"];
+
+/// Whether an installation with these permissions can post comments in pull requests.
+pub fn can_write_comments(permissions: &structs::Permissions) -> bool {
+    COMMENT_PERMISSIONS.iter().any(|p| {
+        permissions
+            .get(*p)
+            .is_some_and(|level| level == "write" || level == "admin")
+    })
+}
+
+/// The error returned when GitHub has throttled requests with a secondary rate limit
+/// (https://docs.github.com/en/rest/using-the-rest-api/rate-limits-for-the-rest-api#about-secondary-rate-limits).
+/// Unlike other client errors, the request is fine and may be repeated once `retry_after` has passed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SecondaryRateLimit {
+    pub url: String,
+    pub retry_after: Duration,
+}
+
+impl std::fmt::Display for SecondaryRateLimit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Secondary rate limit hit at {}, retry after {:?}",
+            self.url, self.retry_after
+        )
+    }
+}
+
+impl std::error::Error for SecondaryRateLimit {}
+
+impl SecondaryRateLimit {
+    /// Find out whether an error has been caused by a secondary rate limit, and how long to wait if so.
+    pub fn find(e: &eyre::Report) -> Option<&Self> {
+        e.downcast_ref::<Self>()
+    }
+}
+
+/// The error returned for an unsuccessful response which can't be retried, or has been retried too many times.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HttpError {
+    pub status: reqwest::StatusCode,
+    message: String,
+}
+
+impl std::fmt::Display for HttpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for HttpError {}
+
+impl HttpError {
+    pub fn find(e: &eyre::Report) -> Option<&Self> {
+        e.downcast_ref::<Self>()
+    }
+
+    /// Whether GitHub has refused the credentials, as it does for suspended or deleted installations.
+    pub fn is_auth_failure(&self) -> bool {
+        matches!(self.status.as_u16(), 401 | 403 | 404)
+    }
+}
+
+/// Failures to get an installation token in a row, after which the installation is quarantined: its token is no longer
+/// requested until the quarantine is over (see [`Client::is_quarantined`]).
+const QUARANTINE_AFTER_FAILURES: u32 = 3;
+const QUARANTINE_MINUTES: i64 = 30;
+
+#[derive(Debug, Clone, Default)]
+struct InstallationHealth {
+    auth_failures: u32,
+    quarantined_until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
 /// Helper for exponential backoff retries. Usage:
 ///
 /// ```ignore
 /// // Allow up to 3 retries and sleep for 1, 1.2, and 1.44s between them.
 /// let mut t = ProgressiveTimeout::new(3);
-/// while let None = fetch_data() {
-///     t.sleep();
+/// while let None = fetch_data().await {
+///     t.sleep().await;
 ///     if let Err(e) = t.tick() {
 ///         panic!("failed to fetch data: {e:?}")
 ///     }
@@ -40,7 +147,9 @@ pub struct ProgressiveTimeout {
     current_timeout: Duration,
     current_retry: i32,
     max_retries: i32,
+    max_timeout: Duration,
     total_time_slept: Duration,
+    sleeper: Option<Box<dyn FnMut(Duration) + Send>>,
 }
 
 impl ProgressiveTimeout {
@@ -49,7 +158,27 @@ impl ProgressiveTimeout {
             current_timeout: MIN_TIMEOUT,
             current_retry: 0,
             max_retries,
+            max_timeout: MAX_TIMEOUT,
             total_time_slept: Duration::new(0, 0),
+            sleeper: None,
+        }
+    }
+
+    /// Retries and pauses between them as set by `policy`.
+    pub fn with_policy(policy: &RetryPolicy) -> Self {
+        Self {
+            current_timeout: Duration::from_millis(policy.base_delay_ms),
+            max_timeout: Duration::from_millis(policy.max_delay_ms),
+            ..Self::new(policy.max_retries)
+        }
+    }
+
+    /// Same as [`ProgressiveTimeout::new`], but waits with `sleeper` instead of the runtime's timer, so that tests don't
+    /// have to wait for real.
+    pub fn with_sleeper(max_retries: i32, sleeper: impl FnMut(Duration) + Send + 'static) -> Self {
+        Self {
+            sleeper: Some(Box::new(sleeper)),
+            ..Self::new(max_retries)
         }
     }
 
@@ -65,6 +194,10 @@ impl ProgressiveTimeout {
         self.max_retries
     }
 
+    pub fn total_time_slept(&self) -> Duration {
+        self.total_time_slept
+    }
+
     pub fn tick(&mut self) -> Result<()> {
         if self.current_retry == self.max_retries {
             eyre::bail!(
@@ -73,18 +206,102 @@ impl ProgressiveTimeout {
                 self.total_time_slept
             )
         }
-        let new_timeout = std::cmp::min(self.current_timeout.mul_f32(BACKOFF_MP), MAX_TIMEOUT);
+        let new_timeout = std::cmp::min(self.current_timeout.mul_f32(BACKOFF_MP), self.max_timeout);
         self.current_retry += 1;
         self.current_timeout = new_timeout;
         Ok(())
     }
 
-    pub fn sleep(&mut self) {
-        std::thread::sleep(self.current_timeout);
+    /// Pause before the next retry. The pause yields to the runtime, so a caller which gives up on the whole operation
+    /// (such as the controller's watchdog, see [`crate::watchdog`]) can drop it midway.
+    pub async fn sleep(&mut self) {
+        match self.sleeper.as_mut() {
+            Some(sleeper) => sleeper(self.current_timeout),
+            None => tokio::time::sleep(self.current_timeout).await,
+        }
         self.total_time_slept += self.current_timeout;
     }
 }
 
+/// How persistently a request is retried.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicy {
+    /// How many times a request is sent at most, the first time included.
+    pub max_retries: i32,
+
+    /// Pauses between retries grow from this many milliseconds, by a fifth with every retry.
+    pub base_delay_ms: u64,
+
+    /// The longest pause between retries, in milliseconds.
+    pub max_delay_ms: u64,
+}
+
+impl RetryPolicy {
+    /// Writes which are not idempotent are sent once, and [`Client::write_with_check`] decides whether to repeat them.
+    const SINGLE_ATTEMPT: Self = Self {
+        max_retries: 1,
+        base_delay_ms: 0,
+        max_delay_ms: 0,
+    };
+
+    fn new(max_retries: i32, max_delay: Duration) -> Self {
+        Self {
+            max_retries,
+            base_delay_ms: MIN_TIMEOUT.as_millis() as u64,
+            max_delay_ms: max_delay.as_millis() as u64,
+        }
+    }
+}
+
+/// Retry policies of different kinds of requests, for [`Client`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryPolicies {
+    /// Pull request diffs and comparisons of commits. A diff which can't be read now is read again on the next push, so
+    /// these give up sooner than other reads, and don't hold up the rest of the controller.
+    pub diffs: RetryPolicy,
+
+    /// Lists and everything else, except for comment writes.
+    pub reads: RetryPolicy,
+
+    /// Posting, updating and deleting comments. Failed writes are queued for later, so these fail fast.
+    pub comment_writes: RetryPolicy,
+}
+
+impl Default for RetryPolicies {
+    fn default() -> Self {
+        Self {
+            diffs: RetryPolicy::new(DIFF_RETRIES, MAX_DIFF_TIMEOUT),
+            reads: RetryPolicy::new(MAX_RETRIES, MAX_TIMEOUT),
+            comment_writes: RetryPolicy::new(WRITE_ATTEMPTS, MAX_WRITE_TIMEOUT),
+        }
+    }
+}
+
+/// What to do about an unsuccessful response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Failure {
+    /// The error is likely transient: try again after a pause, if there are retries left.
+    Retry,
+
+    /// Report the error to the caller.
+    Fail,
+
+    /// GitHub doesn't support the request at all. Handled according to [`FatalHttpErrors`].
+    Fatal,
+}
+
+/// Tell what to do about a response with an error status. Secondary rate limits are told apart before that (see
+/// [`secondary_rate_limit`]).
+pub fn classify_failure(status: reqwest::StatusCode) -> Failure {
+    if RETRYABLE_ERRORS.contains(&status.as_u16()) {
+        Failure::Retry
+    } else if status.as_u16() == FATAL_ERROR {
+        Failure::Fatal
+    } else {
+        Failure::Fail
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct GitHub {
     pub base_api_url: String,
@@ -108,12 +325,21 @@ impl GitHub {
     pub fn pulls(&self, full_repo_name: &str) -> String {
         format!("{}/repos/{full_repo_name}/pulls", self.base_api_url)
     }
+    pub fn pull(&self, full_repo_name: &str, pull_number: i32) -> String {
+        format!(
+            "{}/repos/{full_repo_name}/pulls/{pull_number}",
+            self.base_api_url
+        )
+    }
     pub fn app(&self) -> String {
         format!("{}/app", self.base_api_url)
     }
     pub fn app_installations(&self) -> String {
         format!("{}/app/installations", self.base_api_url)
     }
+    pub fn app_installation(&self, installation_id: i64) -> String {
+        format!("{}/app/installations/{installation_id}", self.base_api_url)
+    }
     pub fn installation_tokens(&self, installation_id: i64) -> String {
         format!(
             "{}/app/installations/{installation_id}/access_tokens",
@@ -129,6 +355,40 @@ impl GitHub {
             self.base_api_url
         )
     }
+    pub fn compare(&self, full_repo_name: &str, base: &str, head: &str) -> String {
+        format!(
+            "{}/repos/{full_repo_name}/compare/{base}...{head}",
+            self.base_api_url
+        )
+    }
+    pub fn meta(&self) -> String {
+        format!("{}/meta", self.base_api_url)
+    }
+    pub fn rate_limit(&self) -> String {
+        format!("{}/rate_limit", self.base_api_url)
+    }
+    pub fn graphql(&self) -> String {
+        format!("{}/graphql", self.base_api_url)
+    }
+    pub fn contents(&self, full_repo_name: &str, path: &str) -> String {
+        format!(
+            "{}/repos/{full_repo_name}/contents/{path}",
+            self.base_api_url
+        )
+    }
+    pub fn issue_labels(&self, full_repo_name: &str, issue_number: i32) -> String {
+        format!(
+            "{}/repos/{full_repo_name}/issues/{issue_number}/labels",
+            self.base_api_url
+        )
+    }
+    pub fn issue_label(&self, full_repo_name: &str, issue_number: i32, label: &str) -> String {
+        // Label names may contain spaces and other characters which need escaping.
+        let mut url =
+            reqwest::Url::parse(&self.issue_labels(full_repo_name, issue_number)).unwrap();
+        url.path_segments_mut().unwrap().push(label);
+        url.to_string()
+    }
     pub fn issue_comment(&self, full_repo_name: &str, comment_id: i64) -> String {
         format!(
             "{}/repos/{full_repo_name}/issues/comments/{comment_id}",
@@ -147,6 +407,36 @@ impl GitHub {
     }
 }
 
+/// Open pull requests with their changed files and comments. Files and comments beyond the first page are not fetched:
+/// pulls with more files have their diffs read separately, and comments are only used as a hint.
+const PULLS_QUERY: &str = r#"
+query($owner: String!, $name: String!, $cursor: String) {
+  repository(owner: $owner, name: $name) {
+    pullRequests(states: OPEN, first: 50, after: $cursor, orderBy: {field: CREATED_AT, direction: ASC}) {
+      pageInfo { hasNextPage endCursor }
+      nodes {
+        databaseId number state title url createdAt updatedAt authorAssociation
+        baseRefName baseRefOid baseRepository { nameWithOwner }
+        headRefName headRefOid headRepository { nameWithOwner }
+        author { __typename login ... on User { databaseId } ... on Bot { databaseId } }
+        files(first: 100) {
+          pageInfo { hasNextPage endCursor }
+          nodes { path changeType }
+        }
+        labels(first: 100) { nodes { name } }
+        comments(first: 100) {
+          pageInfo { hasNextPage endCursor }
+          nodes {
+            databaseId id body createdAt updatedAt authorAssociation
+            author { __typename login ... on User { databaseId } ... on Bot { databaseId } }
+          }
+        }
+      }
+    }
+  }
+}
+"#;
+
 #[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub enum TokenType {
     JWT,
@@ -177,6 +467,16 @@ pub struct Client {
     tokens: Arc<Mutex<HashMap<TokenType, Token>>>,
     pub installations: Arc<Mutex<HashMap<i64, structs::Installation>>>,
     repos: Arc<Mutex<HashMap<i64, Vec<structs::Repository>>>>,
+
+    /// Installations which have had their tokens refused recently, so that one broken installation doesn't flood
+    /// the logs and hold up the others.
+    health: Arc<Mutex<HashMap<i64, InstallationHealth>>>,
+
+    /// Permissions of installations, as reported with their latest tokens.
+    permissions: Arc<Mutex<HashMap<i64, structs::Permissions>>>,
+
+    fatal_http_errors: FatalHttpErrors,
+    retries: RetryPolicies,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -206,17 +506,23 @@ impl Claims {
     }
 }
 
-async fn __json<T>(rb: reqwest::RequestBuilder) -> Result<T>
+async fn __json<T>(
+    rb: reqwest::RequestBuilder,
+    policy: RetryPolicy,
+    on_fatal: FatalHttpErrors,
+) -> Result<T>
 where
     T: for<'de> serde::Deserialize<'de>,
 {
-    __text(rb)
+    __text(rb, policy, on_fatal)
         .await
         .map(|body| Ok(serde_json::from_str(&body)?))?
 }
 
-const INTERESTING_HEADERS: [&str; 7] = [
+const INTERESTING_HEADERS: [&str; 9] = [
     "etag",
+    "link",
+    "retry-after",
     "x-ratelimit-limit",
     "x-ratelimit-remaining",
     "x-ratelimit-reset",
@@ -225,11 +531,138 @@ const INTERESTING_HEADERS: [&str; 7] = [
     "x-github-request-id",
 ];
 
-async fn __text(rb: reqwest::RequestBuilder) -> Result<String> {
-    let prepared_request = rb.headers(Client::default_headers());
+/// The URL of the next page of a list, from a `Link` header such as `<https://...&page=2>; rel="next", <...>; rel="last"`.
+fn next_page_url(link: &str) -> Option<String> {
+    link.split(',').find_map(|part| {
+        let (url, params) = part.split_once(';')?;
+        params
+            .split(';')
+            .any(|p| p.trim() == r#"rel="next""#)
+            .then(|| {
+                url.trim()
+                    .trim_start_matches('<')
+                    .trim_end_matches('>')
+                    .to_string()
+            })
+    })
+}
+
+/// Read every page of a list, starting at `first_page` and following `Link` headers for as long as GitHub sends them.
+/// `make_request` prepares a request to a page URL (for example, adds credentials).
+///
+/// `keep_going` sees every page as soon as it's read, and stops the reading early by returning `false`: the pages read so far
+/// are returned then.
+async fn paginate<T, R, F>(
+    first_page: String,
+    make_request: R,
+    mut keep_going: F,
+    policy: RetryPolicy,
+    on_fatal: FatalHttpErrors,
+) -> Result<Vec<T>>
+where
+    T: for<'de> serde::Deserialize<'de>,
+    R: Fn(&str) -> reqwest::RequestBuilder,
+    F: FnMut(&[T]) -> bool,
+{
+    let mut out = Vec::new();
+    let mut next = Some(first_page);
+    while let Some(url) = next.take() {
+        let (body, headers) = __send_with_headers(
+            make_request(&url).headers(Client::default_headers()),
+            policy,
+            on_fatal,
+        )
+        .await?;
+        let page: Vec<T> = serde_json::from_str(&body)?;
+        let is_enough = !keep_going(&page);
+        out.extend(page);
+        if is_enough {
+            break;
+        }
+        // A page which links to itself would never end the loop.
+        next = headers
+            .get("link")
+            .and_then(|link| next_page_url(link))
+            .filter(|next_url| *next_url != url);
+    }
+    Ok(out)
+}
+
+/// Log a warning if the remaining rate limit budget is low.
+fn check_rate_limit(resource: &str, remaining: i64, limit: i64, context: &str) {
+    if limit > 0 && remaining * 100 < limit * LOW_RATE_LIMIT_PERCENT {
+        log::warn!(
+            "Rate limit budget for {} is running low: {}/{} left ({})",
+            resource,
+            remaining,
+            limit,
+            context
+        );
+    }
+}
+
+/// Tell whether a response is a secondary rate limit, and return the delay requested by GitHub. These come as HTTP 403
+/// or 429 with either a `Retry-After` header, or a message which mentions the limit.
+fn secondary_rate_limit(
+    status: reqwest::StatusCode,
+    headers: &HashMap<String, String>,
+    body: &str,
+) -> Option<Duration> {
+    if status != reqwest::StatusCode::FORBIDDEN && status != reqwest::StatusCode::TOO_MANY_REQUESTS
+    {
+        return None;
+    }
+    let retry_after = headers
+        .get("retry-after")
+        .and_then(|v| v.parse::<u64>().ok())
+        .map(Duration::from_secs);
+    if retry_after.is_none() && !body.to_lowercase().contains("secondary rate limit") {
+        return None;
+    }
+    Some(retry_after.unwrap_or(DEFAULT_RETRY_AFTER))
+}
+
+async fn __text(
+    rb: reqwest::RequestBuilder,
+    policy: RetryPolicy,
+    on_fatal: FatalHttpErrors,
+) -> Result<String> {
+    __send(rb.headers(Client::default_headers()), policy, on_fatal).await
+}
+
+/// Send a request which already has all headers set.
+async fn __send(
+    prepared_request: reqwest::RequestBuilder,
+    policy: RetryPolicy,
+    on_fatal: FatalHttpErrors,
+) -> Result<String> {
+    __send_with_headers(prepared_request, policy, on_fatal)
+        .await
+        .map(|(body, _)| body)
+}
+
+/// Same as [`__send`], but also returns the headers listed in [`INTERESTING_HEADERS`] (lowercase).
+async fn __send_with_headers(
+    prepared_request: reqwest::RequestBuilder,
+    policy: RetryPolicy,
+    on_fatal: FatalHttpErrors,
+) -> Result<(String, HashMap<String, String>)> {
+    __send_with_timer(
+        prepared_request,
+        ProgressiveTimeout::with_policy(&policy),
+        on_fatal,
+    )
+    .await
+}
+
+/// Same as [`__send_with_headers`], with the pauses between retries made by `timer`.
+async fn __send_with_timer(
+    prepared_request: reqwest::RequestBuilder,
+    mut timer: ProgressiveTimeout,
+    on_fatal: FatalHttpErrors,
+) -> Result<(String, HashMap<String, String>)> {
     let mut url: Option<reqwest::Url> = None;
 
-    let mut timer = ProgressiveTimeout::new(10);
     while timer.tick().is_ok() {
         match prepared_request.try_clone().unwrap().send().await {
             Ok(response) => {
@@ -258,10 +691,36 @@ async fn __text(rb: reqwest::RequestBuilder) -> Result<String> {
                     timer.current_retry(),
                     timer.max_retries(),
                 );
+                if let Some(retry_after) =
+                    secondary_rate_limit(status, &headers, body.as_deref().unwrap_or_default())
+                {
+                    log::warn!(
+                        "{}: secondary rate limit, retry after {:?}. Headers: {:?} + body: {:?}",
+                        logging_string,
+                        retry_after,
+                        headers,
+                        body
+                    );
+                    if retry_after <= MAX_INLINE_RETRY_AFTER
+                        && timer.current_retry() < timer.max_retries()
+                    {
+                        tokio::time::sleep(retry_after).await;
+                        continue;
+                    }
+                    return Err(SecondaryRateLimit {
+                        url: url.as_ref().unwrap().to_string(),
+                        retry_after,
+                    }
+                    .into());
+                }
+
                 if status.is_client_error() || status.is_server_error() || body.is_err() {
-                    let can_be_retried = RETRYABLE_ERRORS.contains(&status.as_u16());
-                    let log_level = if can_be_retried {
+                    let failure = classify_failure(status);
+                    // Missing things are expected sometimes (such as optional files), and callers report them otherwise.
+                    let log_level = if failure == Failure::Retry {
                         log::Level::Warn
+                    } else if status == reqwest::StatusCode::NOT_FOUND {
+                        log::Level::Info
                     } else {
                         log::Level::Error
                     };
@@ -273,21 +732,35 @@ async fn __text(rb: reqwest::RequestBuilder) -> Result<String> {
                         body
                     );
 
-                    if can_be_retried {
+                    if failure == Failure::Retry && timer.current_retry() < timer.max_retries() {
                         log::info!("Sleeping for {:?}...", timer.current_timeout);
-                        timer.sleep();
+                        timer.sleep().await;
                         continue;
                     }
 
-                    if status.as_u16() == FATAL_ERROR {
+                    if failure == Failure::Fatal && on_fatal == FatalHttpErrors::Panic {
                         panic!("Fatal HTTP error: {}", logging_string);
                     }
 
-                    eyre::bail!(logging_string);
+                    return Err(HttpError {
+                        status,
+                        message: logging_string,
+                    }
+                    .into());
                 }
 
                 log::debug!("{}. Headers: {:?}", logging_string, headers);
-                return Ok(body.unwrap());
+                let header = |name: &str| headers.get(name).and_then(|v| v.parse::<i64>().ok());
+                if let (Some(remaining), Some(limit)) =
+                    (header("x-ratelimit-remaining"), header("x-ratelimit-limit"))
+                {
+                    let resource = headers
+                        .get("x-ratelimit-resource")
+                        .map(|r| r.as_str())
+                        .unwrap_or("core");
+                    check_rate_limit(resource, remaining, limit, url.as_ref().unwrap().as_str());
+                }
+                return Ok((body.unwrap(), headers));
             }
             Err(e) => {
                 log::error!(
@@ -365,27 +838,191 @@ impl Client {
     }
 
     async fn get_installation_token(&self, installation_id: i64) -> Result<String> {
-        let ttype = TokenType::Installation(installation_id);
-        match self.cached_token(&ttype).await {
+        match self
+            .cached_token(&TokenType::Installation(installation_id))
+            .await
+        {
             Some(t) => Ok(t),
-            None => {
-                let jwt = self.get_jwt_token().await;
-                let req = self
-                    .http_client
-                    .post(self.github.installation_tokens(installation_id))
-                    .bearer_auth(jwt);
-                let response: structs::InstallationToken = __json(req).await?;
-                let token = Token {
-                    t: response.token,
-                    ttype: ttype.clone(),
-                    created_at: chrono::Utc::now(),
-                    expires_at: response.expires_at - chrono::Duration::minutes(5),
-                };
-                self.tokens.lock().unwrap().insert(ttype, token.clone());
-                Ok(token.t)
+            None => self.fetch_installation_token(installation_id).await,
+        }
+    }
+
+    async fn fetch_installation_token(&self, installation_id: i64) -> Result<String> {
+        if let Some(until) = self.quarantined_until(installation_id) {
+            eyre::bail!(
+                "Installation {} is quarantined until {} after repeated authentication failures",
+                installation_id,
+                until
+            );
+        }
+        let ttype = TokenType::Installation(installation_id);
+        let jwt = self.get_jwt_token().await;
+        let req = self
+            .http_client
+            .post(self.github.installation_tokens(installation_id))
+            .bearer_auth(jwt);
+        let response: structs::InstallationToken =
+            match __json(req, self.retries.reads, self.fatal_http_errors).await {
+                Ok(response) => response,
+                Err(e) => {
+                    self.record_token_failure(installation_id, &e);
+                    return Err(e);
+                }
+            };
+        if let Some(health) = self.health.lock().unwrap().remove(&installation_id) {
+            if health.quarantined_until.is_some() {
+                log::info!(
+                    "Installation {} has recovered after {} authentication failures",
+                    installation_id,
+                    health.auth_failures
+                );
+            }
+        }
+        self.permissions
+            .lock()
+            .unwrap()
+            .insert(installation_id, response.permissions);
+        let token = Token {
+            t: response.token,
+            ttype: ttype.clone(),
+            created_at: chrono::Utc::now(),
+            expires_at: response.expires_at - chrono::Duration::minutes(5),
+        };
+        self.tokens.lock().unwrap().insert(ttype, token.clone());
+        Ok(token.t)
+    }
+
+    /// Count a failure to get an installation token, and quarantine the installation if GitHub keeps refusing it.
+    /// An installation is quarantined again right away if the first attempt after its quarantine fails.
+    fn record_token_failure(&self, installation_id: i64, e: &eyre::Report) {
+        if !HttpError::find(e).is_some_and(|e| e.is_auth_failure()) {
+            return;
+        }
+        let mut health = self.health.lock().unwrap();
+        let h = health.entry(installation_id).or_default();
+        h.auth_failures += 1;
+        if h.auth_failures >= QUARANTINE_AFTER_FAILURES {
+            let until = chrono::Utc::now() + chrono::Duration::minutes(QUARANTINE_MINUTES);
+            log::warn!(
+                "Installation {}: {} authentication failures in a row, quarantining it until {}",
+                installation_id,
+                h.auth_failures,
+                until
+            );
+            h.quarantined_until = Some(until);
+        }
+    }
+
+    fn quarantined_until(&self, installation_id: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.health
+            .lock()
+            .unwrap()
+            .get(&installation_id)
+            .and_then(|h| h.quarantined_until)
+            .filter(|until| *until > chrono::Utc::now())
+    }
+
+    /// Whether requests on behalf of an installation are on hold due to repeated authentication failures.
+    /// Periodic work skips such installations until the quarantine is over.
+    pub fn is_quarantined(&self, installation_id: i64) -> bool {
+        self.quarantined_until(installation_id).is_some()
+    }
+
+    /// Fetch tokens for known installations which don't have one, and replace tokens which are about to expire,
+    /// so that requests don't have to wait for a new token in the middle of a burst.
+    ///
+    /// Suspended and quarantined installations are skipped. Quarantined ones are tried again once the quarantine is over,
+    /// and their repositories are read anew if that succeeds.
+    pub async fn refresh_tokens(&self) {
+        let deadline = chrono::Utc::now() + chrono::Duration::minutes(TOKEN_REFRESH_MARGIN_MINUTES);
+        let installation_ids: Vec<_> = self
+            .installations
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|i| i.suspended_at.is_none())
+            .map(|i| i.id)
+            .collect();
+        for installation_id in installation_ids {
+            let is_fresh = self
+                .tokens
+                .lock()
+                .unwrap()
+                .get(&TokenType::Installation(installation_id))
+                .is_some_and(|t| t.expires_at > deadline);
+            if is_fresh || self.is_quarantined(installation_id) {
+                continue;
+            }
+            let was_quarantined = self
+                .health
+                .lock()
+                .unwrap()
+                .get(&installation_id)
+                .is_some_and(|h| h.quarantined_until.is_some());
+            match self.fetch_installation_token(installation_id).await {
+                Ok(_) if was_quarantined => {
+                    let installation = self
+                        .installations
+                        .lock()
+                        .unwrap()
+                        .get(&installation_id)
+                        .cloned();
+                    if let Some(installation) = installation {
+                        // Errors are logged inside.
+                        self.read_and_cache_installation_repos(installation)
+                            .await
+                            .ok();
+                    }
+                }
+                Ok(_) => log::debug!("Refreshed token for installation {}", installation_id),
+                Err(e) => log::error!(
+                    "Failed to refresh token for installation {}: {:?}",
+                    installation_id,
+                    e
+                ),
             }
         }
     }
+
+    /// Replace the token of an installation right away, even if the cached one is still valid.
+    pub async fn refresh_installation_token(&self, installation_id: i64) -> Result<()> {
+        self.fetch_installation_token(installation_id).await?;
+        log::info!(
+            "Refreshed token for installation {} on request",
+            installation_id
+        );
+        Ok(())
+    }
+
+    /// When the cached token of an installation expires, if there is one.
+    pub fn token_expires_at(&self, installation_id: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+        self.tokens
+            .lock()
+            .unwrap()
+            .get(&TokenType::Installation(installation_id))
+            .map(|t| t.expires_at)
+    }
+
+    /// Permissions of an installation, known once it has a token.
+    pub fn installation_permissions(&self, installation_id: i64) -> Option<structs::Permissions> {
+        self.permissions
+            .lock()
+            .unwrap()
+            .get(&installation_id)
+            .cloned()
+    }
+
+    /// Keep calling [`Self::refresh_tokens`] in the background until the app stops.
+    pub fn spawn_token_refresh(&self) -> tokio::task::JoinHandle<()> {
+        let client = self.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(TOKEN_REFRESH_INTERVAL);
+            loop {
+                interval.tick().await;
+                client.refresh_tokens().await;
+            }
+        })
+    }
 }
 
 impl Client {
@@ -398,18 +1035,60 @@ impl Client {
             tokens: Arc::new(Mutex::new(HashMap::new())),
             installations: Arc::new(Mutex::new(HashMap::new())),
             repos: Arc::new(Mutex::new(HashMap::new())),
+            health: Arc::new(Mutex::new(HashMap::new())),
+            permissions: Arc::new(Mutex::new(HashMap::new())),
+            fatal_http_errors: FatalHttpErrors::default(),
+            retries: RetryPolicies::default(),
         }
     }
 
+    pub fn with_retry_policies(mut self, retries: RetryPolicies) -> Self {
+        self.retries = retries;
+        self
+    }
+
+    pub fn with_fatal_http_errors(mut self, fatal_http_errors: FatalHttpErrors) -> Self {
+        self.fatal_http_errors = fatal_http_errors;
+        self
+    }
+
     pub async fn read_app(&self) -> Result<structs::App> {
         let pp = self
             .http_client
             .get(self.github.app())
             .bearer_auth(self.get_jwt_token().await);
-        let app: structs::App = __json(pp).await?;
+        let app: structs::App = __json(pp, self.retries.reads, self.fatal_http_errors).await?;
         Ok(app)
     }
 
+    /// Read GitHub's published IP ranges. This doesn't require authentication.
+    pub async fn read_meta(&self) -> Result<structs::Meta> {
+        __json(
+            self.http_client.get(self.github.meta()),
+            self.retries.reads,
+            self.fatal_http_errors,
+        )
+        .await
+    }
+
+    /// Read the rate limit budget of an installation token. Checking it doesn't count against the limit.
+    pub async fn read_rate_limits(&self, installation_id: i64) -> Result<structs::RateLimits> {
+        let req = self
+            .http_client
+            .get(self.github.rate_limit())
+            .bearer_auth(self.get_installation_token(installation_id).await?);
+        let limits: structs::RateLimits =
+            __json(req, self.retries.reads, self.fatal_http_errors).await?;
+        let context = format!("installation {}", installation_id);
+        for (resource, limit) in [
+            ("core", &limits.resources.core),
+            ("search", &limits.resources.search),
+        ] {
+            check_rate_limit(resource, limit.remaining, limit.limit, &context);
+        }
+        Ok(limits)
+    }
+
     pub fn cache_repositories(
         &self,
         installation_id: i64,
@@ -429,19 +1108,58 @@ impl Client {
         }
     }
 
+    /// Replace a repository which has been renamed or transferred, finding its cached version by ID. The repository
+    /// is moved to `installation_id`, since a transfer may take it to another account. Returns the cached version,
+    /// if there was one.
+    pub fn rename_repository(
+        &self,
+        installation_id: i64,
+        repository: structs::Repository,
+    ) -> Option<structs::Repository> {
+        let mut repos = self.repos.lock().unwrap();
+        let mut previous = None;
+        for rr in repos.values_mut() {
+            if let Some(i) = rr.iter().position(|r| r.id == repository.id) {
+                previous = Some(rr.remove(i));
+            }
+        }
+        if previous.is_some() {
+            if let Some(rr) = repos.get_mut(&installation_id) {
+                rr.push(repository);
+            }
+        }
+        previous
+    }
+
     pub async fn read_installations(&self) -> Result<Vec<structs::Installation>> {
         let pp = self
             .http_client
             .get(self.github.app_installations())
             .bearer_auth(self.get_jwt_token().await);
-        let items: Vec<structs::Installation> = __json(pp).await?;
+        let items: Vec<structs::Installation> =
+            __json(pp, self.retries.reads, self.fatal_http_errors).await?;
         Ok(items)
     }
 
+    pub async fn delete_app_installation(&self, installation_id: i64) -> Result<()> {
+        let req = self
+            .http_client
+            .delete(self.github.app_installation(installation_id))
+            .bearer_auth(self.get_jwt_token().await);
+        __text(req, self.retries.reads, self.fatal_http_errors).await?;
+        Ok(())
+    }
+
     pub async fn read_and_cache_installation_repos(
         &self,
         installation: structs::Installation,
     ) -> Result<structs::Installation> {
+        // Remember the installation even if its token can't be obtained, so that it's tried again later
+        // (see [`Self::refresh_tokens`]).
+        self.installations
+            .lock()
+            .unwrap()
+            .insert(installation.id, installation.clone());
         match self.get_installation_token(installation.id).await {
             Err(e) => {
                 log::error!(
@@ -460,7 +1178,13 @@ impl Client {
                     .http_client
                     .get(self.github.installation_repos())
                     .bearer_auth(token);
-                match __json::<structs::InstallationRepositories>(req).await {
+                match __json::<structs::InstallationRepositories>(
+                    req,
+                    self.retries.reads,
+                    self.fatal_http_errors,
+                )
+                .await
+                {
                     Err(e) => {
                         log::error!("Failed to fetch list of repositories for a fresh installation {}: {:?}", installation.id, e);
                         Err(e)
@@ -474,6 +1198,31 @@ impl Client {
         }
     }
 
+    /// Remember an installation and its repositories without asking GitHub, as if they were read and cached before.
+    pub fn restore_installation(
+        &self,
+        installation: structs::Installation,
+        repositories: Vec<structs::Repository>,
+    ) {
+        self.repos
+            .lock()
+            .unwrap()
+            .insert(installation.id, repositories);
+        self.installations
+            .lock()
+            .unwrap()
+            .insert(installation.id, installation);
+    }
+
+    pub fn cached_installations(&self) -> Vec<structs::Installation> {
+        self.installations
+            .lock()
+            .unwrap()
+            .values()
+            .cloned()
+            .collect()
+    }
+
     pub fn cached_repositories(&self, installation_id: i64) -> Vec<structs::Repository> {
         match self.repos.lock().unwrap().get(&installation_id) {
             Some(v) => v.clone(),
@@ -481,6 +1230,16 @@ impl Client {
         }
     }
 
+    /// Forget all installations, their repositories, credentials and health, even if a panic has left any of them
+    /// locked. Everything is read from GitHub again as the app is used.
+    pub fn reset(&self) {
+        helpers::reset_cache(&self.tokens);
+        helpers::reset_cache(&self.installations);
+        helpers::reset_cache(&self.repos);
+        helpers::reset_cache(&self.health);
+        helpers::reset_cache(&self.permissions);
+    }
+
     pub fn remove_installation(&self, installation: &structs::Installation) {
         self.installations.lock().unwrap().remove(&installation.id);
         self.repos.lock().unwrap().remove(&installation.id);
@@ -488,107 +1247,468 @@ impl Client {
             .lock()
             .unwrap()
             .remove(&TokenType::Installation(installation.id));
+        self.health.lock().unwrap().remove(&installation.id);
+        self.permissions.lock().unwrap().remove(&installation.id);
     }
 
     pub async fn read_pulls(&self, full_repo_name: &str) -> Result<Vec<structs::PullRequest>> {
-        let mut out = Vec::new();
         let token = self.pick_token(full_repo_name).await?;
-        let per_page = 100;
-
-        for page in 1..100 {
-            let req = self
-                .http_client
-                .get(self.github.pulls(full_repo_name))
-                .query(&[
-                    ("state", "open"),
-                    ("direction", "asc"),
-                    ("sort", "created"),
-                    ("per_page", &per_page.to_string()),
-                    ("page", &page.to_string()),
-                ])
-                .bearer_auth(token.clone());
-            let mut response: Vec<structs::PullRequest> = __json(req).await?;
-            let is_last_page = response.len() < per_page;
-            out.append(&mut response);
-            if is_last_page {
-                break;
-            }
-        }
-        Ok(out)
+        let first_page = reqwest::Url::parse_with_params(
+            &self.github.pulls(full_repo_name),
+            &[
+                ("state", "open"),
+                ("direction", "asc"),
+                ("sort", "created"),
+                ("per_page", PER_PAGE),
+                ("page", "1"),
+            ],
+        )?;
+        paginate(
+            first_page.into(),
+            |url| self.http_client.get(url).bearer_auth(&token),
+            |_| true,
+            self.retries.reads,
+            self.fatal_http_errors,
+        )
+        .await
     }
 
-    pub async fn post_comment(
+    /// Read a single pull request, whatever its state is.
+    pub async fn read_pull(
         &self,
         full_repo_name: &str,
-        issue_number: i32,
-        body: String,
-    ) -> Result<()> {
-        let comment = serde_json::to_string(&structs::PostIssueComment { body }).unwrap();
+        pull_number: i32,
+    ) -> Result<structs::PullRequest> {
         let token = self.pick_token(full_repo_name).await?;
         let req = self
             .http_client
-            .post(self.github.comments(full_repo_name, issue_number))
-            .body(comment)
+            .get(self.github.pull(full_repo_name, pull_number))
             .bearer_auth(token);
-        __json::<structs::IssueComment>(req).await?;
-        Ok(())
+        __json(req, self.retries.reads, self.fatal_http_errors).await
     }
 
-    pub async fn update_comment(
+    /// Read open pull requests together with their changed files and comments left by bots, using as few GraphQL queries as possible.
+    ///
+    /// Pulls with too many changed files to fit into one query have their diffs fetched separately.
+    pub async fn read_pulls_graphql(
         &self,
         full_repo_name: &str,
-        comment_id: i64,
-        body: String,
-    ) -> Result<()> {
-        let comment = serde_json::to_string(&structs::PostIssueComment { body }).unwrap();
+    ) -> Result<Vec<(structs::PullRequest, Vec<structs::IssueComment>)>> {
+        let (owner, name) = full_repo_name
+            .split_once('/')
+            .ok_or_else(|| eyre::eyre!("Malformed repository name {}", full_repo_name))?;
+
+        let mut out = Vec::new();
+        let mut cursor: Option<String> = None;
+        loop {
+            let response: structs::GraphQLRepositoryPulls = self
+                .graphql(
+                    full_repo_name,
+                    PULLS_QUERY,
+                    serde_json::json!({ "owner": owner, "name": name, "cursor": cursor }),
+                )
+                .await?;
+            let pulls = response.repository.pull_requests;
+
+            for p in pulls.nodes {
+                let comments: Vec<structs::IssueComment> = p
+                    .comments
+                    .nodes
+                    .into_iter()
+                    .filter(|c| c.author.as_ref().is_some_and(|a| a.typename == "Bot"))
+                    .map(structs::IssueComment::from)
+                    .collect();
+                let mut pull = structs::PullRequest {
+                    id: p.database_id,
+                    number: p.number,
+                    state: p.state.to_lowercase(),
+                    title: p.title,
+                    user: p
+                        .author
+                        .map(structs::Actor::from)
+                        .unwrap_or_else(|| structs::Actor {
+                            id: 0,
+                            login: "ghost".to_string(),
+                        }),
+                    html_url: p.url,
+                    created_at: p.created_at,
+                    updated_at: p.updated_at,
+                    diff: None,
+                    base: structs::PullRequestBranch {
+                        branch: p.base_ref_name,
+                        sha: p.base_ref_oid,
+                        repo: p.base_repository.map(Into::into),
+                    },
+                    head: structs::PullRequestBranch {
+                        branch: p.head_ref_name,
+                        sha: p.head_ref_oid,
+                        repo: p.head_repository.map(Into::into),
+                    },
+                    merged_at: None,
+                    merged: false,
+                    mergeable_state: None,
+                    labels: p.labels.nodes,
+                    author_association: p.author_association,
+                };
+                if p.files.page_info.has_next_page {
+                    pull.set_diff(self.read_pull_diff(full_repo_name, p.number).await?);
+                } else {
+                    pull.diff = Some(DiffSummary::from_files(&p.files.nodes));
+                }
+                out.push((pull, comments));
+            }
+
+            if !pulls.page_info.has_next_page {
+                break;
+            }
+            cursor = pulls.page_info.end_cursor;
+        }
+        Ok(out)
+    }
+
+    /// Post a comment, making sure it only appears once.
+    ///
+    /// A failed request may still have reached GitHub (for example, if the response has timed out), so before trying again,
+    /// existing comments are checked for one with the same header (or the same text, if there is no header).
+    pub async fn post_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        body: String,
+    ) -> Result<()> {
+        let header = CommentHeader::from_comment(&body, None);
+        self.write_with_check(
+            || self.try_post_comment(full_repo_name, issue_number, body.clone()),
+            || async {
+                let is_same = |c: &structs::IssueComment| match header {
+                    Some(ref h) => CommentHeader::from_comment(&c.body, None).as_ref() == Some(h),
+                    None => c.body == body,
+                };
+                let comments = self
+                    .read_comments_while(full_repo_name, issue_number, |page| {
+                        !page.iter().any(is_same)
+                    })
+                    .await?;
+                Ok(comments.iter().any(is_same))
+            },
+        )
+        .await
+    }
+
+    async fn try_post_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        body: String,
+    ) -> Result<()> {
+        let comment = serde_json::to_string(&structs::PostIssueComment { body }).unwrap();
+        let token = self.pick_token(full_repo_name).await?;
+        let req = self
+            .http_client
+            .post(self.github.comments(full_repo_name, issue_number))
+            .body(comment)
+            .bearer_auth(token);
+        __json::<structs::IssueComment>(req, RetryPolicy::SINGLE_ATTEMPT, self.fatal_http_errors)
+            .await?;
+        Ok(())
+    }
+
+    /// Update a comment. If the request fails, the comment is re-read to see whether the update has gone through anyway.
+    pub async fn update_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        comment_id: i64,
+        body: String,
+    ) -> Result<()> {
+        self.write_with_check(
+            || self.try_update_comment(full_repo_name, comment_id, body.clone()),
+            || async {
+                let comments = self.read_comments(full_repo_name, issue_number).await?;
+                Ok(comments
+                    .iter()
+                    .any(|c| c.id == comment_id && c.body == body))
+            },
+        )
+        .await
+    }
+
+    async fn try_update_comment(
+        &self,
+        full_repo_name: &str,
+        comment_id: i64,
+        body: String,
+    ) -> Result<()> {
+        let comment = serde_json::to_string(&structs::PostIssueComment { body }).unwrap();
         let token = self.pick_token(full_repo_name).await?;
         let req = self
             .http_client
             .patch(self.github.issue_comment(full_repo_name, comment_id))
             .body(comment)
             .bearer_auth(token);
-        __json::<structs::IssueComment>(req).await?;
+        __json::<structs::IssueComment>(req, RetryPolicy::SINGLE_ATTEMPT, self.fatal_http_errors)
+            .await?;
         Ok(())
     }
 
-    pub async fn delete_comment(&self, full_repo_name: &str, comment_id: i64) -> Result<()> {
+    /// Delete a comment. If the request fails, the comment may already be gone, which is checked before trying again.
+    pub async fn delete_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        comment_id: i64,
+    ) -> Result<()> {
+        self.write_with_check(
+            || self.try_delete_comment(full_repo_name, comment_id),
+            || async {
+                let comments = self.read_comments(full_repo_name, issue_number).await?;
+                Ok(!comments.iter().any(|c| c.id == comment_id))
+            },
+        )
+        .await
+    }
+
+    async fn try_delete_comment(&self, full_repo_name: &str, comment_id: i64) -> Result<()> {
         let token = self.pick_token(full_repo_name).await?;
         let req = self
             .http_client
             .delete(self.github.issue_comment(full_repo_name, comment_id))
             .bearer_auth(token);
-        __text(req).await?;
+        __text(req, RetryPolicy::SINGLE_ATTEMPT, self.fatal_http_errors).await?;
         Ok(())
     }
 
+    /// Run a write operation as many times as [`RetryPolicies::comment_writes`] allows. After every failure, `landed` checks whether the write has taken
+    /// effect regardless, in which case it is not repeated. Secondary rate limits are returned right away.
+    async fn write_with_check<W, WF, L, LF>(&self, write: W, landed: L) -> Result<()>
+    where
+        W: Fn() -> WF,
+        WF: std::future::Future<Output = Result<()>>,
+        L: Fn() -> LF,
+        LF: std::future::Future<Output = Result<bool>>,
+    {
+        let mut timer = ProgressiveTimeout::with_policy(&self.retries.comment_writes);
+        let mut last_error = None;
+        while timer.tick().is_ok() {
+            match write().await {
+                Ok(()) => return Ok(()),
+                // Repeating the write right away would only extend the limit.
+                Err(e) if SecondaryRateLimit::find(&e).is_some() => return Err(e),
+                Err(e) => {
+                    log::warn!(
+                        "Write failed ({}/{}): {:?}",
+                        timer.current_retry(),
+                        timer.max_retries(),
+                        e
+                    );
+                    last_error = Some(e);
+                }
+            }
+            match landed().await {
+                Ok(true) => {
+                    log::info!("The write has gone through despite the error, not repeating it");
+                    return Ok(());
+                }
+                Ok(false) => (),
+                Err(e) => log::warn!(
+                    "Failed to check whether the write has gone through: {:?}",
+                    e
+                ),
+            }
+            if timer.current_retry() < timer.max_retries() {
+                timer.sleep().await;
+            }
+        }
+        Err(last_error.unwrap_or_else(|| eyre::eyre!("Exhausted write attempts, giving up")))
+    }
+
+    /// Run a GraphQL query or mutation on behalf of the installation which has access to the repository.
+    pub async fn graphql<T>(
+        &self,
+        full_repo_name: &str,
+        query: &str,
+        variables: serde_json::Value,
+    ) -> Result<T>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        let request = serde_json::to_string(&structs::GraphQLRequest {
+            query: query.to_owned(),
+            variables,
+        })
+        .unwrap();
+        let token = self.pick_token(full_repo_name).await?;
+        let req = self
+            .http_client
+            .post(self.github.graphql())
+            .body(request)
+            .bearer_auth(token);
+        let response: structs::GraphQLResponse<T> =
+            __json(req, self.retries.reads, self.fatal_http_errors).await?;
+        if !response.errors.is_empty() {
+            let messages: Vec<_> = response.errors.into_iter().map(|e| e.message).collect();
+            eyre::bail!("GraphQL errors: {}", messages.join("; "));
+        }
+        response
+            .data
+            .ok_or_else(|| eyre::eyre!("GraphQL response contains no data"))
+    }
+
+    /// Hide a comment as resolved. There is no REST API equivalent for this.
+    pub async fn minimize_comment(&self, full_repo_name: &str, node_id: &str) -> Result<()> {
+        self.graphql::<serde_json::Value>(
+            full_repo_name,
+            "mutation($id: ID!) { minimizeComment(input: {subjectId: $id, classifier: RESOLVED}) { minimizedComment { isMinimized } } }",
+            serde_json::json!({ "id": node_id }),
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Pick minimized comments out of the list, by their GraphQL identifiers.
+    pub async fn minimized_comments(
+        &self,
+        full_repo_name: &str,
+        node_ids: &[String],
+    ) -> Result<Vec<String>> {
+        let mut minimized = Vec::new();
+        // The GraphQL API looks up at most 100 nodes at a time.
+        for chunk in node_ids.chunks(100) {
+            let response: structs::GraphQLNodes<structs::GraphQLMinimizedComment> = self
+                .graphql(
+                    full_repo_name,
+                    "query($ids: [ID!]!) { nodes(ids: $ids) { ... on IssueComment { id isMinimized } } }",
+                    serde_json::json!({ "ids": chunk }),
+                )
+                .await?;
+            minimized.extend(
+                response
+                    .nodes
+                    .into_iter()
+                    .flatten()
+                    .filter(|c| c.is_minimized)
+                    .map(|c| c.id),
+            );
+        }
+        Ok(minimized)
+    }
+
     pub async fn read_comments(
         &self,
         full_repo_name: &str,
         issue_number: i32,
     ) -> Result<Vec<structs::IssueComment>> {
-        let mut out = Vec::new();
+        self.read_comments_while(full_repo_name, issue_number, |_| true)
+            .await
+    }
+
+    /// Read comments page by page until `keep_going` returns `false` for a page (see [`paginate`]).
+    async fn read_comments_while<F>(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        keep_going: F,
+    ) -> Result<Vec<structs::IssueComment>>
+    where
+        F: FnMut(&[structs::IssueComment]) -> bool,
+    {
         let token = self.pick_token(full_repo_name).await?;
-        let per_page = 100;
-
-        for page in 1..100 {
-            let req = self
-                .http_client
-                .get(self.github.comments(full_repo_name, issue_number))
-                .query(&[
-                    ("per_page", &per_page.to_string()),
-                    ("page", &page.to_string()),
-                ])
-                .bearer_auth(token.clone());
-            let mut response: Vec<structs::IssueComment> = __json(req).await?;
-            let is_last_page = response.len() < per_page;
-            out.append(&mut response);
-            if is_last_page {
-                break;
+        let first_page = reqwest::Url::parse_with_params(
+            &self.github.comments(full_repo_name, issue_number),
+            &[("per_page", PER_PAGE), ("page", "1")],
+        )?;
+        paginate(
+            first_page.into(),
+            |url| self.http_client.get(url).bearer_auth(&token),
+            keep_going,
+            self.retries.reads,
+            self.fatal_http_errors,
+        )
+        .await
+    }
+
+    /// Compare two commits (or branches). Files are listed relative to their merge base, so comparing a pull's head
+    /// with its base branch reveals changes merged after the pull was branched off.
+    pub async fn compare(
+        &self,
+        full_repo_name: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<structs::Comparison> {
+        let token = self.pick_token(full_repo_name).await?;
+        let req = self
+            .http_client
+            .get(self.github.compare(full_repo_name, base, head))
+            .bearer_auth(token);
+        __json(req, self.retries.diffs, self.fatal_http_errors).await
+    }
+
+    /// Read a file from the default branch of a repository. A missing file is not an error.
+    pub async fn read_file(&self, full_repo_name: &str, path: &str) -> Result<Option<String>> {
+        let token = self.pick_token(full_repo_name).await?;
+        let mut headers = Self::default_headers();
+        headers.insert("Accept", "application/vnd.github.raw".try_into().unwrap());
+        let req = self
+            .http_client
+            .get(self.github.contents(full_repo_name, path))
+            .headers(headers)
+            .bearer_auth(token);
+        match __send(req, self.retries.reads, self.fatal_http_errors).await {
+            Ok(contents) => Ok(Some(contents)),
+            Err(e)
+                if HttpError::find(&e)
+                    .is_some_and(|e| e.status == reqwest::StatusCode::NOT_FOUND) =>
+            {
+                Ok(None)
             }
+            Err(e) => Err(e),
         }
-        Ok(out)
     }
 
+    /// Add labels to a pull request (or an issue). Labels which are already there are left as is.
+    pub async fn add_labels(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        labels: &[String],
+    ) -> Result<()> {
+        let token = self.pick_token(full_repo_name).await?;
+        let req = self
+            .http_client
+            .post(self.github.issue_labels(full_repo_name, issue_number))
+            .bearer_auth(token)
+            .json(&serde_json::json!({ "labels": labels }));
+        __text(req, self.retries.reads, self.fatal_http_errors).await?;
+        Ok(())
+    }
+
+    /// Remove a label from a pull request (or an issue). A label which is not there is not an error.
+    pub async fn remove_label(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        label: &str,
+    ) -> Result<()> {
+        let token = self.pick_token(full_repo_name).await?;
+        let req = self
+            .http_client
+            .delete(self.github.issue_label(full_repo_name, issue_number, label))
+            .bearer_auth(token);
+        match __text(req, self.retries.reads, self.fatal_http_errors).await {
+            Ok(_) => Ok(()),
+            // The label is already gone.
+            Err(e)
+                if HttpError::find(&e)
+                    .is_some_and(|e| e.status == reqwest::StatusCode::NOT_FOUND) =>
+            {
+                Ok(())
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Fetch a pull request diff from github.com, which is cheap on API quota. The web frontend sometimes isn't aware
+    /// of fresh pulls yet, in which case the diff is requested from the API instead.
     pub async fn read_pull_diff(
         &self,
         full_repo_name: &str,
@@ -598,10 +1718,331 @@ impl Client {
         let req = self
             .http_client
             .get(self.github.diff_url(full_repo_name, pull_number))
-            .bearer_auth(token);
-        let response = __text(req).await?;
+            .bearer_auth(token.clone());
+        let response = match __text(req, self.retries.diffs, self.fatal_http_errors).await {
+            Ok(response) => response,
+            Err(e) => {
+                log::warn!(
+                    "Failed to fetch diff for pull #{} from {}, falling back to the API: {:?}",
+                    pull_number,
+                    self.github.base_url,
+                    e
+                );
+                let mut headers = Self::default_headers();
+                headers.insert(
+                    "Accept",
+                    "application/vnd.github.v3.diff".try_into().unwrap(),
+                );
+                let req = self
+                    .http_client
+                    .get(self.github.pull(full_repo_name, pull_number))
+                    .headers(headers)
+                    .bearer_auth(token);
+                __send(req, self.retries.diffs, self.fatal_http_errors).await?
+            }
+        };
         Ok(unidiff::PatchSet::from_str(&response)?)
     }
 }
 
-// TODO: add tests
+/// Everything the controller needs from GitHub. [`Client`] talks to the real API; other implementations may serve
+/// recorded or in-memory data (see `crate::test::MockGitHub`).
+///
+/// Installations and repositories are cached by the backend: the `cached_*`, `restore_*` and `remove_*` methods
+/// never make requests.
+#[async_trait]
+pub trait GitHubInterface: std::fmt::Debug + Send + Sync {
+    /// URL builder for links posted in comments.
+    fn github(&self) -> &GitHub;
+
+    async fn read_app(&self) -> Result<structs::App>;
+    async fn read_installations(&self) -> Result<Vec<structs::Installation>>;
+    async fn delete_app_installation(&self, installation_id: i64) -> Result<()>;
+    async fn read_and_cache_installation_repos(
+        &self,
+        installation: structs::Installation,
+    ) -> Result<structs::Installation>;
+    async fn read_rate_limits(&self, installation_id: i64) -> Result<structs::RateLimits>;
+
+    /// Make sure requests don't have to wait for credentials. Does nothing unless the backend needs any.
+    async fn refresh_tokens(&self) {}
+
+    /// Replace the credentials of an installation right away (see [`Client::refresh_installation_token`]).
+    async fn refresh_installation_token(&self, _installation_id: i64) -> Result<()> {
+        Ok(())
+    }
+
+    /// When the credentials of an installation expire, if the backend has any.
+    fn token_expires_at(&self, _installation_id: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+        None
+    }
+
+    /// Whether the installation is on hold after repeated authentication failures (see [`Client::is_quarantined`]).
+    fn is_quarantined(&self, _installation_id: i64) -> bool {
+        false
+    }
+
+    /// Permissions of an installation, if the backend knows them (see [`Client::installation_permissions`]).
+    fn installation_permissions(&self, _installation_id: i64) -> Option<structs::Permissions> {
+        None
+    }
+
+    /// Drop everything the backend has cached, before the controller rebuilds its state (see [`Client::reset`]).
+    fn reset(&self) {}
+
+    fn restore_installation(
+        &self,
+        installation: structs::Installation,
+        repositories: Vec<structs::Repository>,
+    );
+    fn cached_installations(&self) -> Vec<structs::Installation>;
+    fn cached_repositories(&self, installation_id: i64) -> Vec<structs::Repository>;
+    fn remove_installation(&self, installation: &structs::Installation);
+    fn cache_repositories(&self, installation_id: i64, repositories: Vec<structs::Repository>);
+    fn remove_repositories(&self, installation_id: i64, repositories: &[structs::Repository]);
+    fn rename_repository(
+        &self,
+        installation_id: i64,
+        repository: structs::Repository,
+    ) -> Option<structs::Repository>;
+
+    async fn read_pulls(&self, full_repo_name: &str) -> Result<Vec<structs::PullRequest>>;
+    async fn read_pull(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+    ) -> Result<structs::PullRequest>;
+    async fn read_pulls_graphql(
+        &self,
+        full_repo_name: &str,
+    ) -> Result<Vec<(structs::PullRequest, Vec<structs::IssueComment>)>>;
+    async fn read_pull_diff(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+    ) -> Result<unidiff::PatchSet>;
+    async fn compare(
+        &self,
+        full_repo_name: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<structs::Comparison>;
+    async fn read_file(&self, full_repo_name: &str, path: &str) -> Result<Option<String>>;
+
+    async fn read_comments(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+    ) -> Result<Vec<structs::IssueComment>>;
+    async fn post_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        body: String,
+    ) -> Result<()>;
+    async fn update_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        comment_id: i64,
+        body: String,
+    ) -> Result<()>;
+    async fn delete_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        comment_id: i64,
+    ) -> Result<()>;
+    async fn minimize_comment(&self, full_repo_name: &str, node_id: &str) -> Result<()>;
+    async fn minimized_comments(
+        &self,
+        full_repo_name: &str,
+        node_ids: &[String],
+    ) -> Result<Vec<String>>;
+
+    async fn add_labels(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        labels: &[String],
+    ) -> Result<()>;
+    async fn remove_label(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        label: &str,
+    ) -> Result<()>;
+}
+
+#[async_trait]
+impl GitHubInterface for Client {
+    fn github(&self) -> &GitHub {
+        &self.github
+    }
+
+    async fn read_app(&self) -> Result<structs::App> {
+        Client::read_app(self).await
+    }
+    async fn read_installations(&self) -> Result<Vec<structs::Installation>> {
+        Client::read_installations(self).await
+    }
+    async fn delete_app_installation(&self, installation_id: i64) -> Result<()> {
+        Client::delete_app_installation(self, installation_id).await
+    }
+    async fn read_and_cache_installation_repos(
+        &self,
+        installation: structs::Installation,
+    ) -> Result<structs::Installation> {
+        Client::read_and_cache_installation_repos(self, installation).await
+    }
+    async fn read_rate_limits(&self, installation_id: i64) -> Result<structs::RateLimits> {
+        Client::read_rate_limits(self, installation_id).await
+    }
+    async fn refresh_tokens(&self) {
+        Client::refresh_tokens(self).await
+    }
+    async fn refresh_installation_token(&self, installation_id: i64) -> Result<()> {
+        Client::refresh_installation_token(self, installation_id).await
+    }
+    fn token_expires_at(&self, installation_id: i64) -> Option<chrono::DateTime<chrono::Utc>> {
+        Client::token_expires_at(self, installation_id)
+    }
+    fn is_quarantined(&self, installation_id: i64) -> bool {
+        Client::is_quarantined(self, installation_id)
+    }
+    fn installation_permissions(&self, installation_id: i64) -> Option<structs::Permissions> {
+        Client::installation_permissions(self, installation_id)
+    }
+
+    fn reset(&self) {
+        Client::reset(self)
+    }
+
+    fn restore_installation(
+        &self,
+        installation: structs::Installation,
+        repositories: Vec<structs::Repository>,
+    ) {
+        Client::restore_installation(self, installation, repositories)
+    }
+    fn cached_installations(&self) -> Vec<structs::Installation> {
+        Client::cached_installations(self)
+    }
+    fn cached_repositories(&self, installation_id: i64) -> Vec<structs::Repository> {
+        Client::cached_repositories(self, installation_id)
+    }
+    fn remove_installation(&self, installation: &structs::Installation) {
+        Client::remove_installation(self, installation)
+    }
+    fn cache_repositories(&self, installation_id: i64, repositories: Vec<structs::Repository>) {
+        Client::cache_repositories(self, installation_id, repositories)
+    }
+    fn remove_repositories(&self, installation_id: i64, repositories: &[structs::Repository]) {
+        Client::remove_repositories(self, installation_id, repositories)
+    }
+    fn rename_repository(
+        &self,
+        installation_id: i64,
+        repository: structs::Repository,
+    ) -> Option<structs::Repository> {
+        Client::rename_repository(self, installation_id, repository)
+    }
+
+    async fn read_pulls(&self, full_repo_name: &str) -> Result<Vec<structs::PullRequest>> {
+        Client::read_pulls(self, full_repo_name).await
+    }
+    async fn read_pull(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+    ) -> Result<structs::PullRequest> {
+        Client::read_pull(self, full_repo_name, pull_number).await
+    }
+    async fn read_pulls_graphql(
+        &self,
+        full_repo_name: &str,
+    ) -> Result<Vec<(structs::PullRequest, Vec<structs::IssueComment>)>> {
+        Client::read_pulls_graphql(self, full_repo_name).await
+    }
+    async fn read_pull_diff(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+    ) -> Result<unidiff::PatchSet> {
+        Client::read_pull_diff(self, full_repo_name, pull_number).await
+    }
+    async fn compare(
+        &self,
+        full_repo_name: &str,
+        base: &str,
+        head: &str,
+    ) -> Result<structs::Comparison> {
+        Client::compare(self, full_repo_name, base, head).await
+    }
+    async fn read_file(&self, full_repo_name: &str, path: &str) -> Result<Option<String>> {
+        Client::read_file(self, full_repo_name, path).await
+    }
+
+    async fn read_comments(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+    ) -> Result<Vec<structs::IssueComment>> {
+        Client::read_comments(self, full_repo_name, issue_number).await
+    }
+    async fn post_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        body: String,
+    ) -> Result<()> {
+        Client::post_comment(self, full_repo_name, issue_number, body).await
+    }
+    async fn update_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        comment_id: i64,
+        body: String,
+    ) -> Result<()> {
+        Client::update_comment(self, full_repo_name, issue_number, comment_id, body).await
+    }
+    async fn delete_comment(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        comment_id: i64,
+    ) -> Result<()> {
+        Client::delete_comment(self, full_repo_name, issue_number, comment_id).await
+    }
+    async fn minimize_comment(&self, full_repo_name: &str, node_id: &str) -> Result<()> {
+        Client::minimize_comment(self, full_repo_name, node_id).await
+    }
+    async fn minimized_comments(
+        &self,
+        full_repo_name: &str,
+        node_ids: &[String],
+    ) -> Result<Vec<String>> {
+        Client::minimized_comments(self, full_repo_name, node_ids).await
+    }
+
+    async fn add_labels(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        labels: &[String],
+    ) -> Result<()> {
+        Client::add_labels(self, full_repo_name, issue_number, labels).await
+    }
+    async fn remove_label(
+        &self,
+        full_repo_name: &str,
+        issue_number: i32,
+        label: &str,
+    ) -> Result<()> {
+        Client::remove_label(self, full_repo_name, issue_number, label).await
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,181 @@
+//! Open pull requests of a repository and known conflicts between them, as a graph: clusters of interdependent pulls stand
+//! out at a glance. Served by `GET /api/v1/repos/{owner}/{name}/conflict-graph`, as JSON or in the DOT format of Graphviz.
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::conflicts::{Conflict, ConflictType, Severity};
+use crate::structs::PullRequest;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Node {
+    pub number: i32,
+    pub title: String,
+    pub author: String,
+    pub url: String,
+}
+
+/// A conflict, pointing from the pull which is notified about it to the one it depends on.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Edge {
+    pub from: i32,
+    pub to: i32,
+
+    /// The repository of `to`, if it's not this one (see `controller.repo_groups`). Such pulls are not listed as nodes.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+
+    pub kind: ConflictType,
+    pub severity: Severity,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ConflictGraph {
+    pub repository: String,
+
+    /// Sorted by pull number.
+    pub nodes: Vec<Node>,
+
+    /// Sorted by pull numbers.
+    pub edges: Vec<Edge>,
+}
+
+impl ConflictGraph {
+    pub fn new(full_repo_name: &str, pulls: &[PullRequest], conflicts: &[Conflict]) -> Self {
+        let mut nodes: Vec<_> = pulls
+            .iter()
+            .map(|p| Node {
+                number: p.number,
+                title: p.title.clone(),
+                author: p.user.login.clone(),
+                url: p.html_url.clone(),
+            })
+            .collect();
+        nodes.sort_by_key(|n| n.number);
+
+        let mut edges: Vec<_> = conflicts
+            .iter()
+            .map(|c| Edge {
+                from: c.trigger,
+                to: c.original,
+                repository: c.original_repo.clone(),
+                kind: c.kind.clone(),
+                severity: c.severity,
+            })
+            .collect();
+        edges.sort_by(|a, b| (a.from, a.to, &a.kind).cmp(&(b.from, b.to, &b.kind)));
+        edges.dedup();
+
+        Self {
+            repository: full_repo_name.to_string(),
+            nodes,
+            edges,
+        }
+    }
+
+    /// Render the graph in the DOT format, for `dot -Tsvg` and the like.
+    pub fn to_dot(&self) -> String {
+        let node_id = |number: i32, repository: &Option<String>| match repository {
+            Some(r) => format!("\"{}#{}\"", quote(r), number),
+            None => format!("\"#{}\"", number),
+        };
+
+        let mut lines = vec![format!("digraph \"{}\" {{", quote(&self.repository))];
+        for n in &self.nodes {
+            lines.push(format!(
+                "  {} [label=\"#{} {}\\n{}\", URL=\"{}\"];",
+                node_id(n.number, &None),
+                n.number,
+                quote(&n.title),
+                quote(&n.author),
+                quote(&n.url)
+            ));
+        }
+        for e in &self.edges {
+            let style = match e.severity {
+                Severity::LineLevel => "bold",
+                Severity::FileLevel => "solid",
+            };
+            lines.push(format!(
+                "  {} -> {} [label=\"{:?}\", style={}];",
+                node_id(e.from, &None),
+                node_id(e.to, &e.repository),
+                e.kind,
+                style
+            ));
+        }
+        lines.push("}".to_string());
+        lines.join("\n")
+    }
+}
+
+/// Escape a string for a quoted DOT identifier.
+fn quote(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test::GitHubServer;
+
+    #[tokio::test]
+    async fn graph_and_dot() {
+        let mut server = GitHubServer::new().await;
+        let mut p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+        p1.title = "Update \"Article\"".to_string();
+        let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+        let p3 = server.make_pull("test/repo", &["wiki/Other/en.md"]);
+        let mut overlap = Conflict::overlap(
+            2,
+            1,
+            p1.html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()],
+        );
+        overlap.severity = Severity::LineLevel;
+        let mut across = Conflict::incomplete_translation(
+            3,
+            7,
+            "https://github.com/test/wiki-ru/pull/7".to_string(),
+            vec!["wiki/Other/en.md".to_string()],
+        );
+        across.original_repo = Some("test/wiki-ru".to_string());
+
+        let graph = ConflictGraph::new(
+            "test/repo",
+            &[p3, p2, p1],
+            &[across.clone(), overlap.clone(), overlap],
+        );
+        assert_eq!(
+            graph.nodes.iter().map(|n| n.number).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert_eq!(
+            graph.edges,
+            vec![
+                Edge {
+                    from: 2,
+                    to: 1,
+                    repository: None,
+                    kind: ConflictType::Overlap,
+                    severity: Severity::LineLevel,
+                },
+                Edge {
+                    from: 3,
+                    to: 7,
+                    repository: Some("test/wiki-ru".to_string()),
+                    kind: ConflictType::IncompleteTranslation,
+                    severity: Severity::FileLevel,
+                },
+            ]
+        );
+
+        let dot = graph.to_dot();
+        assert!(dot.starts_with("digraph \"test/repo\" {"));
+        assert!(dot.contains("\"#1\" [label=\"#1 Update \\\"Article\\\"\\nauthor-1\""));
+        assert!(dot.contains("\"#2\" -> \"#1\" [label=\"Overlap\", style=bold];"));
+        assert!(dot.contains(
+            "\"#3\" -> \"test/wiki-ru#7\" [label=\"IncompleteTranslation\", style=solid];"
+        ));
+        assert!(dot.ends_with('}'));
+    }
+}
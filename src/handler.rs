@@ -1,54 +1,272 @@
-use viz::IntoResponse;
-use viz::{Request, RequestExt, StatusCode};
+use eyre::Result;
+use tokio::sync::mpsc;
 
-use crate::{controller, structs};
+use crate::helpers::commands::Command;
+use crate::{controller, repo_config, structs};
 
-pub async fn pull_request_event(req: Request, body: String) -> viz::Result<()> {
-    let controller_handle = req
-        .state::<controller::ControllerHandle>()
-        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
+/// A webhook event which has passed validation, but hasn't been parsed yet.
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// The `X-GitHub-Event` header.
+    pub event_type: String,
 
-    let evt: structs::PullRequestEvent = serde_json::from_str(&body).map_err(|e| {
-        log::error!(
-            "Failed to deserialize a pull request event coming from GitHub: {:?}. JSON: {:?}",
-            e,
-            body
-        );
-        StatusCode::INTERNAL_SERVER_ERROR.into_error()
-    })?;
+    /// The `X-GitHub-Delivery` header, used to trace changes back to the event (see [`crate::audit`]).
+    pub delivery_id: Option<String>,
+
+    pub body: String,
+}
+
+/// The queue of webhook events, which lets the server respond to GitHub right away. Events are parsed and passed
+/// to the controller one by one, in the order they have arrived.
+#[derive(Debug, Clone)]
+pub struct EventQueue {
+    sender: mpsc::UnboundedSender<Event>,
+}
+
+impl EventQueue {
+    pub fn new(controller_handle: controller::ControllerHandle) -> Self {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Event>();
+        tokio::spawn(async move {
+            while let Some(event) = rx.recv().await {
+                let handle = match &event.delivery_id {
+                    Some(delivery_id) => controller_handle.with_delivery(delivery_id.clone()),
+                    None => controller_handle.clone(),
+                };
+                if let Err(e) = dispatch(&handle, &event).await {
+                    log::error!(
+                        "Failed to process a \"{}\" event (delivery {:?}): {:?}",
+                        event.event_type,
+                        event.delivery_id,
+                        e
+                    );
+                }
+            }
+        });
+        Self { sender: tx }
+    }
+
+    pub fn push(&self, event: Event) -> Result<()> {
+        self.sender
+            .send(event)
+            .map_err(|_| eyre::eyre!("The event queue is closed"))
+    }
+}
+
+/// Event types which [`dispatch`] acts on. The server acknowledges others without queueing them.
+pub const EVENT_TYPES: [&str; 10] = [
+    "pull_request",
+    "pull_request_review",
+    "issue_comment",
+    "installation",
+    "installation_repositories",
+    "repository",
+    "push",
+    "merge_group",
+    "installation_target",
+    "github_app_authorization",
+];
+
+pub fn is_supported(event_type: &str) -> bool {
+    EVENT_TYPES.contains(&event_type)
+}
+
+/// Check that the payload of an event can be parsed, without acting on it, so that the server can reject malformed
+/// payloads right away instead of dropping them once they are taken off the queue.
+pub fn validate(event: &Event) -> Result<()> {
+    fn check<T>(body: &str) -> Result<()>
+    where
+        T: for<'de> serde::Deserialize<'de>,
+    {
+        serde_json::from_str::<T>(body)?;
+        Ok(())
+    }
+
+    let body = event.body.as_str();
+    match event.event_type.as_str() {
+        "pull_request" => check::<structs::PullRequestEvent>(body),
+        "pull_request_review" => check::<structs::PullRequestReviewEvent>(body),
+        "issue_comment" => check::<structs::IssueCommentEvent>(body),
+        "installation" => check::<structs::InstallationEvent>(body),
+        "installation_repositories" => check::<structs::InstallationRepositoriesEvent>(body),
+        "repository" => check::<structs::RepositoryEvent>(body),
+        "push" => check::<structs::PushEvent>(body),
+        "merge_group" => check::<structs::MergeGroupEvent>(body),
+        "installation_target" => check::<structs::InstallationTargetEvent>(body),
+        "github_app_authorization" => check::<structs::GitHubAppAuthorizationEvent>(body),
+        _ => Ok(()),
+    }
+}
+
+/// Parse an event and pass it to the controller. Events of unknown types are ignored.
+pub async fn dispatch(
+    controller_handle: &controller::ControllerHandle,
+    event: &Event,
+) -> Result<()> {
+    let body = event.body.as_str();
+    match event.event_type.as_str() {
+        "pull_request" => pull_request_event(controller_handle, body).await,
+        "pull_request_review" => pull_request_review_event(controller_handle, body).await,
+        "issue_comment" => issue_comment_event(controller_handle, body).await,
+        "installation" => installation_event(controller_handle, body).await,
+        "installation_repositories" => {
+            installation_repositories_event(controller_handle, body).await
+        }
+        "repository" => repository_event(controller_handle, body).await,
+        "push" => push_event(controller_handle, body).await,
+        "merge_group" => merge_group_event(controller_handle, body).await,
+        "installation_target" => installation_target_event(controller_handle, body).await,
+        "github_app_authorization" => github_app_authorization_event(body),
+        _ => Ok(()),
+    }
+}
+
+/// Parse an event payload. A payload which doesn't fit is reported and skipped rather than failing: GitHub has been told
+/// that the event is received by then. If `debug.payload_dir` is set, the payload is in the archive, and can be replayed
+/// once the structs are fixed (see [`crate::archive`]).
+fn parse<T>(body: &str) -> Option<T>
+where
+    T: for<'de> serde::Deserialize<'de>,
+{
+    match serde_json::from_str(body) {
+        Ok(evt) => Some(evt),
+        Err(e) => {
+            let name = std::any::type_name::<T>();
+            log::warn!(
+                "Failed to deserialize {} coming from GitHub, skipping it: {:?}. JSON: {:?}",
+                name.rsplit("::").next().unwrap_or(name),
+                e,
+                body
+            );
+            None
+        }
+    }
+}
+
+pub async fn pull_request_event(
+    controller_handle: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::PullRequestEvent>(body) else {
+        return Ok(());
+    };
 
     let pull_number = evt.pull_request.number;
     log::debug!("Pull #{}: received event \"{}\"", pull_number, evt.action);
     match evt.action.as_str() {
-        "opened" | "reopened" => {
+        "opened" => {
             controller_handle
                 .add_pull(&evt.repository.full_name, evt.pull_request, true)
                 .await;
         }
+        "reopened" => {
+            controller_handle
+                .reopen_pull(&evt.repository.full_name, evt.pull_request)
+                .await;
+        }
         "synchronize" => {
             controller_handle
                 .update_pull(&evt.repository.full_name, evt.pull_request, true)
                 .await;
         }
+        // Retargeting a pull changes the set of pulls it may conflict with. Other edits (title, body) don't matter.
+        "edited" => {
+            if let Some(change) = evt.changes.base {
+                log::debug!(
+                    "Pull #{}: base branch changed from {} to {}",
+                    pull_number,
+                    change.branch.from,
+                    evt.pull_request.base.branch
+                );
+                controller_handle
+                    .update_pull(&evt.repository.full_name, evt.pull_request, true)
+                    .await;
+            }
+        }
         "closed" => {
             controller_handle
                 .remove_pull(&evt.repository.full_name, evt.pull_request)
                 .await;
         }
+        // Pulls merged by GitHub on its own are checked on, in case the closing event doesn't come.
+        "enqueued" | "auto_merge_enabled" => {
+            controller_handle
+                .expect_merge(&evt.repository.full_name, evt.pull_request)
+                .await;
+        }
+        "dequeued" | "auto_merge_disabled" => {
+            controller_handle
+                .cancel_merge(&evt.repository.full_name, pull_number)
+                .await;
+        }
         _ => {}
     }
     Ok(())
 }
 
-pub async fn installation_event(req: Request, body: String) -> viz::Result<()> {
-    let controller = req
-        .state::<controller::ControllerHandle>()
-        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
+pub async fn pull_request_review_event(
+    controller_handle: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::PullRequestReviewEvent>(body) else {
+        return Ok(());
+    };
 
-    let evt: structs::InstallationEvent = serde_json::from_str(&body).map_err(|e| {
-        log::error!("Failed to deserialize an installation request event coming from GitHub: {:?}. JSON: {:?}", e, body);
-        StatusCode::INTERNAL_SERVER_ERROR.into_error()
-    })?;
+    let pull_number = evt.pull_request.number;
+    log::debug!(
+        "Pull #{}: received review event \"{}\" ({}) from {}",
+        pull_number,
+        evt.action,
+        evt.review.state,
+        evt.review.user.login
+    );
+    if let "submitted" | "dismissed" = evt.action.as_str() {
+        controller_handle
+            .review_pull(&evt.repository.full_name, pull_number, evt.review)
+            .await;
+    }
+    Ok(())
+}
+
+pub async fn issue_comment_event(
+    controller_handle: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::IssueCommentEvent>(body) else {
+        return Ok(());
+    };
+
+    // Commands are only accepted in fresh comments on pull requests.
+    if evt.action != "created" || evt.issue.pull_request.is_none() {
+        return Ok(());
+    }
+
+    let pull_number = evt.issue.number;
+    for command in Command::parse_all(&evt.comment.body) {
+        log::debug!(
+            "Pull #{}: received command {:?} from {}",
+            pull_number,
+            command,
+            evt.comment.user.login
+        );
+        controller_handle
+            .run_command(
+                &evt.repository.full_name,
+                pull_number,
+                evt.comment.clone(),
+                command,
+            )
+            .await;
+    }
+    Ok(())
+}
+
+pub async fn installation_event(
+    controller: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::InstallationEvent>(body) else {
+        return Ok(());
+    };
 
     let installation_id = evt.installation.id;
     log::debug!(
@@ -63,20 +281,65 @@ pub async fn installation_event(req: Request, body: String) -> viz::Result<()> {
         "deleted" => {
             controller.delete_installation(evt.installation).await;
         }
+        "suspend" => {
+            controller.suspend_installation(evt.installation).await;
+        }
+        "unsuspend" => {
+            controller.unsuspend_installation(evt.installation).await;
+        }
         _ => {}
     }
     Ok(())
 }
 
-pub async fn installation_repositories_event(req: Request, body: String) -> viz::Result<()> {
-    let controller_handle = req
-        .state::<controller::ControllerHandle>()
-        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
+pub async fn installation_target_event(
+    controller_handle: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::InstallationTargetEvent>(body) else {
+        return Ok(());
+    };
+
+    if evt.action == "renamed" {
+        log::debug!(
+            "Installation #{}: {} {} renamed (previously {:?}) by {}",
+            evt.installation.id,
+            evt.target_type,
+            evt.account.login,
+            evt.changes.login.map(|c| c.from),
+            evt.sender.login
+        );
+        controller_handle
+            .rename_installation_target(evt.installation.id, evt.account)
+            .await;
+    }
+    Ok(())
+}
 
-    let evt: structs::InstallationRepositoriesEvent = serde_json::from_str(&body).map_err(|e| {
-        log::error!("Failed to deserialize an installation repositories event coming from GitHub: {:?}. JSON: {:?}", e, body);
-        StatusCode::INTERNAL_SERVER_ERROR.into_error()
-    })?;
+/// The app doesn't act on behalf of users, so there are no user tokens to drop: the revocation is only logged.
+pub fn github_app_authorization_event(body: &str) -> Result<()> {
+    let Some(evt) = parse::<structs::GitHubAppAuthorizationEvent>(body) else {
+        return Ok(());
+    };
+
+    if evt.action == "revoked" {
+        log::warn!(
+            "User {} (#{}) has revoked their authorization of the app. Installations are not affected, \
+            but anything relying on their user token will stop working",
+            evt.sender.login,
+            evt.sender.id
+        );
+    }
+    Ok(())
+}
+
+pub async fn installation_repositories_event(
+    controller_handle: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::InstallationRepositoriesEvent>(body) else {
+        return Ok(());
+    };
 
     match evt.action.as_str() {
         "added" => {
@@ -93,3 +356,76 @@ pub async fn installation_repositories_event(req: Request, body: String) -> viz:
     }
     Ok(())
 }
+
+pub async fn repository_event(
+    controller_handle: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::RepositoryEvent>(body) else {
+        return Ok(());
+    };
+
+    match evt.action.as_str() {
+        "renamed" | "transferred" => {
+            controller_handle
+                .rename_repository(evt.installation.id, evt.repository)
+                .await;
+        }
+        "archived" | "deleted" => {
+            controller_handle
+                .remove_repositories(evt.installation.id, vec![evt.repository])
+                .await;
+        }
+        "unarchived" => {
+            controller_handle
+                .add_repositories(evt.installation.id, vec![evt.repository])
+                .await;
+        }
+        _ => {}
+    }
+    Ok(())
+}
+
+pub async fn merge_group_event(
+    controller_handle: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::MergeGroupEvent>(body) else {
+        return Ok(());
+    };
+
+    // A merged group takes its pulls with it, which are then checked right away instead of waiting for the next check.
+    if evt.action == "destroyed" && evt.reason.as_deref() == Some("merged") {
+        log::debug!(
+            "{}: merge group {} has been merged",
+            evt.repository.full_name,
+            evt.merge_group.head_ref
+        );
+        controller_handle
+            .check_merges(&evt.repository.full_name)
+            .await;
+    }
+    Ok(())
+}
+
+pub async fn push_event(
+    controller_handle: &controller::ControllerHandle,
+    body: &str,
+) -> Result<()> {
+    let Some(evt) = parse::<structs::PushEvent>(body) else {
+        return Ok(());
+    };
+
+    // Only the settings in the default branch are in effect.
+    if evt.is_to_default_branch() && evt.touches(repo_config::PATH) {
+        log::debug!(
+            "{}: {} has changed, reloading it",
+            evt.repository.full_name,
+            repo_config::PATH
+        );
+        controller_handle
+            .reload_repo_config(&evt.repository.full_name)
+            .await;
+    }
+    Ok(())
+}
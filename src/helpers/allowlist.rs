@@ -0,0 +1,175 @@
+//! `allowlist` checks that webhook deliveries come from GitHub's published hook addresses.
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use eyre::Result;
+use ipnet::IpNet;
+
+use crate::github::Client;
+
+/// How long the list of GitHub's hook ranges is trusted before it is requested again.
+const RANGES_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Hook ranges, and when they were read.
+type CachedRanges = (Instant, Vec<IpNet>);
+
+/// Cached hook ranges from GitHub's `/meta` endpoint.
+///
+/// If the ranges can't be read, the last known ones are used. If they have never been read, everything is allowed:
+/// the allowlist is an extra layer on top of signature validation, and shouldn't take the app down with the API.
+#[derive(Debug, Clone)]
+pub struct HookAllowlist {
+    github: Client,
+    ranges: Arc<Mutex<Option<CachedRanges>>>,
+
+    /// Reverse proxies whose `X-Forwarded-For` headers are believed (see `server.trusted_proxies`).
+    trusted_proxies: Vec<IpNet>,
+}
+
+impl HookAllowlist {
+    pub fn new(github: Client) -> Self {
+        Self {
+            github,
+            ranges: Arc::default(),
+            trusted_proxies: Vec::new(),
+        }
+    }
+
+    pub fn with_trusted_proxies(self, trusted_proxies: Vec<IpNet>) -> Self {
+        Self {
+            trusted_proxies,
+            ..self
+        }
+    }
+
+    /// The address which has sent a request through `peer`. If `peer` is a trusted proxy, it's the last address in
+    /// `forwarded_for` which isn't a trusted proxy as well. Returns `None` if the header can't be parsed.
+    pub fn client_ip(&self, peer: IpAddr, forwarded_for: Option<&str>) -> Option<IpAddr> {
+        let mut hops = forwarded_for.unwrap_or_default().rsplit(',');
+        let mut ip = peer.to_canonical();
+        while self.trusted_proxies.iter().any(|r| r.contains(&ip)) {
+            match hops.next().map(str::trim) {
+                None | Some("") => break,
+                Some(hop) => ip = hop.parse::<IpAddr>().ok()?.to_canonical(),
+            }
+        }
+        Some(ip)
+    }
+
+    pub async fn allows(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        let cached = self.ranges.lock().unwrap().clone();
+        let ranges = match cached {
+            Some((fetched_at, ranges)) if fetched_at.elapsed() < RANGES_TTL => ranges,
+            stale => match self.fetch().await {
+                Ok(ranges) => ranges,
+                Err(e) => {
+                    log::error!("Failed to read GitHub hook IP ranges: {:?}", e);
+                    match stale {
+                        Some((_, ranges)) => ranges,
+                        None => return true,
+                    }
+                }
+            },
+        };
+        ranges.iter().any(|r| r.contains(&ip))
+    }
+
+    async fn fetch(&self) -> Result<Vec<IpNet>> {
+        let ranges = self
+            .github
+            .read_meta()
+            .await?
+            .hooks
+            .iter()
+            .map(|r| r.parse::<IpNet>())
+            .collect::<Result<Vec<_>, _>>()?;
+        log::info!("GitHub hook IP ranges: {:?}", ranges);
+        *self.ranges.lock().unwrap() = Some((Instant::now(), ranges.clone()));
+        Ok(ranges)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::github::GitHub;
+
+    #[tokio::test]
+    async fn ranges_are_fetched_once() {
+        let mut server = mockito::Server::new_async().await;
+        let meta_mock = server
+            .mock("GET", "/meta")
+            .with_status(200)
+            .with_body(r#"{"hooks": ["192.30.252.0/22", "2a0a:a440::/29"]}"#)
+            .expect(1)
+            .create();
+
+        let allowlist = HookAllowlist::new(Client::new(
+            GitHub::new(server.url(), server.url()),
+            crate::test::TEST_APP_ID.to_string(),
+            crate::test::TEST_APP_PRIVATE_KEY.to_string(),
+        ));
+        assert!(allowlist.allows("192.30.252.1".parse().unwrap()).await);
+        assert!(
+            allowlist
+                .allows("::ffff:192.30.255.254".parse().unwrap())
+                .await
+        );
+        assert!(allowlist.allows("2a0a:a440::1".parse().unwrap()).await);
+        assert!(!allowlist.allows("127.0.0.1".parse().unwrap()).await);
+        meta_mock.assert();
+    }
+
+    #[test]
+    fn forwarded_for_is_only_read_from_trusted_proxies() {
+        let allowlist = HookAllowlist::new(Client::new(
+            GitHub::default(),
+            crate::test::TEST_APP_ID.to_string(),
+            crate::test::TEST_APP_PRIVATE_KEY.to_string(),
+        ))
+        .with_trusted_proxies(vec![
+            "10.0.0.0/8".parse().unwrap(),
+            "127.0.0.1/32".parse().unwrap(),
+        ]);
+        let ip = |s: &str| s.parse::<IpAddr>().unwrap();
+
+        let header = Some("192.30.252.1, 10.0.0.2");
+        assert_eq!(
+            allowlist.client_ip(ip("127.0.0.1"), header),
+            Some(ip("192.30.252.1"))
+        );
+        assert_eq!(
+            allowlist.client_ip(ip("203.0.113.1"), header),
+            Some(ip("203.0.113.1"))
+        );
+        // Only the addresses added by trusted proxies count: the ones before them may be forged by the sender.
+        assert_eq!(
+            allowlist.client_ip(ip("127.0.0.1"), Some("192.30.252.1, 203.0.113.1")),
+            Some(ip("203.0.113.1"))
+        );
+        assert_eq!(
+            allowlist.client_ip(ip("127.0.0.1"), None),
+            Some(ip("127.0.0.1"))
+        );
+        assert_eq!(
+            allowlist.client_ip(ip("127.0.0.1"), Some("github.com")),
+            None
+        );
+    }
+
+    #[tokio::test]
+    async fn unknown_ranges_allow_everything() {
+        let mut server = mockito::Server::new_async().await;
+        server.mock("GET", "/meta").with_status(404).create();
+
+        let allowlist = HookAllowlist::new(Client::new(
+            GitHub::new(server.url(), server.url()),
+            crate::test::TEST_APP_ID.to_string(),
+            crate::test::TEST_APP_PRIVATE_KEY.to_string(),
+        ));
+        assert!(allowlist.allows("127.0.0.1".parse().unwrap()).await);
+    }
+}
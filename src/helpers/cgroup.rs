@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 use std::path::{Path, PathBuf};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct CGroup {
     pub path: PathBuf,
 }
@@ -32,6 +32,12 @@ impl CGroup {
         std::fs::read_to_string(self.path.as_path().join(subsystem)).ok()
     }
 
+    /// The share of the last 10 seconds, in percent, during which some tasks of the cgroup were stalled waiting for the
+    /// resource (`memory`, `cpu` or `io`), as reported by its pressure stall information.
+    pub fn pressure(&self, resource: &str) -> Option<f64> {
+        parse_pressure(&self.read(&format!("{resource}.pressure"))?)
+    }
+
     pub fn summary(&self) -> BTreeMap<String, String> {
         let mut m = BTreeMap::new();
         if !self.valid() {
@@ -51,3 +57,17 @@ impl CGroup {
         m
     }
 }
+
+/// Parse `avg10` of the `some` line out of a pressure file, which looks like this:
+///
+/// ```text
+/// some avg10=1.50 avg60=0.80 avg300=0.20 total=123456
+/// full avg10=0.00 avg60=0.00 avg300=0.00 total=0
+/// ```
+fn parse_pressure(data: &str) -> Option<f64> {
+    let some = data.lines().find_map(|l| l.strip_prefix("some "))?;
+    some.split_whitespace()
+        .find_map(|field| field.strip_prefix("avg10="))?
+        .parse()
+        .ok()
+}
@@ -0,0 +1,96 @@
+//! `commands` contains a parser for instructions left to the bot in pull request comments.
+
+/// Every command is expected to start with this mention, e.g. `@observatory recheck`.
+pub const MENTION: &str = "@observatory";
+
+/// Author associations which are allowed to control the bot in any pull request. Pull request authors can always
+/// control the bot in their own pulls.
+pub const MAINTAINER_ASSOCIATIONS: [&str; 3] = ["OWNER", "MEMBER", "COLLABORATOR"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    /// Re-run conflict detection for the pull request and update the comments.
+    Recheck,
+
+    /// Dismiss the conflict with another pull request, removing the corresponding comment.
+    /// The conflict is not reported again unless its file set changes.
+    IgnoreConflict(i32),
+
+    /// Reply with the list of conflicts the bot knows about.
+    Status,
+}
+
+impl Command {
+    /// Parse a single line of a comment. Unknown commands and lines without the mention are ignored.
+    pub fn from_line(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix(MENTION)?;
+        if !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+
+        let mut parts = rest.split_whitespace();
+        match parts.next()? {
+            "recheck" => Some(Self::Recheck),
+            "status" => Some(Self::Status),
+            "ignore-conflict" => {
+                let pull_number = parts.next()?;
+                let pull_number = pull_number.strip_prefix('#').unwrap_or(pull_number);
+                pull_number.parse().ok().map(Self::IgnoreConflict)
+            }
+            _ => None,
+        }
+    }
+
+    /// Extract all commands from a comment, one per line, in order of appearance.
+    pub fn parse_all(body: &str) -> Vec<Self> {
+        body.lines().filter_map(Self::from_line).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_single_commands() {
+        assert_eq!(
+            Command::from_line("@observatory recheck"),
+            Some(Command::Recheck)
+        );
+        assert_eq!(
+            Command::from_line("  @observatory status  "),
+            Some(Command::Status)
+        );
+        assert_eq!(
+            Command::from_line("@observatory ignore-conflict #12"),
+            Some(Command::IgnoreConflict(12))
+        );
+        assert_eq!(
+            Command::from_line("@observatory ignore-conflict 12"),
+            Some(Command::IgnoreConflict(12))
+        );
+    }
+
+    #[test]
+    fn parse_bad_commands() {
+        assert_eq!(Command::from_line("recheck"), None);
+        assert_eq!(Command::from_line("@observatoryrecheck"), None);
+        assert_eq!(Command::from_line("@observatory"), None);
+        assert_eq!(Command::from_line("@observatory dance"), None);
+        assert_eq!(Command::from_line("@observatory ignore-conflict"), None);
+        assert_eq!(
+            Command::from_line("@observatory ignore-conflict #abc"),
+            None
+        );
+        assert_eq!(Command::from_line("please @observatory recheck"), None);
+    }
+
+    #[test]
+    fn parse_all_commands() {
+        let body = "Thanks!\n@observatory ignore-conflict #3\n\n@observatory status\n";
+        assert_eq!(
+            Command::parse_all(body),
+            vec![Command::IgnoreConflict(3), Command::Status]
+        );
+    }
+}
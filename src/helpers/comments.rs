@@ -2,26 +2,129 @@
 /// as well as comment templates.
 use serde::{Deserialize, Serialize};
 
-use crate::helpers::conflicts::ConflictType;
+use crate::helpers::conflicts::{Conflict, ConflictType};
+use crate::helpers::digest::RequestValidator;
 use crate::helpers::ToMarkdown;
 
 /// Warn the author of a new pull request about someone else's unmerged work.
 pub const OVERLAP_TEMPLATE: &str = "Someone else has edited same files as you did. Please check their changes in case they conflict with yours:\n";
 
+/// Added to overlap warnings when both pulls change the same lines (see [`crate::helpers::conflicts::Severity::LineLevel`]).
+pub const LINE_LEVEL_OVERLAP_TEMPLATE: &str =
+    "**Note:** some of the changes touch the same lines, so the pull requests will likely conflict when merged.\n";
+
 /// Warn the author of a translation request about new changes in the original article.
 pub const INCOMPLETE_TRANSLATION_TEMPLATE: &str = "Your translation may be missing new information from corresponding English articles. Please update your translation according to the merged changes:\n";
 
+/// Let the author of an original change know about translations which depend on it.
+pub const TRANSLATIONS_IN_FLIGHT_TEMPLATE: &str = "Translations of the articles changed here are being worked on in other pull requests. Merging this one will require updating them:\n";
+
+/// Warn the author of a translation that the original articles were updated after they started working on it.
+pub const OUTDATED_TRANSLATION_TEMPLATE: &str = "The original articles were changed after this branch was created. Please check whether your translation needs to be updated:\n";
+
+/// List all conflicts of a pull request in a single comment (see [`crate::config::NotificationStyle::Digest`]).
+pub const DIGEST_TEMPLATE: &str =
+    "Other pull requests have changes which may conflict with yours. Please check them:\n";
+
+/// Added to conflict comments in approved pull requests (see [`crate::config::ApprovedStyle::Tag`]).
+pub const APPROVED_TEMPLATE: &str =
+    "**Note:** this pull request is already approved, so the conflict may be resolved at merge time.";
+
+/// Added to overlap comments when both pulls have the same author (see [`crate::config::SelfOverlapStyle::Soften`]).
+pub const SELF_OVERLAP_TEMPLATE: &str =
+    "**Note:** both pull requests are yours, so this is likely expected.";
+
+/// Followed by maintainers of the translation's language, in comments about incomplete translations (see `mentions` in
+/// [`crate::repo_config::RepoConfig`]).
+pub const MENTIONS_TEMPLATE: &str = "**cc:**";
+
+/// Followed by the latest merged changes to the originals, in comments about incomplete translations (see
+/// `notifications.article_history`).
+pub const ARTICLE_HISTORY_TEMPLATE: &str = "**Originals last changed:**";
+
+/// Added to digests of pulls which also have git conflicts (see `notifications.git_conflicts`).
+pub const GIT_CONFLICTS_TEMPLATE: &str =
+    "**Note:** this pull request also has merge conflicts with its base branch, which need to be resolved in git.";
+
+/// Opens conflict comments in pull requests of first-time contributors (see [`crate::repo_config::RepoConfig`]).
+pub const FIRST_TIME_CONTRIBUTOR_TEMPLATE: &str = "Thanks for your first contribution! This is an automated note: other pull requests change the same articles as yours, so some changes may need to be shared between them. Nothing needs to be done right away, and maintainers will help with anything that's unclear.";
+
+/// Introduces the list of guidelines for first-time contributors.
+pub const GUIDELINES_TEMPLATE: &str = "These guidelines may come in handy:";
+
+/// Reply to the `status` command when the pull request has no known conflicts.
+pub const STATUS_NO_CONFLICTS_TEMPLATE: &str =
+    "There are no known conflicts with other pull requests.";
+
+/// Reply to the `status` command, followed by the list of conflicts.
+pub const STATUS_TEMPLATE: &str = "Known conflicts with other pull requests:\n";
+
+/// Banner for comments about conflicts which no longer exist (see [`mark_resolved`]).
+pub const RESOLVED_TEMPLATE: &str = "**Resolved:** this conflict no longer applies.";
+
 pub const HTML_COMMENT_START: &str = "<!--";
 pub const HTML_COMMENT_END: &str = "-->";
 
+/// The format version of [`CommentHeader`] written by the app. Bump it when the format changes, and keep accepting the older
+/// formats on read: comments with outdated headers are rewritten the next time they are updated.
+pub const HEADER_VERSION: u32 = 1;
+
 /// Structured header for comments made by the bot, designed to avoid tedious and error-prone parsing.
-#[derive(Debug, Serialize, Deserialize, PartialEq, PartialOrd, Ord, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, PartialOrd, Ord, Eq)]
 pub struct CommentHeader {
+    /// Headers written before versioning was introduced don't have this field, and are read as version 0.
+    #[serde(default)]
+    pub version: u32,
+
     pub pull_number: i32,
+
+    /// The repository of `pull_number`, if it's not the one where the comment is posted (see `controller.repo_groups`).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+
     pub conflict_type: ConflictType,
+
+    /// Conflicts listed in a digest comment, so that it can be updated without relying on memory.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub conflicts: Vec<Conflict>,
+
+    /// HMAC of the other fields, which tells headers written by the app from edited or fake ones
+    /// (see `controller.signing_key` in the config).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
 }
 
 impl CommentHeader {
+    pub fn new(pull_number: i32, conflict_type: ConflictType) -> Self {
+        Self {
+            version: HEADER_VERSION,
+            pull_number,
+            repository: None,
+            conflict_type,
+            conflicts: Vec::new(),
+            signature: None,
+        }
+    }
+
+    fn signed_data(&self) -> String {
+        let unsigned = Self {
+            signature: None,
+            ..self.clone()
+        };
+        serde_yaml::to_string(&unsigned).unwrap()
+    }
+
+    fn has_valid_signature(&self, key: &RequestValidator) -> bool {
+        self.signature
+            .as_ref()
+            .is_some_and(|s| key.validate(&self.signed_data(), s).unwrap_or(false))
+    }
+
+    /// Whether the header was written in an older format, and the comment needs to be rewritten.
+    pub fn is_outdated(&self) -> bool {
+        self.version < HEADER_VERSION
+    }
+
     /// Attempt to extract the header from a Markdown comment.
     /// The header is expected to look like this, with HTML comment tags on separate lines:
     /// ```ignore
@@ -30,25 +133,142 @@ impl CommentHeader {
     ///   key2: value2
     /// -->
     /// ```
-    pub fn from_comment(body: &str) -> Option<Self> {
+    ///
+    /// With a `key`, headers without a valid signature are ignored (see [`sign_comment`]).
+    pub fn from_comment(body: &str, key: Option<&RequestValidator>) -> Option<Self> {
+        let header = Self::parse(body)?;
+        match key {
+            Some(key) if !header.has_valid_signature(key) => {
+                log::warn!(
+                    "Ignoring a comment header with a missing or invalid signature: {:?}",
+                    header
+                );
+                None
+            }
+            _ => Some(header),
+        }
+    }
+
+    fn parse(body: &str) -> Option<Self> {
         if !body.starts_with(HTML_COMMENT_START) {
             return None;
         }
         let mut lines = Vec::new();
         for line in body.split('\n') {
-            let line = line.trim();
-            if line.starts_with(HTML_COMMENT_START) {
+            let trimmed = line.trim();
+            if trimmed.starts_with(HTML_COMMENT_START) {
                 continue;
             }
-            if line.starts_with(HTML_COMMENT_END) {
+            if trimmed.starts_with(HTML_COMMENT_END) {
                 break;
             }
-            lines.push(line.to_string());
+            lines.push(line.trim_end());
         }
+
+        // Only strip the common indentation to keep nested values intact.
+        let indent = lines
+            .iter()
+            .filter(|l| !l.is_empty())
+            .map(|l| l.len() - l.trim_start().len())
+            .min()
+            .unwrap_or(0);
+        let lines: Vec<_> = lines
+            .iter()
+            .map(|l| l.get(indent..).unwrap_or_default())
+            .collect();
         serde_yaml::from_str(&lines.join("\n")).ok()
     }
 }
 
+/// Add a signature to the header of a comment, so that it can be read back with the same key. The signature
+/// covers the header only, since the rest of the comment is never parsed. Comments without a header are returned unchanged.
+pub fn sign_comment(body: &str, key: &RequestValidator) -> String {
+    let (Some(mut header), Some(pos)) = (CommentHeader::parse(body), body.find(HTML_COMMENT_END))
+    else {
+        return body.to_string();
+    };
+    header.signature = Some(key.sign(&header.signed_data()));
+    format!(
+        "{}{}",
+        header.to_markdown(),
+        &body[pos + HTML_COMMENT_END.len()..]
+    )
+}
+
+/// Add a banner to a bot comment and strike the rest of its contents through, keeping the header intact.
+/// Comments which are already marked as resolved are returned unchanged.
+pub fn mark_resolved(body: &str) -> String {
+    if body.contains(RESOLVED_TEMPLATE) {
+        return body.to_string();
+    }
+
+    let (header, contents) = match body.find(HTML_COMMENT_END) {
+        Some(pos) if body.starts_with(HTML_COMMENT_START) => {
+            let end = pos + HTML_COMMENT_END.len();
+            (&body[..end], body[end..].trim_start_matches('\n'))
+        }
+        _ => ("", body),
+    };
+
+    let mut lines = Vec::new();
+    if !header.is_empty() {
+        lines.push(header.to_string());
+    }
+    lines.push(RESOLVED_TEMPLATE.to_string());
+    for line in contents.lines() {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+        if trimmed.is_empty() {
+            lines.push(line.to_string());
+        } else if let Some(item) = trimmed.strip_prefix("- ") {
+            lines.push(format!("{indent}- ~~{item}~~"));
+        } else {
+            lines.push(format!("{indent}~~{trimmed}~~"));
+        }
+    }
+    lines.join("\n")
+}
+
+/// Make the note for first-time contributors, with links to the repository's guidelines, if there are any.
+pub fn welcome(guidelines: &[String]) -> String {
+    if guidelines.is_empty() {
+        return FIRST_TIME_CONTRIBUTOR_TEMPLATE.to_string();
+    }
+    let links: Vec<_> = guidelines.iter().map(|g| format!("- {}", g)).collect();
+    format!(
+        "{}\n\n{}\n{}",
+        FIRST_TIME_CONTRIBUTOR_TEMPLATE,
+        GUIDELINES_TEMPLATE,
+        links.join("\n")
+    )
+}
+
+/// Put a paragraph before the contents of a bot comment, right after its header.
+pub fn with_preamble(body: &str, preamble: &str) -> String {
+    match body.find(HTML_COMMENT_END) {
+        Some(pos) if body.starts_with(HTML_COMMENT_START) => {
+            let end = pos + HTML_COMMENT_END.len();
+            format!(
+                "{}\n{}\n\n{}",
+                &body[..end],
+                preamble,
+                body[end..].trim_start_matches('\n')
+            )
+        }
+        _ => format!("{}\n\n{}", preamble, body),
+    }
+}
+
+/// Render a comment as HTML, roughly the way GitHub shows it. The header stays an HTML comment, and is not visible either.
+pub fn to_html(markdown: &str) -> String {
+    let mut html = String::new();
+    pulldown_cmark::html::push_html(
+        &mut html,
+        pulldown_cmark::Parser::new_ext(markdown, pulldown_cmark::Options::ENABLE_STRIKETHROUGH),
+    );
+    html
+}
+
 impl ToMarkdown for CommentHeader {
     fn to_markdown(&self) -> String {
         format!(
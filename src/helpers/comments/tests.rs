@@ -2,13 +2,11 @@ use super::*;
 
 #[test]
 fn to_markdown() {
-    let hdr = CommentHeader {
-        pull_number: 12,
-        conflict_type: ConflictType::Overlap,
-    };
+    let hdr = CommentHeader::new(12, ConflictType::Overlap);
     assert_eq!(
         hdr.to_markdown(),
         r#"<!--
+version: 1
 pull_number: 12
 conflict_type: Overlap
 -->"#
@@ -18,21 +16,21 @@ conflict_type: Overlap
 #[test]
 fn from_comment_without_header() {
     let comment = "test comment";
-    assert_eq!(CommentHeader::from_comment(comment), None);
+    assert_eq!(CommentHeader::from_comment(comment, None), None);
 }
 
 #[test]
 fn from_comment_with_bad_header() {
     let c1 = r#"<!--
 test comment"#;
-    assert_eq!(CommentHeader::from_comment(c1), None);
+    assert_eq!(CommentHeader::from_comment(c1, None), None);
 
     let c2 = r#"<!--
 pull_number: 12
 some shit
 conflict_type: Overlap
 "#;
-    assert_eq!(CommentHeader::from_comment(c2), None);
+    assert_eq!(CommentHeader::from_comment(c2, None), None);
 }
 
 #[test]
@@ -43,10 +41,160 @@ conflict_type: Overlap
 -->
 Some text here."#;
     assert_eq!(
-        CommentHeader::from_comment(comment),
+        CommentHeader::from_comment(comment, None),
         Some(CommentHeader {
+            version: 0,
             pull_number: 12,
-            conflict_type: ConflictType::Overlap
+            repository: None,
+            conflict_type: ConflictType::Overlap,
+            conflicts: Vec::new(),
+            signature: None,
         })
     );
 }
+
+#[test]
+fn from_comment_indented() {
+    let comment = r#"<!--
+  pull_number: 12
+  conflict_type: Overlap
+-->"#;
+    assert_eq!(
+        CommentHeader::from_comment(comment, None),
+        Some(CommentHeader {
+            version: 0,
+            pull_number: 12,
+            repository: None,
+            conflict_type: ConflictType::Overlap,
+            conflicts: Vec::new(),
+            signature: None,
+        })
+    );
+}
+
+#[test]
+fn from_comment_with_conflicts() {
+    let hdr = CommentHeader {
+        conflicts: vec![Conflict::overlap(
+            3,
+            1,
+            "https://github.com/test/repo/pull/1".to_string(),
+            vec!["wiki/Article/en.md".to_string()],
+        )],
+        ..CommentHeader::new(3, ConflictType::Digest)
+    };
+    let comment = format!("{}\nSome text here.", hdr.to_markdown());
+    assert_eq!(CommentHeader::from_comment(&comment, None), Some(hdr));
+}
+
+#[test]
+fn from_comment_legacy() {
+    let comment = r#"<!--
+pull_number: 12
+conflict_type: ExistingChange
+-->
+Some text here."#;
+    let hdr = CommentHeader::from_comment(comment, None).unwrap();
+    assert_eq!(hdr.conflict_type, ConflictType::Overlap);
+    assert!(hdr.is_outdated());
+
+    let hdr = CommentHeader::new(12, ConflictType::Overlap);
+    let restored = CommentHeader::from_comment(&hdr.to_markdown(), None).unwrap();
+    assert!(!restored.is_outdated());
+    assert_eq!(restored, hdr);
+}
+
+#[test]
+fn from_comment_signed() {
+    let key = RequestValidator::new("iseedeadpeople".to_string());
+    let other_key = RequestValidator::new("not-the-key".to_string());
+    let body = format!(
+        "{}\nSome text here.",
+        CommentHeader::new(12, ConflictType::Overlap).to_markdown()
+    );
+    let signed = sign_comment(&body, &key);
+    assert!(signed.ends_with("\nSome text here."));
+    assert_eq!(sign_comment(&signed, &key), signed);
+
+    let hdr = CommentHeader::from_comment(&signed, Some(&key)).unwrap();
+    assert_eq!(hdr.pull_number, 12);
+    assert!(hdr.signature.is_some());
+    assert_eq!(
+        CommentHeader::from_comment(&mark_resolved(&signed), Some(&key)),
+        Some(hdr)
+    );
+
+    assert_eq!(CommentHeader::from_comment(&body, Some(&key)), None);
+    assert_eq!(CommentHeader::from_comment(&signed, Some(&other_key)), None);
+    let tampered = signed.replace("pull_number: 12", "pull_number: 13");
+    assert_eq!(CommentHeader::from_comment(&tampered, Some(&key)), None);
+    assert_eq!(sign_comment("just a comment", &key), "just a comment");
+}
+
+#[test]
+fn mark_resolved_keeps_header() {
+    let comment = r#"<!--
+pull_number: 12
+conflict_type: Overlap
+-->
+Some text here.
+- https://github.com/test/repo/pull/12, files:
+  - [`wiki/Article/en.md`](https://github.com/test/repo/pull/12/files)"#;
+    let resolved = mark_resolved(comment);
+    assert_eq!(
+        resolved,
+        format!(
+            r#"<!--
+pull_number: 12
+conflict_type: Overlap
+-->
+{}
+~~Some text here.~~
+- ~~https://github.com/test/repo/pull/12, files:~~
+  - ~~[`wiki/Article/en.md`](https://github.com/test/repo/pull/12/files)~~"#,
+            RESOLVED_TEMPLATE
+        )
+    );
+    assert_eq!(
+        CommentHeader::from_comment(&resolved, None),
+        CommentHeader::from_comment(comment, None)
+    );
+    assert_eq!(mark_resolved(&resolved), resolved);
+}
+
+#[test]
+fn preamble_goes_after_header() {
+    let comment = r#"<!--
+pull_number: 12
+conflict_type: Overlap
+-->
+Some text here."#;
+    let welcomed = with_preamble(
+        comment,
+        &welcome(&["https://example.com/guidelines".to_string()]),
+    );
+    assert_eq!(
+        welcomed,
+        format!(
+            r#"<!--
+pull_number: 12
+conflict_type: Overlap
+-->
+{}
+
+{}
+- https://example.com/guidelines
+
+Some text here."#,
+            FIRST_TIME_CONTRIBUTOR_TEMPLATE, GUIDELINES_TEMPLATE
+        )
+    );
+    assert_eq!(
+        CommentHeader::from_comment(&welcomed, None),
+        CommentHeader::from_comment(comment, None)
+    );
+    assert_eq!(
+        with_preamble("Some text here.", "Hello!"),
+        "Hello!\n\nSome text here."
+    );
+}
@@ -1,13 +1,13 @@
 /// `pulls` contains structures and helpers for detecting conflicts between two pull requests.
 use std::cmp::{PartialEq, PartialOrd};
 use std::collections::hash_map::Entry;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
 
 use crate::helpers::ToMarkdown;
-use crate::helpers::{comments, digest};
+use crate::helpers::{comments, diff, digest};
 use crate::structs;
 
 /// Types of pull conflicts
@@ -15,11 +15,24 @@ use crate::structs;
 pub enum ConflictType {
     /// Two pull requests have common file(s).
     /// Target = new pull, reference = old pull.
+    #[serde(alias = "ExistingChange")]
     Overlap,
 
     /// A merged pull request affects an article for which there's a translation open.
     /// Target = old pull (translation), reference = merged pull (original).
     IncompleteTranslation,
+
+    /// Not a conflict by itself, but a summary of open translations depending on an original pull request.
+    /// Target = reference = original pull.
+    TranslationsInFlight,
+
+    /// Not a conflict by itself, but a single comment listing all conflicts of a pull request.
+    /// Target = reference = pull with conflicts.
+    Digest,
+
+    /// Not a conflict by itself, but a notice that original articles were changed after a translation was branched off.
+    /// Target = reference = translation pull.
+    OutdatedTranslation,
 }
 
 impl ToMarkdown for ConflictType {
@@ -27,13 +40,30 @@ impl ToMarkdown for ConflictType {
         match self {
             ConflictType::Overlap => comments::OVERLAP_TEMPLATE,
             ConflictType::IncompleteTranslation => comments::INCOMPLETE_TRANSLATION_TEMPLATE,
+            ConflictType::TranslationsInFlight => comments::TRANSLATIONS_IN_FLIGHT_TEMPLATE,
+            ConflictType::Digest => comments::DIGEST_TEMPLATE,
+            ConflictType::OutdatedTranslation => comments::OUTDATED_TRANSLATION_TEMPLATE,
         }
         .to_string()
     }
 }
 
+/// How closely two overlapping pull requests interfere with each other.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "kebab-case")]
+pub enum Severity {
+    /// Same files are changed, but in different places (or the places are not known).
+    #[default]
+    FileLevel,
+
+    /// Some hunks of the same file overlap.
+    LineLevel,
+}
+
 /// A structure containing information about a conflict between two pull requests.
-#[derive(Debug, Ord, Eq, PartialEq, PartialOrd, Clone)]
+#[derive(Debug, Ord, Eq, PartialEq, PartialOrd, Clone, Serialize, Deserialize)]
 pub struct Conflict {
     /// Type of conflict.
     pub kind: ConflictType,
@@ -48,8 +78,53 @@ pub struct Conflict {
     /// A GitHub URL to the "original" pull request.
     pub reference_url: String,
 
-    /// List of conflicting files. May contain both translations and originals, but articles (= directories) are guaranteed to be unique.
+    /// List of conflicting files, sorted and without duplicates. May contain both translations and originals; overlaps may list
+    /// several translations of one article.
     pub file_set: Vec<String>,
+
+    /// Whether someone has asked the bot to stop reporting the conflict. Reset once the file set changes.
+    #[serde(skip)]
+    pub dismissed: bool,
+
+    /// Whether the comment about the conflict failed to be written, and is waiting for another attempt (see [`crate::retries`]).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub needs_sync: bool,
+
+    /// How serious an overlap is. Other conflict types are always [`Severity::FileLevel`].
+    #[serde(default)]
+    pub severity: Severity,
+
+    /// For line-level overlaps: file -> line ranges changed by both pulls (`[start, end)`, in the base version).
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub line_ranges: structs::LineRanges,
+
+    /// The day when the conflict was first detected (UTC). Days are precise enough for the comments, and keep rendered
+    /// conflicts stable between checks. Missing in conflicts stored or posted by older versions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<chrono::NaiveDate>,
+
+    /// The day when the file set or severity of the conflict last changed (UTC).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub updated_at: Option<chrono::NaiveDate>,
+
+    /// For overlaps: the pull request which is suggested to be merged first (see [`suggest_merge_order`]). Defaults to the
+    /// original pull, which is assumed to have higher priority.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub merge_first: Option<i32>,
+
+    /// For overlaps: whether both pulls were opened by the same person, who likely knows about the overlap already
+    /// (see [`crate::config::SelfOverlapStyle`]).
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub same_author: bool,
+
+    /// The repository of the original pull, if it's not the one where the conflict is stored, which is always the trigger's
+    /// (see [`compare_pulls_across`]).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub original_repo: Option<String>,
+}
+
+fn today() -> chrono::NaiveDate {
+    chrono::Utc::now().date_naive()
 }
 
 impl Conflict {
@@ -65,6 +140,15 @@ impl Conflict {
             original,
             reference_url,
             file_set,
+            dismissed: false,
+            needs_sync: false,
+            severity: Severity::FileLevel,
+            line_ranges: BTreeMap::new(),
+            created_at: Some(today()),
+            updated_at: Some(today()),
+            merge_first: Some(original),
+            same_author: false,
+            original_repo: None,
         }
     }
     pub fn incomplete_translation(
@@ -79,61 +163,419 @@ impl Conflict {
             original,
             reference_url,
             file_set,
+            dismissed: false,
+            needs_sync: false,
+            severity: Severity::FileLevel,
+            line_ranges: BTreeMap::new(),
+            created_at: Some(today()),
+            updated_at: Some(today()),
+            merge_first: None,
+            same_author: false,
+            original_repo: None,
+        }
+    }
+}
+
+impl Conflict {
+    /// Mark an overlap as line-level, with the line ranges changed by both pulls.
+    pub fn with_line_ranges(mut self, line_ranges: structs::LineRanges) -> Self {
+        if !line_ranges.is_empty() {
+            self.severity = Severity::LineLevel;
+        }
+        self.line_ranges = line_ranges;
+        self
+    }
+
+    /// Suggest which of the two pulls to merge first. Only makes sense for overlaps.
+    pub fn with_merge_first(mut self, pull_number: i32) -> Self {
+        self.merge_first = Some(pull_number);
+        self
+    }
+
+    /// Point the conflict to an original pull from another repository.
+    pub fn with_original_repo(mut self, original_repo: Option<String>) -> Self {
+        self.original_repo = original_repo;
+        self
+    }
+
+    /// Mark an overlap between two pulls of the same author.
+    pub fn with_same_author(mut self, same_author: bool) -> Self {
+        self.same_author = same_author;
+        self
+    }
+
+    /// Restore a conflict from a comment posted to its trigger pull, which is the reverse of [`ToMarkdown::to_markdown`].
+    /// Line ranges and the update date are not restored, and comments listing more than 10 files yield an empty file set.
+    pub fn from_comment(
+        trigger: i32,
+        body: &str,
+        key: Option<&digest::RequestValidator>,
+    ) -> Option<Self> {
+        let header = comments::CommentHeader::from_comment(body, key)?;
+        if !matches!(
+            header.conflict_type,
+            ConflictType::Overlap | ConflictType::IncompleteTranslation
+        ) {
+            return None;
         }
+
+        let mut reference_url = None;
+        let mut created_at = None;
+        let mut file_set = Vec::new();
+        let mut merge_first = None;
+        for line in body.lines() {
+            if let Some(order) = line.strip_prefix(MERGE_ORDER) {
+                merge_first = order
+                    .strip_prefix('#')
+                    .and_then(|rest| rest.split(',').next())
+                    .and_then(|number| number.parse().ok());
+                continue;
+            }
+            let Some(item) = line.trim_start().strip_prefix("- ") else {
+                continue;
+            };
+            if let Some((file, _)) = item
+                .strip_prefix("[`")
+                .and_then(|rest| rest.split_once("`]"))
+            {
+                file_set.push(file.to_string());
+            } else if reference_url.is_none() {
+                let url = item.split([',', ' ']).next().unwrap_or_default();
+                reference_url = Some(url.to_string());
+                created_at = item
+                    .split_once(CONFLICTING_SINCE)
+                    .and_then(|(_, rest)| rest.get(..10))
+                    .and_then(|date| date.parse().ok());
+            }
+        }
+
+        Some(Self {
+            kind: header.conflict_type,
+            trigger,
+            original: header.pull_number,
+            reference_url: reference_url?,
+            file_set,
+            dismissed: false,
+            needs_sync: false,
+            severity: Severity::FileLevel,
+            line_ranges: BTreeMap::new(),
+            created_at,
+            updated_at: created_at,
+            merge_first,
+            same_author: false,
+            original_repo: header.repository,
+        })
     }
 }
 
+/// Precedes the date when a conflict was first detected, next to the link to the other pull.
+const CONFLICTING_SINCE: &str = "conflicting since ";
+
+/// Precedes the merge order suggested for an overlap, below the list of files.
+const MERGE_ORDER: &str = "Suggested merge order: ";
+
+/// How many days of waiting an extra changed article is worth when suggesting the merge order.
+const DAYS_PER_ARTICLE: i64 = 7;
+
+/// Suggest which of two overlapping pull requests should be merged first, so that the other one is rebased on top of it.
+///
+/// Pulls covering more articles go first, since they are harder to rebase, and so do the ones waiting for longer: every extra
+/// article counts as [`DAYS_PER_ARTICLE`] days of waiting. Ties go to the older pull.
+pub fn suggest_merge_order(
+    a: &structs::PullRequest,
+    a_articles: usize,
+    b: &structs::PullRequest,
+    b_articles: usize,
+) -> i32 {
+    let score = |pull: &structs::PullRequest, articles: usize| {
+        articles as i64 * DAYS_PER_ARTICLE - pull.created_at.timestamp().div_euclid(86400)
+    };
+    let key = |pull: &structs::PullRequest, articles: usize| {
+        (
+            std::cmp::Reverse(score(pull, articles)),
+            pull.created_at,
+            pull.number,
+        )
+    };
+    if key(a, a_articles) <= key(b, b_articles) {
+        a.number
+    } else {
+        b.number
+    }
+}
+
+/// Format line ranges for humans, e.g. `lines 120–160, 200`.
+fn format_line_ranges(ranges: &[(usize, usize)]) -> String {
+    let parts: Vec<_> = ranges
+        .iter()
+        .map(|(start, end)| {
+            if end - start == 1 {
+                start.to_string()
+            } else {
+                format!("{}–{}", start, end - 1)
+            }
+        })
+        .collect();
+    let noun = if parts.len() == 1 && ranges[0].1 - ranges[0].0 == 1 {
+        "line"
+    } else {
+        "lines"
+    };
+    format!("{} {}", noun, parts.join(", "))
+}
+
 impl ToMarkdown for Conflict {
     fn to_markdown(&self) -> String {
         let header = comments::CommentHeader {
-            pull_number: self.original,
-            conflict_type: self.kind.clone(),
+            repository: self.original_repo.clone(),
+            ..comments::CommentHeader::new(self.original, self.kind.clone())
         };
         let mut lines = Vec::new();
         lines.push(header.to_markdown());
         lines.push(self.kind.to_markdown());
+        if self.severity == Severity::LineLevel {
+            lines.push(comments::LINE_LEVEL_OVERLAP_TEMPLATE.to_string());
+        }
 
+        let since = self
+            .created_at
+            .map(|d| format!("{}{}", CONFLICTING_SINCE, d.format("%Y-%m-%d")));
         if self.file_set.len() > 10 {
-            lines.push(format!("- {} (>10 files)", self.reference_url));
+            let notes: Vec<_> = [Some(">10 files".to_string()), since]
+                .into_iter()
+                .flatten()
+                .collect();
+            lines.push(format!("- {} ({})", self.reference_url, notes.join(", ")));
         } else {
-            lines.push(format!("- {}, files:", self.reference_url));
+            match since {
+                Some(since) => lines.push(format!("- {} ({}), files:", self.reference_url, since)),
+                None => lines.push(format!("- {}, files:", self.reference_url)),
+            }
             let indent = "  ";
             for file in &self.file_set {
                 let file_name_hash = digest::hash_data(&ring::digest::SHA256, file.as_bytes());
                 let file_link = format!("{}/files#diff-{}", self.reference_url, file_name_hash);
-                lines.push(format!("{indent}- [`{file}`]({file_link})"));
+                match self.line_ranges.get(file) {
+                    Some(ranges) if !ranges.is_empty() => lines.push(format!(
+                        "{indent}- [`{file}`]({file_link}): {}",
+                        format_line_ranges(ranges)
+                    )),
+                    _ => lines.push(format!("{indent}- [`{file}`]({file_link})")),
+                }
             }
         }
 
+        if let Some(first) = self.merge_first {
+            let second = if first == self.original {
+                self.trigger
+            } else {
+                self.original
+            };
+            lines.push(String::new());
+            lines.push(format!(
+                "{MERGE_ORDER}#{first}, then #{second} (likely rebase needed after #{first} merges)."
+            ));
+        }
+
         lines.join("\n")
     }
 }
 
+/// A list of open translations which depend on changes in an original pull request.
+/// Its author is made aware of them, since merging the original will make these translations incomplete.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct TranslationsInFlight {
+    /// The pull request with original articles, which receives the summary.
+    pub original: i32,
+
+    /// URLs of dependent translation pull requests, each with the list of affected original files.
+    pub translations: Vec<(String, Vec<String>)>,
+}
+
+impl ToMarkdown for TranslationsInFlight {
+    fn to_markdown(&self) -> String {
+        let header =
+            comments::CommentHeader::new(self.original, ConflictType::TranslationsInFlight);
+        let mut lines = Vec::new();
+        lines.push(header.to_markdown());
+        lines.push(ConflictType::TranslationsInFlight.to_markdown());
+        for (url, file_set) in &self.translations {
+            let files: Vec<_> = file_set.iter().map(|f| format!("`{f}`")).collect();
+            lines.push(format!("- {}, articles: {}", url, files.join(", ")));
+        }
+        lines.join("\n")
+    }
+}
+
+/// Original articles which were changed in the base branch after a translation pull request was branched off.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct OutdatedTranslation {
+    /// The translation pull request, which receives the notice.
+    pub pull_number: i32,
+
+    /// Link to the comparison between the pull request and its base branch.
+    pub compare_url: String,
+
+    /// Changed original files.
+    pub files: Vec<String>,
+}
+
+impl ToMarkdown for OutdatedTranslation {
+    fn to_markdown(&self) -> String {
+        let header =
+            comments::CommentHeader::new(self.pull_number, ConflictType::OutdatedTranslation);
+        let mut lines = vec![
+            header.to_markdown(),
+            ConflictType::OutdatedTranslation.to_markdown(),
+        ];
+        for f in &self.files {
+            lines.push(format!("- `{f}`"));
+        }
+        lines.push(String::new());
+        lines.push(format!("Compare: {}", self.compare_url));
+        lines.join("\n")
+    }
+}
+
+/// All conflicts of a single pull request, rendered as one comment with a table.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Digest {
+    /// The pull request which receives the digest.
+    pub pull_number: i32,
+
+    /// Conflicts where the pull request is the trigger.
+    pub conflicts: Vec<Conflict>,
+}
+
+impl ToMarkdown for Digest {
+    fn to_markdown(&self) -> String {
+        let header = comments::CommentHeader {
+            conflicts: self.conflicts.clone(),
+            ..comments::CommentHeader::new(self.pull_number, ConflictType::Digest)
+        };
+        let mut lines = vec![
+            header.to_markdown(),
+            ConflictType::Digest.to_markdown(),
+            "| Pull request | Type | Files |".to_string(),
+            "| :-- | :-- | :-- |".to_string(),
+        ];
+        for c in &self.conflicts {
+            let files = if c.file_set.len() > 10 {
+                format!("{} files", c.file_set.len())
+            } else {
+                c.file_set
+                    .iter()
+                    .map(|f| format!("`{f}`"))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            };
+            lines.push(format!(
+                "| {} | {:?} | {} |",
+                c.reference_url, c.kind, files
+            ));
+        }
+        lines.join("\n")
+    }
+}
+
+/// How translations of an article are stored next to the original, selectable per repository (see [`crate::repo_config`]).
+#[derive(Serialize, Deserialize, Debug, PartialEq, Eq, Clone, Copy, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArticleLayout {
+    /// A folder per article, with a file per language: `wiki/Article/en.md`, `wiki/Article/ru.md` (osu! wiki).
+    #[default]
+    Folder,
+
+    /// Language suffixes in file names: `docs/Article.md` for the original, `docs/Article.ru.md` for translations.
+    Suffix,
+}
+
+impl ArticleLayout {
+    /// Recognize an article by its file path. Markdown files which are not articles (such as the tournament template,
+    /// https://github.com/TicClick/observatory/issues/17) are skipped.
+    pub fn article(&self, file_path: &str) -> Option<Article> {
+        let fp = std::path::Path::new(file_path);
+        let parent = fp.parent()?.to_str()?;
+        let stem = fp.file_name()?.to_str()?.strip_suffix(".md")?;
+        let (path, language) = match self {
+            Self::Folder => {
+                if !is_language_code(stem) {
+                    return None;
+                }
+                (parent.to_owned(), stem.to_owned())
+            }
+            Self::Suffix => {
+                let (name, language) = match stem.rsplit_once('.') {
+                    Some((name, language)) if is_language_code(language) => (name, language),
+                    _ => (stem, ORIGINAL_LANGUAGE),
+                };
+                let path = if parent.is_empty() {
+                    name.to_owned()
+                } else {
+                    format!("{parent}/{name}")
+                };
+                (path, language.to_owned())
+            }
+        };
+        Some(Article {
+            path,
+            language,
+            layout: *self,
+        })
+    }
+}
+
+const ORIGINAL_LANGUAGE: &str = "en";
+
+/// Whether a file name (without extension) looks like a language code, such as `ru` or `pt-br`.
+fn is_language_code(s: &str) -> bool {
+    let is_code = |part: &str| part.len() == 2 && part.chars().all(|c| c.is_ascii_alphabetic());
+    match s.split_once('-') {
+        Some((language, region)) => is_code(language) && is_code(region),
+        None => is_code(s),
+    }
+}
+
 /// A lightweight article wrapper, made for ease of file path comparison.
 #[derive(Debug)]
 pub struct Article {
+    /// The part of the path shared by the original and its translations.
     pub path: String,
     pub language: String,
+    layout: ArticleLayout,
 }
 
 impl Article {
+    /// Read an article in the folder layout. Any file name is taken for a language.
     pub fn from_file_path(s: &str) -> Self {
         let fp = std::path::Path::new(s);
         let language = fp.file_stem().unwrap().to_str().unwrap().to_owned();
         let path = fp.parent().unwrap().to_str().unwrap().to_owned();
-        Self { path, language }
+        Self {
+            path,
+            language,
+            layout: ArticleLayout::Folder,
+        }
+    }
+
+    fn file_path_for(&self, language: &str) -> String {
+        match self.layout {
+            ArticleLayout::Folder => format!("{}/{}.md", self.path, language),
+            ArticleLayout::Suffix if language == ORIGINAL_LANGUAGE => format!("{}.md", self.path),
+            ArticleLayout::Suffix => format!("{}.{}.md", self.path, language),
+        }
     }
 
     pub fn original_file_path(&self) -> String {
-        format!("{}/en.md", self.path)
+        self.file_path_for(ORIGINAL_LANGUAGE)
     }
 
     pub fn file_path(&self) -> String {
-        format!("{}/{}.md", self.path, self.language)
+        self.file_path_for(&self.language)
     }
 
     pub fn is_original(&self) -> bool {
-        self.language == "en"
+        self.language == ORIGINAL_LANGUAGE
     }
 
     pub fn is_translation(&self) -> bool {
@@ -147,45 +589,131 @@ impl std::cmp::PartialEq for Article {
     }
 }
 
+/// Find line ranges of a file changed by both pulls (under its path in each of them). Without known line ranges, there are none.
+fn overlapping_lines(
+    first: &structs::PullRequest,
+    first_path: &str,
+    second: &structs::PullRequest,
+    second_path: &str,
+) -> Vec<(usize, usize)> {
+    let ranges = |p: &structs::PullRequest, path: &str| {
+        p.diff
+            .as_ref()
+            .and_then(|d| d.hunks(path))
+            .map(|h| h.to_vec())
+            .unwrap_or_default()
+    };
+    let (first, second) = (ranges(first, first_path), ranges(second, second_path));
+    let mut out: Vec<_> = first
+        .iter()
+        .flat_map(|(a_start, a_end)| {
+            second
+                .iter()
+                .filter(move |(b_start, b_end)| a_start < b_end && b_start < a_end)
+                .map(move |(b_start, b_end)| (*a_start.max(b_start), *a_end.min(b_end)))
+        })
+        .collect();
+    out.sort();
+    out.dedup();
+    out
+}
+
 /// Compare two pulls and pinpoint different types of conflicts between them on article level.
+///
+/// Pulls targeting different base branches never conflict.
 pub fn compare_pulls(
     new_pull: &structs::PullRequest,
     other_pull: &structs::PullRequest,
 ) -> Vec<Conflict> {
-    let new_diff = new_pull.diff.as_ref().unwrap();
-    let other_diff = other_pull.diff.as_ref().unwrap();
+    compare_pulls_with(
+        new_pull,
+        other_pull,
+        &diff::Pipeline::new(),
+        ArticleLayout::Folder,
+    )
+}
+
+/// Same as [`compare_pulls`], but files are compared by paths from `filter`, and skipped if it says so
+/// (see [`crate::repo_config::RepoConfig::path_filters`]). Conflicts list files under their paths in the pulls.
+pub fn compare_pulls_with(
+    new_pull: &structs::PullRequest,
+    other_pull: &structs::PullRequest,
+    filter: &dyn diff::PathFilter,
+    layout: ArticleLayout,
+) -> Vec<Conflict> {
+    if new_pull.base != other_pull.base {
+        return Vec::new();
+    }
+    compare_articles(new_pull, other_pull, filter, layout, None)
+}
+
+/// Same as [`compare_pulls_with`], but for pulls from different repositories of a group (see `controller.repo_groups`), such as
+/// `wiki-en` with the originals and `wiki-ru` with translations. Base branches are not compared, since every repository has
+/// its own.
+///
+/// Every conflict has [`Conflict::original_repo`] set, and is meant to be stored in the other repository (the trigger's).
+pub fn compare_pulls_across(
+    new_repo: &str,
+    new_pull: &structs::PullRequest,
+    other_repo: &str,
+    other_pull: &structs::PullRequest,
+    filter: &dyn diff::PathFilter,
+    layout: ArticleLayout,
+) -> Vec<Conflict> {
+    compare_articles(
+        new_pull,
+        other_pull,
+        filter,
+        layout,
+        Some((new_repo, other_repo)),
+    )
+}
+
+/// The comparison behind [`compare_pulls_with`] and [`compare_pulls_across`]. `repos` are the repositories of the new and
+/// the other pull, if they differ.
+fn compare_articles(
+    new_pull: &structs::PullRequest,
+    other_pull: &structs::PullRequest,
+    filter: &dyn diff::PathFilter,
+    layout: ArticleLayout,
+    repos: Option<(&str, &str)>,
+) -> Vec<Conflict> {
+    let (new_repo, other_repo) = match repos {
+        Some((new_repo, other_repo)) => (Some(new_repo.to_string()), Some(other_repo.to_string())),
+        None => (None, None),
+    };
 
     let mut overlaps = BTreeSet::new();
     let mut originals = BTreeSet::new();
+    let mut line_ranges = structs::LineRanges::new();
 
     let mut is_new_translation = false;
 
-    // Only consider Markdown files, and among these, exclude non-articles such as the tournament template
-    // (https://github.com/TicClick/observatory/issues/17)
-    let article_expr = regex::Regex::new(r"^(..|..-..)\.md$").unwrap();
-    let article_filter = |p: &&unidiff::PatchedFile| -> bool {
-        let parts = std::path::Path::new(&p.target_file);
-        if let Some(filename) = parts.file_name() {
-            if let Some(filename_cstr) = filename.to_str() {
-                article_expr.is_match(filename_cstr)
-            } else {
-                false
-            }
-        } else {
-            false
-        }
+    // Only consider articles which remain after the merge.
+    let is_article = |f: &diff::ChangedFile| -> bool {
+        f.target_path().is_some_and(|p| layout.article(p).is_some())
     };
-
-    for incoming in new_diff.files().iter().filter(article_filter) {
-        let other_files: BTreeSet<_> = other_diff
-            .files()
+    // Comparison key -> path in the pull.
+    let articles = |p: &structs::PullRequest| -> BTreeMap<String, String> {
+        p.diff
+            .as_ref()
+            .unwrap()
+            .files
             .iter()
-            .filter(article_filter)
-            .map(|patched| patched.path())
-            .collect();
-        for other in other_files.iter() {
-            let new_article = Article::from_file_path(&incoming.path());
-            let other_article = Article::from_file_path(other);
+            .filter(|f| is_article(f))
+            .filter_map(|f| filter.apply(&f.path).map(|key| (key, f.path.clone())))
+            .collect()
+    };
+
+    let new_files = articles(new_pull);
+    let other_files = articles(other_pull);
+    for (incoming_key, incoming) in new_files.iter() {
+        for (other_key, other) in other_files.iter() {
+            let (Some(new_article), Some(other_article)) =
+                (layout.article(incoming_key), layout.article(other_key))
+            else {
+                continue;
+            };
 
             // Different folders.
             if new_article.path != other_article.path {
@@ -193,21 +721,28 @@ pub fn compare_pulls(
             }
 
             // Protect against duplicate conflicts when an original change also marks translations as outdated:
-            // [EN (meaningful update), RU (outdate translation)] vs [RU (translation update)] produces only one conflict (IncompleteTranslation).
+            // [EN (meaningful update), RU (outdate translation)] vs [RU (translation update)] produces only one conflict (IncompleteTranslation),
+            // regardless of which of the pulls is the new one.
+            let original_file_path = new_article.original_file_path();
             let translation_only_change = new_article.is_translation()
-                && !other_files.contains(&new_article.original_file_path());
+                && !other_files.contains_key(&original_file_path)
+                && !new_files.contains_key(&original_file_path);
 
             if new_article == other_article
                 && (new_article.is_original() || translation_only_change)
             {
-                overlaps.insert(new_article.file_path());
+                let common_lines = overlapping_lines(new_pull, incoming, other_pull, other);
+                if !common_lines.is_empty() {
+                    line_ranges.insert(incoming.clone(), common_lines);
+                }
+                overlaps.insert(incoming.clone());
                 continue;
             }
 
             if new_article.is_original() && other_article.is_translation() {
-                originals.insert(new_article.file_path());
+                originals.insert(incoming.clone());
             } else if other_article.is_original() && new_article.is_translation() {
-                originals.insert(other_article.file_path());
+                originals.insert(other.clone());
                 is_new_translation = true;
             }
         }
@@ -215,46 +750,74 @@ pub fn compare_pulls(
 
     let mut out = Vec::new();
     if !overlaps.is_empty() {
-        out.push(Conflict::overlap(
-            new_pull.number,
-            other_pull.number,
-            other_pull.html_url.clone(),
-            overlaps.into_iter().collect(),
-        ));
+        let merge_first =
+            suggest_merge_order(new_pull, new_files.len(), other_pull, other_files.len());
+        out.push(
+            Conflict::overlap(
+                new_pull.number,
+                other_pull.number,
+                other_pull.html_url.clone(),
+                overlaps.into_iter().collect(),
+            )
+            .with_line_ranges(line_ranges)
+            .with_merge_first(merge_first)
+            .with_same_author(new_pull.user.id == other_pull.user.id)
+            .with_original_repo(other_repo.clone()),
+        );
     }
 
     if !originals.is_empty() {
-        let (trigger, original) = if is_new_translation {
-            (&new_pull, &other_pull)
+        let (trigger, original, original_repo) = if is_new_translation {
+            (&new_pull, &other_pull, other_repo)
         } else {
-            (&other_pull, &new_pull)
+            (&other_pull, &new_pull, new_repo)
         };
-        out.push(Conflict::incomplete_translation(
-            trigger.number,
-            original.number,
-            original.html_url.clone(),
-            originals.into_iter().collect(),
-        ));
+        out.push(
+            Conflict::incomplete_translation(
+                trigger.number,
+                original.number,
+                original.html_url.clone(),
+                originals.into_iter().collect(),
+            )
+            .with_original_repo(original_repo),
+        );
     }
     out.sort();
     out
 }
 
-type ConflictKey = (i32, i32, ConflictType);
-pub fn make_conflict_key(original: i32, trigger: i32, kind: &ConflictType) -> ConflictKey {
-    if original < trigger {
-        (original, trigger, kind.clone())
-    } else {
-        (trigger, original, kind.clone())
+/// The original's repository (if it's not the trigger's), the pull pair, and the conflict type.
+type ConflictKey = (Option<String>, i32, i32, ConflictType);
+
+/// Pulls from the same repository are keyed regardless of their roles. Across repositories, the trigger is always the pull
+/// from the repository where the conflict is stored, so the pair is kept as is.
+pub fn make_conflict_key(
+    original_repo: Option<&str>,
+    original: i32,
+    trigger: i32,
+    kind: &ConflictType,
+) -> ConflictKey {
+    match original_repo {
+        Some(repo) => (Some(repo.to_string()), original, trigger, kind.clone()),
+        None if original < trigger => (None, original, trigger, kind.clone()),
+        None => (None, trigger, original, kind.clone()),
     }
 }
 impl Conflict {
     pub fn key(&self) -> ConflictKey {
-        make_conflict_key(self.original, self.trigger, &self.kind)
+        make_conflict_key(
+            self.original_repo.as_deref(),
+            self.original,
+            self.trigger,
+            &self.kind,
+        )
     }
 }
 
 /// Helper for storing and updating conflicts between pull requests.
+///
+/// Conflicts are keyed by pull pairs. Pulls only conflict if they target the same base branch (see [`compare_pulls`]),
+/// so every stored conflict belongs to one branch, and retargeting a pull drops its conflicts on the next update.
 #[derive(Default, Debug, Clone)]
 pub struct Storage {
     map: Arc<Mutex<HashMap<String, HashMap<ConflictKey, Conflict>>>>,
@@ -264,20 +827,61 @@ impl Storage {
     /// Record or update a conflict, and return its updated version, so that the controller
     /// can send notifications 1) to correct pull, and 2) with proper metadata. The latter is important because
     /// the controller doesn't have full information about what should be posted and where after the update.
+    ///
+    /// Dismissed conflicts stay silent until their file set changes, at which point the dismissal is lifted.
+    /// The order of files doesn't matter: conflicts are stored with their file sets sorted.
+    ///
+    /// A stored conflict keeps its creation date, and its update date only moves when it actually changes.
     pub fn upsert(&self, full_repo_name: &str, c: &Conflict) -> Option<Conflict> {
         let mut all_conflicts = self.map.lock().unwrap();
         let repo_conflicts = all_conflicts.entry(full_repo_name.to_string()).or_default();
+        Self::upsert_into(repo_conflicts, c)
+    }
+
+    /// Same as [`Storage::upsert`] for many conflicts at once, such as ones of a repository which is read for the first time.
+    /// Returns conflicts which have been recorded or updated.
+    pub fn upsert_many(&self, full_repo_name: &str, conflicts: &[Conflict]) -> Vec<Conflict> {
+        if conflicts.is_empty() {
+            return Vec::new();
+        }
+        let mut all_conflicts = self.map.lock().unwrap();
+        let repo_conflicts = all_conflicts.entry(full_repo_name.to_string()).or_default();
+        conflicts
+            .iter()
+            .filter_map(|c| Self::upsert_into(repo_conflicts, c))
+            .collect()
+    }
+
+    fn upsert_into(
+        repo_conflicts: &mut HashMap<ConflictKey, Conflict>,
+        c: &Conflict,
+    ) -> Option<Conflict> {
+        let mut c = c.clone();
+        c.file_set.sort();
+
         match repo_conflicts.entry(c.key()) {
             Entry::Vacant(e) => {
                 e.insert(c.clone());
-                Some(c.clone())
+                Some(c)
             }
             Entry::Occupied(mut e) => {
                 let existing_conflict = e.get_mut();
-                if existing_conflict == c {
+                let updated_at = c.updated_at;
+                c.created_at = existing_conflict.created_at;
+                c.updated_at = existing_conflict.updated_at;
+                if *existing_conflict == c
+                    || (existing_conflict.dismissed && existing_conflict.file_set == c.file_set)
+                {
                     None
                 } else {
-                    existing_conflict.file_set = c.file_set.clone();
+                    existing_conflict.file_set = c.file_set;
+                    existing_conflict.severity = c.severity;
+                    existing_conflict.line_ranges = c.line_ranges;
+                    existing_conflict.merge_first = c.merge_first;
+                    existing_conflict.same_author = c.same_author;
+                    existing_conflict.dismissed = false;
+                    existing_conflict.created_at = existing_conflict.created_at.or(updated_at);
+                    existing_conflict.updated_at = updated_at;
                     Some(existing_conflict.clone())
                 }
             }
@@ -311,7 +915,9 @@ impl Storage {
 
     /// Return conflicts with `pull_number` as the original pull, which counts as the source of truth.
     pub fn by_original(&self, full_repo_name: &str, pull_number: i32) -> Vec<Conflict> {
-        self.select_conflicts(full_repo_name, |c| c.original == pull_number)
+        self.select_conflicts(full_repo_name, |c| {
+            c.original == pull_number && c.original_repo.is_none()
+        })
     }
 
     /// Return conflicts with `pull_number` as the trigger pull, which initiated the conflict and needs to be updated.
@@ -319,18 +925,25 @@ impl Storage {
         self.select_conflicts(full_repo_name, |c| c.trigger == pull_number)
     }
 
-    /// Remove cached conflicts which are not present anymore (stale).
+    /// Remove cached conflicts which are not present anymore (stale). `original_repo` is set for pulls from another
+    /// repository (see [`Conflict::original_repo`]).
     pub fn remove_missing(
         &self,
         full_repo_name: &str,
+        original_repo: Option<&str>,
         original: i32,
         trigger: i32,
         detected: &[Conflict],
     ) -> Vec<Conflict> {
         if let Some(m) = self.map.lock().unwrap().get_mut(full_repo_name) {
             let possible_keys = [
-                make_conflict_key(original, trigger, &ConflictType::Overlap),
-                make_conflict_key(original, trigger, &ConflictType::IncompleteTranslation),
+                make_conflict_key(original_repo, original, trigger, &ConflictType::Overlap),
+                make_conflict_key(
+                    original_repo,
+                    original,
+                    trigger,
+                    &ConflictType::IncompleteTranslation,
+                ),
             ];
             let keys_to_preserve: Vec<_> = detected.iter().map(|c| c.key()).collect();
             let mut removed = Vec::new();
@@ -347,17 +960,225 @@ impl Storage {
         Vec::new()
     }
 
+    /// Mark all conflicts between two pulls as dismissed, and return them.
+    pub fn dismiss(&self, full_repo_name: &str, first: i32, second: i32) -> Vec<Conflict> {
+        let mut dismissed = Vec::new();
+        if let Some(m) = self.map.lock().unwrap().get_mut(full_repo_name) {
+            for kind in [ConflictType::Overlap, ConflictType::IncompleteTranslation] {
+                if let Some(c) = m.get_mut(&make_conflict_key(None, first, second, &kind)) {
+                    c.dismissed = true;
+                    dismissed.push(c.clone());
+                }
+            }
+        }
+        dismissed
+    }
+
+    /// Flag a stored conflict whose comment is out of date, or clear the flag. Returns whether the conflict is stored.
+    pub fn set_needs_sync(&self, full_repo_name: &str, c: &Conflict, needs_sync: bool) -> bool {
+        match self
+            .map
+            .lock()
+            .unwrap()
+            .get_mut(full_repo_name)
+            .and_then(|m| m.get_mut(&c.key()))
+        {
+            Some(stored) => {
+                stored.needs_sync = needs_sync;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Check if the stored counterpart of a conflict has been dismissed.
+    pub fn is_dismissed(&self, full_repo_name: &str, c: &Conflict) -> bool {
+        self.map
+            .lock()
+            .unwrap()
+            .get(full_repo_name)
+            .and_then(|m| m.get(&c.key()))
+            .is_some_and(|stored| stored.dismissed)
+    }
+
+    /// Remove conflicts of `pull_number` whose other party is no longer known (according to `is_known`), and return them.
+    pub fn remove_orphaned<F>(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        is_known: F,
+    ) -> Vec<Conflict>
+    where
+        F: Fn(i32) -> bool,
+    {
+        let mut removed = Vec::new();
+        if let Some(m) = self.map.lock().unwrap().get_mut(full_repo_name) {
+            m.retain(|_, c| {
+                // Pulls from other repositories are not known here.
+                if c.original_repo.is_some() {
+                    return true;
+                }
+                let other = match pull_number {
+                    n if n == c.trigger => c.original,
+                    n if n == c.original => c.trigger,
+                    _ => return true,
+                };
+                if is_known(other) {
+                    return true;
+                }
+                removed.push(c.clone());
+                false
+            });
+        }
+        removed.sort();
+        removed
+    }
+
     /// Remove any conflicts involving `pull_number` being either the original or the cause of conflict.
     pub fn remove_conflicts_by_pull(&self, full_repo_name: &str, pull_number: i32) {
         self.prune_conflicts(full_repo_name, |c| {
-            c.trigger == pull_number || c.original == pull_number
+            c.trigger == pull_number || (c.original == pull_number && c.original_repo.is_none())
         });
     }
 
+    /// Remove conflicts stored in `full_repo_name` whose original pull is `pull_number` from `original_repo`, and return them.
+    pub fn remove_conflicts_by_original_in(
+        &self,
+        full_repo_name: &str,
+        original_repo: &str,
+        pull_number: i32,
+    ) -> Vec<Conflict> {
+        let mut removed = Vec::new();
+        if let Some(m) = self.map.lock().unwrap().get_mut(full_repo_name) {
+            m.retain(|_, c| {
+                if c.original != pull_number || c.original_repo.as_deref() != Some(original_repo) {
+                    return true;
+                }
+                removed.push(c.clone());
+                false
+            });
+        }
+        removed.sort();
+        removed
+    }
+
+    /// All stored conflicts, grouped by repository.
+    pub fn all(&self) -> HashMap<String, Vec<Conflict>> {
+        self.map
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(repo, conflicts)| (repo.clone(), conflicts.values().cloned().collect()))
+            .collect()
+    }
+
+    /// Move conflicts of a repository to its new name, after it has been renamed or transferred.
+    pub fn rename_repository(&self, old_full_name: &str, new_full_name: &str) {
+        let mut map = self.map.lock().unwrap();
+        if let Some(conflicts) = map.remove(old_full_name) {
+            map.insert(new_full_name.to_string(), conflicts);
+        }
+        // Conflicts with originals from the repository are keyed by its name as well.
+        for conflicts in map.values_mut() {
+            if !conflicts
+                .values()
+                .any(|c| c.original_repo.as_deref() == Some(old_full_name))
+            {
+                continue;
+            }
+            *conflicts = std::mem::take(conflicts)
+                .into_values()
+                .map(|mut c| {
+                    if c.original_repo.as_deref() == Some(old_full_name) {
+                        c.original_repo = Some(new_full_name.to_string());
+                    }
+                    (c.key(), c)
+                })
+                .collect();
+        }
+    }
+
     pub fn remove_repository(&self, full_repo_name: &str) {
-        self.map.lock().unwrap().remove(&full_repo_name.to_string());
+        let mut map = self.map.lock().unwrap();
+        map.remove(&full_repo_name.to_string());
+        for conflicts in map.values_mut() {
+            conflicts.retain(|_, c| c.original_repo.as_deref() != Some(full_repo_name));
+        }
+    }
+
+    /// Repositories with stored conflicts.
+    pub fn repositories(&self) -> Vec<String> {
+        let mut repos: Vec<_> = self.map.lock().unwrap().keys().cloned().collect();
+        repos.sort();
+        repos
+    }
+
+    /// Bring stored conflicts of a repository back in line with the invariants which other methods rely on, and report
+    /// what had to be fixed: conflicts with pulls which are no longer known (according to `is_known`, which takes the
+    /// repository of the pull if it's not this one) and conflicts of a pull with itself are removed, file sets are sorted
+    /// and deduplicated, and conflicts are filed under their own keys.
+    pub fn compact<F>(&self, full_repo_name: &str, is_known: F) -> Compaction
+    where
+        F: Fn(Option<&str>, i32) -> bool,
+    {
+        let mut report = Compaction::default();
+        let mut map = self.map.lock().unwrap();
+        let Some(m) = map.get_mut(full_repo_name) else {
+            return report;
+        };
+        for (key, mut c) in std::mem::take(m) {
+            if c.original_repo.is_none() && c.trigger == c.original {
+                report.self_conflicts.push(c);
+                continue;
+            }
+            if !is_known(None, c.trigger) || !is_known(c.original_repo.as_deref(), c.original) {
+                report.orphaned.push(c);
+                continue;
+            }
+            let files = c.file_set.len();
+            c.file_set.sort();
+            c.file_set.dedup();
+            if c.file_set.len() != files {
+                report.file_sets_fixed += 1;
+            }
+            if key != c.key() {
+                report.refiled += 1;
+            }
+            m.insert(c.key(), c);
+        }
+        if m.is_empty() {
+            map.remove(full_repo_name);
+        }
+        report.orphaned.sort();
+        report.self_conflicts.sort();
+        report
+    }
+}
+
+/// What [`Storage::compact`] has fixed in a repository. Any of it means that something went wrong earlier.
+#[derive(Debug, Default, PartialEq)]
+pub struct Compaction {
+    /// Removed conflicts with pulls which are no longer known.
+    pub orphaned: Vec<Conflict>,
+
+    /// Removed conflicts of a pull with itself.
+    pub self_conflicts: Vec<Conflict>,
+
+    /// Conflicts whose file sets had duplicates.
+    pub file_sets_fixed: usize,
+
+    /// Conflicts which were stored under a key of another conflict.
+    pub refiled: usize,
+}
+
+impl Compaction {
+    pub fn is_clean(&self) -> bool {
+        *self == Self::default()
     }
 }
 
 #[cfg(test)]
 mod tests;
+
+#[cfg(test)]
+mod proptests;
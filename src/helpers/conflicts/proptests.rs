@@ -0,0 +1,143 @@
+//! Property-based tests for [`compare_pulls`], run against random sets of changed files.
+use std::collections::BTreeSet;
+
+use proptest::prelude::*;
+
+use super::*;
+use crate::test;
+
+// Few articles and languages, so that random pulls often change the same files.
+const ARTICLES: [&str; 2] = ["wiki/Article", "wiki/People/Team"];
+const TRANSLATIONS: [&str; 3] = ["ru", "pt-br", "zh-tw"];
+
+fn make_pull(number: i32, files: &BTreeSet<String>) -> structs::PullRequest {
+    let file_names: Vec<_> = files.iter().map(|f| f.as_str()).collect();
+    let now = chrono::Utc::now();
+    let mut pull = structs::PullRequest {
+        id: number.into(),
+        number,
+        state: "open".to_string(),
+        title: format!("Pull #{}", number),
+        user: structs::Actor {
+            id: 1,
+            login: "BanchoBot".to_string(),
+        },
+        html_url: format!("https://github.com/test/repo/pull/{}", number),
+        created_at: now,
+        updated_at: now,
+        diff: None,
+        base: structs::PullRequestBranch {
+            branch: "master".to_string(),
+            ..Default::default()
+        },
+        head: structs::PullRequestBranch::default(),
+        merged_at: None,
+        merged: false,
+        mergeable_state: None,
+        labels: Vec::new(),
+        author_association: "CONTRIBUTOR".to_string(),
+    };
+    pull.set_diff(test::make_simple_diff(&file_names, 10 * number as usize));
+    pull
+}
+
+fn original() -> impl Strategy<Value = String> {
+    prop::sample::select(&ARTICLES[..]).prop_map(|a| format!("{}/en.md", a))
+}
+
+fn translation() -> impl Strategy<Value = String> {
+    (
+        prop::sample::select(&ARTICLES[..]),
+        prop::sample::select(&TRANSLATIONS[..]),
+    )
+        .prop_map(|(a, l)| format!("{}/{}.md", a, l))
+}
+
+/// Files which are never compared: images, and Markdown files which are not articles.
+fn other_file() -> impl Strategy<Value = String> {
+    prop::sample::select(&ARTICLES[..]).prop_flat_map(|a| {
+        prop::sample::select(vec![
+            format!("{}/img/test.png", a),
+            format!("{}/TEMPLATE.md", a),
+            format!("{}/en.yaml", a),
+        ])
+    })
+}
+
+fn file_set() -> impl Strategy<Value = BTreeSet<String>> {
+    prop::collection::btree_set(prop_oneof![original(), translation(), other_file()], 0..8)
+}
+
+fn find(conflicts: &[Conflict], kind: ConflictType) -> Option<&Conflict> {
+    conflicts.iter().find(|c| c.kind == kind)
+}
+
+proptest! {
+    #[test]
+    fn overlaps_are_symmetric(first in file_set(), second in file_set()) {
+        let (p1, p2) = (make_pull(1, &first), make_pull(2, &second));
+        let forward = compare_pulls(&p2, &p1);
+        let backward = compare_pulls(&p1, &p2);
+
+        let forward = find(&forward, ConflictType::Overlap);
+        let backward = find(&backward, ConflictType::Overlap);
+        prop_assert_eq!(forward.is_some(), backward.is_some());
+        if let (Some(forward), Some(backward)) = (forward, backward) {
+            prop_assert_eq!((forward.trigger, forward.original), (2, 1));
+            prop_assert_eq!((backward.trigger, backward.original), (1, 2));
+            prop_assert_eq!(&forward.file_set, &backward.file_set);
+            for f in &forward.file_set {
+                prop_assert!(first.contains(f) && second.contains(f));
+            }
+        }
+    }
+
+    #[test]
+    fn translations_are_triggers(
+        originals in prop::collection::btree_set(original(), 1..4),
+        translations in prop::collection::btree_set(translation(), 1..4),
+        translation_first in any::<bool>(),
+    ) {
+        let (o_number, t_number) = if translation_first { (2, 1) } else { (1, 2) };
+        let (o, t) = (make_pull(o_number, &originals), make_pull(t_number, &translations));
+
+        for conflicts in [compare_pulls(&o, &t), compare_pulls(&t, &o)] {
+            prop_assert!(find(&conflicts, ConflictType::Overlap).is_none());
+
+            let expected: BTreeSet<_> = translations
+                .iter()
+                .map(|f| Article::from_file_path(f).original_file_path())
+                .filter(|f| originals.contains(f))
+                .collect();
+            match find(&conflicts, ConflictType::IncompleteTranslation) {
+                None => prop_assert!(expected.is_empty()),
+                Some(c) => {
+                    prop_assert_eq!((c.trigger, c.original), (t_number, o_number));
+                    prop_assert_eq!(&c.reference_url, &o.html_url);
+                    prop_assert_eq!(c.file_set.iter().cloned().collect::<BTreeSet<_>>(), expected);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn file_sets_have_no_duplicates(first in file_set(), second in file_set()) {
+        let (p1, p2) = (make_pull(1, &first), make_pull(2, &second));
+        for c in compare_pulls(&p2, &p1) {
+            prop_assert!(!c.file_set.is_empty());
+            prop_assert!(c.file_set.windows(2).all(|w| w[0] < w[1]), "{:?}", c.file_set);
+            for f in &c.file_set {
+                prop_assert!(f.ends_with(".md") && !f.ends_with("TEMPLATE.md"), "{}", f);
+            }
+            // Overlaps may list several translations of one article, but originals are listed once per article.
+            if c.kind == ConflictType::IncompleteTranslation {
+                let articles: BTreeSet<_> = c
+                    .file_set
+                    .iter()
+                    .map(|f| Article::from_file_path(f).path)
+                    .collect();
+                prop_assert_eq!(articles.len(), c.file_set.len(), "{:?}", c.file_set);
+            }
+        }
+    }
+}
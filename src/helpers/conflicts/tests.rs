@@ -2,49 +2,106 @@ use super::*;
 
 use crate::{github, test};
 
+fn date(s: &str) -> Option<chrono::NaiveDate> {
+    Some(s.parse().unwrap())
+}
+
 #[test]
 fn conflict_to_markdown() {
     let gh = github::GitHub::default();
-    let c1 = Conflict::overlap(
-        1,
-        2,
-        gh.pull_url("test/repo", 2),
-        vec!["wiki/Ranking_Criteria/en.md".to_string()],
-    );
+    let c1 = Conflict {
+        created_at: date("2026-01-12"),
+        ..Conflict::overlap(
+            1,
+            2,
+            gh.pull_url("test/repo", 2),
+            vec!["wiki/Ranking_Criteria/en.md".to_string()],
+        )
+    };
     assert_eq!(
         c1.to_markdown(),
         format!(
             r#"<!--
+version: 1
 pull_number: 2
 conflict_type: Overlap
 -->
 {}
-- https://github.com/test/repo/pull/2, files:
-  - [`wiki/Ranking_Criteria/en.md`](https://github.com/test/repo/pull/2/files#diff-d83e7a1fb8077f937a9a91827c6cb673767a7ebb721e3482bdc146a80802b3d2)"#,
+- https://github.com/test/repo/pull/2 (conflicting since 2026-01-12), files:
+  - [`wiki/Ranking_Criteria/en.md`](https://github.com/test/repo/pull/2/files#diff-d83e7a1fb8077f937a9a91827c6cb673767a7ebb721e3482bdc146a80802b3d2)
+
+Suggested merge order: #2, then #1 (likely rebase needed after #2 merges)."#,
             comments::OVERLAP_TEMPLATE
         )
     );
 
-    let c2 = Conflict::incomplete_translation(
-        2,
-        3,
-        gh.pull_url("test/repo", 3),
-        vec!["wiki/Ranking_criteria/en.md".to_string(); 11],
-    );
+    let c2 = Conflict {
+        created_at: date("2026-01-12"),
+        ..Conflict::incomplete_translation(
+            2,
+            3,
+            gh.pull_url("test/repo", 3),
+            vec!["wiki/Ranking_criteria/en.md".to_string(); 11],
+        )
+    };
     assert_eq!(
         c2.to_markdown(),
         format!(
             r#"<!--
+version: 1
 pull_number: 3
 conflict_type: IncompleteTranslation
 -->
 {}
-- https://github.com/test/repo/pull/3 (>10 files)"#,
+- https://github.com/test/repo/pull/3 (>10 files, conflicting since 2026-01-12)"#,
             comments::INCOMPLETE_TRANSLATION_TEMPLATE
         )
     );
 }
 
+#[test]
+fn conflict_from_comment() {
+    let gh = github::GitHub::default();
+    let c = Conflict::incomplete_translation(
+        1,
+        2,
+        gh.pull_url("test/repo", 2),
+        vec![
+            "wiki/Article/en.md".to_string(),
+            "wiki/Other_article/en.md".to_string(),
+        ],
+    );
+    assert_eq!(
+        Conflict::from_comment(1, &c.to_markdown(), None),
+        Some(c.clone())
+    );
+
+    let many = Conflict::overlap(
+        1,
+        3,
+        gh.pull_url("test/repo", 3),
+        vec!["wiki/Article/en.md".to_string(); 11],
+    );
+    let restored = Conflict::from_comment(1, &many.to_markdown(), None).unwrap();
+    assert_eq!(restored.reference_url, many.reference_url);
+    assert_eq!(restored.created_at, many.created_at);
+    assert_eq!(restored.merge_first, many.merge_first);
+    assert!(restored.file_set.is_empty());
+
+    // Comments posted before conflicts had dates are still understood.
+    let undated = Conflict {
+        created_at: None,
+        updated_at: None,
+        ..c.clone()
+    };
+    assert_eq!(
+        Conflict::from_comment(1, &undated.to_markdown(), None),
+        Some(undated)
+    );
+
+    assert_eq!(Conflict::from_comment(1, "just a comment", None), None);
+}
+
 #[test]
 fn article_basic() {
     let original = Article::from_file_path("wiki/Article/en.md");
@@ -64,6 +121,184 @@ fn article_basic() {
     assert_ne!(original, translation);
 }
 
+#[test]
+fn article_layouts() {
+    let folder = ArticleLayout::Folder;
+    let original = folder.article("wiki/Article/en.md").unwrap();
+    assert_eq!(original.path, "wiki/Article");
+    assert!(original.is_original());
+    assert_eq!(
+        folder.article("wiki/Article/pt-br.md").unwrap().language,
+        "pt-br"
+    );
+    assert!(folder.article("wiki/Tournaments/template.md").is_none());
+    assert!(folder.article("wiki/Article/en.png").is_none());
+
+    let suffix = ArticleLayout::Suffix;
+    let original = suffix.article("docs/Article.md").unwrap();
+    assert_eq!(original.path, "docs/Article");
+    assert!(original.is_original());
+    assert_eq!(original.file_path(), "docs/Article.md");
+
+    let translation = suffix.article("docs/Article.pt-br.md").unwrap();
+    assert_eq!(translation.path, "docs/Article");
+    assert_eq!(translation.language, "pt-br");
+    assert_eq!(translation.file_path(), "docs/Article.pt-br.md");
+    assert_eq!(translation.original_file_path(), "docs/Article.md");
+
+    // Dots which are not followed by a language code are part of the name.
+    let versioned = suffix.article("README.v2.md").unwrap();
+    assert_eq!(versioned.path, "README.v2");
+    assert!(versioned.is_original());
+}
+
+#[tokio::test]
+async fn suffix_layout_conflicts() {
+    let mut gh = test::GitHubServer::new().await;
+    let filters = diff::Pipeline::new();
+
+    let original = gh.make_pull("test/repo", &["docs/Article.md"]);
+    let translation = gh.make_pull("test/repo", &["docs/Article.ru.md", "docs/Other.ru.md"]);
+    let other_translation = gh.make_pull("test/repo", &["docs/Article.ru.md"]);
+
+    assert_eq!(
+        compare_pulls_with(&translation, &original, &filters, ArticleLayout::Suffix),
+        vec![Conflict::incomplete_translation(
+            2,
+            1,
+            gh.url.pull_url("test/repo", 1),
+            vec!["docs/Article.md".to_string()],
+        )]
+    );
+    assert_eq!(
+        compare_pulls_with(
+            &other_translation,
+            &translation,
+            &filters,
+            ArticleLayout::Suffix
+        ),
+        vec![Conflict::overlap(
+            3,
+            2,
+            gh.url.pull_url("test/repo", 2),
+            vec!["docs/Article.ru.md".to_string()],
+        )
+        .with_merge_first(2)]
+    );
+
+    // In the folder layout, these are not articles at all.
+    assert!(compare_pulls(&translation, &original).is_empty());
+}
+
+#[tokio::test]
+async fn overlap_between_pulls_of_same_author_is_marked() {
+    let mut gh = test::GitHubServer::new().await;
+    let existing_pull = gh.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let mut new_pull = gh.make_pull("test/repo", &["wiki/Article/en.md"]);
+    assert!(!compare_pulls(&new_pull, &existing_pull)[0].same_author);
+
+    new_pull.user = existing_pull.user.clone();
+    assert!(compare_pulls(&new_pull, &existing_pull)[0].same_author);
+}
+
+#[tokio::test]
+async fn conflicts_across_repositories() {
+    let mut gh = test::GitHubServer::new().await;
+    let filters = diff::Pipeline::new();
+
+    let original = gh.make_pull("test/wiki-en", &["wiki/Article/en.md"]);
+    let mut translation = gh.make_pull("test/wiki-ru", &["wiki/Article/ru.md"]);
+    // Every repository has its own default branch.
+    translation.base.branch = "main".to_string();
+
+    let expected = vec![Conflict::incomplete_translation(
+        1,
+        1,
+        original.html_url.clone(),
+        vec!["wiki/Article/en.md".to_string()],
+    )
+    .with_original_repo(Some("test/wiki-en".to_string()))];
+    assert_eq!(
+        compare_pulls_across(
+            "test/wiki-ru",
+            &translation,
+            "test/wiki-en",
+            &original,
+            &filters,
+            ArticleLayout::Folder
+        ),
+        expected
+    );
+    assert_eq!(
+        compare_pulls_across(
+            "test/wiki-en",
+            &original,
+            "test/wiki-ru",
+            &translation,
+            &filters,
+            ArticleLayout::Folder
+        ),
+        expected
+    );
+}
+
+#[test]
+fn storage_keys_conflicts_across_repositories() {
+    let storage = Storage::default();
+    let gh = github::GitHub::default();
+    let local = Conflict::overlap(
+        2,
+        1,
+        gh.pull_url("test/wiki-ru", 1),
+        vec!["wiki/Article/ru.md".to_string()],
+    );
+    let remote = Conflict::incomplete_translation(
+        2,
+        1,
+        gh.pull_url("test/wiki-en", 1),
+        vec!["wiki/Article/en.md".to_string()],
+    )
+    .with_original_repo(Some("test/wiki-en".to_string()));
+    let remote_overlap = Conflict {
+        kind: ConflictType::Overlap,
+        ..remote.clone()
+    };
+
+    // Pull #1 from another repository is not the same as the local one.
+    storage.upsert("test/wiki-ru", &local);
+    storage.upsert("test/wiki-ru", &remote_overlap);
+    assert_eq!(storage.by_trigger("test/wiki-ru", 2).len(), 2);
+    assert_eq!(storage.by_original("test/wiki-ru", 1), vec![local.clone()]);
+
+    storage.remove_missing(
+        "test/wiki-ru",
+        Some("test/wiki-en"),
+        1,
+        2,
+        std::slice::from_ref(&remote),
+    );
+    storage.upsert("test/wiki-ru", &remote);
+    assert_eq!(
+        storage.by_trigger("test/wiki-ru", 2),
+        vec![local.clone(), remote.clone()]
+    );
+
+    storage.rename_repository("test/wiki-en", "test/wiki");
+    assert_eq!(
+        storage.by_trigger("test/wiki-ru", 2)[1]
+            .original_repo
+            .as_deref(),
+        Some("test/wiki")
+    );
+    assert_eq!(
+        storage
+            .remove_conflicts_by_original_in("test/wiki-ru", "test/wiki", 1)
+            .len(),
+        1
+    );
+    assert_eq!(storage.by_trigger("test/wiki-ru", 2), vec![local]);
+}
+
 #[tokio::test]
 async fn different_paths_no_conflict() {
     let mut gh = test::GitHubServer::new().await;
@@ -135,7 +370,8 @@ async fn multiple_files_overlap() {
                 "wiki/Article/en.md".to_string(),
                 "wiki/Ranking_criteria/en.md".to_string(),
             ]
-        )]
+        )
+        .with_merge_first(2)]
     );
     assert_eq!(
         compare_pulls(&existing_pull, &new_pull),
@@ -147,10 +383,36 @@ async fn multiple_files_overlap() {
                 "wiki/Article/en.md".to_string(),
                 "wiki/Ranking_criteria/en.md".to_string(),
             ]
-        )]
+        )
+        .with_merge_first(2)]
     );
 }
 
+#[tokio::test]
+async fn merge_order_prefers_larger_and_older_pulls() {
+    let mut gh = test::GitHubServer::new().await;
+
+    let older = gh.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let newer = gh.make_pull("test/repo", &["wiki/Article/en.md"]);
+    assert_eq!(suggest_merge_order(&older, 1, &newer, 1), older.number);
+    assert_eq!(suggest_merge_order(&newer, 1, &older, 1), older.number);
+
+    // A larger change is harder to rebase...
+    assert_eq!(suggest_merge_order(&older, 1, &newer, 3), newer.number);
+
+    // ...unless the other pull has been waiting for much longer.
+    let mut stale = older.clone();
+    stale.created_at -= chrono::Duration::days(30);
+    assert_eq!(suggest_merge_order(&stale, 1, &newer, 3), stale.number);
+
+    let conflict = compare_pulls(&newer, &older).remove(0);
+    assert_eq!(conflict.merge_first, Some(older.number));
+    assert!(conflict.to_markdown().ends_with(&format!(
+        "Suggested merge order: #{0}, then #{1} (likely rebase needed after #{0} merges).",
+        older.number, newer.number
+    )));
+}
+
 #[tokio::test]
 async fn existing_translation_becomes_incomplete() {
     let mut gh = test::GitHubServer::new().await;
@@ -186,3 +448,254 @@ async fn new_translation_marked_as_incomplete() {
         )]
     );
 }
+
+#[test]
+fn storage_dismissed_conflict_stays_silent() {
+    let storage = Storage::default();
+    let url = github::GitHub::default().pull_url("test/repo", 1);
+    let conflict = Conflict::overlap(2, 1, url.clone(), vec!["wiki/Article/en.md".to_string()]);
+
+    assert!(storage.upsert("test/repo", &conflict).is_some());
+    assert!(!storage.is_dismissed("test/repo", &conflict));
+
+    let dismissed = storage.dismiss("test/repo", 1, 2);
+    assert_eq!(dismissed.len(), 1);
+    assert!(dismissed[0].dismissed);
+    assert!(storage.is_dismissed("test/repo", &conflict));
+    assert!(storage.upsert("test/repo", &conflict).is_none());
+
+    let changed = Conflict::overlap(
+        2,
+        1,
+        url,
+        vec![
+            "wiki/Article/en.md".to_string(),
+            "wiki/Other_article/en.md".to_string(),
+        ],
+    );
+    let updated = storage.upsert("test/repo", &changed).unwrap();
+    assert!(!updated.dismissed);
+    assert!(!storage.is_dismissed("test/repo", &changed));
+}
+
+#[test]
+fn storage_ignores_file_order() {
+    let storage = Storage::default();
+    let url = github::GitHub::default().pull_url("test/repo", 1);
+    let files = vec![
+        "wiki/Other_article/en.md".to_string(),
+        "wiki/Article/en.md".to_string(),
+    ];
+    let conflict = Conflict::overlap(2, 1, url.clone(), files.clone());
+    assert_eq!(
+        storage.upsert("test/repo", &conflict).unwrap().file_set,
+        vec![
+            "wiki/Article/en.md".to_string(),
+            "wiki/Other_article/en.md".to_string(),
+        ]
+    );
+
+    let reordered = Conflict::overlap(2, 1, url.clone(), files.into_iter().rev().collect());
+    assert!(storage.upsert("test/repo", &reordered).is_none());
+
+    let mut line_ranges = crate::structs::LineRanges::new();
+    line_ranges.insert("wiki/Article/en.md".to_string(), vec![(1, 5)]);
+    let more_severe = reordered.with_line_ranges(line_ranges.clone());
+    let updated = storage.upsert("test/repo", &more_severe).unwrap();
+    assert_eq!(updated.severity, Severity::LineLevel);
+    assert_eq!(updated.line_ranges, line_ranges);
+}
+
+#[test]
+fn storage_keeps_creation_date() {
+    let storage = Storage::default();
+    let url = github::GitHub::default().pull_url("test/repo", 1);
+    let conflict = Conflict {
+        created_at: date("2026-01-12"),
+        updated_at: date("2026-01-12"),
+        ..Conflict::overlap(2, 1, url.clone(), vec!["wiki/Article/en.md".to_string()])
+    };
+    storage.upsert("test/repo", &conflict).unwrap();
+
+    // Detecting the same conflict again on another day is not a change.
+    let same = Conflict::overlap(2, 1, url.clone(), vec!["wiki/Article/en.md".to_string()]);
+    assert!(storage.upsert("test/repo", &same).is_none());
+
+    let changed = Conflict::overlap(
+        2,
+        1,
+        url,
+        vec![
+            "wiki/Article/en.md".to_string(),
+            "wiki/Other_article/en.md".to_string(),
+        ],
+    );
+    let updated = storage.upsert("test/repo", &changed).unwrap();
+    assert_eq!(updated.created_at, date("2026-01-12"));
+    assert_eq!(updated.updated_at, changed.updated_at);
+    assert!(updated
+        .to_markdown()
+        .contains("conflicting since 2026-01-12"));
+}
+
+#[test]
+fn storage_upserts_many_at_once() {
+    let storage = Storage::default();
+    let url = github::GitHub::default().pull_url("test/repo", 1);
+    assert!(storage.upsert_many("test/repo", &[]).is_empty());
+    assert!(storage.all().is_empty());
+
+    let overlap = Conflict::overlap(
+        2,
+        1,
+        url.clone(),
+        vec![
+            "wiki/Other_article/en.md".to_string(),
+            "wiki/Article/en.md".to_string(),
+        ],
+    );
+    let translation =
+        Conflict::incomplete_translation(3, 1, url, vec!["wiki/Article/en.md".to_string()]);
+    let stored = storage.upsert_many("test/repo", &[overlap.clone(), translation.clone()]);
+    assert_eq!(stored.len(), 2);
+    assert_eq!(
+        stored[0].file_set,
+        vec![
+            "wiki/Article/en.md".to_string(),
+            "wiki/Other_article/en.md".to_string()
+        ]
+    );
+
+    // Known conflicts are not reported again.
+    assert!(storage
+        .upsert_many("test/repo", &[overlap, translation])
+        .is_empty());
+    assert_eq!(storage.all()["test/repo"].len(), 2);
+}
+
+#[test]
+fn storage_compaction() {
+    let storage = Storage::default();
+    let url = github::GitHub::default().pull_url("test/repo", 1);
+    let article = "wiki/Article/en.md".to_string();
+    let healthy = Conflict::overlap(2, 1, url.clone(), vec![article.clone()]);
+    let duplicates = Conflict::overlap(3, 1, url.clone(), vec![article.clone(), article.clone()]);
+    let with_itself = Conflict::overlap(3, 3, url.clone(), vec![article.clone()]);
+    let orphaned = Conflict::overlap(2, 4, url.clone(), vec![article.clone()]);
+    let mut across = Conflict::overlap(2, 7, url, vec![article.clone()]);
+    across.original_repo = Some("test/wiki-ru".to_string());
+    for c in [&healthy, &duplicates, &with_itself, &orphaned, &across] {
+        storage.upsert("test/repo", c);
+    }
+
+    let is_known = |repo: Option<&str>, n: i32| match repo {
+        None => [1, 2, 3].contains(&n),
+        Some(_) => n == 7,
+    };
+    let report = storage.compact("test/repo", is_known);
+    assert_eq!(report.orphaned, vec![orphaned]);
+    assert_eq!(report.self_conflicts, vec![with_itself]);
+    assert_eq!(report.file_sets_fixed, 1);
+    assert_eq!(report.refiled, 0);
+    assert_eq!(storage.by_trigger("test/repo", 2), vec![healthy, across]);
+    assert_eq!(
+        storage.by_trigger("test/repo", 3)[0].file_set,
+        vec![article]
+    );
+
+    assert!(storage.compact("test/repo", is_known).is_clean());
+    assert!(storage.compact("test/unknown", is_known).is_clean());
+    storage.compact("test/repo", |_, _| false);
+    assert!(storage.repositories().is_empty());
+}
+
+#[test]
+fn translations_in_flight_to_markdown() {
+    let gh = github::GitHub::default();
+    let summary = TranslationsInFlight {
+        original: 1,
+        translations: vec![
+            (
+                gh.pull_url("test/repo", 2),
+                vec!["wiki/Article/en.md".to_string()],
+            ),
+            (
+                gh.pull_url("test/repo", 3),
+                vec![
+                    "wiki/Article/en.md".to_string(),
+                    "wiki/Other_article/en.md".to_string(),
+                ],
+            ),
+        ],
+    };
+    assert_eq!(
+        summary.to_markdown(),
+        format!(
+            r#"<!--
+version: 1
+pull_number: 1
+conflict_type: TranslationsInFlight
+-->
+{}
+- https://github.com/test/repo/pull/2, articles: `wiki/Article/en.md`
+- https://github.com/test/repo/pull/3, articles: `wiki/Article/en.md`, `wiki/Other_article/en.md`"#,
+            comments::TRANSLATIONS_IN_FLIGHT_TEMPLATE
+        )
+    );
+}
+
+#[tokio::test]
+async fn paths_can_be_compared_regardless_of_case() {
+    let mut gh = test::GitHubServer::new().await;
+
+    let existing_pull = gh.make_pull("test/repo", &["wiki/FAQ/en.md", "wiki/Legal/en.md"]);
+    let new_pull = gh.make_pull("test/repo", &["wiki/Faq/en.md", "wiki/legal/EN.md"]);
+    assert!(compare_pulls(&new_pull, &existing_pull).is_empty());
+
+    let filters = diff::Pipeline::new().then(diff::CaseInsensitive);
+    assert_eq!(
+        compare_pulls_with(&new_pull, &existing_pull, &filters, ArticleLayout::Folder),
+        vec![Conflict::overlap(
+            2,
+            1,
+            gh.url.pull_url("test/repo", 1),
+            // Files are listed as the new pull has them.
+            vec!["wiki/Faq/en.md".to_string(), "wiki/legal/EN.md".to_string()],
+        )]
+    );
+}
+
+#[tokio::test]
+async fn overlap_severity_depends_on_lines() {
+    let mut gh = test::GitHubServer::new().await;
+
+    let mut existing_pull = gh.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let mut new_pull = gh.make_pull("test/repo", &["wiki/Article/en.md"]);
+    existing_pull.set_diff(test::make_simple_diff(&["wiki/Article/en.md"], 10));
+    new_pull.set_diff(test::make_simple_diff(&["wiki/Article/en.md"], 100));
+    assert_eq!(
+        compare_pulls(&new_pull, &existing_pull)[0].severity,
+        Severity::FileLevel
+    );
+
+    new_pull.set_diff(test::make_simple_diff(&["wiki/Article/en.md"], 12));
+    let conflicts = compare_pulls(&new_pull, &existing_pull);
+    assert_eq!(
+        conflicts,
+        vec![Conflict::overlap(
+            2,
+            1,
+            gh.url.pull_url("test/repo", 1),
+            vec!["wiki/Article/en.md".to_string()],
+        )
+        .with_line_ranges(structs::LineRanges::from([(
+            "wiki/Article/en.md".to_string(),
+            vec![(12, 16)]
+        )]))]
+    );
+    assert_eq!(conflicts[0].severity, Severity::LineLevel);
+
+    let markdown = conflicts[0].to_markdown();
+    assert!(markdown.contains(comments::LINE_LEVEL_OVERLAP_TEMPLATE));
+    assert!(markdown.contains("): lines 12–15"));
+}
@@ -0,0 +1,369 @@
+//! Summaries of pull request diffs, which keep only what conflict detection needs.
+//!
+//! A diff is reduced to its Markdown files, how each of them was changed, and which lines of the base version its hunks
+//! touch. Before pulls are compared, paths go through a [`PathFilter`], which may skip files or rewrite the paths
+//! they are compared by (for example, to match them regardless of case).
+use std::fmt::Write;
+
+use crate::structs::{GraphQLPullRequestFile, LineRanges};
+
+/// How a file was changed by a pull.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Deleted,
+
+    /// The file was moved to a new path (only known if its contents were changed as well).
+    Renamed {
+        to: String,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedFile {
+    /// The path in the base version, or the new path for added files.
+    pub path: String,
+    pub kind: ChangeKind,
+
+    /// `[start, end)` line ranges of hunks, in line numbers of the base version. Pure additions are not included.
+    pub hunks: Vec<(usize, usize)>,
+}
+
+impl ChangedFile {
+    /// The path of the file after the pull is merged, if it still exists.
+    pub fn target_path(&self) -> Option<&str> {
+        match &self.kind {
+            ChangeKind::Deleted => None,
+            ChangeKind::Renamed { to } => Some(to),
+            _ => Some(&self.path),
+        }
+    }
+}
+
+/// Changed Markdown files of a pull, sorted by path.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct DiffSummary {
+    pub files: Vec<ChangedFile>,
+
+    /// Whether hunks are known. They are not for pulls read via GraphQL, or restored from a snapshot without them.
+    pub has_line_ranges: bool,
+}
+
+fn is_markdown(path: &str) -> bool {
+    path.ends_with(".md")
+}
+
+impl From<&unidiff::PatchSet> for DiffSummary {
+    fn from(diff: &unidiff::PatchSet) -> Self {
+        let strip = |p: &str, prefix: &str| p.strip_prefix(prefix).unwrap_or(p).to_string();
+        let mut files: Vec<_> = diff
+            .files()
+            .iter()
+            .filter(|f| is_markdown(&f.path()))
+            .map(|f| {
+                // unidiff guesses these from hunks, which misreads insertions at the top of a file.
+                let kind = if f.source_file == "/dev/null" {
+                    ChangeKind::Added
+                } else if f.target_file == "/dev/null" {
+                    ChangeKind::Deleted
+                } else if strip(&f.source_file, "a/") != strip(&f.target_file, "b/") {
+                    ChangeKind::Renamed {
+                        to: strip(&f.target_file, "b/"),
+                    }
+                } else {
+                    ChangeKind::Modified
+                };
+                ChangedFile {
+                    path: f.path(),
+                    kind,
+                    hunks: f
+                        .hunks()
+                        .iter()
+                        .filter(|h| h.source_length > 0)
+                        .map(|h| (h.source_start, h.source_start + h.source_length))
+                        .collect(),
+                }
+            })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Self {
+            files,
+            has_line_ranges: true,
+        }
+    }
+}
+
+impl DiffSummary {
+    /// Summarize a list of changed files, as reported by GraphQL. Their hunks are not known.
+    pub fn from_files(files: &[GraphQLPullRequestFile]) -> Self {
+        let mut files: Vec<_> = files
+            .iter()
+            .filter(|f| is_markdown(&f.path))
+            .map(|f| ChangedFile {
+                path: f.path.clone(),
+                kind: match f.change_type.as_str() {
+                    "ADDED" => ChangeKind::Added,
+                    "DELETED" => ChangeKind::Deleted,
+                    _ => ChangeKind::Modified,
+                },
+                hunks: Vec::new(),
+            })
+            .collect();
+        files.sort_by(|a, b| a.path.cmp(&b.path));
+        Self {
+            files,
+            has_line_ranges: false,
+        }
+    }
+
+    /// Attach hunks which were saved separately (see [`Self::line_ranges`]).
+    pub fn with_line_ranges(mut self, ranges: Option<LineRanges>) -> Self {
+        let Some(mut ranges) = ranges else {
+            return self;
+        };
+        for f in self.files.iter_mut() {
+            f.hunks = ranges.remove(&f.path).unwrap_or_default();
+        }
+        self.has_line_ranges = true;
+        self
+    }
+
+    /// List changed files in the same form as GraphQL reports them. Renamed files are listed under their old paths.
+    pub fn changed_files(&self) -> Vec<GraphQLPullRequestFile> {
+        self.files
+            .iter()
+            .map(|f| GraphQLPullRequestFile {
+                path: f.path.clone(),
+                change_type: match f.kind {
+                    ChangeKind::Added => "ADDED",
+                    ChangeKind::Deleted => "DELETED",
+                    _ => "MODIFIED",
+                }
+                .to_string(),
+            })
+            .collect()
+    }
+
+    /// File path -> hunks, if they are known.
+    pub fn line_ranges(&self) -> Option<LineRanges> {
+        self.has_line_ranges.then(|| {
+            self.files
+                .iter()
+                .map(|f| (f.path.clone(), f.hunks.clone()))
+                .collect()
+        })
+    }
+
+    /// Hunks of a file, if they are known.
+    pub fn hunks(&self, path: &str) -> Option<&[(usize, usize)]> {
+        if !self.has_line_ranges {
+            return None;
+        }
+        self.files
+            .iter()
+            .find(|f| f.path == path)
+            .map(|f| f.hunks.as_slice())
+    }
+}
+
+/// Render the summary as a unified diff, which parses back into the same summary (with blank lines for contents).
+impl std::fmt::Display for DiffSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut out = String::new();
+        for file in &self.files {
+            let source = match file.kind {
+                ChangeKind::Added => "/dev/null".to_string(),
+                _ => format!("a/{}", file.path),
+            };
+            let target = match file.target_path() {
+                None => "/dev/null".to_string(),
+                Some(p) => format!("b/{}", p),
+            };
+            writeln!(out, "--- {source}\n+++ {target}")?;
+            if file.hunks.is_empty() {
+                match file.kind {
+                    ChangeKind::Deleted => writeln!(out, "@@ -1 +0,0 @@\n-")?,
+                    _ => writeln!(out, "@@ -0,0 +1 @@\n+")?,
+                }
+            }
+            for (start, end) in &file.hunks {
+                let length = end - start;
+                let (target_range, line) = match file.kind {
+                    ChangeKind::Deleted => ("0,0".to_string(), "-"),
+                    _ => (format!("{start},{length}"), " "),
+                };
+                writeln!(out, "@@ -{start},{length} +{target_range} @@")?;
+                for _ in 0..length {
+                    writeln!(out, "{line}")?;
+                }
+            }
+        }
+        f.write_str(out.trim_end_matches('\n'))
+    }
+}
+
+/// A step of preparing changed files for comparison.
+pub trait PathFilter: std::fmt::Debug + Send + Sync {
+    /// The path to compare a file by, or `None` to skip the file.
+    fn apply(&self, path: &str) -> Option<String>;
+}
+
+/// Compare paths regardless of case, for wikis whose articles are renamed by changing case.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CaseInsensitive;
+
+impl PathFilter for CaseInsensitive {
+    fn apply(&self, path: &str) -> Option<String> {
+        Some(path.to_lowercase())
+    }
+}
+
+/// Filters applied one after another. An empty pipeline leaves paths as is.
+#[derive(Debug, Default)]
+pub struct Pipeline(Vec<Box<dyn PathFilter>>);
+
+impl Pipeline {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn then(mut self, filter: impl PathFilter + 'static) -> Self {
+        self.0.push(Box::new(filter));
+        self
+    }
+}
+
+impl PathFilter for Pipeline {
+    fn apply(&self, path: &str) -> Option<String> {
+        self.0
+            .iter()
+            .try_fold(path.to_string(), |path, filter| filter.apply(&path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    const DIFF: &str = r#"diff --git a/wiki/Article/en.md b/wiki/Article/en.md
+--- a/wiki/Article/en.md
++++ b/wiki/Article/en.md
+@@ -10,3 +10,4 @@
+ a
++b
+ c
+ d
+diff --git a/wiki/Old/ru.md b/wiki/New/ru.md
+--- a/wiki/Old/ru.md
++++ b/wiki/New/ru.md
+@@ -1,1 +1,1 @@
+-a
++b
+diff --git a/wiki/Gone/fr.md b/wiki/Gone/fr.md
+--- a/wiki/Gone/fr.md
++++ /dev/null
+@@ -1,2 +0,0 @@
+-a
+-b
+diff --git a/wiki/img/picture.png b/wiki/img/picture.png
+--- /dev/null
++++ b/wiki/img/picture.png
+@@ -0,0 +1 @@
++binary
+diff --git a/wiki/Fresh/en.md b/wiki/Fresh/en.md
+--- /dev/null
++++ b/wiki/Fresh/en.md
+@@ -0,0 +1,2 @@
++a
++b"#;
+
+    #[test]
+    fn summary() {
+        let summary = DiffSummary::from(&unidiff::PatchSet::from_str(DIFF).unwrap());
+        assert_eq!(
+            summary.files,
+            vec![
+                ChangedFile {
+                    path: "wiki/Article/en.md".to_string(),
+                    kind: ChangeKind::Modified,
+                    hunks: vec![(10, 13)],
+                },
+                ChangedFile {
+                    path: "wiki/Fresh/en.md".to_string(),
+                    kind: ChangeKind::Added,
+                    hunks: Vec::new(),
+                },
+                ChangedFile {
+                    path: "wiki/Gone/fr.md".to_string(),
+                    kind: ChangeKind::Deleted,
+                    hunks: vec![(1, 3)],
+                },
+                ChangedFile {
+                    path: "wiki/Old/ru.md".to_string(),
+                    kind: ChangeKind::Renamed {
+                        to: "wiki/New/ru.md".to_string()
+                    },
+                    hunks: vec![(1, 2)],
+                },
+            ]
+        );
+        assert_eq!(summary.hunks("wiki/Article/en.md"), Some(&[(10, 13)][..]));
+        assert_eq!(summary.files[2].target_path(), None);
+        assert_eq!(summary.files[3].target_path(), Some("wiki/New/ru.md"));
+    }
+
+    #[test]
+    fn summary_round_trip() {
+        let summary = DiffSummary::from(&unidiff::PatchSet::from_str(DIFF).unwrap());
+        let rendered = unidiff::PatchSet::from_str(&summary.to_string()).unwrap();
+        assert_eq!(DiffSummary::from(&rendered), summary);
+
+        let restored = DiffSummary::from_files(&summary.changed_files())
+            .with_line_ranges(summary.line_ranges());
+        assert_eq!(restored.hunks("wiki/Gone/fr.md"), Some(&[(1, 3)][..]));
+        assert_eq!(restored.files.len(), summary.files.len());
+    }
+
+    #[test]
+    fn files_without_hunks() {
+        let summary = DiffSummary::from_files(&[
+            GraphQLPullRequestFile {
+                path: "wiki/Article/en.md".to_string(),
+                change_type: "MODIFIED".to_string(),
+            },
+            GraphQLPullRequestFile {
+                path: "wiki/Article/img/picture.png".to_string(),
+                change_type: "ADDED".to_string(),
+            },
+        ]);
+        assert_eq!(summary.files.len(), 1);
+        assert_eq!(summary.hunks("wiki/Article/en.md"), None);
+        assert_eq!(summary.line_ranges(), None);
+    }
+
+    #[derive(Debug)]
+    struct SkipLegal;
+
+    impl PathFilter for SkipLegal {
+        fn apply(&self, path: &str) -> Option<String> {
+            (!path.starts_with("wiki/legal/")).then(|| path.to_string())
+        }
+    }
+
+    #[test]
+    fn pipeline() {
+        assert_eq!(
+            Pipeline::new().apply("wiki/Article/en.md"),
+            Some("wiki/Article/en.md".to_string())
+        );
+
+        let pipeline = Pipeline::new().then(CaseInsensitive).then(SkipLegal);
+        assert_eq!(
+            pipeline.apply("wiki/Article/EN.md"),
+            Some("wiki/article/en.md".to_string())
+        );
+        assert_eq!(pipeline.apply("wiki/Legal/Terms/en.md"), None);
+    }
+}
@@ -19,6 +19,18 @@ pub fn hash_to_string(hash: &[u8]) -> String {
     format!("{}", HashWrapper(hash))
 }
 
+/// The reverse of [`hash_to_string`]: `None` if the string is not an even number of hex digits.
+pub fn hash_from_string(s: &str) -> Option<Vec<u8>> {
+    let digits: Vec<u8> = s
+        .chars()
+        .map(|c| c.to_digit(16).map(|d| d as u8))
+        .collect::<Option<_>>()?;
+    if !digits.len().is_multiple_of(2) {
+        return None;
+    }
+    Some(digits.chunks(2).map(|d| d[0] << 4 | d[1]).collect())
+}
+
 #[derive(Debug, Clone)]
 pub struct RequestValidator {
     token: String,
@@ -30,9 +42,13 @@ impl RequestValidator {
     }
 
     pub fn validate(&self, data: &str, signature: &str) -> Result<bool> {
+        Ok(signature == self.sign(data))
+    }
+
+    /// HMAC-SHA256 of the data, as a hex string.
+    pub fn sign(&self, data: &str) -> String {
         let key = &ring::hmac::Key::new(ring::hmac::HMAC_SHA256, self.token.as_bytes());
-        let local_signature = ring::hmac::sign(key, data.as_bytes());
-        Ok(signature == hash_to_string(local_signature.as_ref()))
+        hash_to_string(ring::hmac::sign(key, data.as_bytes()).as_ref())
     }
 }
 
@@ -1,8 +1,20 @@
+pub mod allowlist;
 pub mod cgroup;
+pub mod commands;
 pub mod comments;
 pub mod conflicts;
+pub mod diff;
 pub mod digest;
+pub mod process;
+
+use std::sync::{Mutex, PoisonError};
 
 pub trait ToMarkdown {
     fn to_markdown(&self) -> String;
 }
+
+/// Empty a cache shared behind a lock, even if a thread has panicked while holding it, so that the cache can be used again.
+pub fn reset_cache<T: Default>(cache: &Mutex<T>) {
+    *cache.lock().unwrap_or_else(PoisonError::into_inner) = T::default();
+    cache.clear_poison();
+}
@@ -0,0 +1,48 @@
+//! Resource usage of the current process, for hosts where it can't be read from a cgroup (Windows, macOS, or Linux
+//! without cgroup v2).
+use std::collections::BTreeMap;
+
+use sysinfo::{ProcessRefreshKind, ProcessesToUpdate, System};
+
+pub fn summary() -> BTreeMap<String, String> {
+    let mut m = BTreeMap::new();
+    let Ok(pid) = sysinfo::get_current_pid() else {
+        return m;
+    };
+    let mut system = System::new();
+    system.refresh_processes_specifics(
+        ProcessesToUpdate::Some(&[pid]),
+        true,
+        ProcessRefreshKind::nothing().with_memory().with_cpu(),
+    );
+    let Some(process) = system.process(pid) else {
+        return m;
+    };
+
+    m.insert(
+        "memory.rss".to_owned(),
+        format!("{} MiB", process.memory() >> 20),
+    );
+    m.insert(
+        "cpu.time".to_owned(),
+        format!("{:.1} s", process.accumulated_cpu_time() as f64 / 1000.),
+    );
+    m.insert("uptime".to_owned(), format!("{} s", process.run_time()));
+    if let Some(files) = process.open_files() {
+        m.insert("open.files".to_owned(), files.to_string());
+    }
+    m
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn current_process_is_reported() {
+        let m = summary();
+        assert!(m.contains_key("memory.rss"));
+        assert!(m.contains_key("cpu.time"));
+        assert!(m.contains_key("uptime"));
+    }
+}
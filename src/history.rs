@@ -0,0 +1,172 @@
+//! The latest merged change to every original article, so that translators can tell how fresh the original is. Only merges
+//! seen by the app are recorded: the history is kept in memory and in state snapshots (see [`crate::state`]), and is not
+//! read back from GitHub.
+use std::collections::{BTreeMap, HashMap};
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::conflicts::ArticleLayout;
+use crate::structs::PullRequest;
+
+/// The pull request which last changed an original article.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LastMerge {
+    pub pull_number: i32,
+    pub html_url: String,
+    pub merged_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// File path of an original article -> its latest merged change.
+pub type RepoHistory = BTreeMap<String, LastMerge>;
+
+/// Merge history of originals, split by repository.
+#[derive(Debug, Clone, Default)]
+pub struct ArticleHistory {
+    repos: Arc<Mutex<HashMap<String, RepoHistory>>>,
+}
+
+impl ArticleHistory {
+    /// Remember a merged pull as the latest change to every original it adds or modifies. Merges older than the ones already
+    /// known are ignored, and so are deleted files.
+    pub fn record(&self, full_repo_name: &str, pull: &PullRequest, layout: ArticleLayout) {
+        let merge = LastMerge {
+            pull_number: pull.number,
+            html_url: pull.html_url.clone(),
+            merged_at: pull.merged_at.unwrap_or_else(chrono::Utc::now),
+        };
+        let mut g = self.repos.lock().unwrap();
+        let history = g.entry(full_repo_name.to_string()).or_default();
+        for path in pull
+            .diff
+            .iter()
+            .flat_map(|d| &d.files)
+            .filter_map(|f| f.target_path())
+            .filter(|p| layout.article(p).is_some_and(|a| a.is_original()))
+        {
+            match history.get(path) {
+                Some(known) if known.merged_at > merge.merged_at => {}
+                _ => {
+                    history.insert(path.to_string(), merge.clone());
+                }
+            }
+        }
+    }
+
+    /// The latest merged change to an original, by its file path.
+    pub fn last_merge(&self, full_repo_name: &str, file_path: &str) -> Option<LastMerge> {
+        self.repos
+            .lock()
+            .unwrap()
+            .get(full_repo_name)
+            .and_then(|h| h.get(file_path))
+            .cloned()
+    }
+
+    pub fn repository(&self, full_repo_name: &str) -> RepoHistory {
+        self.repos
+            .lock()
+            .unwrap()
+            .get(full_repo_name)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Copies of histories of all repositories, for a snapshot.
+    pub fn all(&self) -> HashMap<String, RepoHistory> {
+        self.repos.lock().unwrap().clone()
+    }
+
+    /// Add entries from a snapshot, keeping the latest of the known and restored merges.
+    pub fn restore(&self, full_repo_name: &str, entries: RepoHistory) {
+        let mut g = self.repos.lock().unwrap();
+        let history = g.entry(full_repo_name.to_string()).or_default();
+        for (path, merge) in entries {
+            match history.get(&path) {
+                Some(known) if known.merged_at > merge.merged_at => {}
+                _ => {
+                    history.insert(path, merge);
+                }
+            }
+        }
+    }
+
+    pub fn rename_repository(&self, old_full_name: &str, new_full_name: &str) {
+        let mut g = self.repos.lock().unwrap();
+        if let Some(history) = g.remove(old_full_name) {
+            g.insert(new_full_name.to_string(), history);
+        }
+    }
+
+    pub fn remove_repository(&self, full_repo_name: &str) {
+        self.repos.lock().unwrap().remove(full_repo_name);
+    }
+
+    /// Keep using the history after a panic while it was being updated. Every entry is written whole, so it is still
+    /// valid, if possibly missing the last merge.
+    pub fn clear_poison(&self) {
+        self.repos.clear_poison();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::helpers::diff::{ChangeKind, ChangedFile, DiffSummary};
+    use crate::test::GitHubServer;
+
+    #[tokio::test]
+    async fn only_latest_merges_of_originals_are_kept() {
+        let mut server = GitHubServer::new().await;
+        let mut merged_pull = |files: &[&str], merged_at: &str| {
+            let mut pull = server.make_pull("test/repo", files);
+            pull.merged = true;
+            pull.merged_at = Some(merged_at.parse().unwrap());
+            pull
+        };
+        let first = merged_pull(
+            &[
+                "wiki/Article/en.md",
+                "wiki/Article/ru.md",
+                "wiki/Other/en.md",
+            ],
+            "2024-01-02T00:00:00Z",
+        );
+        let second = merged_pull(&["wiki/Article/en.md"], "2024-01-03T00:00:00Z");
+        let mut deleted = merged_pull(&[], "2024-01-04T00:00:00Z");
+        deleted.diff = Some(DiffSummary {
+            files: vec![ChangedFile {
+                path: "wiki/Other/en.md".to_string(),
+                kind: ChangeKind::Deleted,
+                hunks: Vec::new(),
+            }],
+            has_line_ranges: false,
+        });
+
+        let history = ArticleHistory::default();
+        history.record("test/repo", &second, ArticleLayout::Folder);
+        history.record("test/repo", &first, ArticleLayout::Folder);
+        history.record("test/repo", &deleted, ArticleLayout::Folder);
+
+        let entries = history.repository("test/repo");
+        assert_eq!(
+            entries.keys().collect::<Vec<_>>(),
+            vec!["wiki/Article/en.md", "wiki/Other/en.md"]
+        );
+        assert_eq!(entries["wiki/Article/en.md"].pull_number, second.number);
+        assert_eq!(
+            history.last_merge("test/repo", "wiki/Other/en.md"),
+            Some(LastMerge {
+                pull_number: first.number,
+                html_url: first.html_url.clone(),
+                merged_at: first.merged_at.unwrap(),
+            })
+        );
+
+        history.rename_repository("test/repo", "test/renamed");
+        assert!(history.repository("test/repo").is_empty());
+        assert_eq!(history.repository("test/renamed"), entries);
+        history.remove_repository("test/renamed");
+        assert!(history.all().is_empty());
+    }
+}
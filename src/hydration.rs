@@ -0,0 +1,140 @@
+//! Progress of reading installations, repositories and their pull requests on start-up, which takes a while for large
+//! installations. It is logged as it goes, and served by `GET /api/v1/hydration`, so that a slow start can be told from
+//! a stuck one.
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "kebab-case")]
+pub enum Stage {
+    /// Start-up hasn't begun yet.
+    #[default]
+    Pending,
+    InProgress,
+    Done,
+
+    /// Start-up has stopped with an error (see the log).
+    Failed,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct Progress {
+    pub stage: Stage,
+    pub started_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    pub installations_total: usize,
+    pub installations_done: usize,
+
+    /// Repositories of the installations read so far: the total grows as installations are read.
+    pub repositories_total: usize,
+    pub repositories_done: usize,
+
+    /// The repository whose pulls are being read.
+    pub current_repository: Option<String>,
+
+    pub pulls_hydrated: usize,
+
+    /// Diffs read one by one. Pulls read via GraphQL come with their files, and are not counted here.
+    pub diffs_fetched: usize,
+}
+
+/// Start-up progress, updated by the controller and read by the API while the controller is busy.
+#[derive(Debug, Clone, Default)]
+pub struct Hydration(Arc<Mutex<Progress>>);
+
+impl Hydration {
+    pub fn progress(&self) -> Progress {
+        self.0.lock().unwrap().clone()
+    }
+
+    /// Start over, for example after the controller has recovered from a panic.
+    pub fn begin(&self) {
+        *self.0.lock().unwrap() = Progress {
+            stage: Stage::InProgress,
+            started_at: Some(chrono::Utc::now()),
+            ..Default::default()
+        };
+    }
+
+    pub fn set_installations(&self, total: usize) {
+        self.0.lock().unwrap().installations_total = total;
+        log::info!("Hydration: reading {} installation(s)", total);
+    }
+
+    pub fn add_repositories(&self, count: usize) {
+        if let Some(mut p) = self.in_progress() {
+            p.repositories_total += count;
+        }
+    }
+
+    pub fn start_repository(&self, full_repo_name: &str) {
+        if let Some(mut p) = self.in_progress() {
+            p.current_repository = Some(full_repo_name.to_string());
+            log::info!(
+                "Hydration: reading {} (repository {} of {} known so far)",
+                full_repo_name,
+                p.repositories_done + 1,
+                p.repositories_total
+            );
+        }
+    }
+
+    pub fn finish_repository(&self) {
+        if let Some(mut p) = self.in_progress() {
+            p.repositories_done += 1;
+            log::info!(
+                "Hydration: {} (repository {} of {}) is done, {} pull(s) hydrated and {} diff(s) fetched so far",
+                p.current_repository.take().unwrap_or_default(),
+                p.repositories_done,
+                p.repositories_total,
+                p.pulls_hydrated,
+                p.diffs_fetched
+            );
+        }
+    }
+
+    pub fn finish_installation(&self) {
+        if let Some(mut p) = self.in_progress() {
+            p.installations_done += 1;
+        }
+    }
+
+    pub fn pull_hydrated(&self) {
+        if let Some(mut p) = self.in_progress() {
+            p.pulls_hydrated += 1;
+        }
+    }
+
+    pub fn diff_fetched(&self) {
+        if let Some(mut p) = self.in_progress() {
+            p.diffs_fetched += 1;
+        }
+    }
+
+    /// Mark start-up as finished. A state imported from a snapshot counts as a finished start-up without any reads.
+    pub fn finish(&self, succeeded: bool) {
+        let mut p = self.0.lock().unwrap();
+        p.stage = if succeeded {
+            Stage::Done
+        } else {
+            Stage::Failed
+        };
+        p.current_repository = None;
+        p.finished_at = Some(chrono::Utc::now());
+        log::info!(
+            "Hydration: {:?} with {} repositories, {} pull(s) hydrated and {} diff(s) fetched",
+            p.stage,
+            p.repositories_done,
+            p.pulls_hydrated,
+            p.diffs_fetched
+        );
+    }
+
+    /// Progress is only counted during start-up: repositories and pulls added later are not part of it.
+    fn in_progress(&self) -> Option<std::sync::MutexGuard<'_, Progress>> {
+        let p = self.0.lock().unwrap();
+        (p.stage == Stage::InProgress).then_some(p)
+    }
+}
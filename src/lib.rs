@@ -1,10 +1,27 @@
+pub mod api;
+pub mod archive;
+pub mod audit;
 pub mod config;
 pub mod controller;
 pub mod github;
+pub mod graph;
 pub mod handler;
 pub mod helpers;
+pub mod history;
+pub mod hydration;
 pub mod memory;
+pub mod notifiers;
+pub mod repo_config;
+pub mod retries;
+pub mod self_check;
+pub mod server;
+pub mod state;
 pub mod structs;
+pub mod throttle;
+pub mod top;
+pub mod trace;
+pub mod version;
+pub mod watchdog;
 
 #[cfg(test)]
 pub(crate) mod test;
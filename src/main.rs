@@ -2,17 +2,15 @@
 
 use std::net::SocketAddr;
 
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use eyre::Result;
+use hyper::server::conn::{AddrIncoming, AddrStream};
 
-use observatory::github::GitHub;
-use viz::middleware::limits;
-use viz::{types::State, Router, Server, ServiceMaker};
-use viz::{IntoResponse, Response, ResponseExt};
-use viz::{Request, RequestExt, StatusCode};
+use observatory::github::{Client, GitHub};
+use viz::{Server, ServiceMaker};
 
-use observatory::helpers::digest::RequestValidator;
-use observatory::{config, controller, handler, helpers::cgroup};
+use observatory::helpers::allowlist::HookAllowlist;
+use observatory::{archive, config, controller, handler, server, state, top};
 
 #[derive(Parser, Debug)]
 #[command(version)]
@@ -20,57 +18,103 @@ struct Args {
     /// Path to settings.yaml. Omit to search in current working directory
     #[arg(short, long, default_value_t = config::DEFAULT_FILE_NAME.to_string())]
     config: String,
+
+    /// Seed the app with a state snapshot (from /api/v1/state/export) instead of reading everything from GitHub
+    #[arg(long)]
+    import_state: Option<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-pub async fn index(_: Request) -> viz::Result<Response> {
-    if !cfg!(windows) {
-        let mut body = Vec::new();
-        for (header, value) in cgroup::CGroup::current().summary() {
-            let val = value.replace('\n', "<br/>");
-            body.push(format!(r"<h3><tt>{header}</tt></h3><tt>{val}</tt><br/>"));
-        }
-        return Ok(Response::html(body.join("")));
-    }
-    Ok(Response::html(r"¯\_(ツ)_/¯".to_owned()))
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Process a webhook payload saved to `debug.payload_dir` as if it has just arrived, and exit
+    Replay {
+        /// Path to the archived payload
+        file: String,
+
+        /// Actually post, update and remove comments on GitHub, which is disabled by default
+        #[arg(long)]
+        post_comments: bool,
+    },
+
+    /// Show the state of a running instance in the terminal, refreshed until interrupted. Requires the admin API
+    Top {
+        /// Where the instance is served
+        #[arg(long, default_value = "http://127.0.0.1:3000")]
+        url: String,
+
+        /// One of `server.admin_tokens`. Read from OBSERVATORY_ADMIN_TOKEN if omitted
+        #[arg(long)]
+        token: Option<String>,
+
+        /// Seconds between refreshes
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
 }
 
-pub async fn github_events(mut req: Request) -> viz::Result<()> {
-    let event_type = req.header::<_, String>("X-GitHub-Event").ok_or_else(|| {
-        log::warn!("GitHub event is missing the event type header, rejecting");
-        StatusCode::FORBIDDEN.into_error()
-    })?;
-
-    let signature_header = req
-        .header::<_, String>("X-Hub-Signature-256")
-        .ok_or_else(|| {
-            log::warn!("GitHub event is missing the signature header, rejecting");
-            StatusCode::FORBIDDEN.into_error()
-        })?;
-    let signature = &signature_header.strip_prefix("sha256=").unwrap();
-
-    let body = req.text().await?;
-    let validator = req
-        .state::<RequestValidator>()
-        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
-    if !validator.validate(&body, signature).unwrap() {
-        return Err(StatusCode::FORBIDDEN.into_error());
+/// Run all startup checks before anything else is set up, and report every problem found at once.
+async fn validate_settings(settings: &config::Config) -> Result<()> {
+    let mut problems = settings.validate();
+    if problems.is_empty() {
+        let private_key = std::fs::read_to_string(&settings.github.app_key_path)?;
+        let client = Client::new(
+            GitHub::default(),
+            settings.github.app_id.clone(),
+            private_key,
+        );
+        if let Err(e) = client.read_app().await {
+            problems.push(format!("GitHub App is not reachable: {}", e));
+        }
     }
 
-    match event_type.as_str() {
-        "pull_request" => handler::pull_request_event(req, body).await,
-        "installation" => handler::installation_event(req, body).await,
-        "installation_repositories" => handler::installation_repositories_event(req, body).await,
-        _ => Ok(()),
+    if !problems.is_empty() {
+        eyre::bail!(
+            "Invalid configuration:\n{}",
+            problems
+                .iter()
+                .map(|p| format!("  - {p}"))
+                .collect::<Vec<_>>()
+                .join("\n")
+        );
     }
+    Ok(())
 }
 
-const DEFAULT_DATA_LIMIT: u64 = 10 * 1024 * 1024; // 10 Mb
-
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
-    let settings = config::Config::from_path(&args.config)?;
+    if let Some(Command::Top {
+        url,
+        token,
+        interval,
+    }) = args.command
+    {
+        // A client of another instance, which needs none of the settings.
+        let token = match token {
+            Some(token) => token,
+            None => std::env::var("OBSERVATORY_ADMIN_TOKEN")
+                .map_err(|_| eyre::eyre!("Pass --token or set OBSERVATORY_ADMIN_TOKEN"))?,
+        };
+        return top::run(
+            top::Client::new(&url, token),
+            std::time::Duration::from_secs(interval.max(1)),
+        )
+        .await;
+    }
+    let mut settings = config::Config::from_path(&args.config)?;
+    if let Some(Command::Replay { post_comments, .. }) = &args.command {
+        // Keep a local replay from interfering with GitHub and the retry queue of a running instance.
+        if !post_comments {
+            settings.controller.disable_comments();
+        }
+        settings.controller.retry_queue = None;
+    }
+    validate_settings(&settings).await?;
     let addr = SocketAddr::from((settings.server.bind_ip, settings.server.port));
+    let events_endpoints = settings.events_endpoints();
 
     let logging_config = simplelog::ConfigBuilder::new()
         .set_time_format_custom(simplelog::format_description!(
@@ -89,7 +133,7 @@ async fn main() -> Result<()> {
         let file = std::fs::OpenOptions::new()
             .create(true)
             .append(true)
-            .open(settings.logging.file)
+            .open(&settings.logging.file)
             .expect("Failed to open the log file -- check CLI arguments");
         simplelog::WriteLogger::init(settings.logging.level, logging_config, file)
             .expect("Failed to configure the file logger");
@@ -100,36 +144,83 @@ async fn main() -> Result<()> {
 
     let private_key = std::fs::read_to_string(std::path::Path::new(&settings.github.app_key_path))
         .expect("Failed to read GitHub App private key");
-    let webhook_secret = settings.github.webhook_secret;
-
-    let validator = RequestValidator::new(webhook_secret);
+    let allowlist = settings.server.github_ips_only.then(|| {
+        HookAllowlist::new(Client::new(
+            GitHub::default(),
+            settings.github.app_id.clone(),
+            private_key.clone(),
+        ))
+        .with_trusted_proxies(settings.server.trusted_proxy_ranges())
+    });
     let controller_handle = controller::ControllerHandle::new(
         GitHub::default(),
-        settings.github.app_id,
+        settings.github.app_id.clone(),
         private_key,
         settings.controller.clone(),
     );
-    controller_handle.init().await?;
-
-    let ls = viz::types::Limits::new()
-        .insert("bytes", DEFAULT_DATA_LIMIT)
-        .insert("json", DEFAULT_DATA_LIMIT)
-        .insert("payload", DEFAULT_DATA_LIMIT)
-        .insert("text", DEFAULT_DATA_LIMIT);
-
-    let app = Router::new()
-        .post(&settings.server.events_endpoint, github_events)
-        .get("/", index)
-        .with(State::new(controller_handle))
-        .with(State::new(validator))
-        .with(limits::Config::default().limits(ls));
-
-    log::info!("Listening on {}/{}", addr, settings.server.events_endpoint);
-    if let Err(err) = Server::bind(&addr).serve(ServiceMaker::from(app)).await {
+    let startup_handle = controller_handle.clone();
+    let startup = async move {
+        match args.import_state {
+            Some(path) => {
+                log::info!("Importing state from {}", path);
+                startup_handle
+                    .import_state(state::Snapshot::from_path(&path)?)
+                    .await
+            }
+            None => startup_handle.init().await,
+        }
+    };
+
+    if let Some(Command::Replay { file, .. }) = args.command {
+        startup.await?;
+        let event = archive::load(std::path::Path::new(&file))?;
+        log::info!(
+            "Replaying a \"{}\" event (delivery {:?}) from {}",
+            event.event_type,
+            event.delivery_id,
+            file
+        );
+        handler::dispatch(&controller_handle, &event).await?;
+        // The controller handles requests in order, so this returns once the event has been processed.
+        controller_handle.export_state().await?;
+        log::info!("----- Replay finished");
+        return Ok(());
+    }
+
+    let app = server::make_app(&settings, controller_handle, allowlist)?;
+
+    for e in &events_endpoints {
+        log::info!("Listening on {}{}", addr, e.path);
+    }
+    // Requests are served while repositories are still being read, so that the progress can be followed via
+    // `/api/v1/hydration`. Webhook events wait in the queue until the controller is ready.
+    let mut serve: std::pin::Pin<Box<dyn std::future::Future<Output = hyper::Result<()>>>> =
+        match &settings.server.tls {
+            Some(tls) => {
+                let tls_config = viz::tls::rustls::Config::new()
+                    .cert(std::fs::read(&tls.cert_path)?)
+                    .key(std::fs::read(&tls.key_path)?)
+                    .build()
+                    .map_err(|e| eyre::eyre!("Failed to set up TLS: {}", e))?;
+                let listener = viz::tls::Listener::<_, _, AddrStream>::new(
+                    AddrIncoming::bind(&addr)?,
+                    viz::tls::rustls::TlsAcceptor::from(std::sync::Arc::new(tls_config)),
+                );
+                log::info!("Serving HTTPS");
+                Box::pin(Server::builder(listener).serve(ServiceMaker::from(app)))
+            }
+            None => Box::pin(Server::bind(&addr).serve(ServiceMaker::from(app))),
+        };
+    let result = tokio::select! {
+        started = startup => {
+            started?;
+            serve.await
+        }
+        result = &mut serve => result,
+    };
+    if let Err(err) = result {
         log::error!("{:?}", err);
     }
 
     Ok(())
 }
-
-// TODO: add tests for event processing?
@@ -1,14 +1,97 @@
-// TODO: document members of the module where it makes sense
+//! Storage of open pull requests, which incoming updates are compared against to find conflicts.
 
-use std::collections::HashMap;
+use std::collections::{BTreeSet, HashMap};
 use std::sync::{Arc, Mutex};
 
 use crate::structs::*;
 
-/// The two-level pull request storage (repository -> pull number -> pull object)
+/// Index keys for all files changed by a pull: its base branch, and the directory of a file.
+/// For articles, the directory is the article path shared by all its translations. Directories are lowercased,
+/// so that pulls compared regardless of case (see [`crate::helpers::diff::CaseInsensitive`]) are found as well.
+fn article_keys(pull: &PullRequest) -> BTreeSet<ArticleKey> {
+    pull.diff
+        .iter()
+        .flat_map(|d| &d.files)
+        .filter_map(|f| {
+            std::path::Path::new(&f.path)
+                .parent()
+                .map(|p| (pull.base.branch.clone(), p.to_string_lossy().to_lowercase()))
+        })
+        .collect()
+}
+
+/// (Base branch, article directory). Pulls only conflict within the branch they target.
+type ArticleKey = (String, String);
+
+/// Article key -> numbers of pulls which change it.
+type ArticleIndex = HashMap<ArticleKey, BTreeSet<i32>>;
+
+/// Pull number -> IDs of approving reviewers.
+type Approvals = HashMap<i32, BTreeSet<i64>>;
+
+/// The name of a repository, as a key of [`Memory`]. It can be borrowed as `&str` for lookups.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct RepoKey(String);
+
+impl RepoKey {
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for RepoKey {
+    fn from(full_repo_name: &str) -> Self {
+        Self(full_repo_name.to_string())
+    }
+}
+
+impl std::borrow::Borrow<str> for RepoKey {
+    fn borrow(&self) -> &str {
+        &self.0
+    }
+}
+
+impl std::fmt::Display for RepoKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// Everything known about pulls of one repository. Kept together, so that the index never disagrees with the pulls.
+#[derive(Default, Debug)]
+struct RepoMemory {
+    /// Pull number -> pull object.
+    pulls: HashMap<i32, PullRequest>,
+    /// Inverted index of changed articles, used to skip comparing unrelated pulls.
+    articles: ArticleIndex,
+    /// IDs of maintainers whose latest review of a pull is an approval.
+    approvals: Approvals,
+}
+
+impl RepoMemory {
+    fn index(&mut self, pull: &PullRequest) {
+        for key in article_keys(pull) {
+            self.articles.entry(key).or_default().insert(pull.number);
+        }
+    }
+
+    fn unindex(&mut self, pull: &PullRequest) {
+        for key in article_keys(pull) {
+            if let Some(numbers) = self.articles.get_mut(&key) {
+                numbers.remove(&pull.number);
+                if numbers.is_empty() {
+                    self.articles.remove(&key);
+                }
+            }
+        }
+    }
+}
+
+/// Pull request storage, split by repository. Pulls are handed out one by one, or visited in place,
+/// so that whole repositories are never copied.
 #[derive(Default, Debug, Clone)]
 pub struct Memory {
-    pub pulls: Arc<Mutex<HashMap<String, HashMap<i32, PullRequest>>>>,
+    repos: Arc<Mutex<HashMap<RepoKey, RepoMemory>>>,
 }
 
 impl Memory {
@@ -16,46 +99,215 @@ impl Memory {
         Self::default()
     }
 
-    pub fn contains(&self, full_repo_name: &str, pr: &PullRequest) -> bool {
-        let g = self.pulls.lock().unwrap();
-        g.get(full_repo_name)
-            .map_or(false, |pulls| pulls.contains_key(&pr.number))
+    pub fn contains(&self, full_repo_name: &str, pull_number: i32) -> bool {
+        self.repos
+            .lock()
+            .unwrap()
+            .get(full_repo_name)
+            .is_some_and(|r| r.pulls.contains_key(&pull_number))
+    }
+
+    /// Whether any pulls of the repository have been stored (even if all of them have been removed since).
+    pub fn has_repository(&self, full_repo_name: &str) -> bool {
+        self.repos.lock().unwrap().contains_key(full_repo_name)
     }
 
+    /// Save a pull request, unless a newer version of it is already stored.
     pub fn insert_pull(&self, full_repo_name: &str, new_pull: PullRequest) {
-        let mut g = self.pulls.lock().unwrap();
-        if let Some(pull) = g
-            .entry(full_repo_name.to_string())
-            .or_default()
-            .get(&new_pull.number)
-        {
+        let mut g = self.repos.lock().unwrap();
+        if !g.contains_key(full_repo_name) {
+            g.insert(full_repo_name.into(), RepoMemory::default());
+        }
+        let repo = g.get_mut(full_repo_name).unwrap();
+        if let Some(pull) = repo.pulls.get(&new_pull.number) {
             if pull.updated_at >= new_pull.updated_at {
                 return;
             }
         }
-        g.entry(full_repo_name.to_string())
-            .or_default()
-            .insert(new_pull.number, new_pull);
+        if let Some(old_pull) = repo.pulls.remove(&new_pull.number) {
+            repo.unindex(&old_pull);
+        }
+        repo.index(&new_pull);
+        repo.pulls.insert(new_pull.number, new_pull);
     }
 
     pub fn remove_pull(&self, full_repo_name: &str, p: &PullRequest) {
-        if let Some(pulls) = self.pulls.lock().unwrap().get_mut(full_repo_name) {
-            pulls.remove(&p.number);
+        if let Some(repo) = self.repos.lock().unwrap().get_mut(full_repo_name) {
+            if let Some(old_pull) = repo.pulls.remove(&p.number) {
+                repo.unindex(&old_pull);
+            }
+            repo.approvals.remove(&p.number);
         }
     }
 
-    pub fn pulls(&self, full_repo_name: &str) -> Option<HashMap<i32, PullRequest>> {
-        self.pulls
+    /// Remember whether a reviewer currently approves a pull.
+    pub fn set_approval(
+        &self,
+        full_repo_name: &str,
+        pull_number: i32,
+        reviewer_id: i64,
+        approved: bool,
+    ) {
+        let mut g = self.repos.lock().unwrap();
+        if !g.contains_key(full_repo_name) {
+            g.insert(full_repo_name.into(), RepoMemory::default());
+        }
+        let reviewers = g
+            .get_mut(full_repo_name)
+            .unwrap()
+            .approvals
+            .entry(pull_number)
+            .or_default();
+        if approved {
+            reviewers.insert(reviewer_id);
+        } else {
+            reviewers.remove(&reviewer_id);
+        }
+    }
+
+    pub fn is_approved(&self, full_repo_name: &str, pull_number: i32) -> bool {
+        self.repos
+            .lock()
+            .unwrap()
+            .get(full_repo_name)
+            .and_then(|r| r.approvals.get(&pull_number))
+            .is_some_and(|reviewers| !reviewers.is_empty())
+    }
+
+    /// Remember the labels of a stored pull request after changing them.
+    pub fn set_labels(&self, full_repo_name: &str, pull_number: i32, labels: Vec<Label>) {
+        if let Some(pull) = self
+            .repos
             .lock()
             .unwrap()
-            .get(&full_repo_name.to_string())
+            .get_mut(full_repo_name)
+            .and_then(|r| r.pulls.get_mut(&pull_number))
+        {
+            pull.labels = labels;
+        }
+    }
+
+    pub fn pull(&self, full_repo_name: &str, pull_number: i32) -> Option<PullRequest> {
+        self.repos
+            .lock()
+            .unwrap()
+            .get(full_repo_name)
+            .and_then(|r| r.pulls.get(&pull_number))
             .cloned()
     }
 
-    pub fn drop_repository(&self, full_repo_name: &str) {
-        self.pulls
+    /// Stored pulls which target the same base branch and change at least one article also changed by `pull`
+    /// (the pull itself included, if it is stored).
+    pub fn pulls_sharing_articles(
+        &self,
+        full_repo_name: &str,
+        pull: &PullRequest,
+    ) -> HashMap<i32, PullRequest> {
+        let g = self.repos.lock().unwrap();
+        let Some(repo) = g.get(full_repo_name) else {
+            return HashMap::new();
+        };
+        article_keys(pull)
+            .iter()
+            .filter_map(|key| repo.articles.get(key))
+            .flatten()
+            .filter_map(|number| repo.pulls.get(number).map(|p| (*number, p.clone())))
+            .collect()
+    }
+
+    /// Stored pulls which change files in `directory` (see [`article_keys`]), on any base branch, in ascending order of their
+    /// numbers. `None` if the repository is unknown.
+    pub fn pulls_in_directory(
+        &self,
+        full_repo_name: &str,
+        directory: &str,
+    ) -> Option<Vec<PullRequest>> {
+        let g = self.repos.lock().unwrap();
+        let repo = g.get(full_repo_name)?;
+        let directory = directory.to_lowercase();
+        let branches: BTreeSet<&String> = repo.pulls.values().map(|p| &p.base.branch).collect();
+        let numbers: BTreeSet<i32> = branches
+            .into_iter()
+            .filter_map(|branch| repo.articles.get(&(branch.clone(), directory.clone())))
+            .flatten()
+            .copied()
+            .collect();
+        Some(
+            numbers
+                .iter()
+                .filter_map(|number| repo.pulls.get(number).cloned())
+                .collect(),
+        )
+    }
+
+    /// Numbers of stored pulls of the repository, in ascending order.
+    pub fn pull_numbers(&self, full_repo_name: &str) -> Vec<i32> {
+        let mut numbers: Vec<_> = self
+            .repos
             .lock()
             .unwrap()
-            .remove(&full_repo_name.to_string());
+            .get(full_repo_name)
+            .map(|r| r.pulls.keys().copied().collect())
+            .unwrap_or_default();
+        numbers.sort();
+        numbers
+    }
+
+    /// Visit stored pulls of the repository in ascending order of their numbers, without copying them.
+    ///
+    /// The storage is locked while visiting, so `f` must not call back into it.
+    pub fn for_each_pull(&self, full_repo_name: &str, mut f: impl FnMut(&PullRequest)) {
+        let g = self.repos.lock().unwrap();
+        let Some(repo) = g.get(full_repo_name) else {
+            return;
+        };
+        let mut pulls: Vec<_> = repo.pulls.values().collect();
+        pulls.sort_by_key(|p| p.number);
+        for p in pulls {
+            f(p);
+        }
+    }
+
+    /// Stored pulls of the repository which don't fit into `limit`, least recently updated first: the ones to forget in
+    /// order to keep the `limit` most recently updated pulls.
+    pub fn pulls_beyond(&self, full_repo_name: &str, limit: usize) -> Vec<PullRequest> {
+        let g = self.repos.lock().unwrap();
+        let Some(repo) = g.get(full_repo_name) else {
+            return Vec::new();
+        };
+        let Some(excess) = repo.pulls.len().checked_sub(limit) else {
+            return Vec::new();
+        };
+        let mut pulls: Vec<_> = repo.pulls.values().collect();
+        pulls.sort_by_key(|p| (p.updated_at, p.number));
+        pulls.into_iter().take(excess).cloned().collect()
+    }
+
+    /// Names of all repositories with stored pulls.
+    pub fn repositories(&self) -> Vec<RepoKey> {
+        let mut keys: Vec<_> = self.repos.lock().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    /// Copies of all stored pulls of the repository.
+    pub fn pulls(&self, full_repo_name: &str) -> Option<HashMap<i32, PullRequest>> {
+        self.repos
+            .lock()
+            .unwrap()
+            .get(full_repo_name)
+            .map(|r| r.pulls.clone())
+    }
+
+    /// Move pulls of a repository to its new name, after it has been renamed or transferred.
+    pub fn rename_repository(&self, old_full_name: &str, new_full_name: &str) {
+        let mut g = self.repos.lock().unwrap();
+        if let Some(repo) = g.remove(old_full_name) {
+            g.insert(new_full_name.into(), repo);
+        }
+    }
+
+    pub fn drop_repository(&self, full_repo_name: &str) {
+        self.repos.lock().unwrap().remove(full_repo_name);
     }
 }
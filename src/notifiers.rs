@@ -0,0 +1,147 @@
+//! Destinations for conflict notifications besides pull request comments, such as chat webhooks and email.
+//!
+//! Every destination implements [`Notifier`], and [`Notifiers`] routes conflicts to the destinations configured for their
+//! repository. Notifications follow conflict comments: they are sent when a conflict is found, changes, or is resolved.
+use async_trait::async_trait;
+use eyre::Result;
+
+use crate::config;
+use crate::helpers::conflicts::{Conflict, ConflictType};
+
+mod email;
+mod webhook;
+
+#[cfg(test)]
+use email::Contents;
+pub use email::Email;
+pub use webhook::ChatWebhook;
+
+/// Conflicts listing more files than this are summarized with the number of files.
+const MAX_LISTED_FILES: usize = 10;
+
+/// A change to a conflict between two pull requests.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Notification {
+    pub full_repo_name: String,
+    pub conflict: Conflict,
+
+    /// A link to the trigger pull request (the original one is [`Conflict::reference_url`]).
+    pub trigger_url: String,
+
+    /// Whether the conflict no longer exists.
+    pub resolved: bool,
+}
+
+impl Notification {
+    /// A short Markdown description of the change, understood by both Discord and Slack.
+    pub fn summary(&self) -> String {
+        let c = &self.conflict;
+        if self.resolved {
+            return format!(
+                "[{}] Resolved: {} no longer conflicts with {}",
+                self.full_repo_name, self.trigger_url, c.reference_url
+            );
+        }
+
+        let description = match c.kind {
+            ConflictType::IncompleteTranslation => "may be missing changes from",
+            _ => "has edited same files as",
+        };
+        let mut lines = vec![format!(
+            "[{}] {} {} {}",
+            self.full_repo_name, self.trigger_url, description, c.reference_url
+        )];
+        if c.file_set.len() > MAX_LISTED_FILES {
+            lines.push(format!("- {} files", c.file_set.len()));
+        } else {
+            lines.extend(c.file_set.iter().map(|f| format!("- `{f}`")));
+        }
+        lines.join("\n")
+    }
+}
+
+#[async_trait]
+pub trait Notifier: std::fmt::Debug + Send + Sync {
+    /// Whether the destination is interested in the notification at all.
+    fn wants(&self, _notification: &Notification) -> bool {
+        true
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()>;
+}
+
+/// A destination along with the repositories it is interested in.
+#[derive(Debug)]
+struct Route {
+    repositories: Vec<String>,
+    notifier: Box<dyn Notifier>,
+}
+
+impl Route {
+    fn accepts(&self, full_repo_name: &str) -> bool {
+        self.repositories.is_empty() || self.repositories.iter().any(|r| r == full_repo_name)
+    }
+}
+
+/// All configured destinations. Without any, nothing is sent.
+#[derive(Debug, Default)]
+pub struct Notifiers {
+    routes: Vec<Route>,
+}
+
+impl Notifiers {
+    pub fn new(settings: &[config::Notifier]) -> Self {
+        let mut routes = Vec::new();
+        for s in settings {
+            let notifier: Box<dyn Notifier> = match &s.sink {
+                config::Sink::Discord { webhook_url } => {
+                    Box::new(ChatWebhook::discord(webhook_url.clone()))
+                }
+                config::Sink::Slack { webhook_url } => {
+                    Box::new(ChatWebhook::slack(webhook_url.clone()))
+                }
+                config::Sink::Email(email) => match Email::smtp(email) {
+                    Ok(n) => Box::new(n),
+                    Err(e) => {
+                        log::error!(
+                            "Failed to set up email notifications via {}, skipping them: {:?}",
+                            email.smtp_server,
+                            e
+                        );
+                        continue;
+                    }
+                },
+            };
+            routes.push(Route {
+                repositories: s.repositories.clone(),
+                notifier,
+            });
+        }
+        Self { routes }
+    }
+
+    /// Deliver notifications to every destination which is interested in their repositories. Destinations are independent,
+    /// and failures are only logged, since comments remain the primary way of notifying people.
+    pub async fn send(&self, notifications: &[Notification]) {
+        for n in notifications {
+            for route in self
+                .routes
+                .iter()
+                .filter(|r| r.accepts(&n.full_repo_name) && r.notifier.wants(n))
+            {
+                if let Err(e) = route.notifier.send(n).await {
+                    log::error!(
+                        "Failed to send a notification about pull #{} in {} to {:?}: {:?}",
+                        n.conflict.trigger,
+                        n.full_repo_name,
+                        route.notifier,
+                        e
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,163 @@
+//! Emails about incomplete translations, for translators who don't follow GitHub notifications.
+//!
+//! Emails carry the same text as the comment in the translation pull request, in both plain text and HTML.
+use async_trait::async_trait;
+use eyre::Result;
+use lettre::message::{Mailbox, MultiPart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use pulldown_cmark::{Event, Options, Parser, Tag};
+
+use super::{Notification, Notifier};
+use crate::config;
+use crate::helpers::comments;
+use crate::helpers::conflicts::ConflictType;
+use crate::helpers::ToMarkdown;
+
+pub struct Email<T = AsyncSmtpTransport<Tokio1Executor>> {
+    transport: T,
+    from: Mailbox,
+    to: Vec<Mailbox>,
+}
+
+impl<T> std::fmt::Debug for Email<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let to: Vec<_> = self.to.iter().map(|m| m.email.to_string()).collect();
+        write!(f, "email to {}", to.join(", "))
+    }
+}
+
+impl Email {
+    /// Set up sending over SMTP with STARTTLS. No connection is made until the first email is sent.
+    pub fn smtp(settings: &config::Email) -> Result<Self> {
+        let transport =
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&settings.smtp_server)?
+                .port(settings.smtp_port)
+                .credentials(Credentials::new(
+                    settings.username.clone(),
+                    settings.password.clone(),
+                ))
+                .build();
+        Self::with_transport(transport, settings)
+    }
+}
+
+impl<T> Email<T> {
+    pub fn with_transport(transport: T, settings: &config::Email) -> Result<Self> {
+        Ok(Self {
+            transport,
+            from: settings.from.parse()?,
+            to: settings
+                .to
+                .iter()
+                .map(|address| address.parse())
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    fn message(&self, notification: &Notification) -> Result<Message> {
+        let contents = Contents::new(notification);
+        let mut builder = Message::builder()
+            .from(self.from.clone())
+            .subject(contents.subject);
+        for to in &self.to {
+            builder = builder.to(to.clone());
+        }
+        Ok(builder.multipart(MultiPart::alternative_plain_html(
+            contents.text,
+            contents.html,
+        ))?)
+    }
+}
+
+/// The subject and both versions of the body of an email.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Contents {
+    pub subject: String,
+    pub text: String,
+    pub html: String,
+}
+
+impl Contents {
+    pub(super) fn new(notification: &Notification) -> Self {
+        let c = &notification.conflict;
+        let (subject, markdown) = if notification.resolved {
+            (
+                format!(
+                    "[{}] Resolved: incomplete translation in #{}",
+                    notification.full_repo_name, c.trigger
+                ),
+                comments::mark_resolved(&c.to_markdown()),
+            )
+        } else {
+            (
+                format!(
+                    "[{}] Incomplete translation in #{}: see #{}",
+                    notification.full_repo_name, c.trigger, c.original
+                ),
+                c.to_markdown(),
+            )
+        };
+        // The machine-readable header is of no use outside of GitHub.
+        let markdown = match markdown.split_once(comments::HTML_COMMENT_END) {
+            Some((_, text)) => text.trim_start().to_string(),
+            None => markdown,
+        };
+        let markdown = format!("{}\n\nPull request: {}", markdown, notification.trigger_url);
+
+        Self {
+            subject,
+            text: to_plain_text(&markdown),
+            html: comments::to_html(&markdown),
+        }
+    }
+}
+
+#[async_trait]
+impl<T> Notifier for Email<T>
+where
+    T: AsyncTransport + Send + Sync,
+    T::Error: std::error::Error + Send + Sync + 'static,
+{
+    fn wants(&self, notification: &Notification) -> bool {
+        notification.conflict.kind == ConflictType::IncompleteTranslation
+    }
+
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        self.transport.send(self.message(notification)?).await?;
+        Ok(())
+    }
+}
+
+/// Strip Markdown formatting, keeping list structure and link targets.
+fn to_plain_text(markdown: &str) -> String {
+    let mut text = String::new();
+    let mut depth = 0;
+    for event in Parser::new_ext(markdown, Options::ENABLE_STRIKETHROUGH) {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(&t),
+            Event::SoftBreak | Event::HardBreak => text.push('\n'),
+            Event::Start(Tag::List(_)) => {
+                if depth > 0 && !text.ends_with('\n') {
+                    text.push('\n');
+                }
+                depth += 1;
+            }
+            Event::End(Tag::List(_)) => {
+                depth -= 1;
+                if depth == 0 {
+                    text.push('\n');
+                }
+            }
+            Event::Start(Tag::Item) => {
+                text.push_str(&"  ".repeat(depth - 1));
+                text.push_str("- ");
+            }
+            Event::End(Tag::Item) if !text.ends_with('\n') => text.push('\n'),
+            Event::End(Tag::Link(_, url, _)) => text.push_str(&format!(" ({url})")),
+            Event::End(Tag::Paragraph) if depth == 0 => text.push_str("\n\n"),
+            _ => {}
+        }
+    }
+    text.trim_end().to_string()
+}
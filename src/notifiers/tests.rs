@@ -0,0 +1,246 @@
+use super::*;
+
+fn make_notification(full_repo_name: &str, files: &[&str], resolved: bool) -> Notification {
+    Notification {
+        full_repo_name: full_repo_name.to_string(),
+        conflict: Conflict::overlap(
+            2,
+            1,
+            "https://github.com/test/repo/pull/1".to_string(),
+            files.iter().map(|f| f.to_string()).collect(),
+        ),
+        trigger_url: "https://github.com/test/repo/pull/2".to_string(),
+        resolved,
+    }
+}
+
+#[test]
+fn notification_summary() {
+    assert_eq!(
+        make_notification(
+            "test/repo",
+            &["wiki/Article/en.md", "wiki/Other/en.md"],
+            false
+        )
+        .summary(),
+        r#"[test/repo] https://github.com/test/repo/pull/2 has edited same files as https://github.com/test/repo/pull/1
+- `wiki/Article/en.md`
+- `wiki/Other/en.md`"#
+    );
+    assert_eq!(
+        make_notification("test/repo", &["wiki/Article/en.md"; 11], false).summary(),
+        r#"[test/repo] https://github.com/test/repo/pull/2 has edited same files as https://github.com/test/repo/pull/1
+- 11 files"#
+    );
+    assert_eq!(
+        make_notification("test/repo", &["wiki/Article/en.md"], true).summary(),
+        "[test/repo] Resolved: https://github.com/test/repo/pull/2 no longer conflicts with https://github.com/test/repo/pull/1"
+    );
+}
+
+#[tokio::test]
+async fn notifications_are_routed_by_repository() {
+    let mut server = mockito::Server::new_async().await;
+    let notification = make_notification("test/repo", &["wiki/Article/en.md"], false);
+    let discord = server
+        .mock("POST", "/discord")
+        .match_body(mockito::Matcher::Json(serde_json::json!({
+            "content": notification.summary(),
+            "allowed_mentions": { "parse": [] },
+        })))
+        .with_status(204)
+        .expect(1)
+        .create_async()
+        .await;
+    let slack = server
+        .mock("POST", "/slack")
+        .match_body(mockito::Matcher::Json(
+            serde_json::json!({ "text": notification.summary() }),
+        ))
+        .with_status(200)
+        .expect(1)
+        .create_async()
+        .await;
+    let other = server.mock("POST", "/other").expect(0).create_async().await;
+
+    let notifiers = Notifiers::new(&[
+        config::Notifier {
+            repositories: Vec::new(),
+            sink: config::Sink::Discord {
+                webhook_url: format!("{}/discord", server.url()),
+            },
+        },
+        config::Notifier {
+            repositories: vec!["test/repo".to_string()],
+            sink: config::Sink::Slack {
+                webhook_url: format!("{}/slack", server.url()),
+            },
+        },
+        config::Notifier {
+            repositories: vec!["test/other-repo".to_string()],
+            sink: config::Sink::Slack {
+                webhook_url: format!("{}/other", server.url()),
+            },
+        },
+    ]);
+    notifiers.send(&[notification]).await;
+
+    discord.assert_async().await;
+    slack.assert_async().await;
+    other.assert_async().await;
+}
+
+#[tokio::test]
+async fn failed_notifier_does_not_stop_others() {
+    let mut server = mockito::Server::new_async().await;
+    let broken = server
+        .mock("POST", "/broken")
+        .with_status(404)
+        .expect(2)
+        .create_async()
+        .await;
+    let working = server
+        .mock("POST", "/working")
+        .with_status(200)
+        .expect(2)
+        .create_async()
+        .await;
+
+    let notifiers = Notifiers::new(&[
+        config::Notifier {
+            repositories: Vec::new(),
+            sink: config::Sink::Discord {
+                webhook_url: format!("{}/broken", server.url()),
+            },
+        },
+        config::Notifier {
+            repositories: Vec::new(),
+            sink: config::Sink::Slack {
+                webhook_url: format!("{}/working", server.url()),
+            },
+        },
+    ]);
+    notifiers
+        .send(&[
+            make_notification("test/repo", &["wiki/Article/en.md"], false),
+            make_notification("test/repo", &["wiki/Article/en.md"], true),
+        ])
+        .await;
+
+    broken.assert_async().await;
+    working.assert_async().await;
+}
+
+fn make_email_settings() -> config::Email {
+    config::Email {
+        smtp_server: "smtp.example.com".to_string(),
+        smtp_port: 587,
+        username: "observatory".to_string(),
+        password: "hunter2".to_string(),
+        from: "Observatory <observatory@example.com>".to_string(),
+        to: vec![
+            "translators@example.com".to_string(),
+            "Someone <someone@example.com>".to_string(),
+        ],
+    }
+}
+
+fn make_translation_notification(resolved: bool) -> Notification {
+    Notification {
+        full_repo_name: "test/repo".to_string(),
+        conflict: Conflict::incomplete_translation(
+            2,
+            1,
+            "https://github.com/test/repo/pull/1".to_string(),
+            vec!["wiki/Article/en.md".to_string()],
+        ),
+        trigger_url: "https://github.com/test/repo/pull/2".to_string(),
+        resolved,
+    }
+}
+
+#[test]
+fn email_is_sent_for_incomplete_translations_only() {
+    let email = Email::with_transport(
+        lettre::transport::stub::AsyncStubTransport::new_ok(),
+        &make_email_settings(),
+    )
+    .unwrap();
+    assert!(email.wants(&make_translation_notification(false)));
+    assert!(email.wants(&make_translation_notification(true)));
+    assert!(!email.wants(&make_notification(
+        "test/repo",
+        &["wiki/Article/en.md"],
+        false
+    )));
+}
+
+#[test]
+fn email_contents() {
+    let found = Contents::new(&make_translation_notification(false));
+    let since = chrono::Utc::now().date_naive().format("%Y-%m-%d");
+    let link = "https://github.com/test/repo/pull/1/files#diff-0e92956c18740f21a8a9b829761d51c81f66a1503c573e66b1646145214ea38b";
+    assert_eq!(
+        found.subject,
+        "[test/repo] Incomplete translation in #2: see #1"
+    );
+    assert_eq!(
+        found.text,
+        format!(
+            r#"Your translation may be missing new information from corresponding English articles. Please update your translation according to the merged changes:
+
+- https://github.com/test/repo/pull/1 (conflicting since {since}), files:
+  - wiki/Article/en.md ({link})
+
+Pull request: https://github.com/test/repo/pull/2"#
+        )
+    );
+    assert!(!found.html.contains("conflict_type"));
+    assert!(found.html.contains(&format!(
+        r#"<li><a href="{link}"><code>wiki/Article/en.md</code></a></li>"#
+    )));
+
+    let resolved = Contents::new(&make_translation_notification(true));
+    assert_eq!(
+        resolved.subject,
+        "[test/repo] Resolved: incomplete translation in #2"
+    );
+    assert!(resolved
+        .text
+        .starts_with("Resolved: this conflict no longer applies."));
+    assert!(resolved.html.contains("<del>"));
+}
+
+#[tokio::test]
+async fn email_is_sent_to_all_recipients() {
+    let transport = lettre::transport::stub::AsyncStubTransport::new_ok();
+    let email = Email::with_transport(transport.clone(), &make_email_settings()).unwrap();
+    email
+        .send(&make_translation_notification(false))
+        .await
+        .unwrap();
+
+    let messages = transport.messages().await;
+    assert_eq!(messages.len(), 1);
+    let (envelope, message) = &messages[0];
+    assert_eq!(envelope.to().len(), 2);
+    assert_eq!(
+        envelope.from().unwrap().to_string(),
+        "observatory@example.com"
+    );
+    assert!(message.contains("Subject: [test/repo] Incomplete translation in #2: see #1"));
+    assert!(message.contains("Content-Type: multipart/alternative"));
+}
+
+#[test]
+fn invalid_email_address_is_rejected() {
+    let settings = config::Email {
+        to: vec!["not an address".to_string()],
+        ..make_email_settings()
+    };
+    assert!(Email::with_transport(
+        lettre::transport::stub::AsyncStubTransport::new_ok(),
+        &settings
+    )
+    .is_err());
+}
@@ -0,0 +1,78 @@
+//! Incoming webhooks of chat services, which post a message to a channel for every notification.
+use async_trait::async_trait;
+use eyre::Result;
+
+use super::{Notification, Notifier};
+
+/// Slow chat services shouldn't hold up the controller for long.
+const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Service {
+    Discord,
+    Slack,
+}
+
+pub struct ChatWebhook {
+    service: Service,
+    url: String,
+    http_client: reqwest::Client,
+}
+
+// The URL contains a secret token, and is not to be logged.
+impl std::fmt::Debug for ChatWebhook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?} webhook", self.service)
+    }
+}
+
+impl ChatWebhook {
+    fn new(service: Service, url: String) -> Self {
+        Self {
+            service,
+            url,
+            http_client: reqwest::Client::builder().timeout(TIMEOUT).build().unwrap(),
+        }
+    }
+
+    pub fn discord(url: String) -> Self {
+        Self::new(Service::Discord, url)
+    }
+
+    pub fn slack(url: String) -> Self {
+        Self::new(Service::Slack, url)
+    }
+
+    fn payload(&self, notification: &Notification) -> serde_json::Value {
+        let text = notification.summary();
+        match self.service {
+            // File names are not meant to mention anyone.
+            Service::Discord => serde_json::json!({
+                "content": text,
+                "allowed_mentions": { "parse": [] },
+            }),
+            Service::Slack => serde_json::json!({ "text": text }),
+        }
+    }
+}
+
+#[async_trait]
+impl Notifier for ChatWebhook {
+    async fn send(&self, notification: &Notification) -> Result<()> {
+        let response = self
+            .http_client
+            .post(&self.url)
+            .json(&self.payload(notification))
+            .send()
+            .await?;
+        if !response.status().is_success() {
+            eyre::bail!(
+                "{:?} responded with {}: {}",
+                self,
+                response.status(),
+                response.text().await.unwrap_or_default()
+            );
+        }
+        Ok(())
+    }
+}
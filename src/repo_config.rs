@@ -0,0 +1,297 @@
+//! Per-repository settings, which maintainers commit to [`PATH`] in the default branch of their repository.
+//!
+//! All settings are optional. The file is read when a repository is added, and again after a push to the default branch
+//! changes it. A file which can't be parsed is treated as missing, so that a typo doesn't stop the app.
+//!
+//! The same settings can be given to all repositories of an account in the app's config (`controller.accounts`).
+//! Settings are merged from the global config, to the account's, to the repository's, with the last one set winning.
+use std::collections::BTreeMap;
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::conflicts::ArticleLayout;
+use crate::helpers::diff;
+
+pub const PATH: &str = ".github/observatory.yml";
+
+#[derive(Serialize, Deserialize, Debug, PartialEq, Clone, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct RepoConfig {
+    /// Set to `false` to keep the app quiet in the repository. Comments which are disabled by the app's config
+    /// (`controller.post_comments`, or the account's settings in `controller.accounts`) can't be enabled here.
+    pub post_comments: Option<bool>,
+
+    /// Labels which are added to pull requests with conflicts, and removed once the conflicts are gone.
+    pub labels: Vec<String>,
+
+    /// Languages whose articles are checked for conflicts, such as `ru` or `pt-br`. Empty means all.
+    /// English originals are always checked, since translations are compared against them.
+    pub languages: Vec<String>,
+
+    /// Paths which are never checked for conflicts, as globs: `*` matches within a directory, `**` across directories.
+    pub ignore: Vec<String>,
+
+    /// Compare paths regardless of case, so that `wiki/FAQ/en.md` and `wiki/Faq/en.md` are the same article.
+    pub case_insensitive_paths: Option<bool>,
+
+    /// Where translations are stored: `folder` (`wiki/Article/ru.md`, the default) or `suffix` (`docs/Article.ru.md`).
+    pub layout: Option<ArticleLayout>,
+
+    /// Open comments in pull requests of first-time contributors with a short explanation of what they are about.
+    pub welcome_first_time_contributors: Option<bool>,
+
+    /// Links to translation or contribution guidelines, which are mentioned to first-time contributors.
+    pub guidelines: Vec<String>,
+
+    /// Language -> users or teams to mention in comments about incomplete translations into it, such as `ru: ["@ruteam"]`.
+    pub mentions: BTreeMap<String, Vec<String>>,
+}
+
+impl RepoConfig {
+    pub fn parse(contents: &str) -> Result<Self> {
+        // An empty file is a valid YAML document, but not a valid map.
+        if contents.trim().is_empty() {
+            return Ok(Self::default());
+        }
+        Ok(serde_yaml::from_str(contents)?)
+    }
+
+    /// Apply `other` on top of these settings: values set there win, and its empty lists fall back to ours.
+    pub fn merged_with(&self, other: &RepoConfig) -> RepoConfig {
+        let pick = |ours: &Vec<String>, theirs: &Vec<String>| {
+            if theirs.is_empty() { ours } else { theirs }.clone()
+        };
+        RepoConfig {
+            post_comments: other.post_comments.or(self.post_comments),
+            labels: pick(&self.labels, &other.labels),
+            languages: pick(&self.languages, &other.languages),
+            ignore: pick(&self.ignore, &other.ignore),
+            case_insensitive_paths: other.case_insensitive_paths.or(self.case_insensitive_paths),
+            layout: other.layout.or(self.layout),
+            welcome_first_time_contributors: other
+                .welcome_first_time_contributors
+                .or(self.welcome_first_time_contributors),
+            guidelines: pick(&self.guidelines, &other.guidelines),
+            mentions: if other.mentions.is_empty() {
+                &self.mentions
+            } else {
+                &other.mentions
+            }
+            .clone(),
+        }
+    }
+
+    /// Make filters which prepare changed files for comparison (see [`crate::helpers::conflicts::compare_pulls_with`]).
+    pub fn path_filters(&self) -> diff::Pipeline {
+        let pipeline = diff::Pipeline::new().then(self.file_filter());
+        if self.case_insensitive_paths.unwrap_or(false) {
+            pipeline.then(diff::CaseInsensitive)
+        } else {
+            pipeline
+        }
+    }
+
+    /// Make a filter for changed files, which tells whether a file is checked for conflicts.
+    pub fn file_filter(&self) -> FileFilter {
+        FileFilter {
+            languages: self.languages.clone(),
+            layout: self.layout.unwrap_or_default(),
+            ignore: self
+                .ignore
+                .iter()
+                .filter_map(|g| match glob_to_regex(g) {
+                    Ok(r) => Some(r),
+                    Err(e) => {
+                        log::warn!("Skipping the invalid glob {:?}: {:?}", g, e);
+                        None
+                    }
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Compiled file settings of a [`RepoConfig`].
+#[derive(Debug, Clone, Default)]
+pub struct FileFilter {
+    languages: Vec<String>,
+    layout: ArticleLayout,
+    ignore: Vec<regex::Regex>,
+}
+
+impl FileFilter {
+    pub fn accepts(&self, path: &str) -> bool {
+        if self.ignore.iter().any(|r| r.is_match(path)) {
+            return false;
+        }
+        if self.languages.is_empty() {
+            return true;
+        }
+        // Files which are not articles have no language to check.
+        self.layout
+            .article(path)
+            .is_none_or(|a| a.is_original() || self.languages.contains(&a.language))
+    }
+}
+
+impl diff::PathFilter for FileFilter {
+    fn apply(&self, path: &str) -> Option<String> {
+        self.accepts(path).then(|| path.to_string())
+    }
+}
+
+fn glob_to_regex(glob: &str) -> Result<regex::Regex> {
+    let mut expr = String::from("^");
+    let mut chars = glob.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                // `a/**/b` also matches `a/b`.
+                if chars.peek() == Some(&'/') {
+                    chars.next();
+                    expr.push_str("(.*/)?");
+                } else {
+                    expr.push_str(".*");
+                }
+            }
+            '*' => expr.push_str("[^/]*"),
+            '?' => expr.push_str("[^/]"),
+            c => expr.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    expr.push('$');
+    Ok(regex::Regex::new(&expr)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse() {
+        assert_eq!(RepoConfig::parse("").unwrap(), RepoConfig::default());
+        assert_eq!(
+            RepoConfig::parse(
+                r#"
+post_comments: false
+labels: ["conflict"]
+languages: [ru, pt-br]
+ignore:
+  - "wiki/Legal/**"
+"#
+            )
+            .unwrap(),
+            RepoConfig {
+                post_comments: Some(false),
+                labels: vec!["conflict".to_string()],
+                languages: vec!["ru".to_string(), "pt-br".to_string()],
+                ignore: vec!["wiki/Legal/**".to_string()],
+                case_insensitive_paths: None,
+                layout: None,
+                welcome_first_time_contributors: None,
+                guidelines: Vec::new(),
+                mentions: BTreeMap::new(),
+            }
+        );
+        assert!(RepoConfig::parse("post_comment: false").is_err());
+        assert_eq!(
+            RepoConfig::parse("layout: suffix").unwrap().layout,
+            Some(ArticleLayout::Suffix)
+        );
+        let welcome = RepoConfig::parse(
+            "welcome_first_time_contributors: true\nguidelines: [\"https://example.com/guide\"]",
+        )
+        .unwrap();
+        assert_eq!(welcome.welcome_first_time_contributors, Some(true));
+        assert_eq!(welcome.guidelines, vec!["https://example.com/guide"]);
+        assert_eq!(
+            RepoConfig::parse("mentions:\n  ru: [\"@ruteam\"]")
+                .unwrap()
+                .mentions,
+            BTreeMap::from([("ru".to_string(), vec!["@ruteam".to_string()])])
+        );
+    }
+
+    #[test]
+    fn merge() {
+        let account = RepoConfig {
+            post_comments: Some(true),
+            labels: vec!["conflict".to_string()],
+            languages: vec!["ru".to_string()],
+            ..Default::default()
+        };
+        let repository = RepoConfig {
+            post_comments: Some(false),
+            languages: vec!["fr".to_string()],
+            ..Default::default()
+        };
+        assert_eq!(
+            account.merged_with(&repository),
+            RepoConfig {
+                post_comments: Some(false),
+                labels: vec!["conflict".to_string()],
+                languages: vec!["fr".to_string()],
+                ignore: Vec::new(),
+                case_insensitive_paths: None,
+                layout: None,
+                welcome_first_time_contributors: None,
+                guidelines: Vec::new(),
+                mentions: BTreeMap::new(),
+            }
+        );
+        assert_eq!(account.merged_with(&RepoConfig::default()), account);
+    }
+
+    #[test]
+    fn globs() {
+        let matches = |glob: &str, path: &str| glob_to_regex(glob).unwrap().is_match(path);
+        assert!(matches("wiki/Legal/**", "wiki/Legal/Terms/en.md"));
+        assert!(!matches("wiki/Legal/**", "wiki/Legality/en.md"));
+        assert!(matches("wiki/*/ru.md", "wiki/Article/ru.md"));
+        assert!(!matches("wiki/*/ru.md", "wiki/Article/Sub/ru.md"));
+        assert!(matches("**/fr.md", "wiki/Article/Sub/fr.md"));
+        assert!(matches("wiki/**/en.md", "wiki/en.md"));
+        assert!(matches("wiki/Article/??.md", "wiki/Article/ru.md"));
+        assert!(!matches("wiki/Article/??.md", "wiki/Article/pt-br.md"));
+    }
+
+    #[test]
+    fn file_filter() {
+        let filter = RepoConfig {
+            languages: vec!["ru".to_string()],
+            ignore: vec!["wiki/Legal/**".to_string()],
+            ..Default::default()
+        }
+        .file_filter();
+        assert!(filter.accepts("wiki/Article/en.md"));
+        assert!(filter.accepts("wiki/Article/ru.md"));
+        assert!(!filter.accepts("wiki/Article/fr.md"));
+        assert!(!filter.accepts("wiki/Legal/Terms/en.md"));
+        assert!(RepoConfig::default()
+            .file_filter()
+            .accepts("wiki/Article/fr.md"));
+    }
+
+    #[test]
+    fn path_filters() {
+        use diff::PathFilter;
+
+        let settings = RepoConfig {
+            ignore: vec!["wiki/Legal/**".to_string()],
+            case_insensitive_paths: Some(true),
+            ..Default::default()
+        };
+        let filters = settings.path_filters();
+        assert_eq!(
+            filters.apply("wiki/FAQ/en.md"),
+            Some("wiki/faq/en.md".to_string())
+        );
+        assert_eq!(filters.apply("wiki/Legal/Terms/en.md"), None);
+        assert_eq!(
+            RepoConfig::default().path_filters().apply("wiki/FAQ/en.md"),
+            Some("wiki/FAQ/en.md".to_string())
+        );
+    }
+}
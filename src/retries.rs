@@ -0,0 +1,216 @@
+//! Conflict comments which failed to be written, kept for another attempt so that comments don't drift away
+//! from stored conflicts. The queue is optionally saved to a file, which is read back on start-up.
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::conflicts::Conflict;
+
+/// The delay before the first retry, doubled with every failed attempt.
+const MIN_RETRY_DELAY_SECONDS: i64 = 60;
+const MAX_RETRY_DELAY_SECONDS: i64 = 60 * 60;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PendingSync {
+    pub full_repo_name: String,
+    pub conflict: Conflict,
+
+    /// Whether the comment about the conflict is to be retired, rather than posted or updated.
+    pub resolved: bool,
+
+    /// How many attempts have failed so far.
+    pub attempts: u32,
+    pub next_attempt_at: chrono::DateTime<chrono::Utc>,
+}
+
+impl PendingSync {
+    fn is_about(&self, full_repo_name: &str, conflict: &Conflict) -> bool {
+        self.full_repo_name == full_repo_name && self.conflict.key() == conflict.key()
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct RetryQueue {
+    path: Option<String>,
+    items: Arc<Mutex<Vec<PendingSync>>>,
+}
+
+impl RetryQueue {
+    /// Create the queue, and restore its contents from `path` if it's set. An unreadable file is reported and ignored.
+    pub fn new(path: Option<String>) -> Self {
+        let items = match &path {
+            None => Vec::new(),
+            Some(p) => match std::fs::read_to_string(p) {
+                Ok(contents) => serde_json::from_str(&contents).unwrap_or_else(|e| {
+                    log::error!("Failed to parse the retry queue {:?}: {:?}", p, e);
+                    Vec::new()
+                }),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+                Err(e) => {
+                    log::error!("Failed to read the retry queue {:?}: {:?}", p, e);
+                    Vec::new()
+                }
+            },
+        };
+        Self {
+            path,
+            items: Arc::new(Mutex::new(items)),
+        }
+    }
+
+    /// Schedule another attempt to sync the comment about a conflict. If one is already scheduled, it is postponed
+    /// further, and its kind is replaced.
+    pub fn schedule(&self, full_repo_name: &str, conflict: &Conflict, resolved: bool) {
+        let mut items = self.items.lock().unwrap();
+        let attempts = match items
+            .iter()
+            .position(|p| p.is_about(full_repo_name, conflict))
+        {
+            Some(i) => items.remove(i).attempts + 1,
+            None => 1,
+        };
+        let delay =
+            (MIN_RETRY_DELAY_SECONDS << (attempts - 1).min(16)).min(MAX_RETRY_DELAY_SECONDS);
+        items.push(PendingSync {
+            full_repo_name: full_repo_name.to_string(),
+            conflict: conflict.clone(),
+            resolved,
+            attempts,
+            next_attempt_at: chrono::Utc::now() + chrono::Duration::seconds(delay),
+        });
+        self.save(&items);
+    }
+
+    /// Forget about a conflict, since its comment is in sync now. Returns whether there was anything to forget.
+    pub fn complete(&self, full_repo_name: &str, conflict: &Conflict) -> bool {
+        let mut items = self.items.lock().unwrap();
+        let len = items.len();
+        items.retain(|p| !p.is_about(full_repo_name, conflict));
+        let removed = items.len() != len;
+        if removed {
+            self.save(&items);
+        }
+        removed
+    }
+
+    /// Remove an item, unless it has been rescheduled after being read (see [`Self::due`]).
+    pub fn complete_attempt(&self, attempt: &PendingSync) -> bool {
+        let mut items = self.items.lock().unwrap();
+        let len = items.len();
+        items.retain(|p| p != attempt);
+        let removed = items.len() != len;
+        if removed {
+            self.save(&items);
+        }
+        removed
+    }
+
+    /// Point pending syncs of a repository to its new name, after it has been renamed or transferred.
+    pub fn rename_repository(&self, old_full_name: &str, new_full_name: &str) {
+        let mut items = self.items.lock().unwrap();
+        let mut renamed = false;
+        for p in items
+            .iter_mut()
+            .filter(|p| p.full_repo_name == old_full_name)
+        {
+            p.full_repo_name = new_full_name.to_string();
+            renamed = true;
+        }
+        if renamed {
+            self.save(&items);
+        }
+    }
+
+    /// Forget about all pending syncs of a repository which the app no longer has access to.
+    pub fn remove_repository(&self, full_repo_name: &str) {
+        let mut items = self.items.lock().unwrap();
+        let len = items.len();
+        items.retain(|p| p.full_repo_name != full_repo_name);
+        if items.len() != len {
+            self.save(&items);
+        }
+    }
+
+    /// Items which should be retried by now. They stay in the queue until completed.
+    pub fn due(&self, now: chrono::DateTime<chrono::Utc>) -> Vec<PendingSync> {
+        self.items
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|p| p.next_attempt_at <= now)
+            .cloned()
+            .collect()
+    }
+
+    pub fn all(&self) -> Vec<PendingSync> {
+        self.items.lock().unwrap().clone()
+    }
+
+    fn save(&self, items: &[PendingSync]) {
+        let Some(path) = &self.path else {
+            return;
+        };
+        if let Err(e) = std::fs::write(path, serde_json::to_string(items).unwrap()) {
+            log::error!("Failed to save the retry queue to {:?}: {:?}", path, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_conflict(trigger: i32) -> Conflict {
+        Conflict::overlap(
+            trigger,
+            1,
+            "https://github.com/test/repo/pull/1".to_string(),
+            vec!["wiki/Article/en.md".to_string()],
+        )
+    }
+
+    #[test]
+    fn schedule_backs_off_and_persists() {
+        let path = std::env::temp_dir().join(format!(
+            "observatory-retries-{}.json",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap()
+        ));
+        let path = path.to_str().unwrap().to_string();
+        let queue = RetryQueue::new(Some(path.clone()));
+
+        queue.schedule("test/repo", &make_conflict(2), false);
+        queue.schedule("test/repo", &make_conflict(2), false);
+        queue.schedule("test/repo", &make_conflict(3), true);
+        let items = queue.all();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[0].attempts, 2);
+        assert!(items[0].next_attempt_at > items[1].next_attempt_at);
+
+        assert!(queue.due(chrono::Utc::now()).is_empty());
+        let later = chrono::Utc::now() + chrono::Duration::minutes(1);
+        assert_eq!(queue.due(later), vec![items[1].clone()]);
+
+        assert_eq!(RetryQueue::new(Some(path.clone())).all(), items);
+        assert!(queue.complete("test/repo", &make_conflict(2)));
+        assert!(!queue.complete("test/repo", &make_conflict(2)));
+        assert_eq!(
+            RetryQueue::new(Some(path.clone())).all(),
+            vec![items[1].clone()]
+        );
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn rescheduled_attempt_is_not_completed() {
+        let queue = RetryQueue::default();
+        queue.schedule("test/repo", &make_conflict(2), false);
+        let attempt = queue.all().remove(0);
+
+        queue.schedule("test/repo", &make_conflict(2), false);
+        assert!(!queue.complete_attempt(&attempt));
+
+        let attempt = queue.all().remove(0);
+        assert!(queue.complete_attempt(&attempt));
+        assert!(queue.all().is_empty());
+    }
+}
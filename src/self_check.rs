@@ -0,0 +1,200 @@
+//! A periodic comparison of what the app knows about open pull requests with what GitHub has, which catches logic bugs
+//! before users do (see `controller.self_check` in the config).
+//!
+//! Divergences are only reported: fixing them automatically would hide the bugs which caused them.
+use std::collections::BTreeSet;
+
+use crate::helpers::comments::CommentHeader;
+use crate::helpers::conflicts::{Conflict, ConflictType};
+use crate::helpers::diff::DiffSummary;
+
+/// A mismatch between the app's state and GitHub, found in a pull request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Divergence {
+    /// A known conflict which is worth a comment, but has none.
+    MissingComment { original: i32, kind: ConflictType },
+
+    /// A comment about a conflict which is not known.
+    OrphanComment {
+        comment_id: i64,
+        original: i32,
+        kind: ConflictType,
+    },
+
+    /// Changed files which are stored for the pull, but are no longer in its diff, and the other way round.
+    StaleFileSet {
+        missing: Vec<String>,
+        extra: Vec<String>,
+    },
+}
+
+impl std::fmt::Display for Divergence {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingComment { original, kind } => {
+                write!(f, "no comment about {:?} with #{}", kind, original)
+            }
+            Self::OrphanComment {
+                comment_id,
+                original,
+                kind,
+            } => write!(
+                f,
+                "comment #{} is about an unknown {:?} with #{}",
+                comment_id, kind, original
+            ),
+            Self::StaleFileSet { missing, extra } => write!(
+                f,
+                "stored files are out of date (not in the diff: {:?}, not stored: {:?})",
+                extra, missing
+            ),
+        }
+    }
+}
+
+/// Pick up to `size` pulls to check in the given round, so that all of them are checked over consecutive rounds.
+pub fn sample<T>(items: &[T], round: usize, size: usize) -> Vec<&T> {
+    if items.is_empty() {
+        return Vec::new();
+    }
+    let start = round.wrapping_mul(size) % items.len();
+    items
+        .iter()
+        .cycle()
+        .skip(start)
+        .take(size.min(items.len()))
+        .collect()
+}
+
+/// Compare changed files of a pull as stored with the ones from its current diff.
+pub fn compare_files(stored: &DiffSummary, current: &DiffSummary) -> Option<Divergence> {
+    let stored: BTreeSet<_> = stored.files.iter().map(|f| &f.path).collect();
+    let current: BTreeSet<_> = current.files.iter().map(|f| &f.path).collect();
+    if stored == current {
+        return None;
+    }
+    Some(Divergence::StaleFileSet {
+        missing: current.difference(&stored).map(|p| p.to_string()).collect(),
+        extra: stored.difference(&current).map(|p| p.to_string()).collect(),
+    })
+}
+
+/// Compare conflicts stored for a pull with headers of the app's comments there. Conflicts for which `expects_comment` is
+/// `false` may go without a comment, but their comments are not orphans either.
+///
+/// Only per-conflict comments are considered: summaries, digests and notices have headers of other types.
+pub fn compare_comments<F>(
+    stored: &[Conflict],
+    headers: &[(i64, CommentHeader)],
+    expects_comment: F,
+) -> Vec<Divergence>
+where
+    F: Fn(&Conflict) -> bool,
+{
+    let is_conflict_comment = |h: &CommentHeader| {
+        matches!(
+            h.conflict_type,
+            ConflictType::Overlap | ConflictType::IncompleteTranslation
+        )
+    };
+    let mut out = Vec::new();
+    for c in stored.iter().filter(|c| expects_comment(c)) {
+        let has_comment = headers.iter().any(|(_, h)| {
+            h.pull_number == c.original
+                && h.conflict_type == c.kind
+                && h.repository == c.original_repo
+        });
+        if !has_comment {
+            out.push(Divergence::MissingComment {
+                original: c.original,
+                kind: c.kind.clone(),
+            });
+        }
+    }
+    for (comment_id, h) in headers.iter().filter(|(_, h)| is_conflict_comment(h)) {
+        let is_known = stored.iter().any(|c| {
+            h.pull_number == c.original
+                && h.conflict_type == c.kind
+                && h.repository == c.original_repo
+        });
+        if !is_known {
+            out.push(Divergence::OrphanComment {
+                comment_id: *comment_id,
+                original: h.pull_number,
+                kind: h.conflict_type.clone(),
+            });
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::test;
+
+    #[test]
+    fn sample_rotates() {
+        let items = [1, 2, 3, 4, 5];
+        assert_eq!(sample(&items, 0, 2), vec![&1, &2]);
+        assert_eq!(sample(&items, 1, 2), vec![&3, &4]);
+        assert_eq!(sample(&items, 2, 2), vec![&5, &1]);
+        assert_eq!(sample(&items, 0, 10).len(), 5);
+        assert!(sample::<i32>(&[], 3, 2).is_empty());
+    }
+
+    #[test]
+    fn stale_file_sets() {
+        let stored = DiffSummary::from(&test::make_simple_diff(
+            &["wiki/Article/en.md", "wiki/Other/en.md"],
+            0,
+        ));
+        let current = DiffSummary::from(&test::make_simple_diff(
+            &["wiki/Article/en.md", "wiki/New/en.md"],
+            0,
+        ));
+        assert_eq!(compare_files(&stored, &stored), None);
+        assert_eq!(
+            compare_files(&stored, &current),
+            Some(Divergence::StaleFileSet {
+                missing: vec!["wiki/New/en.md".to_string()],
+                extra: vec!["wiki/Other/en.md".to_string()],
+            })
+        );
+    }
+
+    #[test]
+    fn missing_and_orphan_comments() {
+        let overlap = Conflict::overlap(
+            2,
+            1,
+            "https://github.com/test/repo/pull/1".to_string(),
+            vec!["wiki/Article/en.md".to_string()],
+        );
+        let headers = vec![
+            (10, CommentHeader::new(1, ConflictType::Overlap)),
+            (
+                11,
+                CommentHeader::new(3, ConflictType::IncompleteTranslation),
+            ),
+            (12, CommentHeader::new(2, ConflictType::OutdatedTranslation)),
+        ];
+        assert_eq!(
+            compare_comments(std::slice::from_ref(&overlap), &headers, |_| true),
+            vec![Divergence::OrphanComment {
+                comment_id: 11,
+                original: 3,
+                kind: ConflictType::IncompleteTranslation,
+            }]
+        );
+        assert_eq!(
+            compare_comments(std::slice::from_ref(&overlap), &[], |_| true),
+            vec![Divergence::MissingComment {
+                original: 1,
+                kind: ConflictType::Overlap,
+            }]
+        );
+        assert!(compare_comments(&[overlap], &[], |_| false).is_empty());
+    }
+}
@@ -0,0 +1,308 @@
+//! The HTTP server: webhook endpoints, the index page and the health check, with the administrative API
+//! (see [`crate::api`]) mounted on top of them.
+use eyre::Result;
+use viz::middleware::limits;
+use viz::{types::State, Router};
+use viz::{HandlerExt, IntoResponse, Response, ResponseExt};
+use viz::{Request, RequestExt, StatusCode};
+
+use crate::helpers::allowlist::HookAllowlist;
+use crate::helpers::digest::{self, RequestValidator};
+use crate::{
+    api, archive, audit, config, controller, handler, helpers::cgroup, helpers::process, structs,
+    version,
+};
+
+pub async fn index(req: Request) -> viz::Result<Response> {
+    let mut body = Vec::new();
+    // Outside of a cgroup (or on Windows), fall back to what the OS reports about the process itself.
+    let cgroup = cgroup::CGroup::current();
+    let resources = if cgroup.valid() {
+        cgroup.summary()
+    } else {
+        process::summary()
+    };
+    for (header, value) in resources {
+        let val = value.replace('\n', "<br/>");
+        body.push(format!(r"<h3><tt>{header}</tt></h3><tt>{val}</tt><br/>"));
+    }
+    if body.is_empty() {
+        body.push(r"¯\_(ツ)_/¯".to_owned());
+    }
+    body.push(footer(&req));
+    Ok(Response::html(body.join("")))
+}
+
+/// The version of the app, for the bottom of the pages (see [`crate::version`]).
+fn footer(req: &Request) -> String {
+    req.state::<version::Version>()
+        .map(|v| format!(r"<hr/><tt>{}</tt>", v.summary()))
+        .unwrap_or_default()
+}
+
+/// `GET /installations`: a page with known installations and their repositories, with buttons to refresh installation
+/// tokens, read repositories again, or forget installations. The page itself is public, and reads everything from the
+/// administrative API with a token entered by the user.
+pub async fn installations(req: Request) -> viz::Result<Response> {
+    Ok(Response::html(
+        include_str!("server/installations.html").replace("<!-- footer -->", &footer(&req)),
+    ))
+}
+
+/// `GET /health`: whether the controller is running, how many times it has recovered from a panic, how many
+/// divergences from GitHub the self-check has found, which repositories have more open pulls than are tracked, and whether
+/// background work is throttled.
+pub async fn health(req: Request) -> viz::Result<Response> {
+    let controller_handle = req
+        .state::<controller::ControllerHandle>()
+        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
+    let alive = controller_handle.is_alive();
+    let mut response = Response::json(serde_json::json!({
+        "controller": if alive { "running" } else { "stopped" },
+        "controller_restarts": controller_handle.restarts(),
+        "self_check_divergences": controller_handle.divergences(),
+        "queue_depth": controller_handle.queue_depth(),
+        "repositories_over_limit": controller_handle.repositories_over_limit(),
+        "throttle": controller_handle.throttle(),
+    }))?;
+    if !alive {
+        *response.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+    }
+    Ok(response)
+}
+
+/// Read the body as text, up to the `text` limit (`server.data_limit`). Oversized bodies are rejected with 413 as soon as
+/// that's known: upfront if `Content-Length` says so, or otherwise once the chunks read so far exceed the limit.
+async fn read_text_body(req: &mut Request) -> viz::Result<String> {
+    let limit = req
+        .extensions()
+        .get::<viz::types::Limits>()
+        .and_then(|l| l.get("text"))
+        .unwrap_or(viz::types::Limits::NORMAL);
+    let too_large = |size: u64| {
+        log::warn!(
+            "Request body is larger than {} bytes ({} at least), rejecting",
+            limit,
+            size
+        );
+        StatusCode::PAYLOAD_TOO_LARGE.into_error()
+    };
+    if let Some(size) = req.content_length().filter(|size| *size > limit) {
+        return Err(too_large(size));
+    }
+
+    let mut body = std::mem::replace(req.body_mut(), viz::Body::empty());
+    let mut data = Vec::new();
+    while let Some(chunk) = hyper::body::HttpBody::data(&mut body).await {
+        let chunk = chunk.map_err(|e| {
+            log::warn!("Failed to read a request body: {:?}", e);
+            StatusCode::BAD_REQUEST.into_error()
+        })?;
+        let size = (data.len() + chunk.len()) as u64;
+        if size > limit {
+            return Err(too_large(size));
+        }
+        data.extend_from_slice(&chunk);
+    }
+    String::from_utf8(data).map_err(|_| StatusCode::BAD_REQUEST.into_error())
+}
+
+/// Receive a webhook event. Responses tell GitHub whether redelivering the event makes sense:
+/// - 202: the event is queued, and will be handled shortly;
+/// - 204: the app doesn't act on events of this type;
+/// - 400: the payload is malformed (it's still archived, if `debug.payload_dir` is set);
+/// - 403: the request is not from GitHub, or is not signed with the webhook secret;
+/// - 503: the event can't be queued right now.
+pub async fn github_events(mut req: Request) -> viz::Result<Response> {
+    let event_type = req.header::<_, String>("X-GitHub-Event").ok_or_else(|| {
+        log::warn!("GitHub event is missing the event type header, rejecting");
+        StatusCode::FORBIDDEN.into_error()
+    })?;
+
+    if let Some(allowlist) = req.state::<HookAllowlist>() {
+        let forwarded_for = req
+            .headers()
+            .get_all("X-Forwarded-For")
+            .iter()
+            .filter_map(|v| v.to_str().ok())
+            .collect::<Vec<_>>()
+            .join(",");
+        let sender = req
+            .remote_addr()
+            .and_then(|addr| allowlist.client_ip(addr.ip(), Some(&forwarded_for)));
+        let allowed = match sender {
+            Some(ip) => allowlist.allows(ip).await,
+            None => false,
+        };
+        if !allowed {
+            log::warn!(
+                "GitHub event from {:?} (via {:?}) is not from GitHub's hook IP ranges, rejecting",
+                sender,
+                req.remote_addr()
+            );
+            return Err(StatusCode::FORBIDDEN.into_error());
+        }
+    }
+
+    let signature_header = req
+        .header::<_, String>("X-Hub-Signature-256")
+        .ok_or_else(|| {
+            log::warn!("GitHub event is missing the signature header, rejecting");
+            StatusCode::FORBIDDEN.into_error()
+        })?;
+    let Some(signature) = signature_header
+        .strip_prefix("sha256=")
+        .filter(|s| digest::hash_from_string(s).is_some())
+    else {
+        log::warn!("GitHub event has a malformed signature header, rejecting");
+        return Err(StatusCode::FORBIDDEN.into_error());
+    };
+
+    let body = read_text_body(&mut req).await?;
+    let validator = req
+        .state::<RequestValidator>()
+        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
+    if !validator.validate(&body, signature).unwrap_or(false) {
+        return Err(StatusCode::FORBIDDEN.into_error());
+    }
+
+    if event_type == "ping" {
+        return Ok(ping(&body));
+    }
+    if !handler::is_supported(&event_type) {
+        log::debug!("Ignoring an event of type \"{}\"", event_type);
+        return Ok(StatusCode::NO_CONTENT.into_response());
+    }
+
+    // Processing may take a while, and GitHub doesn't wait for long: the event is handled after responding.
+    let event = handler::Event {
+        event_type,
+        delivery_id: req.header::<_, String>("X-GitHub-Delivery"),
+        body,
+    };
+    if let Some(archive) = req.state::<archive::PayloadArchive>() {
+        if let Err(e) = archive.store(&event) {
+            log::error!("Failed to archive a GitHub event: {:?}", e);
+        }
+    }
+    if let Err(e) = handler::validate(&event) {
+        log::warn!(
+            "Malformed \"{}\" event (delivery {:?}), rejecting: {:?}",
+            event.event_type,
+            event.delivery_id,
+            e
+        );
+        return Err(StatusCode::BAD_REQUEST.into_error());
+    }
+    let queue = req
+        .state::<handler::EventQueue>()
+        .ok_or_else(|| StatusCode::INTERNAL_SERVER_ERROR.into_error())?;
+    queue.push(event).map_err(|e| {
+        log::error!("Failed to enqueue a GitHub event: {:?}", e);
+        StatusCode::SERVICE_UNAVAILABLE.into_error()
+    })?;
+    Ok(StatusCode::ACCEPTED.into_response())
+}
+
+/// GitHub sends a ping once a webhook is set up. Answering with its zen right away confirms that the app is reachable,
+/// and that the secret matches.
+fn ping(body: &str) -> Response {
+    let evt: structs::PingEvent = match serde_json::from_str(body) {
+        Ok(evt) => evt,
+        Err(e) => {
+            log::warn!("Failed to deserialize a ping event: {:?}", e);
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+    log::info!(
+        "Webhook #{} ({}, app {:?}) is set up for events {:?}: {}",
+        evt.hook_id,
+        evt.hook.hook_type,
+        evt.hook.app_id,
+        evt.hook.events,
+        evt.zen
+    );
+    Response::text(evt.zen)
+}
+
+/// Set up all routes and their shared state. The controller may still be starting up: requests to it wait until it's done.
+pub fn make_app(
+    settings: &config::Config,
+    controller_handle: controller::ControllerHandle,
+    allowlist: Option<HookAllowlist>,
+) -> Result<Router> {
+    let data_limit = settings.server.data_limit;
+    let ls = viz::types::Limits::new()
+        .insert("bytes", data_limit)
+        .insert("json", data_limit)
+        .insert("payload", data_limit)
+        .insert("text", data_limit);
+
+    // Every endpoint checks signatures with its own secret.
+    let mut app = Router::new().get("/", index).get("/health", health);
+    for e in &settings.events_endpoints() {
+        app = app.post(
+            &e.path,
+            github_events.with(State::new(RequestValidator::new(e.secret.clone()))),
+        );
+    }
+    let admin_tokens = settings.server.admin_tokens.clone();
+    if !admin_tokens.is_empty() {
+        app = app
+            .get("/installations", installations)
+            .get("/api/v1/state/export", api::export_state)
+            .get("/api/v1/audit", api::audit)
+            .get("/api/v1/ratelimit", api::rate_limits)
+            .get("/api/v1/hydration", api::hydration)
+            .get("/api/v1/messages", api::messages)
+            .get(
+                "/api/v1/repos/:owner/:name/conflict-graph",
+                api::conflict_graph,
+            )
+            .get(
+                "/api/v1/repos/:owner/:name/articles/:path+",
+                api::article_pulls,
+            )
+            .get("/api/v1/repos/:owner/:name/history", api::article_history)
+            .get("/api/v1/version", api::version)
+            .get("/api/v1/installations", api::installations)
+            .post(
+                "/api/v1/installations/:id/token",
+                api::refresh_installation_token,
+            )
+            .delete("/api/v1/installations/:id", api::forget_installation)
+            .post("/api/v1/repos/:owner/:name/resync", api::resync_repository)
+            .post("/api/v1/repos/:owner/:name/simulate", api::simulate)
+            .post("/api/v1/preview-comment", api::preview_comment)
+            .post("/api/v1/trigger", api::trigger)
+            .with(State::new(audit::AuditLog::new(
+                settings.controller.audit_log.clone(),
+            )));
+    }
+
+    // Anything added above which is not public requires credentials (and without any, there is nothing else).
+    let mut public_paths = vec![
+        "/".to_string(),
+        "/health".to_string(),
+        "/installations".to_string(),
+    ];
+    public_paths.extend(settings.events_endpoints().into_iter().map(|e| e.path));
+    app = app.with(api::auth::AdminAuth::new(admin_tokens, public_paths));
+    if let Some(allowlist) = allowlist {
+        app = app.with(State::new(allowlist));
+    }
+    if let Some(archive) = archive::PayloadArchive::new(&settings.debug)? {
+        app = app.with(State::new(archive));
+    }
+    let app = app
+        .with(State::new(handler::EventQueue::new(
+            controller_handle.clone(),
+        )))
+        .with(State::new(controller_handle))
+        .with(State::new(version::Version::new(&settings.github.app_id)))
+        .with(limits::Config::default().limits(ls));
+    Ok(app)
+}
+
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,898 @@
+use super::*;
+
+use crate::github::GitHub;
+use crate::helpers::digest;
+use crate::structs;
+use crate::test::{GitHubServer, TEST_APP_ID, TEST_APP_PRIVATE_KEY};
+use crate::top;
+
+const EVENTS_ENDPOINT: &str = "/github-events";
+const WEBHOOK_SECRET: &str = "iseedeadpeople";
+
+/// Start the app on a random local port, on top of an initialized controller. Returns the base URL.
+async fn serve(github: GitHub) -> String {
+    serve_with(github, config::Config::from_path(".config.yaml").unwrap()).await
+}
+
+async fn serve_with(github: GitHub, settings: config::Config) -> String {
+    let controller_handle = controller::ControllerHandle::new(
+        github,
+        TEST_APP_ID.to_string(),
+        TEST_APP_PRIVATE_KEY.to_string(),
+        settings.controller.clone(),
+    );
+    controller_handle.init().await.unwrap();
+
+    let app = make_app(&settings, controller_handle, None).unwrap();
+    let server =
+        viz::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(viz::ServiceMaker::from(app));
+    let url = format!("http://{}", server.local_addr());
+    tokio::spawn(server);
+    url
+}
+
+fn sign(body: &str, secret: &str) -> String {
+    let key = ring::hmac::Key::new(ring::hmac::HMAC_SHA256, secret.as_bytes());
+    format!(
+        "sha256={}",
+        digest::hash_to_string(ring::hmac::sign(&key, body.as_bytes()).as_ref())
+    )
+}
+
+async fn send_event(url: &str, event_type: &str, body: &str, secret: &str) -> reqwest::Response {
+    reqwest::Client::new()
+        .post(format!("{}{}", url, EVENTS_ENDPOINT))
+        .header("X-GitHub-Event", event_type)
+        .header("X-GitHub-Delivery", "72d3162e-cc78-11e3-81ab-4c9367dc0958")
+        .header("X-Hub-Signature-256", sign(body, secret))
+        .body(body.to_string())
+        .send()
+        .await
+        .unwrap()
+}
+
+/// Events are processed after the response is sent, so the effects have to be waited for.
+async fn wait_for(mock: &mockito::Mock) {
+    for _ in 0..50 {
+        if mock.matched_async().await {
+            return;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    }
+    mock.assert_async().await;
+}
+
+fn pull_request_event(action: &str, pull: &structs::PullRequest) -> String {
+    serde_json::json!({
+        "action": action,
+        "number": pull.number,
+        "pull_request": pull,
+        "repository": {
+            "id": 1,
+            "name": "repo",
+            "full_name": "test/repo",
+        },
+        "installation": { "id": 1 },
+        "sender": pull.user,
+    })
+    .to_string()
+}
+
+#[tokio::test]
+async fn test_pull_request_event_leads_to_comment() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_default_app_installations()
+        .with_pull("test/repo", &p1);
+    let url = serve(server.url.clone()).await;
+
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pull("test/repo", &p2)
+        .with_comments("test/repo", p2.number, &[]);
+    let comment = server.mock_pull_comments("test/repo", p2.number, None);
+
+    let response = send_event(
+        &url,
+        "pull_request",
+        &pull_request_event("opened", &p2),
+        WEBHOOK_SECRET,
+    )
+    .await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    wait_for(&comment).await;
+}
+
+#[tokio::test]
+async fn test_event_with_invalid_signature_is_rejected() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_default_app_installations()
+        .with_pull("test/repo", &p1);
+    let url = serve(server.url.clone()).await;
+
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let diff = server
+        .server
+        .mock("GET", "/test/repo/pull/2.diff")
+        .expect(0)
+        .create();
+
+    let response = send_event(
+        &url,
+        "pull_request",
+        &pull_request_event("opened", &p2),
+        "not-the-secret",
+    )
+    .await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    tokio::time::sleep(std::time::Duration::from_millis(500)).await;
+    diff.assert();
+}
+
+#[tokio::test]
+async fn test_installation_event_reads_pulls() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_app_installations(&[]);
+    let url = serve(server.url.clone()).await;
+
+    let installation = server.make_installation();
+    let repo = server.make_repo(installation.id, "test/repo");
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server.with_app_installations(&[(installation.clone(), vec![repo.clone()])]);
+    let diff = server
+        .server
+        .mock("GET", "/test/repo/pull/1.diff")
+        .with_status(200)
+        .with_body(pull.diff.as_ref().unwrap().to_string())
+        .expect(1)
+        .create();
+
+    let body = serde_json::json!({
+        "action": "created",
+        "installation": installation,
+        "sender": installation.account,
+        "repositories": [repo],
+    })
+    .to_string();
+    let response = send_event(&url, "installation", &body, WEBHOOK_SECRET).await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    wait_for(&diff).await;
+}
+
+#[tokio::test]
+async fn test_trigger_endpoint_requests_rescan() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Other_article/en.md"]);
+    server = server
+        .with_pulls("test/repo", &[p1.clone(), p2.clone()])
+        .with_default_app_installations()
+        .with_comments("test/repo", p2.number, &[]);
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+
+    let p2 = server.change_pull_diff("test/repo", p2.number, &["wiki/Article/en.md"]);
+    server = server.with_pull("test/repo", &p2);
+    let comment = server.mock_pull_comments("test/repo", p2.number, None);
+
+    let trigger = |token: &str, inputs: serde_json::Value| {
+        reqwest::Client::new()
+            .post(format!("{}/api/v1/trigger", url))
+            .bearer_auth(token)
+            .json(&serde_json::json!({
+                "ref": "refs/heads/master",
+                "inputs": inputs,
+                "repository": { "id": 1, "name": "repo", "full_name": "test/repo" },
+                "workflow": ".github/workflows/sync.yml",
+            }))
+            .send()
+    };
+    let response = trigger("not-the-token", serde_json::json!({}))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+    let response = trigger("letmein", serde_json::json!({ "pull_number": "two" }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::BAD_REQUEST);
+
+    let response = trigger("letmein", serde_json::json!({ "pull_number": "2" }))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+    wait_for(&comment).await;
+}
+
+#[tokio::test]
+async fn test_admin_routes_require_credentials() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![
+        config::AdminToken::Token {
+            token: "letmein".to_string(),
+        },
+        config::AdminToken::HmacSecret {
+            hmac_secret: "sesame".to_string(),
+        },
+    ];
+    let url = serve_with(server.url.clone(), settings).await;
+    let client = reqwest::Client::new();
+
+    let signed = |method: reqwest::Method, path: &str, body: &str, secret: &str, age: i64| {
+        let timestamp = (chrono::Utc::now().timestamp() - age).to_string();
+        let message = format!("{}.{}.{}.{}", timestamp, method, path, body);
+        client
+            .request(method, format!("{}{}", url, path))
+            .header(api::auth::TIMESTAMP_HEADER, timestamp)
+            .header(
+                api::auth::SIGNATURE_HEADER,
+                format!("sha256={}", api::auth::sign(secret, message.as_bytes())),
+            )
+            .body(body.to_string())
+    };
+
+    let status = |r: reqwest::Response| r.status();
+    let audit = format!("{}/api/v1/audit?limit=5", url);
+    assert_eq!(
+        status(client.get(&audit).send().await.unwrap()),
+        reqwest::StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        status(
+            client
+                .get(&audit)
+                .bearer_auth("sesame")
+                .send()
+                .await
+                .unwrap()
+        ),
+        reqwest::StatusCode::UNAUTHORIZED
+    );
+    assert_eq!(
+        status(
+            client
+                .get(&audit)
+                .bearer_auth("letmein")
+                .send()
+                .await
+                .unwrap()
+        ),
+        reqwest::StatusCode::OK
+    );
+
+    let get = reqwest::Method::GET;
+    for (secret, age, expected) in [
+        ("sesame", 0, reqwest::StatusCode::OK),
+        ("letmein", 0, reqwest::StatusCode::UNAUTHORIZED),
+        ("sesame", 3600, reqwest::StatusCode::UNAUTHORIZED),
+    ] {
+        let r = signed(get.clone(), "/api/v1/audit?limit=5", "", secret, age)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(r.status(), expected, "{} signed {}s ago", secret, age);
+    }
+
+    // The body is still there for the handler after the signature is checked.
+    let body = serde_json::json!({ "repository": { "full_name": "test/repo" } }).to_string();
+    let r = signed(reqwest::Method::POST, "/api/v1/trigger", &body, "sesame", 0)
+        .header("Content-Type", "application/json")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::ACCEPTED);
+
+    let r = client.get(format!("{}/health", url)).send().await.unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::OK);
+    let health: serde_json::Value = r.json().await.unwrap();
+    assert_eq!(health["throttle"]["throttled"], false);
+}
+
+#[tokio::test]
+async fn test_event_with_malformed_signature_is_rejected() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let url = serve(server.url.clone()).await;
+
+    let body = crate::test::load_fixture("ping.json");
+    let hex = sign(&body, WEBHOOK_SECRET)
+        .strip_prefix("sha256=")
+        .unwrap()
+        .to_string();
+    for signature in [
+        format!("md5={}", hex),
+        hex.clone(),
+        "sha256=not-a-hex-digest".to_string(),
+    ] {
+        let response = reqwest::Client::new()
+            .post(format!("{}{}", url, EVENTS_ENDPOINT))
+            .header("X-GitHub-Event", "ping")
+            .header("X-Hub-Signature-256", signature.as_str())
+            .body(body.clone())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::FORBIDDEN,
+            "{}",
+            signature
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_oversized_signed_request_is_rejected() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.data_limit = 1024;
+    settings.server.admin_tokens = vec![config::AdminToken::HmacSecret {
+        hmac_secret: "sesame".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+    let client = reqwest::Client::new();
+
+    // The signature doesn't even need to be valid: the body is too large to be read for checking it.
+    let timestamp = chrono::Utc::now().timestamp().to_string();
+    let r = client
+        .post(format!("{}/api/v1/trigger", url))
+        .header(api::auth::TIMESTAMP_HEADER, timestamp)
+        .header(api::auth::SIGNATURE_HEADER, "sha256=00")
+        .body("x".repeat(2048))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_oversized_event_is_rejected() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.data_limit = 1024;
+    let url = serve_with(server.url.clone(), settings).await;
+
+    let body = serde_json::json!({ "zen": "x".repeat(2048) }).to_string();
+    let response = send_event(&url, "ping", &body, WEBHOOK_SECRET).await;
+    assert_eq!(response.status(), reqwest::StatusCode::PAYLOAD_TOO_LARGE);
+}
+
+#[tokio::test]
+async fn test_body_without_length_is_capped_while_reading() {
+    let (mut sender, body) = viz::Body::channel();
+    let mut req = Request::new(body);
+    req.extensions_mut()
+        .insert(viz::types::Limits::new().insert("text", 10));
+    tokio::spawn(async move {
+        for _ in 0..100 {
+            if sender.send_data("abcd".into()).await.is_err() {
+                break;
+            }
+        }
+    });
+    let err = read_text_body(&mut req).await.unwrap_err();
+    assert_eq!(err.into_response().status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+    let mut req = Request::new(viz::Body::from("abcd"));
+    req.extensions_mut()
+        .insert(viz::types::Limits::new().insert("text", 10));
+    assert_eq!(read_text_body(&mut req).await.unwrap(), "abcd");
+}
+
+#[tokio::test]
+async fn test_ping_is_answered_with_zen() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let url = serve(server.url.clone()).await;
+
+    let body = crate::test::load_fixture("ping.json");
+    let response = send_event(&url, "ping", &body, WEBHOOK_SECRET).await;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    assert_eq!(response.text().await.unwrap(), "Design for failure.");
+
+    let response = send_event(&url, "ping", &body, "not-the-secret").await;
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_event_statuses() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let url = serve(server.url.clone()).await;
+
+    let response = send_event(&url, "star", r#"{"action": "created"}"#, WEBHOOK_SECRET).await;
+    assert_eq!(response.status(), reqwest::StatusCode::NO_CONTENT);
+
+    for body in ["not json", r#"{"action": "opened"}"#] {
+        let response = send_event(&url, "pull_request", body, WEBHOOK_SECRET).await;
+        assert_eq!(
+            response.status(),
+            reqwest::StatusCode::BAD_REQUEST,
+            "{}",
+            body
+        );
+    }
+
+    let body = crate::test::load_fixture("repository.archived.json");
+    let response = send_event(&url, "repository", &body, WEBHOOK_SECRET).await;
+    assert_eq!(response.status(), reqwest::StatusCode::ACCEPTED);
+}
+
+#[tokio::test]
+async fn test_top_reads_admin_api() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let limit = serde_json::json!({ "limit": 5000, "remaining": 4990, "used": 10, "reset": 0 });
+    server
+        .server
+        .mock("GET", "/rate_limit")
+        .with_status(200)
+        .with_body(
+            serde_json::json!({ "resources": { "core": limit, "search": limit } }).to_string(),
+        )
+        .create();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+
+    let overview = top::Client::new(&url, "letmein".to_string())
+        .overview()
+        .await
+        .unwrap();
+    assert_eq!(overview.health.controller, "running");
+    assert_eq!(overview.rate_limits.len(), 1);
+    assert_eq!(overview.rate_limits[0].core.remaining, 4990);
+    assert!(top::Client::new(&url, "wrong".to_string())
+        .overview()
+        .await
+        .is_err());
+}
+
+#[tokio::test]
+async fn test_index_does_not_query_rate_limits() {
+    let mut server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let rate_limit = server
+        .server
+        .mock("GET", "/rate_limit")
+        .with_status(200)
+        .expect(0)
+        .create();
+    let settings = config::Config::from_path(".config.yaml").unwrap();
+    let url = serve_with(server.url.clone(), settings).await;
+
+    let body = reqwest::get(format!("{}/", url))
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(!body.contains("rate limits"));
+    rate_limit.assert();
+}
+
+#[tokio::test]
+async fn test_hydration_progress_is_served() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pull("test/repo", &pull)
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+
+    let progress: crate::hydration::Progress = reqwest::Client::new()
+        .get(format!("{}/api/v1/hydration", url))
+        .bearer_auth("letmein")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(progress.stage, crate::hydration::Stage::Done);
+    assert_eq!(progress.installations_total, 1);
+    assert_eq!(progress.installations_done, 1);
+    assert_eq!(progress.repositories_total, 1);
+    assert_eq!(progress.repositories_done, 1);
+    assert_eq!(progress.current_repository, None);
+    assert_eq!(progress.pulls_hydrated, 1);
+    assert_eq!(progress.diffs_fetched, 1);
+    assert!(progress.finished_at >= progress.started_at);
+}
+
+#[tokio::test]
+async fn test_conflict_graph_is_served() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let p1 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let p2 = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pulls("test/repo", &[p1, p2])
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+    let get = |path: &str| {
+        reqwest::Client::new()
+            .get(format!("{}{}", url, path))
+            .bearer_auth("letmein")
+            .send()
+    };
+
+    let graph: crate::graph::ConflictGraph = get("/api/v1/repos/test/repo/conflict-graph")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(graph.repository, "test/repo");
+    assert_eq!(graph.nodes.len(), 2);
+    assert_eq!(graph.edges.len(), 1);
+
+    let dot = get("/api/v1/repos/test/repo/conflict-graph?format=dot")
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+    assert!(dot.starts_with("digraph \"test/repo\""));
+    assert!(dot.contains("->"));
+
+    let missing = get("/api/v1/repos/test/other/conflict-graph")
+        .await
+        .unwrap();
+    assert_eq!(missing.status(), reqwest::StatusCode::NOT_FOUND);
+    let unknown_format = get("/api/v1/repos/test/repo/conflict-graph?format=svg")
+        .await
+        .unwrap();
+    assert_eq!(unknown_format.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_recent_messages_are_served() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+
+    let messages: Vec<crate::trace::Entry> = reqwest::Client::new()
+        .get(format!("{}/api/v1/messages?limit=5", url))
+        .bearer_auth("letmein")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let init = messages.iter().find(|m| m.kind == "Init").unwrap();
+    assert_eq!(init.sequence, 1);
+    assert_eq!(init.outcome, crate::trace::Outcome::Done);
+    assert!(init.duration_ms.is_some());
+}
+
+#[tokio::test]
+async fn test_installations_are_managed() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    server = server
+        .with_pull("test/repo", &pull)
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+    let request = |method: reqwest::Method, path: &str| {
+        reqwest::Client::new()
+            .request(method, format!("{}{}", url, path))
+            .bearer_auth("letmein")
+            .send()
+    };
+
+    // The page is public, and the data behind it is not.
+    let page = reqwest::get(format!("{}/installations", url))
+        .await
+        .unwrap();
+    assert_eq!(page.status(), reqwest::StatusCode::OK);
+    assert!(page.text().await.unwrap().contains("/api/v1/installations"));
+    let anonymous = reqwest::get(format!("{}/api/v1/installations", url))
+        .await
+        .unwrap();
+    assert_eq!(anonymous.status(), reqwest::StatusCode::UNAUTHORIZED);
+
+    let installations: Vec<structs::InstallationSummary> =
+        request(reqwest::Method::GET, "/api/v1/installations")
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    assert_eq!(installations.len(), 1);
+    let installation = &installations[0];
+    assert!(installation.token_expires_at.is_some());
+    assert_eq!(
+        installation.repositories,
+        vec![structs::RepositorySummary {
+            full_name: "test/repo".to_string(),
+            pulls: 1,
+            hydrated: true,
+        }]
+    );
+
+    let id = installation.installation_id;
+    let r = request(
+        reqwest::Method::POST,
+        &format!("/api/v1/installations/{}/token", id),
+    )
+    .await
+    .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::NO_CONTENT);
+    let r = request(reqwest::Method::POST, "/api/v1/repos/test/repo/resync")
+        .await
+        .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::ACCEPTED);
+
+    let path = format!("/api/v1/installations/{}", id);
+    let r = request(reqwest::Method::DELETE, &path).await.unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::NO_CONTENT);
+    let r = request(reqwest::Method::DELETE, &path).await.unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::NOT_FOUND);
+    let installations: Vec<structs::InstallationSummary> =
+        request(reqwest::Method::GET, "/api/v1/installations")
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    assert!(installations.is_empty());
+}
+
+#[tokio::test]
+async fn test_version_is_served() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+
+    let version: crate::version::Version = reqwest::Client::new()
+        .get(format!("{}/api/v1/version", url))
+        .bearer_auth("letmein")
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+    assert_eq!(version.app_id, "1****6");
+
+    for page in ["/", "/installations"] {
+        let body = reqwest::get(format!("{}{}", url, page))
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert!(body.contains(&version.summary()), "{}", page);
+    }
+}
+
+#[tokio::test]
+async fn test_comment_preview() {
+    let server = GitHubServer::new()
+        .await
+        .with_default_github_app()
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+    let preview = |body: String| {
+        reqwest::Client::new()
+            .post(format!("{}/api/v1/preview-comment?repo=test/repo", url))
+            .bearer_auth("letmein")
+            .header("Content-Type", "application/json")
+            .body(body)
+            .send()
+    };
+
+    let conflict = crate::helpers::conflicts::Conflict::overlap(
+        2,
+        1,
+        "https://github.com/test/repo/pull/1".to_string(),
+        vec!["wiki/Article/en.md".to_string()],
+    );
+    let r = preview(serde_json::to_string(&conflict).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::OK);
+    let rendered: api::CommentPreview = r.json().await.unwrap();
+    assert!(rendered
+        .markdown
+        .contains(crate::helpers::comments::OVERLAP_TEMPLATE.trim()));
+    assert!(rendered.markdown.contains("`wiki/Article/en.md`"));
+    assert!(rendered.html.contains("<code>wiki/Article/en.md</code>"));
+
+    let r = preview(r#"{"kind": "overlap"}"#.to_string()).await.unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_simulation() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let pull = server.make_pull("test/repo", &["wiki/Article/en.md", "wiki/Other/fr.md"]);
+    server = server
+        .with_pull("test/repo", &pull)
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+    let simulate = |repo: &str, body: serde_json::Value| {
+        reqwest::Client::new()
+            .post(format!("{}/api/v1/repos/{}/simulate", url, repo))
+            .bearer_auth("letmein")
+            .json(&body)
+            .send()
+    };
+
+    let r = simulate(
+        "test/repo",
+        serde_json::json!({"files": ["wiki/Article/en.md", "wiki/Unrelated/en.md"]}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::OK);
+    let conflicts: Vec<crate::helpers::conflicts::Conflict> = r.json().await.unwrap();
+    assert_eq!(
+        conflicts,
+        vec![crate::helpers::conflicts::Conflict::overlap(
+            0,
+            pull.number,
+            pull.html_url.clone(),
+            vec!["wiki/Article/en.md".to_string()],
+        )]
+    );
+
+    let diff = crate::test::make_simple_diff(&["wiki/Other/fr.md"], 1).to_string();
+    let conflicts: Vec<crate::helpers::conflicts::Conflict> =
+        simulate("test/repo", serde_json::json!({"diff": diff}))
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+    assert_eq!(conflicts.len(), 1);
+    assert_eq!(conflicts[0].file_set, vec!["wiki/Other/fr.md".to_string()]);
+
+    let conflicts: Vec<crate::helpers::conflicts::Conflict> = simulate(
+        "test/repo",
+        serde_json::json!({"files": ["wiki/Article/en.md"], "base": "elsewhere"}),
+    )
+    .await
+    .unwrap()
+    .json()
+    .await
+    .unwrap();
+    assert!(conflicts.is_empty());
+
+    let r = simulate("test/missing", serde_json::json!({"files": []}))
+        .await
+        .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::NOT_FOUND);
+    let r = simulate(
+        "test/repo",
+        serde_json::json!({"files": "wiki/Article/en.md"}),
+    )
+    .await
+    .unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn test_article_pulls() {
+    let mut server = GitHubServer::new().await.with_default_github_app();
+    let original = server.make_pull("test/repo", &["wiki/Article/en.md"]);
+    let translation = server.make_pull("test/repo", &["wiki/Article/fr.md", "wiki/Other/fr.md"]);
+    let unrelated = server.make_pull("test/repo", &["wiki/Article_2/en.md"]);
+    server = server
+        .with_pulls(
+            "test/repo",
+            &[original.clone(), translation.clone(), unrelated],
+        )
+        .with_default_app_installations();
+    let mut settings = config::Config::from_path(".config.yaml").unwrap();
+    settings.server.admin_tokens = vec![config::AdminToken::Token {
+        token: "letmein".to_string(),
+    }];
+    let url = serve_with(server.url.clone(), settings).await;
+    let lookup = |path: &str| {
+        reqwest::Client::new()
+            .get(format!("{}/api/v1/repos/{}", url, path))
+            .bearer_auth("letmein")
+            .send()
+    };
+
+    let pulls: Vec<structs::ArticlePull> = lookup("test/repo/articles/wiki/Article")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        pulls
+            .iter()
+            .map(|p| (p.number, p.files.clone()))
+            .collect::<Vec<_>>(),
+        vec![
+            (original.number, vec!["wiki/Article/en.md".to_string()]),
+            (translation.number, vec!["wiki/Article/fr.md".to_string()]),
+        ]
+    );
+    assert_eq!(pulls[0].html_url, original.html_url);
+
+    let same: Vec<structs::ArticlePull> = lookup("test/repo/articles/wiki/article/ru.md")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(same, pulls);
+
+    let none: Vec<structs::ArticlePull> = lookup("test/repo/articles/wiki/Missing%20article")
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert!(none.is_empty());
+
+    let r = lookup("test/missing/articles/wiki/Article").await.unwrap();
+    assert_eq!(r.status(), reqwest::StatusCode::NOT_FOUND);
+}
@@ -0,0 +1,88 @@
+//! Snapshots of what the app knows, for moving it between hosts without reading everything from GitHub again.
+use std::collections::HashMap;
+
+use eyre::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::helpers::conflicts::Conflict;
+use crate::helpers::diff::DiffSummary;
+use crate::history::RepoHistory;
+use crate::structs::{GraphQLPullRequestFile, Installation, LineRanges, PullRequest, Repository};
+
+/// Snapshot format version. Bump it on incompatible changes: snapshots of other versions are rejected on import.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub installations: Vec<InstallationSnapshot>,
+
+    /// Full repository name -> open pulls.
+    pub pulls: HashMap<String, Vec<PullSnapshot>>,
+
+    /// Full repository name -> known conflicts.
+    pub conflicts: HashMap<String, Vec<Conflict>>,
+
+    /// Full repository name -> the latest merged change to every original. Missing in snapshots of older versions.
+    #[serde(default)]
+    pub article_history: HashMap<String, RepoHistory>,
+}
+
+impl Snapshot {
+    pub fn from_path(path: &str) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_json(&contents)
+    }
+
+    pub fn from_json(contents: &str) -> Result<Self> {
+        let snapshot: Self = serde_json::from_str(contents)?;
+        if snapshot.version != SNAPSHOT_VERSION {
+            eyre::bail!(
+                "Unsupported snapshot version {} (expected {})",
+                snapshot.version,
+                SNAPSHOT_VERSION
+            );
+        }
+        Ok(snapshot)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct InstallationSnapshot {
+    pub installation: Installation,
+    pub repositories: Vec<Repository>,
+}
+
+/// A pull request with its changed files instead of a full diff.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PullSnapshot {
+    #[serde(flatten)]
+    pub pull: PullRequest,
+    pub files: Vec<GraphQLPullRequestFile>,
+
+    #[serde(default)]
+    pub line_ranges: Option<LineRanges>,
+}
+
+impl From<&PullRequest> for PullSnapshot {
+    fn from(p: &PullRequest) -> Self {
+        Self {
+            pull: p.clone(),
+            files: p
+                .diff
+                .as_ref()
+                .map(DiffSummary::changed_files)
+                .unwrap_or_default(),
+            line_ranges: p.diff.as_ref().and_then(DiffSummary::line_ranges),
+        }
+    }
+}
+
+impl PullSnapshot {
+    pub fn into_pull(self) -> PullRequest {
+        let mut pull = self.pull;
+        pull.diff = Some(DiffSummary::from_files(&self.files).with_line_ranges(self.line_ranges));
+        pull
+    }
+}
@@ -1,12 +1,17 @@
 // https://docs.github.com/developers/webhooks-and-events/webhooks/webhook-events-and-payloads
 
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::helpers::diff::DiffSummary;
 
 // https://docs.github.com/en/rest/users/users
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq)]
 pub struct Actor {
     pub id: i64,
+
+    /// Enterprise accounts (which may own installations) have a slug instead of a login.
+    #[serde(alias = "slug")]
     pub login: String,
 }
 
@@ -18,6 +23,10 @@ pub struct Repository {
     pub full_name: String,
     pub fork: Option<bool>,   // missing in installation events
     pub owner: Option<Actor>, // missing in installation events
+
+    /// Archived repositories are read-only, so the app leaves them alone.
+    #[serde(default)]
+    pub archived: bool,
 }
 
 // https://docs.github.com/en/rest/pulls/pulls
@@ -31,31 +40,137 @@ pub struct PullRequest {
     pub html_url: String,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+
+    /// Changed files, without their contents (see [`Self::set_diff`]).
     #[serde(skip)]
-    pub diff: Option<unidiff::PatchSet>,
+    pub diff: Option<DiffSummary>,
+
+    #[serde(default)]
+    pub base: PullRequestBranch,
+
+    #[serde(default)]
+    pub head: PullRequestBranch,
 
     #[serde(default)]
     pub merged_at: Option<chrono::DateTime<chrono::Utc>>,
 
     #[serde(default)]
     pub merged: bool,
+
+    /// GitHub's merge status, such as `clean` or `dirty`. It is only computed on request, so listings and events may not have it.
+    #[serde(default)]
+    pub mergeable_state: Option<String>,
+
+    #[serde(default)]
+    pub labels: Vec<Label>,
+
+    /// How the author is related to the repository, such as `FIRST_TIME_CONTRIBUTOR` or `MEMBER`.
+    #[serde(default)]
+    pub author_association: String,
 }
 
+// https://docs.github.com/en/rest/issues/labels
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Label {
+    pub name: String,
+}
+
+/// File path -> `[start, end)` line ranges of hunks, in line numbers of the base version.
+pub type LineRanges = BTreeMap<String, Vec<(usize, usize)>>;
+
 impl PullRequest {
     pub fn is_merged(&self) -> bool {
         self.merged || self.merged_at.is_some()
     }
+
+    /// Whether the pull can't be merged without resolving git conflicts first.
+    pub fn has_git_conflicts(&self) -> bool {
+        self.mergeable_state.as_deref() == Some("dirty")
+    }
+
+    /// Whether the changes come from another repository. Pulls from deleted forks count, pulls with unknown repositories don't.
+    pub fn is_from_fork(&self) -> bool {
+        match (&self.head.repo, &self.base.repo) {
+            (Some(head), Some(base)) => !head.full_name.eq_ignore_ascii_case(&base.full_name),
+            (None, Some(_)) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether this is the author's first pull request in the repository, or on GitHub at all.
+    pub fn is_from_first_time_contributor(&self) -> bool {
+        matches!(
+            self.author_association.as_str(),
+            "FIRST_TIME_CONTRIBUTOR" | "FIRST_TIMER"
+        )
+    }
+
+    /// Attach a complete diff, keeping only its summary.
+    pub fn set_diff(&mut self, diff: unidiff::PatchSet) {
+        self.diff = Some(DiffSummary::from(&diff));
+    }
 }
 
+/// One end of a pull request: the branch it is going to be merged into (`base`), or the one with the changes (`head`).
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PullRequestBranch {
+    #[serde(rename = "ref", default)]
+    pub branch: String,
+
+    /// The latest commit of the branch.
+    #[serde(default)]
+    pub sha: String,
+
+    /// The repository with the branch. This is missing if it has been deleted, as it happens to forks.
+    #[serde(default)]
+    pub repo: Option<PullRequestRepository>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct PullRequestRepository {
+    pub full_name: String,
+}
+
+/// Keys of a webhook payload which the app doesn't read. They are kept instead of failing the event if GitHub adds or
+/// changes something, and show up in debug output.
+pub type Extra = BTreeMap<String, serde_json::Value>;
+
 // https://docs.github.com/en/developers/webhooks-and-events/webhooks/webhook-events-and-payloads#pull_request
 #[derive(Debug, Serialize, Deserialize)]
 pub struct PullRequestEvent {
     pub action: String,
+
+    #[serde(default)]
     pub number: i32,
     pub pull_request: PullRequest,
     pub repository: Repository,
     pub installation: InstallationIdWrapper,
+    #[serde(default)]
     pub sender: Actor,
+
+    /// Previous values of changed fields (only for `edited` events).
+    #[serde(default)]
+    pub changes: PullRequestChanges,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct PullRequestChanges {
+    #[serde(default)]
+    pub base: Option<PullRequestBaseChange>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullRequestBaseChange {
+    #[serde(rename = "ref")]
+    pub branch: ChangedValue,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ChangedValue {
+    pub from: String,
 }
 
 // https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#installation
@@ -63,10 +178,14 @@ pub struct PullRequestEvent {
 pub struct InstallationEvent {
     pub action: String,
     pub installation: Installation,
+    #[serde(default)]
     pub sender: Actor,
 
     #[serde(default)]
     pub repositories: Vec<Repository>,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
 }
 
 // https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#installation_repositories
@@ -74,9 +193,168 @@ pub struct InstallationEvent {
 pub struct InstallationRepositoriesEvent {
     pub action: String,
     pub installation: Installation,
+    #[serde(default)]
     pub sender: Actor,
+
+    #[serde(default)]
     pub repositories_added: Vec<Repository>,
+    #[serde(default)]
     pub repositories_removed: Vec<Repository>,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+// https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#repository
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RepositoryEvent {
+    pub action: String,
+    pub repository: Repository,
+    pub installation: InstallationIdWrapper,
+    #[serde(default)]
+    pub sender: Actor,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+// https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#merge_group
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeGroupEvent {
+    pub action: String,
+
+    /// Why the group was destroyed: `merged`, `invalidated` or `dequeued` (only for `destroyed` events).
+    #[serde(default)]
+    pub reason: Option<String>,
+    pub merge_group: MergeGroup,
+    pub repository: Repository,
+    pub installation: InstallationIdWrapper,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeGroup {
+    pub head_sha: String,
+
+    /// The temporary branch of the group, such as `refs/heads/gh-readonly-queue/master/pr-123-<sha>`.
+    pub head_ref: String,
+    pub base_ref: String,
+}
+
+// https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#push
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushEvent {
+    /// The full name of the pushed ref, such as `refs/heads/master`.
+    #[serde(rename = "ref")]
+    pub git_ref: String,
+    pub repository: PushRepository,
+    pub installation: InstallationIdWrapper,
+
+    /// Up to 2048 latest commits of the push.
+    #[serde(default)]
+    pub commits: Vec<PushCommit>,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushRepository {
+    pub full_name: String,
+    pub default_branch: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PushCommit {
+    #[serde(default)]
+    pub added: Vec<String>,
+    #[serde(default)]
+    pub removed: Vec<String>,
+    #[serde(default)]
+    pub modified: Vec<String>,
+}
+
+impl PushEvent {
+    /// Whether the push is to the default branch of the repository.
+    pub fn is_to_default_branch(&self) -> bool {
+        self.git_ref.strip_prefix("refs/heads/") == Some(self.repository.default_branch.as_str())
+    }
+
+    /// Whether any of the commits has added, changed or removed the file.
+    pub fn touches(&self, path: &str) -> bool {
+        self.commits.iter().any(|c| {
+            c.added
+                .iter()
+                .chain(&c.removed)
+                .chain(&c.modified)
+                .any(|p| p == path)
+        })
+    }
+}
+
+// https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#installation_target
+#[derive(Debug, Serialize, Deserialize)]
+pub struct InstallationTargetEvent {
+    pub action: String,
+
+    /// The account which the app is installed on, as it is now.
+    pub account: Actor,
+    pub installation: InstallationIdWrapper,
+
+    /// `Organization`, `User` or `Enterprise`.
+    pub target_type: String,
+    pub sender: Actor,
+
+    #[serde(default)]
+    pub changes: InstallationTargetChanges,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct InstallationTargetChanges {
+    /// Enterprise accounts have a slug instead of a login.
+    #[serde(default, alias = "slug")]
+    pub login: Option<ChangedValue>,
+}
+
+// https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#github_app_authorization
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GitHubAppAuthorizationEvent {
+    pub action: String,
+    pub sender: Actor,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+// https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#ping
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PingEvent {
+    #[serde(default)]
+    pub zen: String,
+    #[serde(default)]
+    pub hook_id: i64,
+    pub hook: Hook,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Hook {
+    /// `App` for GitHub App webhooks, `Repository` or `Organization` for others.
+    #[serde(rename = "type")]
+    pub hook_type: String,
+
+    #[serde(default)]
+    pub events: Vec<String>,
+
+    /// Only set for GitHub App webhooks.
+    pub app_id: Option<i64>,
 }
 
 // Pull request events only contain installation id
@@ -91,15 +369,22 @@ pub struct Installation {
     pub id: i64,
     pub account: Actor,
     pub app_id: i64,
+
+    /// Set while the installation is suspended: GitHub refuses its tokens, and the app leaves its repositories alone.
+    #[serde(default)]
+    pub suspended_at: Option<chrono::DateTime<chrono::Utc>>,
 }
 
+/// Permission name -> access level (`read`, `write` or `admin`), such as `issues: write`.
+pub type Permissions = HashMap<String, String>;
+
 // https://docs.github.com/en/rest/reference/apps#create-an-installation-access-token-for-an-app
 #[derive(Debug, Serialize, Deserialize)]
 pub struct InstallationToken {
     pub token: String,
     pub expires_at: chrono::DateTime<chrono::Utc>,
     pub repositories: Option<Vec<Repository>>,
-    pub permissions: HashMap<String, String>,
+    pub permissions: Permissions,
 }
 
 // https://docs.github.com/en/rest/apps/installations#list-repositories-accessible-to-the-app-installation
@@ -117,6 +402,179 @@ pub struct IssueComment {
     pub user: Actor,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub updated_at: chrono::DateTime<chrono::Utc>,
+
+    #[serde(default)]
+    pub author_association: String,
+
+    // GraphQL identifier, used for mutations which are not available in the REST API
+    #[serde(default)]
+    pub node_id: String,
+
+    /// The app the comment was made through, if any.
+    #[serde(default)]
+    pub performed_via_github_app: Option<CommentApp>,
+}
+
+/// GitHub sends the whole app, but only its ID is of any use.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CommentApp {
+    pub id: i64,
+}
+
+// https://docs.github.com/en/rest/issues/issues
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Issue {
+    pub number: i32,
+    pub user: Actor,
+
+    // Only present if the issue is a pull request
+    #[serde(default)]
+    pub pull_request: Option<IssuePullRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct IssuePullRequest {
+    pub url: String,
+}
+
+// https://docs.github.com/en/webhooks/webhook-events-and-payloads#pull_request_review
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PullRequestReviewEvent {
+    pub action: String,
+    pub review: PullRequestReview,
+    pub pull_request: PullRequest,
+    pub repository: Repository,
+    pub installation: InstallationIdWrapper,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct PullRequestReview {
+    pub id: i64,
+    pub user: Actor,
+
+    /// One of `approved`, `changes_requested`, `commented` or `dismissed`.
+    pub state: String,
+
+    #[serde(default)]
+    pub author_association: String,
+}
+
+// https://docs.github.com/en/rest/rate-limit/rate-limit#get-rate-limit-status-for-the-authenticated-user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimits {
+    pub resources: RateLimitResources,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitResources {
+    pub core: RateLimit,
+    pub search: RateLimit,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    pub limit: i64,
+    pub remaining: i64,
+    pub used: i64,
+
+    /// When the budget is replenished, as a UNIX timestamp.
+    pub reset: i64,
+}
+
+/// Rate limit budget of a single installation token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallationRateLimits {
+    pub installation_id: i64,
+    pub account: String,
+    pub core: RateLimit,
+    pub search: RateLimit,
+}
+
+/// An installation as the app knows it, with its repositories (see `GET /api/v1/installations`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InstallationSummary {
+    pub installation_id: i64,
+    pub account: String,
+    pub suspended: bool,
+
+    /// Whether requests on its behalf are on hold after repeated authentication failures.
+    pub quarantined: bool,
+
+    /// When the cached installation token expires, if there is one.
+    pub token_expires_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// Whether the permissions of the installation don't allow posting comments (see
+    /// [`crate::github::can_write_comments`]). Only known once the installation has a token.
+    pub cannot_comment: bool,
+
+    /// Sorted by full name.
+    pub repositories: Vec<RepositorySummary>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RepositorySummary {
+    pub full_name: String,
+
+    /// Open pull requests kept in memory.
+    pub pulls: usize,
+
+    /// Whether its pull requests have been read (see `controller.hydration`).
+    pub hydrated: bool,
+}
+
+/// An open pull request which changes an article (see `GET /api/v1/repos/{owner}/{name}/articles/{path}`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ArticlePull {
+    pub number: i32,
+    pub title: String,
+    pub html_url: String,
+    pub author: String,
+    pub base_branch: String,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+
+    /// Changed files of the article (the original and translations), sorted by path.
+    pub files: Vec<String>,
+}
+
+// https://docs.github.com/en/rest/meta/meta#get-github-meta-information
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Meta {
+    /// CIDR ranges which webhook deliveries originate from.
+    #[serde(default)]
+    pub hooks: Vec<String>,
+}
+
+// https://docs.github.com/en/rest/commits/commits#compare-two-commits
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Comparison {
+    pub html_url: String,
+    pub total_commits: i32,
+
+    #[serde(default)]
+    pub files: Vec<ComparisonFile>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ComparisonFile {
+    pub filename: String,
+}
+
+// https://docs.github.com/webhooks-and-events/webhooks/webhook-events-and-payloads#issue_comment
+#[derive(Debug, Serialize, Deserialize)]
+pub struct IssueCommentEvent {
+    pub action: String,
+    pub issue: Issue,
+    pub comment: IssueComment,
+    pub repository: Repository,
+    pub installation: InstallationIdWrapper,
+    #[serde(default)]
+    pub sender: Actor,
+
+    #[serde(flatten, skip_serializing_if = "Extra::is_empty")]
+    pub extra: Extra,
 }
 
 // https://docs.github.com/en/rest/issues/comments#create-an-issue-comment
@@ -133,3 +591,189 @@ pub struct App {
     pub owner: Actor,
     pub name: String,
 }
+
+// https://docs.github.com/en/graphql/guides/forming-calls-with-graphql
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphQLRequest {
+    pub query: String,
+    pub variables: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphQLResponse<T> {
+    pub data: Option<T>,
+
+    #[serde(default)]
+    pub errors: Vec<GraphQLError>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphQLError {
+    pub message: String,
+}
+
+// https://docs.github.com/en/graphql/reference/objects#pullrequestconnection
+// The GraphQL counterparts of REST objects contain only what's needed for hydrating repositories.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLRepositoryPulls {
+    pub repository: GraphQLRepository,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLRepository {
+    pub pull_requests: GraphQLConnection<GraphQLPullRequest>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLConnection<T> {
+    pub page_info: GraphQLPageInfo,
+    pub nodes: Vec<T>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLPageInfo {
+    pub has_next_page: bool,
+    pub end_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLActor {
+    #[serde(rename = "__typename")]
+    pub typename: String,
+    pub login: String,
+
+    #[serde(default)]
+    pub database_id: Option<i64>,
+}
+
+impl From<GraphQLActor> for Actor {
+    fn from(a: GraphQLActor) -> Self {
+        // GraphQL omits the suffix which the REST API adds to app logins.
+        let login = if a.typename == "Bot" {
+            format!("{}[bot]", a.login)
+        } else {
+            a.login
+        };
+        Self {
+            id: a.database_id.unwrap_or_default(),
+            login,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLPullRequest {
+    pub database_id: i64,
+    pub number: i32,
+    pub state: String,
+    pub title: String,
+    pub url: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub author: Option<GraphQLActor>,
+
+    #[serde(default)]
+    pub author_association: String,
+
+    #[serde(default)]
+    pub base_ref_name: String,
+
+    #[serde(default)]
+    pub base_ref_oid: String,
+
+    #[serde(default)]
+    pub base_repository: Option<GraphQLRepositoryName>,
+
+    #[serde(default)]
+    pub head_ref_name: String,
+
+    #[serde(default)]
+    pub head_ref_oid: String,
+
+    #[serde(default)]
+    pub head_repository: Option<GraphQLRepositoryName>,
+    pub files: GraphQLConnection<GraphQLPullRequestFile>,
+    pub comments: GraphQLConnection<GraphQLIssueComment>,
+
+    #[serde(default)]
+    pub labels: GraphQLLabels,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct GraphQLLabels {
+    pub nodes: Vec<Label>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLRepositoryName {
+    pub name_with_owner: String,
+}
+
+impl From<GraphQLRepositoryName> for PullRequestRepository {
+    fn from(r: GraphQLRepositoryName) -> Self {
+        Self {
+            full_name: r.name_with_owner,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLPullRequestFile {
+    pub path: String,
+    pub change_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLIssueComment {
+    pub database_id: i64,
+    pub id: String,
+    pub body: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub updated_at: chrono::DateTime<chrono::Utc>,
+    pub author: Option<GraphQLActor>,
+    pub author_association: String,
+}
+
+impl From<GraphQLIssueComment> for IssueComment {
+    fn from(c: GraphQLIssueComment) -> Self {
+        Self {
+            id: c.database_id,
+            body: c.body,
+            user: c.author.map(Actor::from).unwrap_or_else(|| Actor {
+                id: 0,
+                login: "ghost".to_string(),
+            }),
+            created_at: c.created_at,
+            updated_at: c.updated_at,
+            author_association: c.author_association,
+            node_id: c.id,
+            performed_via_github_app: None,
+        }
+    }
+}
+
+// https://docs.github.com/en/graphql/reference/queries#nodes
+#[derive(Debug, Serialize, Deserialize)]
+pub struct GraphQLNodes<T> {
+    pub nodes: Vec<Option<T>>,
+}
+
+/// Whether a comment is hidden, which the REST API doesn't tell.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GraphQLMinimizedComment {
+    pub id: String,
+    pub is_minimized: bool,
+}
+
+#[cfg(test)]
+mod tests;
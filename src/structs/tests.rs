@@ -0,0 +1,202 @@
+use super::*;
+
+use crate::test::{load_fixture, load_typed_fixture, FIXTURES_DIR};
+
+/// Every fixture has to deserialize into the struct which [`crate::handler`] uses for its event type, so that a new
+/// payload variant is covered as soon as it is added.
+#[test]
+fn test_all_fixtures_deserialize() {
+    let mut names: Vec<_> = std::fs::read_dir(FIXTURES_DIR)
+        .unwrap()
+        .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+        .filter(|n| n.ends_with(".json"))
+        .collect();
+    names.sort();
+    assert!(!names.is_empty());
+
+    for name in names {
+        let body = load_fixture(&name);
+        let result = match name.split('.').next().unwrap() {
+            "pull_request" => serde_json::from_str::<PullRequestEvent>(&body).map(|_| ()),
+            "pull_request_review" => {
+                serde_json::from_str::<PullRequestReviewEvent>(&body).map(|_| ())
+            }
+            "issue_comment" => serde_json::from_str::<IssueCommentEvent>(&body).map(|_| ()),
+            "installation" => serde_json::from_str::<InstallationEvent>(&body).map(|_| ()),
+            "installation_repositories" => {
+                serde_json::from_str::<InstallationRepositoriesEvent>(&body).map(|_| ())
+            }
+            "repository" => serde_json::from_str::<RepositoryEvent>(&body).map(|_| ()),
+            "installation_target" => {
+                serde_json::from_str::<InstallationTargetEvent>(&body).map(|_| ())
+            }
+            "github_app_authorization" => {
+                serde_json::from_str::<GitHubAppAuthorizationEvent>(&body).map(|_| ())
+            }
+            "ping" => serde_json::from_str::<PingEvent>(&body).map(|_| ()),
+            "push" => serde_json::from_str::<PushEvent>(&body).map(|_| ()),
+            "merge_group" => serde_json::from_str::<MergeGroupEvent>(&body).map(|_| ()),
+            other => panic!("{}: unknown event type {:?}", name, other),
+        };
+        if let Err(e) = result {
+            panic!("{}: {}", name, e);
+        }
+    }
+}
+
+#[test]
+fn test_pull_request_variants() {
+    let evt: PullRequestEvent = load_typed_fixture("pull_request.opened.json");
+    assert_eq!(evt.number, 11);
+    assert_eq!(evt.repository.full_name, "ppy/osu-wiki");
+    assert_eq!(evt.pull_request.base.branch, "master");
+    assert_eq!(evt.pull_request.head.branch, "update-rc");
+    assert!(!evt.pull_request.is_merged());
+    assert!(!evt.pull_request.is_from_fork());
+    assert_eq!(evt.pull_request.author_association, "CONTRIBUTOR");
+    assert!(!evt.pull_request.is_from_first_time_contributor());
+
+    let evt: PullRequestEvent = load_typed_fixture("pull_request.opened.draft_from_fork.json");
+    assert_eq!(evt.pull_request.user.login, "Walavouchey");
+    assert_eq!(evt.repository.fork, Some(false));
+    assert_eq!(
+        evt.pull_request.head.repo.as_ref().unwrap().full_name,
+        "Walavouchey/osu-wiki"
+    );
+    assert!(evt.pull_request.is_from_fork());
+
+    let evt: PullRequestEvent = load_typed_fixture("pull_request.opened.bot.json");
+    assert_eq!(evt.pull_request.user.login, "dependabot[bot]");
+
+    let evt: PullRequestEvent = load_typed_fixture("pull_request.closed.merged.json");
+    assert!(evt.pull_request.is_merged());
+
+    let evt: PullRequestEvent = load_typed_fixture("pull_request.closed.deleted_fork.json");
+    assert!(!evt.pull_request.is_merged());
+    assert_eq!(
+        evt.pull_request.head.sha,
+        "6dcb09b5b57875f334f61aebed695e2e4193db5e"
+    );
+    assert!(evt.pull_request.head.repo.is_none());
+    assert!(evt.pull_request.is_from_fork());
+
+    let evt: PullRequestEvent = load_typed_fixture("pull_request.edited.base.json");
+    assert_eq!(evt.changes.base.unwrap().branch.from, "master");
+    assert_eq!(evt.pull_request.base.branch, "staging");
+
+    let evt: PullRequestEvent = load_typed_fixture("pull_request.edited.title.json");
+    assert!(evt.changes.base.is_none());
+}
+
+#[test]
+fn test_comment_and_review_variants() {
+    let evt: IssueCommentEvent = load_typed_fixture("issue_comment.created.json");
+    assert!(evt.issue.pull_request.is_some());
+    assert_eq!(evt.comment.author_association, "MEMBER");
+    assert_eq!(evt.comment.performed_via_github_app, None);
+
+    let evt: IssueCommentEvent = load_typed_fixture("issue_comment.created.issue.json");
+    assert!(evt.issue.pull_request.is_none());
+
+    let evt: PullRequestReviewEvent = load_typed_fixture("pull_request_review.submitted.json");
+    assert_eq!(evt.review.state, "approved");
+    assert_eq!(evt.review.user.login, "peppy");
+}
+
+#[test]
+fn test_installation_variants() {
+    let evt: InstallationEvent = load_typed_fixture("installation.created.json");
+    assert_eq!(evt.installation.account.login, "ppy");
+    assert_eq!(evt.repositories.len(), 1);
+    assert_eq!(evt.repositories[0].fork, None);
+
+    let evt: InstallationEvent = load_typed_fixture("installation.new_permissions_accepted.json");
+    assert!(evt.repositories.is_empty());
+
+    let evt: InstallationEvent = load_typed_fixture("installation.suspend.json");
+    assert!(evt.installation.suspended_at.is_some());
+    let evt: InstallationEvent = load_typed_fixture("installation.unsuspend.json");
+    assert!(evt.installation.suspended_at.is_none());
+
+    // Enterprise accounts have a slug instead of a login.
+    let evt: InstallationEvent = load_typed_fixture("installation.created.enterprise.json");
+    assert_eq!(evt.installation.account.login, "ppy-enterprise");
+
+    let evt: InstallationRepositoriesEvent =
+        load_typed_fixture("installation_repositories.added.json");
+    assert_eq!(evt.repositories_added[0].full_name, "ppy/osu");
+    assert!(evt.repositories_removed.is_empty());
+
+    let evt: InstallationTargetEvent = load_typed_fixture("installation_target.renamed.json");
+    assert_eq!(evt.account.login, "ppy-renamed");
+    assert_eq!(evt.changes.login.unwrap().from, "ppy");
+
+    let evt: GitHubAppAuthorizationEvent =
+        load_typed_fixture("github_app_authorization.revoked.json");
+    assert_eq!(evt.sender.login, "TicClick");
+}
+
+#[test]
+fn test_repository_variants() {
+    let evt: RepositoryEvent = load_typed_fixture("repository.renamed.json");
+    assert_eq!(evt.repository.id, 1);
+    assert_eq!(evt.repository.full_name, "ppy/osu-wiki-renamed");
+
+    let evt: RepositoryEvent = load_typed_fixture("repository.transferred.json");
+    assert_eq!(evt.repository.id, 1);
+    assert_eq!(evt.repository.full_name, "osu-community/osu-wiki");
+
+    let evt: RepositoryEvent = load_typed_fixture("repository.archived.json");
+    assert!(evt.repository.archived);
+}
+
+#[test]
+fn test_unknown_and_missing_keys_are_tolerated() {
+    let mut body: serde_json::Value =
+        serde_json::from_str(&load_fixture("pull_request.opened.json")).unwrap();
+    let payload = body.as_object_mut().unwrap();
+    payload.remove("sender");
+    payload.remove("number");
+    payload.insert(
+        "brand_new_key".to_string(),
+        serde_json::json!({"nested": [1, 2]}),
+    );
+
+    let evt: PullRequestEvent = serde_json::from_value(body).unwrap();
+    assert_eq!(evt.action, "opened");
+    assert_eq!(evt.sender, Actor::default());
+    assert_eq!(
+        evt.extra["brand_new_key"],
+        serde_json::json!({"nested": [1, 2]})
+    );
+    assert!(!evt.extra.contains_key("pull_request"));
+}
+
+#[test]
+fn test_ping() {
+    let evt: PingEvent = load_typed_fixture("ping.json");
+    assert_eq!(evt.zen, "Design for failure.");
+    assert_eq!(evt.hook.hook_type, "App");
+    assert_eq!(evt.hook.app_id, Some(123456));
+    assert!(evt.hook.events.contains(&"pull_request".to_string()));
+}
+
+#[test]
+fn test_merge_group() {
+    let evt: MergeGroupEvent = load_typed_fixture("merge_group.destroyed.json");
+    assert_eq!(evt.action, "destroyed");
+    assert_eq!(evt.reason.as_deref(), Some("merged"));
+    assert_eq!(evt.repository.full_name, "ppy/osu-wiki");
+    assert_eq!(evt.merge_group.base_ref, "refs/heads/master");
+}
+
+#[test]
+fn test_push() {
+    let mut evt: PushEvent = load_typed_fixture("push.config_changed.json");
+    assert!(evt.is_to_default_branch());
+    assert!(evt.touches(crate::repo_config::PATH));
+    assert!(!evt.touches("wiki/Article/en.md"));
+
+    evt.git_ref = "refs/heads/feature".to_string();
+    assert!(!evt.is_to_default_branch());
+}
@@ -0,0 +1,135 @@
+//! Slowing down expensive background work (reading diffs on start-up, reconciling comments, self-checks) while the app's
+//! cgroup is short of memory or CPU, judging by its pressure stall information. The latest state is reported by
+//! `GET /health`. Hosts without cgroup v2 are never throttled.
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+use crate::config;
+use crate::helpers::cgroup::CGroup;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct State {
+    pub throttled: bool,
+
+    /// Pressure readings, in percent of time stalled over the last 10 seconds. Missing if the cgroup doesn't report them.
+    pub memory_pressure: Option<f64>,
+    pub cpu_pressure: Option<f64>,
+
+    pub checked_at: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+/// The throttle, checked by the controller before background work and read by the health check.
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    config: config::Throttling,
+    cgroup: CGroup,
+    state: Arc<Mutex<State>>,
+}
+
+impl Throttle {
+    pub fn new(config: config::Throttling, cgroup: CGroup) -> Self {
+        Self {
+            config,
+            cgroup,
+            state: Arc::default(),
+        }
+    }
+
+    /// Read the pressure anew, and tell whether background work should be slowed down.
+    pub fn check(&self) -> bool {
+        self.state().throttled
+    }
+
+    /// Read the pressure anew, and remember it.
+    pub fn state(&self) -> State {
+        let memory_pressure = self.cgroup.pressure("memory");
+        let cpu_pressure = self.cgroup.pressure("cpu");
+        let over = |reading: Option<f64>, threshold: f64| {
+            threshold > 0.0 && reading.is_some_and(|r| r >= threshold)
+        };
+        let current = State {
+            throttled: over(memory_pressure, self.config.memory_pressure)
+                || over(cpu_pressure, self.config.cpu_pressure),
+            memory_pressure,
+            cpu_pressure,
+            checked_at: Some(chrono::Utc::now()),
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if current.throttled != state.throttled {
+            if current.throttled {
+                log::warn!(
+                    "Throttling background work: memory pressure {:?}, CPU pressure {:?}",
+                    memory_pressure,
+                    cpu_pressure
+                );
+            } else {
+                log::info!("Pressure has dropped, no longer throttling background work");
+            }
+        }
+        *state = current.clone();
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn throttled_above_thresholds() {
+        let dir = std::env::temp_dir().join(format!(
+            "observatory-cgroup-{}",
+            chrono::Utc::now().timestamp_nanos_opt().unwrap()
+        ));
+        std::fs::create_dir_all(&dir).unwrap();
+        let write_pressure = |resource: &str, avg10: f64| {
+            std::fs::write(
+                dir.join(format!("{}.pressure", resource)),
+                format!(
+                    "some avg10={:.2} avg60=0.00 avg300=0.00 total=100\nfull avg10=0.00 avg60=0.00 avg300=0.00 total=0\n",
+                    avg10
+                ),
+            )
+            .unwrap();
+        };
+        write_pressure("memory", 12.5);
+        write_pressure("cpu", 3.0);
+        let cgroup = CGroup { path: dir.clone() };
+
+        let disabled = Throttle::new(config::Throttling::default(), cgroup.clone());
+        let state = disabled.state();
+        assert!(!state.throttled);
+        assert_eq!(state.memory_pressure, Some(12.5));
+        assert_eq!(state.cpu_pressure, Some(3.0));
+
+        let throttle = Throttle::new(
+            config::Throttling {
+                memory_pressure: 20.0,
+                cpu_pressure: 50.0,
+            },
+            cgroup,
+        );
+        assert!(!throttle.check());
+        write_pressure("memory", 25.0);
+        assert!(throttle.check());
+        write_pressure("memory", 0.0);
+        write_pressure("cpu", 80.0);
+        assert!(throttle.check());
+
+        let elsewhere = Throttle::new(
+            config::Throttling {
+                memory_pressure: 1.0,
+                cpu_pressure: 1.0,
+            },
+            CGroup {
+                path: dir.join("missing"),
+            },
+        );
+        let state = elsewhere.state();
+        assert!(!state.throttled);
+        assert_eq!((state.memory_pressure, state.cpu_pressure), (None, None));
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
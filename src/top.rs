@@ -0,0 +1,238 @@
+//! A terminal view of a running instance, built from its admin API (see [`crate::api`]): the controller queue, rate limit
+//! budgets, known conflicts and latest audit log entries, refreshed periodically.
+use std::collections::BTreeMap;
+
+use eyre::Result;
+use serde::Deserialize;
+
+use crate::audit;
+use crate::helpers::conflicts::ConflictType;
+use crate::state::Snapshot;
+use crate::structs::InstallationRateLimits;
+
+/// How many audit log entries are shown.
+const RECENT_EVENTS: usize = 10;
+
+/// The response of `GET /health`.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Health {
+    pub controller: String,
+    pub controller_restarts: usize,
+    pub self_check_divergences: usize,
+
+    #[serde(default)]
+    pub queue_depth: usize,
+}
+
+/// Everything which is shown at once.
+#[derive(Debug)]
+pub struct Overview {
+    pub taken_at: chrono::DateTime<chrono::Utc>,
+    pub health: Health,
+    pub rate_limits: Vec<InstallationRateLimits>,
+
+    /// Full repository name -> conflict type -> how many are known.
+    pub conflicts: BTreeMap<String, BTreeMap<ConflictType, usize>>,
+
+    /// Newest first. Empty if the instance keeps no audit log.
+    pub events: Vec<audit::Entry>,
+}
+
+/// Admin API client, authenticated with one of `server.admin_tokens`.
+pub struct Client {
+    http_client: reqwest::Client,
+    base_url: String,
+    token: String,
+}
+
+impl Client {
+    pub fn new(base_url: &str, token: String) -> Self {
+        Self {
+            http_client: reqwest::Client::new(),
+            base_url: base_url.trim_end_matches('/').to_string(),
+            token,
+        }
+    }
+
+    async fn get<T>(&self, path: &str) -> Result<T>
+    where
+        T: for<'de> Deserialize<'de>,
+    {
+        let response = self
+            .http_client
+            .get(format!("{}{}", self.base_url, path))
+            .bearer_auth(&self.token)
+            .send()
+            .await?
+            .error_for_status()?;
+        Ok(response.json().await?)
+    }
+
+    pub async fn overview(&self) -> Result<Overview> {
+        let health: Health = self.get("/health").await?;
+        let rate_limits = self.get("/api/v1/ratelimit").await?;
+        let snapshot: Snapshot = self.get("/api/v1/state/export").await?;
+        let events = self
+            .get(&format!("/api/v1/audit?limit={}", RECENT_EVENTS))
+            .await?;
+
+        let mut conflicts: BTreeMap<String, BTreeMap<ConflictType, usize>> = BTreeMap::new();
+        for (full_repo_name, cc) in snapshot.conflicts {
+            let counts = conflicts.entry(full_repo_name).or_default();
+            for c in cc {
+                *counts.entry(c.kind).or_default() += 1;
+            }
+        }
+        Ok(Overview {
+            taken_at: chrono::Utc::now(),
+            health,
+            rate_limits,
+            conflicts,
+            events,
+        })
+    }
+}
+
+fn describe(action: &audit::Action) -> String {
+    let value = serde_json::to_value(action).unwrap_or_default();
+    let name = value["action"].as_str().unwrap_or("unknown");
+    match value["pull_number"]
+        .as_i64()
+        .or_else(|| value["conflict"]["trigger"].as_i64())
+    {
+        Some(n) => format!("{} #{}", name, n),
+        None => name.to_string(),
+    }
+}
+
+/// Plain text with one section per source, fit for a terminal.
+pub fn render(overview: &Overview) -> String {
+    let mut lines = vec![
+        format!(
+            "Controller: {}, {} restart(s), {} request(s) queued, {} self-check divergence(s)",
+            overview.health.controller,
+            overview.health.controller_restarts,
+            overview.health.queue_depth,
+            overview.health.self_check_divergences
+        ),
+        String::new(),
+        "Rate limits:".to_string(),
+    ];
+    for r in &overview.rate_limits {
+        let resets_in = chrono::DateTime::from_timestamp(r.core.reset, 0)
+            .map(|reset| (reset - overview.taken_at).num_minutes().max(0))
+            .unwrap_or_default();
+        lines.push(format!(
+            "  {} (#{}): core {}/{} (resets in {} min), search {}/{}",
+            r.account,
+            r.installation_id,
+            r.core.remaining,
+            r.core.limit,
+            resets_in,
+            r.search.remaining,
+            r.search.limit
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Active conflicts:".to_string());
+    for (full_repo_name, counts) in &overview.conflicts {
+        let total: usize = counts.values().sum();
+        let by_kind: Vec<_> = counts
+            .iter()
+            .map(|(kind, n)| format!("{:?}: {}", kind, n))
+            .collect();
+        lines.push(format!(
+            "  {}: {} ({})",
+            full_repo_name,
+            total,
+            by_kind.join(", ")
+        ));
+    }
+
+    lines.push(String::new());
+    lines.push("Recent events:".to_string());
+    for e in &overview.events {
+        lines.push(format!(
+            "  {} {} {}",
+            e.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            e.repository,
+            describe(&e.action)
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Redraw the view every `interval` until interrupted. Failed refreshes are shown instead of the view, and don't stop it.
+pub async fn run(client: Client, interval: std::time::Duration) -> Result<()> {
+    let mut timer = tokio::time::interval(interval);
+    loop {
+        timer.tick().await;
+        let text = match client.overview().await {
+            Ok(overview) => render(&overview),
+            Err(e) => format!("Failed to read {}: {}", client.base_url, e),
+        };
+        // Clear the screen and move the cursor to the top left corner.
+        println!("\x1b[2J\x1b[H{}", text);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::helpers::conflicts::Conflict;
+    use crate::structs::RateLimit;
+
+    #[test]
+    fn render_lists_every_section() {
+        let now = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let limit = |remaining| RateLimit {
+            limit: 5000,
+            remaining,
+            used: 5000 - remaining,
+            reset: (now + chrono::Duration::minutes(30)).timestamp(),
+        };
+        let overview = Overview {
+            taken_at: now,
+            health: Health {
+                controller: "running".to_string(),
+                controller_restarts: 1,
+                self_check_divergences: 0,
+                queue_depth: 3,
+            },
+            rate_limits: vec![InstallationRateLimits {
+                installation_id: 7,
+                account: "ppy".to_string(),
+                core: limit(4990),
+                search: limit(5000),
+            }],
+            conflicts: BTreeMap::from([(
+                "ppy/osu-wiki".to_string(),
+                BTreeMap::from([
+                    (ConflictType::Overlap, 2),
+                    (ConflictType::IncompleteTranslation, 1),
+                ]),
+            )]),
+            events: vec![audit::Entry {
+                timestamp: now,
+                repository: "ppy/osu-wiki".to_string(),
+                delivery_id: None,
+                action: audit::Action::ConflictStored {
+                    conflict: Conflict::overlap(
+                        2,
+                        1,
+                        "https://github.com/ppy/osu-wiki/pull/1".to_string(),
+                        vec!["wiki/Article/en.md".to_string()],
+                    ),
+                },
+            }],
+        };
+
+        let text = render(&overview);
+        assert!(text.contains("Controller: running, 1 restart(s), 3 request(s) queued"));
+        assert!(text.contains("ppy (#7): core 4990/5000 (resets in 30 min)"));
+        assert!(text.contains("ppy/osu-wiki: 3 (Overlap: 2, IncompleteTranslation: 1)"));
+        assert!(text.contains("ppy/osu-wiki conflict-stored #2"));
+    }
+}
@@ -0,0 +1,155 @@
+//! Recently processed controller messages, numbered in the order the controller has picked them up, with the time each
+//! has taken. Served by `GET /api/v1/messages`, so that messages handled out of order, or not at all, can be diagnosed in
+//! production. Reading the trace doesn't go through the controller queue, so it works even if the controller is stuck.
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+/// How many messages are remembered.
+pub const CAPACITY: usize = 500;
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Outcome {
+    InProgress,
+    Done,
+
+    /// The controller has panicked while handling the message, and has rebuilt its state afterwards.
+    Panicked,
+
+    /// The watchdog has given up on the message, and the controller has rebuilt its state (see [`crate::watchdog`]).
+    Abandoned,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Entry {
+    /// Grows by one with every message, starting from 1 when the app starts.
+    pub sequence: u64,
+
+    /// The kind of the request, such as `PullRequestUpdated`.
+    pub kind: String,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub repository: Option<String>,
+
+    /// The webhook delivery which has caused the message, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub delivery_id: Option<String>,
+
+    pub started_at: chrono::DateTime<chrono::Utc>,
+    pub finished_at: Option<chrono::DateTime<chrono::Utc>>,
+    pub duration_ms: Option<i64>,
+    pub outcome: Outcome,
+}
+
+#[derive(Debug, Default)]
+struct Messages {
+    last_sequence: u64,
+
+    /// Oldest first.
+    entries: VecDeque<Entry>,
+}
+
+/// The trace, written by the controller and read by the API.
+#[derive(Debug, Clone, Default)]
+pub struct Trace(Arc<Mutex<Messages>>);
+
+impl Trace {
+    /// Record that a message is being handled, and return its sequence number.
+    pub fn start(&self, kind: &str, repository: Option<&str>, delivery_id: Option<&str>) -> u64 {
+        let mut m = self.0.lock().unwrap();
+        m.last_sequence += 1;
+        let sequence = m.last_sequence;
+        if m.entries.len() == CAPACITY {
+            m.entries.pop_front();
+        }
+        m.entries.push_back(Entry {
+            sequence,
+            kind: kind.to_string(),
+            repository: repository.map(str::to_string),
+            delivery_id: delivery_id.map(str::to_string),
+            started_at: chrono::Utc::now(),
+            finished_at: None,
+            duration_ms: None,
+            outcome: Outcome::InProgress,
+        });
+        sequence
+    }
+
+    pub fn finish(&self, sequence: u64, outcome: Outcome) {
+        let mut m = self.0.lock().unwrap();
+        // Messages are handled one at a time, so the one being finished is almost always the latest.
+        if let Some(e) = m.entries.iter_mut().rev().find(|e| e.sequence == sequence) {
+            let now = chrono::Utc::now();
+            e.finished_at = Some(now);
+            e.duration_ms = Some((now - e.started_at).num_milliseconds());
+            e.outcome = outcome;
+        }
+    }
+
+    /// The message being handled right now, if any.
+    pub fn in_flight(&self) -> Option<Entry> {
+        let m = self.0.lock().unwrap();
+        m.entries
+            .back()
+            .filter(|e| e.outcome == Outcome::InProgress)
+            .cloned()
+    }
+
+    /// When the controller has last finished handling a message.
+    pub fn last_finished_at(&self) -> Option<chrono::DateTime<chrono::Utc>> {
+        let m = self.0.lock().unwrap();
+        m.entries.iter().rev().find_map(|e| e.finished_at)
+    }
+
+    /// Up to `limit` latest messages, newest first.
+    pub fn latest(&self, limit: usize) -> Vec<Entry> {
+        self.0
+            .lock()
+            .unwrap()
+            .entries
+            .iter()
+            .rev()
+            .take(limit)
+            .cloned()
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_are_numbered_and_capped() {
+        let trace = Trace::default();
+        let first = trace.start("Init", None, None);
+        trace.finish(first, Outcome::Done);
+        let second = trace.start("PullRequestUpdated", Some("test/repo"), Some("abc"));
+        assert_eq!((first, second), (1, 2));
+
+        let latest = trace.latest(10);
+        assert_eq!(latest.len(), 2);
+        assert_eq!(latest[0].sequence, 2);
+        assert_eq!(latest[0].outcome, Outcome::InProgress);
+        assert_eq!(latest[0].repository.as_deref(), Some("test/repo"));
+        assert_eq!(latest[0].delivery_id.as_deref(), Some("abc"));
+        assert_eq!(latest[1].outcome, Outcome::Done);
+        assert!(latest[1].duration_ms.is_some());
+        assert_eq!(trace.in_flight().unwrap().sequence, 2);
+        assert_eq!(trace.last_finished_at(), latest[1].finished_at);
+
+        trace.finish(second, Outcome::Panicked);
+        assert_eq!(trace.latest(1)[0].outcome, Outcome::Panicked);
+        assert!(trace.in_flight().is_none());
+
+        for _ in 0..CAPACITY {
+            trace.start("SelfCheck", None, None);
+        }
+        let latest = trace.latest(CAPACITY + 10);
+        assert_eq!(latest.len(), CAPACITY);
+        assert_eq!(latest[0].sequence, CAPACITY as u64 + 2);
+        assert_eq!(latest.last().unwrap().sequence, 3);
+    }
+}
@@ -0,0 +1,86 @@
+//! What is running: the crate version, the commit and the time it was built from, and the GitHub App it works for. Served
+//! by `GET /api/v1/version` and shown at the bottom of the pages, so that instances can be told apart when several are
+//! deployed.
+use serde::{Deserialize, Serialize};
+
+/// Cargo features the app has been built with.
+const FEATURES: &[(&str, bool)] = &[("soak", cfg!(feature = "soak"))];
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Version {
+    pub version: String,
+
+    /// The abbreviated commit hash, if the app was built from a git checkout.
+    pub git_commit: Option<String>,
+
+    pub built_at: Option<chrono::DateTime<chrono::Utc>>,
+
+    /// `github.app_id` from the config, with all but its first and last characters hidden.
+    pub app_id: String,
+
+    pub features: Vec<String>,
+}
+
+impl Version {
+    pub fn new(app_id: &str) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            git_commit: Some(env!("OBSERVATORY_GIT_COMMIT").to_string()).filter(|c| !c.is_empty()),
+            built_at: env!("OBSERVATORY_BUILT_AT")
+                .parse()
+                .ok()
+                .and_then(|t| chrono::DateTime::from_timestamp(t, 0)),
+            app_id: mask(app_id),
+            features: FEATURES
+                .iter()
+                .filter(|(_, enabled)| *enabled)
+                .map(|(name, _)| name.to_string())
+                .collect(),
+        }
+    }
+
+    /// A single line for page footers.
+    pub fn summary(&self) -> String {
+        let mut parts = vec![format!("observatory {}", self.version)];
+        if let Some(commit) = &self.git_commit {
+            parts.push(format!("commit {}", commit));
+        }
+        if let Some(built_at) = self.built_at {
+            parts.push(format!(
+                "built at {}",
+                built_at.format("%Y-%m-%d %H:%M:%S UTC")
+            ));
+        }
+        parts.push(format!("app {}", self.app_id));
+        if !self.features.is_empty() {
+            parts.push(format!("features: {}", self.features.join(", ")));
+        }
+        parts.join(", ")
+    }
+}
+
+fn mask(s: &str) -> String {
+    let chars: Vec<char> = s.chars().collect();
+    match chars.len() {
+        0..=2 => "*".repeat(chars.len()),
+        n => format!("{}{}{}", chars[0], "*".repeat(n - 2), chars[n - 1]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn app_id_is_masked() {
+        assert_eq!(mask("123456"), "1****6");
+        assert_eq!(mask("12"), "**");
+        assert_eq!(mask(""), "");
+
+        let version = Version::new("123456");
+        assert_eq!(version.version, env!("CARGO_PKG_VERSION"));
+        assert_eq!(version.app_id, "1****6");
+        assert!(version.built_at.is_some());
+        assert!(version.summary().contains("app 1****6"));
+    }
+}
@@ -0,0 +1,103 @@
+//! A guard against the controller hanging on a message (say, in a retry loop which never ends), which would otherwise
+//! stall all event processing without a word. If a message takes longer than `controller.watchdog_seconds` while other
+//! messages are waiting, the watchdog logs what the controller is busy with and makes it give up on the message and
+//! rebuild its state, as after a panic.
+//!
+//! Start-up is not watched: it may take a long time for large installations, and its progress is reported separately
+//! (see [`crate::hydration`]).
+use std::sync::Arc;
+
+use tokio::sync::watch;
+
+use crate::trace::{self, Trace};
+
+/// The kind of the message which is never given up on.
+const START_UP: &str = "Init";
+
+/// Looks at the message trace to tell a stuck controller from a busy one.
+#[derive(Debug, Clone)]
+pub struct Watchdog {
+    trace: Trace,
+
+    /// The sequence number of the latest message given up on.
+    given_up: Arc<watch::Sender<u64>>,
+}
+
+impl Watchdog {
+    pub fn new(trace: Trace) -> Self {
+        Self {
+            trace,
+            given_up: Arc::new(watch::channel(0).0),
+        }
+    }
+
+    /// The message being handled, if it has taken longer than `stale_after` while `waiting` more messages are queued.
+    pub fn stuck_message(
+        &self,
+        stale_after: chrono::Duration,
+        waiting: usize,
+    ) -> Option<trace::Entry> {
+        if waiting == 0 {
+            return None;
+        }
+        self.trace
+            .in_flight()
+            .filter(|e| e.kind != START_UP && chrono::Utc::now() - e.started_at > stale_after)
+    }
+
+    /// Give up on the message being handled if it's stuck, and tell if that's the case.
+    pub fn check(&self, stale_after: chrono::Duration, waiting: usize) -> bool {
+        let Some(entry) = self.stuck_message(stale_after, waiting) else {
+            return false;
+        };
+        log::error!(
+            "Watchdog: the controller has been stuck on message #{} for {} s with {} more waiting, restarting it. \
+            Message: {:?}; last message finished at {:?}",
+            entry.sequence,
+            (chrono::Utc::now() - entry.started_at).num_seconds(),
+            waiting,
+            entry,
+            self.trace.last_finished_at()
+        );
+        self.given_up.send_replace(entry.sequence);
+        true
+    }
+
+    /// Wait until the watchdog gives up on the message with this sequence number.
+    pub async fn given_up(&self, sequence: u64) {
+        let mut receiver = self.given_up.subscribe();
+        // The sender lives as long as the watchdog, so this never fails.
+        let _ = receiver.wait_for(|s| *s == sequence).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn only_long_messages_with_others_waiting_are_given_up_on() {
+        let trace = Trace::default();
+        let watchdog = Watchdog::new(trace.clone());
+        let init = trace.start(START_UP, None, None);
+        assert!(!watchdog.check(chrono::Duration::zero(), 5));
+        trace.finish(init, trace::Outcome::Done);
+
+        let sequence = trace.start("PullRequestUpdated", Some("test/repo"), Some("abc"));
+        assert!(!watchdog.check(chrono::Duration::minutes(1), 5));
+        assert!(!watchdog.check(chrono::Duration::zero(), 0));
+
+        let given_up = tokio::spawn({
+            let watchdog = watchdog.clone();
+            async move { watchdog.given_up(sequence).await }
+        });
+        assert!(watchdog.check(chrono::Duration::zero(), 1));
+        tokio::time::timeout(std::time::Duration::from_secs(1), given_up)
+            .await
+            .unwrap()
+            .unwrap();
+
+        trace.finish(sequence, trace::Outcome::Abandoned);
+        assert!(!watchdog.check(chrono::Duration::zero(), 1));
+    }
+}